@@ -0,0 +1,181 @@
+//! Compares the heap footprint and lookup speed of many small collections held in a listpack
+//! with the same collections held in the std containers the full encodings use.
+//!
+//! Run with `cargo bench --bench encoding [-- <collections>]`.
+
+// Clippy checks benches with `--cfg test` but without the test harness, which leaves the
+// module's tests empty, and the bench only needs part of the listpack API.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/store/listpack.rs"]
+mod listpack;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::{HashMap, HashSet, VecDeque},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use listpack::Listpack;
+
+/// Forwards to the system allocator while keeping track of the bytes currently allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Elements per collection, well under every listpack threshold.
+const ELEMENTS: usize = 16;
+
+fn field(i: usize) -> String {
+    format!("field:{}", i)
+}
+
+fn value(i: usize) -> String {
+    format!("value-{:08}", i.wrapping_mul(0x9E37_79B9))
+}
+
+/// Builds `collections` collections with `build`, then times `ELEMENTS` lookups in each.
+fn measure<T>(
+    name: &str,
+    collections: usize,
+    build: impl Fn(usize) -> T,
+    lookup: impl Fn(&T, usize) -> bool,
+) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let started = Instant::now();
+    let built: Vec<T> = (0..collections).map(build).collect();
+    let building = started.elapsed();
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+
+    let started = Instant::now();
+    let found = built
+        .iter()
+        .map(|collection| (0..ELEMENTS).filter(|&i| lookup(collection, i)).count())
+        .sum::<usize>();
+    let looking_up = started.elapsed();
+    assert_eq!(found, collections * ELEMENTS);
+    report(name, bytes / collections, building, looking_up, found);
+    black_box(built);
+}
+
+fn report(name: &str, bytes: usize, building: Duration, looking_up: Duration, lookups: usize) {
+    println!(
+        "{:<20} {:>6} bytes each   build {:>10.2?}   {:>6.1} ns/lookup",
+        name,
+        bytes,
+        building,
+        looking_up.as_nanos() as f64 / lookups.max(1) as f64
+    );
+}
+
+fn hashes(collections: usize) {
+    measure(
+        "hash: hashtable",
+        collections,
+        |_| {
+            (0..ELEMENTS)
+                .map(|i| (field(i), value(i)))
+                .collect::<HashMap<_, _>>()
+        },
+        |hash, i| hash.get(&field(i)) == Some(&value(i)),
+    );
+    measure(
+        "hash: listpack",
+        collections,
+        |_| {
+            let mut pack = Listpack::default();
+            for i in 0..ELEMENTS {
+                pack.push_back(&field(i));
+                pack.push_back(&value(i));
+            }
+            pack
+        },
+        |pack, i| {
+            let field = field(i);
+            pack.pairs()
+                .find(|&(known, _)| known == field)
+                .is_some_and(|(_, known)| known == value(i))
+        },
+    );
+}
+
+fn sets(collections: usize) {
+    measure(
+        "set: hashtable",
+        collections,
+        |_| (0..ELEMENTS).map(value).collect::<HashSet<_>>(),
+        |set, i| set.contains(&value(i)),
+    );
+    measure(
+        "set: listpack",
+        collections,
+        |_| {
+            let mut pack = Listpack::default();
+            for i in 0..ELEMENTS {
+                pack.push_back(&value(i));
+            }
+            pack
+        },
+        |pack, i| {
+            let member = value(i);
+            pack.iter().any(|known| known == member)
+        },
+    );
+}
+
+fn lists(collections: usize) {
+    measure(
+        "list: deque",
+        collections,
+        |_| (0..ELEMENTS).map(value).collect::<VecDeque<_>>(),
+        |list, i| list[i] == value(i),
+    );
+    measure(
+        "list: listpack",
+        collections,
+        |_| {
+            let mut pack = Listpack::default();
+            for i in 0..ELEMENTS {
+                pack.push_back(&value(i));
+            }
+            pack
+        },
+        |pack, i| pack.get(i) == Some(value(i).as_str()),
+    );
+}
+
+fn main() {
+    let collections = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(100_000);
+    println!(
+        "{} collections of {} elements, heap bytes per collection",
+        collections, ELEMENTS
+    );
+    hashes(collections);
+    sets(collections);
+    lists(collections);
+}
@@ -0,0 +1,142 @@
+//! Measures how the real `Cache` copes with many volatile keys: setting and updating their
+//! TTLs, deleting them, and how many of the keys that fall due the active expiry cycle
+//! reclaims. Compare the default sampling cycle with the timing wheel by running it with and
+//! without the feature:
+//!
+//! `cargo bench --bench expiry [--features timing-wheel] [-- <volatile keys>]`.
+
+// The bench drives the store on its own, so most of its API goes unused here, and clippy checks
+// benches with `--cfg test` but without the test harness.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/store/mod.rs"]
+mod store;
+
+#[macro_use(concat_string)]
+extern crate concat_string;
+
+use std::{
+    hint::black_box,
+    thread,
+    time::{Duration, Instant},
+};
+
+use store::{
+    db::{unix_time_ms, Cache},
+    string::{SetCondition, TtlUpdate},
+};
+
+/// Long TTLs start a minute out and spread over an hour, so none of them is due while the
+/// bench runs.
+const LONG_FROM_MS: u64 = 60_000;
+const SPREAD_MS: u64 = 3_600_000;
+/// Every this many keys one gets a TTL short enough to fall due during the bench.
+const DUE_EVERY: usize = 100;
+/// Short TTLs are spread over this many milliseconds from the start of the bench.
+const DUE_WITHIN_MS: u64 = 1_000;
+/// How long the active cycle gets to reclaim the keys once all of them are due.
+const RECLAIM_WAIT: Duration = Duration::from_secs(5);
+
+fn key(i: usize) -> String {
+    format!("key:{}", i)
+}
+
+/// Multiplicative hashing spreads consecutive keys over the whole range.
+fn spread(i: usize, range: u64) -> u64 {
+    1 + (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) % range
+}
+
+fn report(name: &str, elapsed: Duration, operations: usize) {
+    println!(
+        "{:<44} {:>10.2?} total {:>10.1} ns/op",
+        name,
+        elapsed,
+        elapsed.as_nanos() as f64 / operations.max(1) as f64
+    );
+}
+
+fn set_all(cache: &Cache, keys: usize, start: u64) -> usize {
+    let started = Instant::now();
+    let mut due = 0;
+    for i in 0..keys {
+        let at = if i % DUE_EVERY == 0 {
+            due += 1;
+            start + spread(i, DUE_WITHIN_MS)
+        } else {
+            start + LONG_FROM_MS + spread(i, SPREAD_MS)
+        };
+        cache
+            .set_with(
+                &key(i),
+                "v".to_string(),
+                &SetCondition::Always,
+                TtlUpdate::At(at),
+                false,
+            )
+            .expect("the bench owns the cache");
+    }
+    report("SET PXAT: insert", started.elapsed(), keys);
+    due
+}
+
+/// Moves the TTL of every tenth long lived key, skipping the ones that fall due.
+fn update_some(cache: &Cache, keys: usize, start: u64) {
+    let started = Instant::now();
+    let mut updated = 0;
+    for i in (1..keys).step_by(10).filter(|i| i % DUE_EVERY != 0) {
+        let at = start + LONG_FROM_MS + spread(i + 1, SPREAD_MS);
+        updated += cache
+            .expire(&key(i), at as i64, &[])
+            .expect("the bench owns the cache") as usize;
+    }
+    report("PEXPIREAT: update 10%", started.elapsed(), updated);
+}
+
+/// Waits for the short TTLs to pass, then gives the background cycle `RECLAIM_WAIT` to delete
+/// the due keys. DBSIZE counts keys until they are reclaimed, so it shows the cycle's progress.
+fn reclaim(cache: &Cache, left: usize, due: usize, start: u64) {
+    let last_due = start + DUE_WITHIN_MS;
+    thread::sleep(Duration::from_millis(
+        last_due.saturating_sub(unix_time_ms()),
+    ));
+    let started = Instant::now();
+    let mut size = cache.dbsize().expect("the bench owns the cache");
+    while size > left - due && started.elapsed() < RECLAIM_WAIT {
+        thread::sleep(Duration::from_millis(10));
+        size = cache.dbsize().expect("the bench owns the cache");
+    }
+    let reclaimed = left - size;
+    println!(
+        "active expiry: {} of {} due keys reclaimed {:.2?} after the last one fell due",
+        reclaimed,
+        due,
+        started.elapsed()
+    );
+}
+
+fn remove_some(cache: &Cache, keys: usize) -> usize {
+    let doomed: Vec<String> = (5..keys).step_by(10).map(key).collect();
+    let started = Instant::now();
+    let removed = cache.unlink(&doomed).expect("the bench owns the cache");
+    report("UNLINK: remove 10%", started.elapsed(), removed);
+    removed
+}
+
+fn main() {
+    let keys = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(1_000_000);
+    let expiry = if cfg!(feature = "timing-wheel") {
+        "timing wheel"
+    } else {
+        "sampling cycle"
+    };
+    println!("{} volatile keys, {}", keys, expiry);
+    let cache = Cache::new();
+    let start = unix_time_ms();
+    let due = set_all(&cache, keys, start);
+    update_some(&cache, keys, start);
+    let removed = remove_some(&cache, keys);
+    reclaim(&cache, keys - removed, due, start);
+    black_box(cache);
+}
@@ -0,0 +1,443 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        bitmap::{
+            BitOperation, BitRange, BitUnit, BitfieldCommand, BitfieldOp, BitfieldType, Overflow,
+            MAX_BIT_OFFSET,
+        },
+        db::Cache,
+    },
+};
+
+use super::utils::{parse_integer, serialize_error, serialize_len, throw_err_if_num_of_args_wrong};
+
+fn parse_bit_offset(arg: &str) -> Result<u64, Cow<'static, str>> {
+    arg.parse::<u64>()
+        .ok()
+        .filter(|&offset| offset <= MAX_BIT_OFFSET)
+        .ok_or_else(|| serialize_error("-ERR bit offset is not an integer or out of range"))
+}
+
+/// Parses the `start [end [BYTE|BIT]]` arguments of BITCOUNT and BITPOS.
+fn parse_bit_range(args: &[String]) -> Result<Option<BitRange>, Cow<'static, str>> {
+    let Some(start) = args.first() else {
+        return Ok(None);
+    };
+    let end = args.get(1).map(|end| parse_integer(end)).transpose()?;
+    let unit = match args.get(2).map(|unit| unit.to_uppercase()).as_deref() {
+        None | Some("BYTE") => BitUnit::Byte,
+        Some("BIT") => BitUnit::Bit,
+        Some(_) => return Err(serialize_error("-ERR syntax error")),
+    };
+    if args.len() > 3 {
+        return Err(serialize_error("-ERR syntax error"));
+    }
+    Ok(Some(BitRange {
+        start: parse_integer(start)?,
+        end,
+        unit,
+    }))
+}
+
+/// SETBIT key offset value
+pub fn handle_setbit(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, offset, value] = args else {
+        return throw_err_if_num_of_args_wrong("setbit");
+    };
+    let offset = match parse_bit_offset(offset) {
+        Ok(offset) => offset,
+        Err(err) => return err,
+    };
+    let value = match value.as_str() {
+        "0" => false,
+        "1" => true,
+        _ => return serialize_error("-ERR bit is not an integer or out of range"),
+    };
+    match cache.setbit(key, offset, value) {
+        Ok(previous) => serialize(InputVariants::NumberVariant(i64::from(previous))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// GETBIT key offset
+pub fn handle_getbit(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, offset] = args else {
+        return throw_err_if_num_of_args_wrong("getbit");
+    };
+    let offset = match parse_bit_offset(offset) {
+        Ok(offset) => offset,
+        Err(err) => return err,
+    };
+    match cache.getbit(key, offset) {
+        Ok(bit) => serialize(InputVariants::NumberVariant(i64::from(bit))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// BITCOUNT key [start end [BYTE|BIT]]
+pub fn handle_bitcount(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, range @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("bitcount");
+    };
+    if range.len() == 1 {
+        return serialize_error("-ERR syntax error");
+    }
+    let range = match parse_bit_range(range) {
+        Ok(range) => range,
+        Err(err) => return err,
+    };
+    match cache.bitcount(key, range) {
+        Ok(count) => serialize(InputVariants::NumberVariant(count as i64)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// BITPOS key bit [start [end [BYTE|BIT]]]
+pub fn handle_bitpos(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, bit, range @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("bitpos");
+    };
+    let bit = match parse_integer(bit) {
+        Ok(0) => false,
+        Ok(1) => true,
+        Ok(_) => return serialize_error("-ERR The bit argument must be 1 or 0."),
+        Err(err) => return err,
+    };
+    let range = match parse_bit_range(range) {
+        Ok(range) => range,
+        Err(err) => return err,
+    };
+    match cache.bitpos(key, bit, range) {
+        Ok(position) => serialize(InputVariants::NumberVariant(position)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// BITOP AND|OR|XOR|NOT|DIFF destkey key [key ...]
+pub fn handle_bitop(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [operation, destination, keys @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("bitop");
+    };
+    if keys.is_empty() {
+        return throw_err_if_num_of_args_wrong("bitop");
+    }
+    let operation = match operation.to_uppercase().as_str() {
+        "AND" => BitOperation::And,
+        "OR" => BitOperation::Or,
+        "XOR" => BitOperation::Xor,
+        "NOT" if keys.len() == 1 => BitOperation::Not,
+        "NOT" => return serialize_error("-ERR BITOP NOT must be called with a single source key."),
+        "DIFF" if keys.len() >= 2 => BitOperation::Diff,
+        "DIFF" => {
+            return serialize_error("-ERR BITOP DIFF must be called with at least two source keys.")
+        }
+        _ => return serialize_error("-ERR syntax error"),
+    };
+    match cache.bitop(operation, destination, keys) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+fn parse_bitfield_type(arg: &str) -> Result<BitfieldType, Cow<'static, str>> {
+    let signed = arg.starts_with(['i', 'I']);
+    let max_bits = if signed { 64 } else { 63 };
+    arg.strip_prefix(['i', 'I', 'u', 'U'])
+        .and_then(|bits| bits.parse::<u32>().ok())
+        .filter(|bits| (1..=max_bits).contains(bits))
+        .map(|bits| BitfieldType { signed, bits })
+        .ok_or_else(|| {
+            serialize_error(
+                "-ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.",
+            )
+        })
+}
+
+/// Offsets prefixed with `#` count in units of the field width.
+fn parse_bitfield_offset(arg: &str, kind: BitfieldType) -> Result<u64, Cow<'static, str>> {
+    let bits = u64::from(kind.bits);
+    let offset = match arg.strip_prefix('#') {
+        Some(index) => index.parse::<u64>().ok().and_then(|i| i.checked_mul(bits)),
+        None => arg.parse::<u64>().ok(),
+    };
+    offset
+        .filter(|&offset| offset + bits - 1 <= MAX_BIT_OFFSET)
+        .ok_or_else(|| serialize_error("-ERR bit offset is not an integer or out of range"))
+}
+
+/// Parses `GET type offset`, `SET type offset value`, `INCRBY type offset increment` and
+/// `OVERFLOW WRAP|SAT|FAIL`, the last applying to the writes that follow it.
+fn parse_bitfield_commands(
+    args: &[String],
+    read_only: bool,
+) -> Result<Vec<BitfieldCommand>, Cow<'static, str>> {
+    let mut commands = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    let mut rest = args;
+    while let [subcommand, tail @ ..] = rest {
+        let subcommand = subcommand.to_uppercase();
+        if subcommand == "OVERFLOW" {
+            let [kind, tail @ ..] = tail else {
+                return Err(serialize_error("-ERR syntax error"));
+            };
+            overflow = match kind.to_uppercase().as_str() {
+                "WRAP" => Overflow::Wrap,
+                "SAT" => Overflow::Sat,
+                "FAIL" => Overflow::Fail,
+                _ => return Err(serialize_error("-ERR Invalid OVERFLOW type specified")),
+            };
+            rest = tail;
+            continue;
+        }
+        let arity = match subcommand.as_str() {
+            "GET" => 2,
+            "SET" | "INCRBY" => 3,
+            _ => return Err(serialize_error("-ERR syntax error")),
+        };
+        if tail.len() < arity {
+            return Err(serialize_error("-ERR syntax error"));
+        }
+        let kind = parse_bitfield_type(&tail[0])?;
+        let offset = parse_bitfield_offset(&tail[1], kind)?;
+        let op = match subcommand.as_str() {
+            "GET" => BitfieldOp::Get,
+            _ if read_only => {
+                return Err(serialize_error(
+                    "-ERR BITFIELD_RO only supports the GET subcommand",
+                ))
+            }
+            "SET" => BitfieldOp::Set(parse_integer(&tail[2])?),
+            _ => BitfieldOp::IncrBy(parse_integer(&tail[2])?),
+        };
+        commands.push(BitfieldCommand {
+            op,
+            kind,
+            offset,
+            overflow,
+        });
+        rest = &tail[arity..];
+    }
+    Ok(commands)
+}
+
+/// BITFIELD key [GET type offset] [SET type offset value] [INCRBY type offset increment]
+/// [OVERFLOW WRAP|SAT|FAIL] ... and BITFIELD_RO key [GET type offset ...]
+pub fn handle_bitfield(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, subcommands @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let commands = match parse_bitfield_commands(subcommands, command == "bitfield_ro") {
+        Ok(commands) => commands,
+        Err(err) => return err,
+    };
+    match cache.bitfield(key, &commands) {
+        Ok(replies) => serialize(InputVariants::Array(
+            replies
+                .into_iter()
+                .map(|reply| reply.map_or(InputVariants::Nullish, InputVariants::NumberVariant))
+                .collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection_manager::{client::Client, test_utils::run};
+
+    use super::*;
+
+    #[test]
+    fn should_set_get_and_count_bits() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["SETBIT", "b", "7", "1"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["SETBIT", "b", "7", "1"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["SETBIT", "b", "22", "1"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["GET", "b"]), "$3\r\n\u{1}\0\u{2}\r\n");
+        assert_eq!(run(&cache, client, &["GETBIT", "b", "22"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["GETBIT", "b", "1000"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["SETBIT", "b", "1", "2"]),
+            "-ERR bit is not an integer or out of range\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SETBIT", "b", "-1", "1"]),
+            "-ERR bit offset is not an integer or out of range\r\n"
+        );
+
+        run(&cache, client, &["SET", "s", "foobar"]);
+        assert_eq!(run(&cache, client, &["BITCOUNT", "s"]), ":26\r\n");
+        assert_eq!(run(&cache, client, &["BITCOUNT", "s", "1", "1"]), ":6\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITCOUNT", "s", "-2", "-1"]),
+            ":7\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BITCOUNT", "s", "5", "30", "BIT"]),
+            ":17\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BITCOUNT", "s", "1"]),
+            "-ERR syntax error\r\n"
+        );
+        assert_eq!(run(&cache, client, &["BITCOUNT", "nope"]), ":0\r\n");
+    }
+
+    #[test]
+    fn should_find_bit_positions() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "s", "\u{ff}\u{f0}\0"]);
+        assert_eq!(run(&cache, client, &["BITPOS", "s", "0"]), ":12\r\n");
+        assert_eq!(run(&cache, client, &["BITPOS", "s", "1", "2"]), ":-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITPOS", "s", "1", "7", "15", "BIT"]),
+            ":7\r\n"
+        );
+        run(&cache, client, &["SET", "ones", "\u{ff}\u{ff}"]);
+        assert_eq!(run(&cache, client, &["BITPOS", "ones", "0"]), ":16\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITPOS", "ones", "0", "0", "-1"]),
+            ":-1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["BITPOS", "nope", "0"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["BITPOS", "nope", "1"]), ":-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITPOS", "s", "2"]),
+            "-ERR The bit argument must be 1 or 0.\r\n"
+        );
+    }
+
+    #[test]
+    fn should_combine_bitmaps() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "a", "\u{ff}\u{0f}"]);
+        run(&cache, client, &["SET", "b", "\u{f0}"]);
+        run(&cache, client, &["SET", "c", "\u{0c}"]);
+        assert_eq!(
+            run(&cache, client, &["BITOP", "AND", "d", "a", "b"]),
+            ":2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "d"]), "$2\r\n\u{f0}\0\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITOP", "OR", "d", "b", "c"]),
+            ":1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "d"]), "$1\r\n\u{fc}\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITOP", "XOR", "d", "a", "b"]),
+            ":2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "d"]), "$2\r\n\u{0f}\u{0f}\r\n");
+        assert_eq!(run(&cache, client, &["BITOP", "NOT", "d", "b"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["GET", "d"]), "$1\r\n\u{0f}\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITOP", "DIFF", "d", "a", "b", "c"]),
+            ":2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "d"]), "$2\r\n\u{03}\u{0f}\r\n");
+        assert_eq!(run(&cache, client, &["BITOP", "OR", "d", "nope"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["EXISTS", "d"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["BITOP", "NOT", "d", "a", "b"]),
+            "-ERR BITOP NOT must be called with a single source key.\r\n"
+        );
+    }
+
+    #[test]
+    fn should_drop_the_ttl_of_a_bitop_destination() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "a", "x"]);
+        run(&cache, client, &["SET", "d", "x", "EX", "1000"]);
+        assert_eq!(
+            run(&cache, client, &["BITOP", "AND", "d", "a", "a"]),
+            ":1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["TTL", "d"]), ":-1\r\n");
+
+        run(&cache, client, &["EXPIRE", "d", "1000"]);
+        run(&cache, client, &["SETBIT", "d", "0", "1"]);
+        assert_eq!(run(&cache, client, &["TTL", "d"]), ":1000\r\n");
+    }
+
+    #[test]
+    fn should_read_back_a_value_starting_with_plus() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        for offset in ["2", "4", "6", "7"] {
+            run(&cache, client, &["SETBIT", "plus", offset, "1"]);
+        }
+        assert_eq!(run(&cache, client, &["GET", "plus"]), "$1\r\n+\r\n");
+        assert_eq!(
+            run(&cache, client, &["GETRANGE", "plus", "0", "-1"]),
+            "$1\r\n+\r\n"
+        );
+    }
+
+    #[test]
+    fn should_run_bitfield_subcommands() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["BITFIELD", "f", "SET", "i8", "0", "100", "GET", "u4", "0"]
+            ),
+            "*2\r\n:0\r\n:6\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["BITFIELD", "f", "INCRBY", "i8", "0", "100"]
+            ),
+            "*1\r\n:-56\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "BITFIELD", "f", "OVERFLOW", "SAT", "INCRBY", "i8", "0", "-100", "OVERFLOW",
+                    "FAIL", "INCRBY", "i8", "0", "-1"
+                ]
+            ),
+            "*2\r\n:-128\r\n$-1\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["BITFIELD", "f", "SET", "u8", "#1", "255", "GET", "u16", "0"]
+            ),
+            "*2\r\n:0\r\n:33023\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BITFIELD_RO", "f", "GET", "i16", "0"]),
+            "*1\r\n:-32513\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BITFIELD_RO", "f", "SET", "i8", "0", "1"]),
+            "-ERR BITFIELD_RO only supports the GET subcommand\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BITFIELD", "f", "GET", "u64", "0"]),
+            "-ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BITFIELD", "f", "OVERFLOW", "NOPE"]),
+            "-ERR Invalid OVERFLOW type specified\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BITFIELD", "missing", "GET", "u8", "0"]),
+            "*1\r\n:0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "missing"]), ":0\r\n");
+    }
+}
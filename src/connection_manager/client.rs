@@ -0,0 +1,80 @@
+use std::{
+    io::ErrorKind,
+    net::TcpStream,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::store::blocking::ClientId;
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Per connection state that outlives a single command.
+#[derive(Debug)]
+pub struct Client {
+    pub id: ClientId,
+    /// RESP version negotiated with HELLO, 2 until the client asks for 3.
+    pub protocol: u8,
+    pub name: Option<String>,
+    /// Database chosen with SELECT.
+    pub db: usize,
+    stream: Option<TcpStream>,
+    queued: Option<Vec<Vec<String>>>,
+    executing: bool,
+}
+
+impl Client {
+    pub fn new(stream: Option<TcpStream>) -> Self {
+        Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            protocol: 2,
+            name: None,
+            db: 0,
+            stream,
+            queued: None,
+            executing: false,
+        }
+    }
+
+    /// Peeks at the socket without consuming anything to find out whether the peer hung up.
+    /// Clients without a socket (tests) are always connected.
+    pub fn is_connected(&self) -> bool {
+        let Some(stream) = &self.stream else {
+            return true;
+        };
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let connected = match stream.peek(&mut [0_u8; 1]) {
+            Ok(size) => size > 0,
+            Err(err) => err.kind() == ErrorKind::WouldBlock,
+        };
+        connected && stream.set_nonblocking(false).is_ok()
+    }
+
+    /// Blocking commands must answer right away while a transaction is queued or executing.
+    pub fn may_block(&self) -> bool {
+        self.queued.is_none() && !self.executing
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.queued.is_some()
+    }
+
+    pub fn begin_transaction(&mut self) {
+        self.queued = Some(Vec::new());
+    }
+
+    pub fn queue_command(&mut self, command: Vec<String>) {
+        if let Some(queued) = self.queued.as_mut() {
+            queued.push(command);
+        }
+    }
+
+    pub fn take_transaction(&mut self) -> Option<Vec<Vec<String>>> {
+        self.queued.take()
+    }
+
+    pub fn set_executing(&mut self, executing: bool) {
+        self.executing = executing;
+    }
+}
@@ -3,12 +3,23 @@ use std::{
     net::{Shutdown, TcpStream},
 };
 
-use crate::store::db::Cache;
+use crate::{
+    resp::deserialize::frame_length,
+    store::{
+        bytes::{from_bytes, to_bytes},
+        db::Cache,
+    },
+};
 
-use super::command_handler::handle_command;
+use super::{client::Client, command_handler::handle_command, utils::serialize_error};
 
+/// Reads from the stream until at least one complete command is buffered, then answers every
+/// complete command in order. Partial commands stay in the buffer for the next read, so large
+/// values and pipelined requests are both handled.
 pub fn handle_stream(mut stream: TcpStream, cache: &Cache) {
-    let mut data = [0_u8; 128];
+    let mut client = Client::new(stream.try_clone().ok());
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut data = [0_u8; 4096];
     loop {
         match stream.read(&mut data) {
             Ok(size) => {
@@ -18,10 +29,33 @@ pub fn handle_stream(mut stream: TcpStream, cache: &Cache) {
                         Err(_) => println!("Connection closed but could not get peer address."),
                     }
                     break;
-                } else {
-                    let human_readable = String::from_utf8_lossy(&data);
-                    let serialized_response = handle_command(human_readable, cache);
-                    match stream.write_all(serialized_response.as_bytes()) {
+                }
+                buffer.extend_from_slice(&data[..size]);
+                loop {
+                    let length = match frame_length(&buffer) {
+                        Ok(Some(length)) => length,
+                        Ok(None) => break,
+                        Err(err) => {
+                            // Like Redis, a protocol error is answered and ends the connection.
+                            if let Err(err) = stream.write_all(&to_bytes(&serialize_error(err))) {
+                                println!(
+                                    "An error occurred while writing a protocol error: {}",
+                                    err
+                                );
+                            }
+                            if let Err(err) = stream.shutdown(Shutdown::Both) {
+                                println!(
+                                    "An error occurred while shutting down the stream: {}",
+                                    err
+                                );
+                            }
+                            return;
+                        }
+                    };
+                    let frame: Vec<u8> = buffer.drain(..length).collect();
+                    let human_readable = from_bytes(&frame);
+                    let serialized_response = handle_command(human_readable, cache, &mut client);
+                    match stream.write_all(&to_bytes(&serialized_response)) {
                         Ok(_) => {}
                         Err(err) => {
                             match stream.peer_addr() {
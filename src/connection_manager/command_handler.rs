@@ -1,42 +1,77 @@
 use std::borrow::Cow;
 
 use super::{
+    bitmap_commands::{
+        handle_bitcount, handle_bitfield, handle_bitop, handle_bitpos, handle_getbit, handle_setbit,
+    },
+    client::Client,
     commands::{
-        handle_decr, handle_del, handle_echo, handle_exists, handle_get, handle_incr, handle_ping,
-        handle_set, ignore_command,
+        handle_append, handle_client, handle_del, handle_echo, handle_exists, handle_get,
+        handle_getdel, handle_getex, handle_getrange, handle_getset, handle_hello, handle_incr,
+        handle_incrbyfloat, handle_lcs, handle_mget, handle_mset, handle_msetex, handle_ping,
+        handle_set, handle_setex, handle_setnx, handle_setrange, handle_strlen, ignore_command,
+    },
+    consumer_group_commands::{
+        handle_xack, handle_xautoclaim, handle_xclaim, handle_xgroup, handle_xpending,
+        handle_xreadgroup,
+    },
+    expire_commands::{handle_expire, handle_persist, handle_ttl},
+    geo_commands::{
+        handle_geoadd, handle_geodist, handle_geohash, handle_geopos, handle_geosearch,
+        handle_geosearchstore,
+    },
+    hash_commands::{
+        handle_hdel, handle_hexists, handle_hexpire, handle_hget, handle_hgetall, handle_hincrby,
+        handle_hincrbyfloat, handle_hlen, handle_hmget, handle_hpersist, handle_hrandfield,
+        handle_hscan, handle_hset, handle_hsetnx, handle_hstrlen, handle_httl,
+    },
+    hyperloglog_commands::{handle_pfadd, handle_pfcount, handle_pfmerge},
+    key_commands::{
+        handle_copy, handle_dbsize, handle_flush, handle_keys, handle_move, handle_object,
+        handle_randomkey, handle_rename, handle_scan, handle_touch, handle_type, handle_unlink,
+    },
+    list_commands::{
+        handle_blmove, handle_blmpop, handle_blocking_pop, handle_brpoplpush, handle_llen,
+        handle_lmove, handle_lmpop, handle_lrange, handle_pop, handle_push, handle_rpoplpush,
+    },
+    server_commands::{handle_config, handle_info, handle_memory, handle_select, handle_swapdb},
+    set_commands::{
+        handle_sadd_srem, handle_scard, handle_set_combine, handle_set_combine_store,
+        handle_sintercard, handle_sismember, handle_smembers, handle_smismember, handle_smove,
+        handle_spop, handle_srandmember, handle_sscan,
+    },
+    stream_commands::{
+        handle_xadd, handle_xdel, handle_xinfo, handle_xlen, handle_xrange, handle_xread,
+        handle_xtrim,
+    },
+    utils::{serialize_error, throw_err_if_num_of_args_wrong},
+    zset_commands::{
+        handle_bzmpop, handle_bzpop, handle_zadd, handle_zcard, handle_zcombine,
+        handle_zcombine_store, handle_zcount, handle_zincrby, handle_zintercard, handle_zlexcount,
+        handle_zmpop, handle_zmscore, handle_zpop, handle_zrandmember, handle_zrange,
+        handle_zrangestore, handle_zrank, handle_zrem, handle_zremrange, handle_zscan,
+        handle_zscore,
     },
-    utils::serialize_error,
 };
-use crate::{deserialize, resp::deserialize::RespResponse, store::db::Cache};
+use crate::{
+    deserialize,
+    resp::{
+        deserialize::RespResponse,
+        serialize::{serialize, InputVariants},
+    },
+    store::{db::Cache, list::ListEnd, set::SetOperation},
+};
 
-pub fn handle_command(human_readable: Cow<'_, str>, cache: &Cache) -> Cow<'static, str> {
+pub fn handle_command(
+    human_readable: Cow<'_, str>,
+    cache: &Cache,
+    client: &mut Client,
+) -> Cow<'static, str> {
     let command = deserialize(&human_readable);
 
     match command {
         Ok(deserialized_command) => match deserialized_command {
-            RespResponse::VecVariant(commands, _) => {
-                if let Some(command) = commands.first().map(|s| s.to_lowercase()) {
-                    let args = &commands[1..];
-
-                    match command.as_str() {
-                        "command" => ignore_command(),
-                        "ping" => handle_ping(),
-                        "echo" => handle_echo(args),
-                        "set" => handle_set(args, cache),
-                        "get" => handle_get(args, cache),
-                        "exists" => handle_exists(args, cache),
-                        "del" => handle_del(args, cache),
-                        "incr" => handle_incr(args, cache),
-                        "decr" => handle_decr(args, cache),
-                        unknown_command => {
-                            let message = "-unknown command '".to_owned() + unknown_command + "'";
-                            serialize_error(message.as_str())
-                        }
-                    }
-                } else {
-                    serialize_error("-commands array is empty")
-                }
-            }
+            RespResponse::VecVariant(commands, _) => execute(commands, cache, client),
             _ => serialize_error("-unsupported RESP type"),
         },
         Err(err) => {
@@ -46,28 +81,303 @@ pub fn handle_command(human_readable: Cow<'_, str>, cache: &Cache) -> Cow<'stati
     }
 }
 
+/// Commands that can grow the data set. Like Redis' `denyoom` flag, they make room under
+/// `maxmemory` before running and are refused when that is not possible.
+const DENYOOM_COMMANDS: [&str; 50] = [
+    "set",
+    "setnx",
+    "setex",
+    "psetex",
+    "append",
+    "setrange",
+    "incr",
+    "decr",
+    "incrby",
+    "decrby",
+    "incrbyfloat",
+    "mset",
+    "msetnx",
+    "msetex",
+    "getset",
+    "lpush",
+    "rpush",
+    "lpushx",
+    "rpushx",
+    "lmove",
+    "rpoplpush",
+    "blmove",
+    "brpoplpush",
+    "hset",
+    "hmset",
+    "hsetnx",
+    "hincrby",
+    "hincrbyfloat",
+    "sadd",
+    "smove",
+    "sinterstore",
+    "sunionstore",
+    "sdiffstore",
+    "zadd",
+    "zincrby",
+    "zunionstore",
+    "zinterstore",
+    "zdiffstore",
+    "zrangestore",
+    "xadd",
+    "xgroup",
+    "xreadgroup",
+    "pfadd",
+    "pfmerge",
+    "setbit",
+    "bitop",
+    "bitfield",
+    "geoadd",
+    "geosearchstore",
+    "copy",
+];
+
+/// Queues everything but the transaction commands themselves while a MULTI is open.
+fn execute(commands: Vec<String>, cache: &Cache, client: &mut Client) -> Cow<'static, str> {
+    let Some(command) = commands.first().map(|s| s.to_lowercase()) else {
+        return serialize_error("-commands array is empty");
+    };
+    let args = &commands[1..];
+
+    match command.as_str() {
+        "multi" if !args.is_empty() => throw_err_if_num_of_args_wrong("multi"),
+        "multi" if client.in_transaction() => serialize_error("-ERR MULTI calls can not be nested"),
+        "multi" => {
+            client.begin_transaction();
+            serialize(InputVariants::StringVariant("+OK".to_string()))
+        }
+        "discard" => match client.take_transaction() {
+            Some(_) => serialize(InputVariants::StringVariant("+OK".to_string())),
+            None => serialize_error("-ERR DISCARD without MULTI"),
+        },
+        "exec" => match client.take_transaction() {
+            Some(queued) => handle_exec(queued, cache, client),
+            None => serialize_error("-ERR EXEC without MULTI"),
+        },
+        _ if client.in_transaction() => {
+            client.queue_command(commands);
+            serialize(InputVariants::StringVariant("+QUEUED".to_string()))
+        }
+        _ => dispatch(&command, args, cache, client),
+    }
+}
+
+fn handle_exec(queued: Vec<Vec<String>>, cache: &Cache, client: &mut Client) -> Cow<'static, str> {
+    client.set_executing(true);
+    let replies: Vec<String> = queued
+        .into_iter()
+        .map(|commands| {
+            let command = commands[0].to_lowercase();
+            dispatch(&command, &commands[1..], cache, client).into_owned()
+        })
+        .collect();
+    client.set_executing(false);
+
+    Cow::Owned(concat_string!(
+        "*",
+        replies.len().to_string(),
+        "\r\n",
+        replies.concat()
+    ))
+}
+
+fn dispatch(
+    command: &str,
+    args: &[String],
+    cache: &Cache,
+    client: &mut Client,
+) -> Cow<'static, str> {
+    // Every connection shares one handle and picks its database per command, so a SELECT
+    // queued in MULTI applies to the commands queued after it.
+    let selected = (client.db != cache.index())
+        .then(|| cache.select(client.db))
+        .flatten();
+    let cache = selected.as_ref().unwrap_or(cache);
+    if DENYOOM_COMMANDS.contains(&command) {
+        if let Err(err) = cache.free_memory_if_needed() {
+            return serialize_error(concat_string!("-", err).as_str());
+        }
+    }
+    match command {
+        "command" => ignore_command(),
+        "ping" => handle_ping(),
+        "echo" => handle_echo(args),
+        "hello" => handle_hello(args, client),
+        "client" => handle_client(args, cache, client),
+        "set" => handle_set(args, cache),
+        "setnx" => handle_setnx(args, cache),
+        "setex" | "psetex" => handle_setex(args, cache, command),
+        "get" => handle_get(args, cache),
+        "exists" => handle_exists(args, cache),
+        "del" => handle_del(args, cache),
+        "keys" => handle_keys(args, cache),
+        "scan" => handle_scan(args, cache),
+        "rename" | "renamenx" => handle_rename(args, cache, command),
+        "copy" => handle_copy(args, cache),
+        "move" => handle_move(args, cache),
+        "select" => handle_select(args, cache, client),
+        "swapdb" => handle_swapdb(args, cache),
+        "info" => handle_info(args, cache),
+        "config" => handle_config(args, cache, client),
+        "memory" => handle_memory(args, cache, client),
+        "type" => handle_type(args, cache),
+        "touch" => handle_touch(args, cache),
+        "object" => handle_object(args, cache),
+        "unlink" => handle_unlink(args, cache),
+        "randomkey" => handle_randomkey(args, cache),
+        "dbsize" => handle_dbsize(args, cache),
+        "flushdb" | "flushall" => handle_flush(args, cache, command),
+        "expire" | "pexpire" | "expireat" | "pexpireat" => handle_expire(args, cache, command),
+        "ttl" | "pttl" | "expiretime" | "pexpiretime" => handle_ttl(args, cache, command),
+        "persist" => handle_persist(args, cache),
+        "incr" | "decr" | "incrby" | "decrby" => handle_incr(args, cache, command),
+        "incrbyfloat" => handle_incrbyfloat(args, cache),
+        "lpush" => handle_push(args, cache, ListEnd::Left, false, command),
+        "rpush" => handle_push(args, cache, ListEnd::Right, false, command),
+        "lpushx" => handle_push(args, cache, ListEnd::Left, true, command),
+        "rpushx" => handle_push(args, cache, ListEnd::Right, true, command),
+        "lpop" => handle_pop(args, cache, ListEnd::Left, command),
+        "rpop" => handle_pop(args, cache, ListEnd::Right, command),
+        "lrange" => handle_lrange(args, cache),
+        "llen" => handle_llen(args, cache),
+        "lmove" => handle_lmove(args, cache),
+        "rpoplpush" => handle_rpoplpush(args, cache),
+        "lmpop" => handle_lmpop(args, cache),
+        "blpop" => handle_blocking_pop(args, cache, client, ListEnd::Left, command),
+        "brpop" => handle_blocking_pop(args, cache, client, ListEnd::Right, command),
+        "blmove" => handle_blmove(args, cache, client),
+        "brpoplpush" => handle_brpoplpush(args, cache, client),
+        "blmpop" => handle_blmpop(args, cache, client),
+        "hset" | "hmset" => handle_hset(args, cache, command),
+        "hsetnx" => handle_hsetnx(args, cache),
+        "hget" => handle_hget(args, cache),
+        "hmget" => handle_hmget(args, cache),
+        "hdel" => handle_hdel(args, cache),
+        "hexists" => handle_hexists(args, cache),
+        "hlen" => handle_hlen(args, cache),
+        "hstrlen" => handle_hstrlen(args, cache),
+        "hkeys" | "hvals" | "hgetall" => handle_hgetall(args, cache, client, command),
+        "hincrby" => handle_hincrby(args, cache),
+        "hincrbyfloat" => handle_hincrbyfloat(args, cache),
+        "hrandfield" => handle_hrandfield(args, cache, client),
+        "hscan" => handle_hscan(args, cache),
+        "hexpire" | "hpexpire" | "hexpireat" | "hpexpireat" => handle_hexpire(args, cache, command),
+        "httl" | "hpttl" | "hexpiretime" | "hpexpiretime" => handle_httl(args, cache, command),
+        "hpersist" => handle_hpersist(args, cache),
+        "sadd" | "srem" => handle_sadd_srem(args, cache, command),
+        "sismember" => handle_sismember(args, cache),
+        "smismember" => handle_smismember(args, cache),
+        "smembers" => handle_smembers(args, cache),
+        "scard" => handle_scard(args, cache),
+        "spop" => handle_spop(args, cache),
+        "srandmember" => handle_srandmember(args, cache),
+        "smove" => handle_smove(args, cache),
+        "sinter" => handle_set_combine(args, cache, SetOperation::Inter, command),
+        "sunion" => handle_set_combine(args, cache, SetOperation::Union, command),
+        "sdiff" => handle_set_combine(args, cache, SetOperation::Diff, command),
+        "sinterstore" => handle_set_combine_store(args, cache, SetOperation::Inter, command),
+        "sunionstore" => handle_set_combine_store(args, cache, SetOperation::Union, command),
+        "sdiffstore" => handle_set_combine_store(args, cache, SetOperation::Diff, command),
+        "sintercard" => handle_sintercard(args, cache),
+        "sscan" => handle_sscan(args, cache),
+        "zadd" => handle_zadd(args, cache, client),
+        "zincrby" => handle_zincrby(args, cache, client),
+        "zrem" => handle_zrem(args, cache),
+        "zscore" => handle_zscore(args, cache, client),
+        "zmscore" => handle_zmscore(args, cache, client),
+        "zcard" => handle_zcard(args, cache),
+        "zcount" => handle_zcount(args, cache),
+        "zrank" | "zrevrank" => handle_zrank(args, cache, client, command),
+        "zrange" | "zrevrange" | "zrangebyscore" | "zrevrangebyscore" | "zrangebylex"
+        | "zrevrangebylex" => handle_zrange(args, cache, client, command),
+        "zrangestore" => handle_zrangestore(args, cache),
+        "zunion" => handle_zcombine(args, cache, client, SetOperation::Union, command),
+        "zinter" => handle_zcombine(args, cache, client, SetOperation::Inter, command),
+        "zdiff" => handle_zcombine(args, cache, client, SetOperation::Diff, command),
+        "zunionstore" => handle_zcombine_store(args, cache, SetOperation::Union, command),
+        "zinterstore" => handle_zcombine_store(args, cache, SetOperation::Inter, command),
+        "zdiffstore" => handle_zcombine_store(args, cache, SetOperation::Diff, command),
+        "zintercard" => handle_zintercard(args, cache),
+        "zlexcount" => handle_zlexcount(args, cache),
+        "zremrangebyrank" | "zremrangebyscore" | "zremrangebylex" => {
+            handle_zremrange(args, cache, command)
+        }
+        "zpopmin" | "zpopmax" => handle_zpop(args, cache, client, command),
+        "zmpop" => handle_zmpop(args, cache, client),
+        "bzpopmin" | "bzpopmax" => handle_bzpop(args, cache, client, command),
+        "bzmpop" => handle_bzmpop(args, cache, client),
+        "zrandmember" => handle_zrandmember(args, cache, client),
+        "zscan" => handle_zscan(args, cache),
+        "xadd" => handle_xadd(args, cache),
+        "xlen" => handle_xlen(args, cache),
+        "xrange" | "xrevrange" => handle_xrange(args, cache, command),
+        "xdel" => handle_xdel(args, cache),
+        "xtrim" => handle_xtrim(args, cache),
+        "xinfo" => handle_xinfo(args, cache, client),
+        "xread" => handle_xread(args, cache, client),
+        "xgroup" => handle_xgroup(args, cache),
+        "xreadgroup" => handle_xreadgroup(args, cache, client),
+        "xack" => handle_xack(args, cache),
+        "xpending" => handle_xpending(args, cache),
+        "xclaim" => handle_xclaim(args, cache),
+        "xautoclaim" => handle_xautoclaim(args, cache),
+        "pfadd" => handle_pfadd(args, cache),
+        "pfcount" => handle_pfcount(args, cache),
+        "pfmerge" => handle_pfmerge(args, cache),
+        "setbit" => handle_setbit(args, cache),
+        "getbit" => handle_getbit(args, cache),
+        "bitcount" => handle_bitcount(args, cache),
+        "bitpos" => handle_bitpos(args, cache),
+        "bitop" => handle_bitop(args, cache),
+        "bitfield" | "bitfield_ro" => handle_bitfield(args, cache, command),
+        "mget" => handle_mget(args, cache),
+        "mset" | "msetnx" => handle_mset(args, cache, command),
+        "msetex" => handle_msetex(args, cache),
+        "append" => handle_append(args, cache),
+        "strlen" => handle_strlen(args, cache),
+        "getrange" | "substr" => handle_getrange(args, cache, command),
+        "setrange" => handle_setrange(args, cache),
+        "lcs" => handle_lcs(args, cache, client),
+        "getdel" => handle_getdel(args, cache),
+        "getex" => handle_getex(args, cache),
+        "getset" => handle_getset(args, cache),
+        "geoadd" => handle_geoadd(args, cache),
+        "geopos" => handle_geopos(args, cache, client),
+        "geodist" => handle_geodist(args, cache),
+        "geohash" => handle_geohash(args, cache),
+        "geosearch" => handle_geosearch(args, cache, client),
+        "geosearchstore" => handle_geosearchstore(args, cache),
+        unknown_command => {
+            let message = "-unknown command '".to_owned() + unknown_command + "'";
+            serialize_error(message.as_str())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{thread, time::Duration};
 
-    use crate::{
-        connection_manager::utils::throw_err_if_num_of_args_wrong,
-        resp::serialize::{serialize, InputVariants},
-    };
-
     use super::*;
 
     #[test]
     fn should_return_serialized_pong() {
         let input = Cow::Borrowed("*1\r\n$4\r\nPING\r\n");
-        assert_eq!("$4\r\npong\r\n", handle_command(input, &Cache::new()))
+        assert_eq!(
+            "$4\r\npong\r\n",
+            handle_command(input, &Cache::new(), &mut Client::new(None))
+        )
     }
     #[test]
     fn should_echo_hello_world() {
         let input = Cow::Borrowed("*2\r\n$4\r\nECHO\r\n$11\r\nHELLO WORLD\r\n");
         assert_eq!(
             "$11\r\nHELLO WORLD\r\n",
-            handle_command(input, &Cache::new())
+            handle_command(input, &Cache::new(), &mut Client::new(None))
         );
     }
 
@@ -76,7 +386,7 @@ mod tests {
         let input = Cow::Borrowed("*3\r\n$4\r\nECHO\r\n$4\r\nHEHE\r\n$4\r\nHEHE\r\n");
         assert_eq!(
             throw_err_if_num_of_args_wrong("echo"),
-            handle_command(input, &Cache::new())
+            handle_command(input, &Cache::new(), &mut Client::new(None))
         );
     }
 
@@ -85,7 +395,7 @@ mod tests {
         let input = Cow::Borrowed("*1\r\nSILLY");
         assert_eq!(
             serialize_error("-failed to deserialize"),
-            handle_command(input, &Cache::new())
+            handle_command(input, &Cache::new(), &mut Client::new(None))
         );
     }
 
@@ -94,7 +404,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$5\r\nECHOO\r\n$2\r\nRT\r\n");
         assert_eq!(
             serialize_error(format!("-unknown command '{}'", "echoo").as_str()),
-            handle_command(input, &Cache::new())
+            handle_command(input, &Cache::new(), &mut Client::new(None))
         );
     }
 
@@ -103,7 +413,7 @@ mod tests {
         let input = Cow::Borrowed("*3\r\n$3\r\nset\r\n$4\r\nname\r\n$12\r\nWizard of Oz\r\n");
         assert_eq!(
             serialize(InputVariants::StringVariant("+OK".to_string())),
-            handle_command(input, &Cache::new())
+            handle_command(input, &Cache::new(), &mut Client::new(None))
         );
     }
 
@@ -116,7 +426,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$3\r\nget\r\n$4\r\nname\r\n");
         assert_eq!(
             serialize(InputVariants::StringVariant("Wizard of Oz".to_string())),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         );
     }
 
@@ -127,12 +437,12 @@ mod tests {
             "*5\r\n$3\r\nset\r\n$4\r\nname\r\n$12\r\nWizard of Oz\r\n$2\r\nEX\r\n$1\r\n3",
         );
         let get_input = Cow::Borrowed("*2\r\n$3\r\nget\r\n$4\r\nname\r\n");
-        handle_command(set_input, &cache);
+        handle_command(set_input, &cache, &mut Client::new(None));
         thread::sleep(Duration::from_secs(4));
 
         assert_eq!(
             serialize(InputVariants::StringVariant("+(nil)".to_string())),
-            handle_command(get_input, &cache)
+            handle_command(get_input, &cache, &mut Client::new(None))
         );
     }
 
@@ -143,8 +453,8 @@ mod tests {
             "*5\r\n$3\r\nset\r\n$4\r\nname\r\n$12\r\nWizard of Oz\r\n$6\r\nEXATAT\r\n$1\r\n3",
         );
         assert_eq!(
-            serialize_error("-unknown SET variant"),
-            handle_command(set_input, &cache)
+            serialize_error("-ERR syntax error"),
+            handle_command(set_input, &cache, &mut Client::new(None))
         );
     }
 
@@ -155,8 +465,8 @@ mod tests {
             "*5\r\n$3\r\nset\r\n$4\r\nname\r\n$12\r\nWizard of Oz\r\n$6\r\nEXATAT\r\n$3\r\nAAA",
         );
         assert_eq!(
-            serialize_error("-invalid SET expiration"),
-            handle_command(set_input, &cache)
+            serialize_error("-ERR syntax error"),
+            handle_command(set_input, &cache, &mut Client::new(None))
         );
     }
 
@@ -176,7 +486,7 @@ mod tests {
             Cow::Borrowed("*4\r\n$6\r\nexists\r\n$4\r\nname\r\n$5\r\nname1\r\n$5\r\nname2\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(3)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 
@@ -186,7 +496,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$6\r\nexists\r\n$4\r\nname\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(0)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 
@@ -206,7 +516,7 @@ mod tests {
             Cow::Borrowed("*4\r\n$3\r\ndel\r\n$4\r\nname\r\n$5\r\nname1\r\n$5\r\nname2\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(3)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 
@@ -216,7 +526,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$3\r\ndel\r\n$4\r\nname\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(0)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 
@@ -226,7 +536,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$4\r\nincr\r\n$5\r\nmykey\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(1)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 
@@ -237,7 +547,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$4\r\nincr\r\n$5\r\nmykey\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(12)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 
@@ -247,7 +557,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$4\r\ndecr\r\n$5\r\nmykey\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(-1)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 
@@ -258,7 +568,7 @@ mod tests {
         let input = Cow::Borrowed("*2\r\n$4\r\ndecr\r\n$5\r\nmykey\r\n");
         assert_eq!(
             serialize(InputVariants::NumberVariant(10)),
-            handle_command(input, &cache)
+            handle_command(input, &cache, &mut Client::new(None))
         )
     }
 }
@@ -1,14 +1,21 @@
-use std::{
-    borrow::Cow,
-    time::{Duration, SystemTime, UNIX_EPOCH},
-};
+use std::borrow::Cow;
 
 use crate::{
     resp::serialize::{serialize, InputVariants},
-    store::db::Cache,
+    store::{
+        db::Cache,
+        string::{SetCondition, TtlUpdate, MAX_STRING_LEN, STRING_TOO_LONG_ERR},
+    },
 };
 
-use super::utils::{serialize_error, throw_err_if_num_of_args_wrong};
+use super::{
+    client::Client,
+    stream_commands::info_variant,
+    utils::{
+        optional_string, parse_expire_at, parse_float, parse_integer, serialize_error,
+        serialize_len, throw_err_if_num_of_args_wrong,
+    },
+};
 
 pub fn handle_echo(args: &[String]) -> Cow<'static, str> {
     match args.len() {
@@ -25,77 +32,209 @@ pub fn ignore_command() -> Cow<'static, str> {
     serialize(InputVariants::Nullish)
 }
 
-pub fn handle_get(args: &[String], cache: &Cache) -> Cow<'static, str> {
-    if let Some(key) = args.get(0) {
-        if let Ok(Some(response)) = cache.get(key) {
-            serialize(InputVariants::StringVariant(response))
-        } else {
-            serialize(InputVariants::StringVariant("+(nil)".to_string()))
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+pub fn handle_hello(args: &[String], client: &mut Client) -> Cow<'static, str> {
+    let protocol = match args.first().map(|arg| parse_integer(arg)) {
+        None => client.protocol,
+        Some(Ok(version @ (2 | 3))) => version as u8,
+        Some(Ok(_)) => return serialize_error("-NOPROTO unsupported protocol version"),
+        Some(Err(_)) => {
+            return serialize_error("-ERR Protocol version is not an integer or out of range")
+        }
+    };
+    let mut name = None;
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        match (option.to_uppercase().as_str(), options.next()) {
+            ("SETNAME", Some(client_name)) => name = Some(client_name.clone()),
+            ("AUTH", Some(_)) => return serialize_error(
+                "-ERR AUTH <password> called without any password configured for the default user",
+            ),
+            _ => return serialize_error("-ERR syntax error"),
         }
+    }
+    client.protocol = protocol;
+    if name.is_some() {
+        client.name = name;
+    }
+
+    let text = |value: &str| InputVariants::StringVariant(value.to_string());
+    let fields = vec![
+        (text("server"), text("redis")),
+        (text("version"), text(env!("CARGO_PKG_VERSION"))),
+        (
+            text("proto"),
+            InputVariants::NumberVariant(i64::from(protocol)),
+        ),
+        (text("id"), InputVariants::NumberVariant(client.id as i64)),
+        (text("mode"), text("standalone")),
+        (text("role"), text("master")),
+        (text("modules"), InputVariants::Array(Vec::new())),
+    ];
+    if protocol >= 3 {
+        serialize(InputVariants::Map(fields))
     } else {
-        serialize_error("-invalid GET arguments")
+        serialize(InputVariants::Array(
+            fields
+                .into_iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+        ))
     }
 }
 
-/// args.get(2) and args.get(3) suppose to give us expiration variant such as EX,PX and time of expiration respectively.
-pub fn handle_set(args: &[String], cache: &Cache) -> Cow<'static, str> {
-    match (args.get(2), args.get(3)) {
-        (Some(exp_variant), Some(exp_time_str)) => {
-            if let Ok(exp_time) = exp_time_str.parse::<u64>() {
-                match exp_variant.as_str() {
-                    "EX" => handle_set_with_expiration(args, cache, Duration::from_secs(exp_time)),
-                    "PX" => {
-                        handle_set_with_expiration(args, cache, Duration::from_millis(exp_time))
-                    }
-                    "EXAT" => {
-                        let now = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .expect("Impossibru!");
-                        handle_set_with_expiration(
-                            args,
-                            cache,
-                            Duration::from_secs(exp_time - now.as_secs()),
-                        )
-                    }
-                    "PXAT" => {
-                        let now = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .expect("Impossibru!");
-                        handle_set_with_expiration(
-                            args,
-                            cache,
-                            Duration::from_millis(exp_time - now.as_millis() as u64),
-                        )
-                    }
-                    _ => serialize_error("-unknown SET variant"),
+/// CLIENT ID | GETNAME | SETNAME name | UNBLOCK client-id [TIMEOUT|ERROR]
+pub fn handle_client(args: &[String], cache: &Cache, client: &mut Client) -> Cow<'static, str> {
+    let Some(subcommand) = args.first().map(|s| s.to_lowercase()) else {
+        return throw_err_if_num_of_args_wrong("client");
+    };
+    match (subcommand.as_str(), &args[1..]) {
+        ("id", []) => serialize(InputVariants::NumberVariant(client.id as i64)),
+        ("getname", []) => match &client.name {
+            Some(name) => serialize(InputVariants::StringVariant(name.clone())),
+            None => serialize(InputVariants::Nullish),
+        },
+        ("setname", [name]) if name.contains(' ') => serialize_error(
+            "-ERR Client names cannot contain spaces, newlines or special characters.",
+        ),
+        ("setname", [name]) => {
+            client.name = Some(name.clone()).filter(|name| !name.is_empty());
+            serialize(InputVariants::StringVariant("+OK".to_string()))
+        }
+        ("unblock", [id, mode @ ..]) if mode.len() <= 1 => {
+            let error = match mode.first().map(|s| s.to_uppercase()).as_deref() {
+                None | Some("TIMEOUT") => false,
+                Some("ERROR") => true,
+                Some(_) => {
+                    return serialize_error("-ERR CLIENT UNBLOCK reason should be TIMEOUT or ERROR")
+                }
+            };
+            match parse_integer(id) {
+                Ok(id) => {
+                    let unblocked =
+                        u64::try_from(id).is_ok_and(|id| cache.unblock_client(id, error));
+                    serialize(InputVariants::NumberVariant(i64::from(unblocked)))
                 }
-            } else {
-                serialize_error("-invalid SET expiration")
+                Err(err) => err,
             }
         }
-        _ => handle_set_without_expiration(args, cache),
+        ("id" | "getname" | "setname" | "unblock", _) => {
+            throw_err_if_num_of_args_wrong(concat_string!("client|", subcommand).as_str())
+        }
+        _ => serialize_error(
+            concat_string!(
+                "-ERR unknown subcommand '",
+                subcommand,
+                "'. Try CLIENT HELP."
+            )
+            .as_str(),
+        ),
     }
 }
 
-fn handle_set_without_expiration(args: &[String], cache: &Cache) -> Cow<'static, str> {
-    if let (Some(key), Some(value)) = (args.get(0), args.get(1)) {
-        match cache.set(key.clone(), value.clone()) {
-            Ok(_) => serialize(InputVariants::StringVariant("+OK".to_string())),
+pub fn handle_get(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    if let Some(key) = args.first() {
+        match cache.get(key) {
+            Ok(Some(response)) => serialize(InputVariants::BulkString(response)),
+            Ok(None) => serialize(InputVariants::StringVariant("+(nil)".to_string())),
             Err(err) => serialize_error(concat_string!("-", err).as_str()),
         }
     } else {
-        serialize_error("-invalid SET arguments")
+        serialize_error("-invalid GET arguments")
     }
 }
 
-fn handle_set_with_expiration(args: &[String], cache: &Cache, time: Duration) -> Cow<'static, str> {
-    if let (Some(key), Some(value)) = (args.get(0), args.get(1)) {
-        match cache.set_with_expiration(key.clone(), value.clone(), time) {
-            Ok(_) => serialize(InputVariants::StringVariant("+OK".to_string())),
-            Err(err) => serialize_error(concat_string!("-", err).as_str()),
+/// SET key value [NX | XX | IFEQ comparison-value] [GET]
+/// [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+pub fn handle_set(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, value, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("set");
+    };
+    let mut options = options;
+    let mut condition = None;
+    let mut ttl = None;
+    let mut get = false;
+    while let [option, tail @ ..] = options {
+        let option = option.to_uppercase();
+        options = match (option.as_str(), tail) {
+            ("NX", _) if condition.is_none() => {
+                condition = Some(SetCondition::Nx);
+                tail
+            }
+            ("XX", _) if condition.is_none() => {
+                condition = Some(SetCondition::Xx);
+                tail
+            }
+            ("IFEQ", [expected, tail @ ..]) if condition.is_none() => {
+                condition = Some(SetCondition::IfEq(expected.clone()));
+                tail
+            }
+            ("GET", _) => {
+                get = true;
+                tail
+            }
+            ("KEEPTTL", _) if ttl.is_none() => {
+                ttl = Some(TtlUpdate::Keep);
+                tail
+            }
+            ("EX" | "PX" | "EXAT" | "PXAT", [value, tail @ ..]) if ttl.is_none() => {
+                match parse_expire_at(&option, value, "set") {
+                    Ok(at) => ttl = Some(TtlUpdate::At(at)),
+                    Err(err) => return err,
+                }
+                tail
+            }
+            _ => return serialize_error("-ERR syntax error"),
+        };
+    }
+    let condition = condition.unwrap_or_default();
+    let ttl = ttl.unwrap_or(TtlUpdate::Persist);
+    match cache.set_with(key, value.clone(), &condition, ttl, get) {
+        Ok(outcome) if get => serialize(optional_string(outcome.previous)),
+        Ok(outcome) if outcome.written => {
+            serialize(InputVariants::StringVariant("+OK".to_string()))
         }
-    } else {
-        serialize_error("-invalid SET arguments")
+        Ok(_) => serialize(InputVariants::Nullish),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SETNX key value
+pub fn handle_setnx(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, value] = args else {
+        return throw_err_if_num_of_args_wrong("setnx");
+    };
+    match cache.set_with(
+        key,
+        value.clone(),
+        &SetCondition::Nx,
+        TtlUpdate::Persist,
+        false,
+    ) {
+        Ok(outcome) => serialize(InputVariants::NumberVariant(i64::from(outcome.written))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SETEX key seconds value and PSETEX key milliseconds value
+pub fn handle_setex(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, time, value] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let unit = if command == "psetex" { "PX" } else { "EX" };
+    let at = match parse_expire_at(unit, time, command) {
+        Ok(at) => at,
+        Err(err) => return err,
+    };
+    match cache.set_with(
+        key,
+        value.clone(),
+        &SetCondition::Always,
+        TtlUpdate::At(at),
+        false,
+    ) {
+        Ok(_) => serialize(InputVariants::StringVariant("+OK".to_string())),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
     }
 }
 
@@ -104,8 +243,8 @@ pub fn handle_exists(args: &[String], cache: &Cache) -> Cow<'static, str> {
         .iter()
         .filter(|key| cache.exists(key).unwrap_or(false))
         .count();
-    match i32::try_from(count) {
-        Ok(count_i32) => serialize(InputVariants::NumberVariant(count_i32)),
+    match i64::try_from(count) {
+        Ok(count_i64) => serialize(InputVariants::NumberVariant(count_i64)),
         Err(_) => serialize_error("-something went wrong during exists"),
     }
 }
@@ -121,54 +260,695 @@ pub fn handle_del(args: &[String], cache: &Cache) -> Cow<'static, str> {
             }
         })
         .count();
-    match i32::try_from(count) {
-        Ok(count_i32) => serialize(InputVariants::NumberVariant(count_i32)),
+    match i64::try_from(count) {
+        Ok(count_i64) => serialize(InputVariants::NumberVariant(count_i64)),
         Err(_) => serialize_error("-something went wrong during del"),
     }
 }
 
-pub fn handle_incr(args: &[String], cache: &Cache) -> Cow<'static, str> {
-    if let Some(key) = args.get(0) {
-        let existing_value = cache.get(key);
+/// INCR key, DECR key, INCRBY key increment and DECRBY key decrement
+pub fn handle_incr(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let (key, delta) = match (command, args) {
+        ("incr", [key]) => (key, 1),
+        ("decr", [key]) => (key, -1),
+        ("incrby", [key, delta]) => match parse_integer(delta) {
+            Ok(delta) => (key, delta),
+            Err(err) => return err,
+        },
+        ("decrby", [key, delta]) => match parse_integer(delta).map(i64::checked_neg) {
+            Ok(Some(delta)) => (key, delta),
+            Ok(None) => return serialize_error("-ERR decrement would overflow"),
+            Err(err) => return err,
+        },
+        _ => return throw_err_if_num_of_args_wrong(command),
+    };
+    match cache.incr_by(key, delta) {
+        Ok(updated) => serialize(InputVariants::NumberVariant(updated)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
 
-        if let Ok(Some(value_in_cache)) = existing_value {
-            match value_in_cache.parse::<i32>().map(|v| v + 1) {
-                Ok(new_value) => match cache.set(key.clone(), new_value.to_string()) {
-                    Ok(_) => serialize(InputVariants::NumberVariant(new_value)),
-                    Err(err) => serialize_error(concat_string!("-", err).as_str()),
-                },
-                Err(_) => serialize_error("-could not parse stored number"),
-            }
-        } else {
-            match cache.set(key.clone(), 1.to_string()) {
-                Ok(_) => serialize(InputVariants::NumberVariant(1)),
-                Err(err) => serialize_error(concat_string!("-", err).as_str()),
-            }
+/// INCRBYFLOAT key increment
+pub fn handle_incrbyfloat(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, delta] = args else {
+        return throw_err_if_num_of_args_wrong("incrbyfloat");
+    };
+    let delta = match parse_float(delta) {
+        Ok(delta) => delta,
+        Err(err) => return err,
+    };
+    match cache.incr_by_float(key, delta) {
+        Ok(updated) => serialize(InputVariants::StringVariant(updated)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// APPEND key value
+pub fn handle_append(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, value] = args else {
+        return throw_err_if_num_of_args_wrong("append");
+    };
+    match cache.append(key, value) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// STRLEN key
+pub fn handle_strlen(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("strlen");
+    };
+    match cache.strlen(key) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// GETRANGE key start end, also answering to its old name SUBSTR
+pub fn handle_getrange(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, start, end] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let (start, end) = match (parse_integer(start), parse_integer(end)) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    match cache.getrange(key, start, end) {
+        Ok(range) => serialize(InputVariants::BulkString(range)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SETRANGE key offset value
+pub fn handle_setrange(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, offset, value] = args else {
+        return throw_err_if_num_of_args_wrong("setrange");
+    };
+    let offset = match parse_integer(offset) {
+        Ok(offset) if offset < 0 => return serialize_error("-ERR offset is out of range"),
+        Ok(offset) if offset as u64 > MAX_STRING_LEN as u64 => {
+            return serialize_error(concat_string!("-", STRING_TOO_LONG_ERR).as_str())
         }
+        Ok(offset) => offset as usize,
+        Err(err) => return err,
+    };
+    match cache.setrange(key, offset, value) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// LCS key1 key2 [LEN] [IDX] [MINMATCHLEN min-match-len] [WITHMATCHLEN]
+pub fn handle_lcs(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [first, second, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("lcs");
+    };
+    let (mut len, mut idx, mut with_match_len, mut min_match_len) = (false, false, false, 0);
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match (option.to_uppercase().as_str(), options.len()) {
+            ("LEN", _) => len = true,
+            ("IDX", _) => idx = true,
+            ("WITHMATCHLEN", _) => with_match_len = true,
+            ("MINMATCHLEN", 1..) => match options.next().map(|arg| parse_integer(arg)) {
+                Some(Ok(value)) => min_match_len = value.max(0) as usize,
+                Some(Err(err)) => return err,
+                None => unreachable!("checked there is an argument left"),
+            },
+            _ => return serialize_error("-ERR syntax error"),
+        }
+    }
+    if len && idx {
+        return serialize_error(
+            "-ERR If you want both the length and indexes, please just use IDX.",
+        );
+    }
+    let lcs = match cache.lcs(first, second) {
+        Ok(lcs) => lcs,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    let total = lcs.text.chars().count();
+    if len {
+        return serialize_len(total);
+    }
+    if !idx {
+        return serialize(InputVariants::StringVariant(lcs.text));
+    }
+    let range = |(start, end): (usize, usize)| {
+        InputVariants::Array(vec![
+            InputVariants::NumberVariant(start as i64),
+            InputVariants::NumberVariant(end as i64),
+        ])
+    };
+    let matches = lcs
+        .matches
+        .into_iter()
+        .filter(|found| found.len >= min_match_len)
+        .map(|found| {
+            let mut item = vec![range(found.first), range(found.second)];
+            if with_match_len {
+                item.push(InputVariants::NumberVariant(found.len as i64));
+            }
+            InputVariants::Array(item)
+        })
+        .collect();
+    serialize(info_variant(
+        vec![
+            ("matches", InputVariants::Array(matches)),
+            ("len", InputVariants::NumberVariant(total as i64)),
+        ],
+        client.protocol,
+    ))
+}
+
+/// GETDEL key
+pub fn handle_getdel(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("getdel");
+    };
+    match cache.getdel(key) {
+        Ok(value) => serialize(optional_string(value)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+/// PXAT unix-time-milliseconds | PERSIST]
+pub fn handle_getex(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("getex");
+    };
+    let update = match options {
+        [] => TtlUpdate::Keep,
+        [option] if option.eq_ignore_ascii_case("PERSIST") => TtlUpdate::Persist,
+        [option, value] => match option.to_uppercase().as_str() {
+            unit @ ("EX" | "PX" | "EXAT" | "PXAT") => match parse_expire_at(unit, value, "getex") {
+                Ok(at) => TtlUpdate::At(at),
+                Err(err) => return err,
+            },
+            _ => return serialize_error("-ERR syntax error"),
+        },
+        _ => return serialize_error("-ERR syntax error"),
+    };
+    match cache.getex(key, update) {
+        Ok(value) => serialize(optional_string(value)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// GETSET key value
+pub fn handle_getset(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, value] = args else {
+        return throw_err_if_num_of_args_wrong("getset");
+    };
+    match cache.getset(key, value.clone()) {
+        Ok(previous) => serialize(optional_string(previous)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// MGET key [key ...]
+pub fn handle_mget(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    if args.is_empty() {
+        return throw_err_if_num_of_args_wrong("mget");
+    }
+    match cache.mget(args) {
+        Ok(values) => serialize(InputVariants::Array(
+            values.into_iter().map(optional_string).collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+fn parse_pairs(args: &[String], command: &str) -> Result<Vec<(String, String)>, Cow<'static, str>> {
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return Err(throw_err_if_num_of_args_wrong(command));
+    }
+    Ok(args
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect())
+}
+
+/// MSET key value [key value ...] and MSETNX key value [key value ...]
+pub fn handle_mset(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let pairs = match parse_pairs(args, command) {
+        Ok(pairs) => pairs,
+        Err(err) => return err,
+    };
+    let condition = if command == "msetnx" {
+        SetCondition::Nx
     } else {
-        serialize_error("-invalid INCR arguments")
+        SetCondition::Always
+    };
+    match cache.mset(&pairs, &condition, TtlUpdate::Persist) {
+        Ok(set) if command == "msetnx" => serialize(InputVariants::NumberVariant(i64::from(set))),
+        Ok(_) => serialize(InputVariants::StringVariant("+OK".to_string())),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
     }
 }
 
-pub fn handle_decr(args: &[String], cache: &Cache) -> Cow<'static, str> {
-    if let Some(key) = args.get(0) {
-        let existing_value = cache.get(key);
+/// MSETEX numkeys key value [key value ...] [NX | XX]
+/// [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+pub fn handle_msetex(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [numkeys, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("msetex");
+    };
+    let numkeys = match parse_integer(numkeys) {
+        Ok(numkeys) if numkeys > 0 => numkeys as usize,
+        Ok(_) => return serialize_error("-ERR numkeys should be greater than 0"),
+        Err(err) => return err,
+    };
+    if numkeys.checked_mul(2).is_none_or(|len| len > rest.len()) {
+        return throw_err_if_num_of_args_wrong("msetex");
+    }
+    let (pairs, mut options) = rest.split_at(numkeys * 2);
+    let pairs = match parse_pairs(pairs, "msetex") {
+        Ok(pairs) => pairs,
+        Err(err) => return err,
+    };
 
-        if let Ok(Some(value_in_cache)) = existing_value {
-            match value_in_cache.parse::<i32>().map(|v| v - 1) {
-                Ok(new_value) => match cache.set(key.clone(), new_value.to_string()) {
-                    Ok(_) => serialize(InputVariants::NumberVariant(new_value)),
-                    Err(err) => serialize_error(concat_string!("-", err).as_str()),
-                },
-                Err(_) => serialize_error("-could not parse stored number"),
+    let mut condition = None;
+    let mut ttl = None;
+    while let [option, tail @ ..] = options {
+        let option = option.to_uppercase();
+        options = match (option.as_str(), tail) {
+            ("NX" | "XX", _) if condition.is_none() => {
+                condition = Some(if option == "NX" {
+                    SetCondition::Nx
+                } else {
+                    SetCondition::Xx
+                });
+                tail
             }
-        } else {
-            match cache.set(key.clone(), (-1).to_string()) {
-                Ok(_) => serialize(InputVariants::NumberVariant(-1)),
-                Err(err) => serialize_error(concat_string!("-", err).as_str()),
+            ("KEEPTTL", _) if ttl.is_none() => {
+                ttl = Some(TtlUpdate::Keep);
+                tail
             }
+            ("EX" | "PX" | "EXAT" | "PXAT", [value, tail @ ..]) if ttl.is_none() => {
+                match parse_expire_at(&option, value, "msetex") {
+                    Ok(at) => ttl = Some(TtlUpdate::At(at)),
+                    Err(err) => return err,
+                }
+                tail
+            }
+            _ => return serialize_error("-ERR syntax error"),
+        };
+    }
+    let condition = condition.unwrap_or_default();
+    match cache.mset(&pairs, &condition, ttl.unwrap_or(TtlUpdate::Persist)) {
+        Ok(set) => serialize(InputVariants::NumberVariant(i64::from(set))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    #[test]
+    fn should_append_and_read_ranges() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["APPEND", "s", "Hello"]), ":5\r\n");
+        assert_eq!(run(&cache, client, &["APPEND", "s", " World"]), ":11\r\n");
+        assert_eq!(run(&cache, client, &["STRLEN", "s"]), ":11\r\n");
+        assert_eq!(run(&cache, client, &["STRLEN", "nope"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["GETRANGE", "s", "0", "4"]),
+            "$5\r\nHello\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GETRANGE", "s", "-5", "-1"]),
+            "$5\r\nWorld\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SUBSTR", "s", "6", "100"]),
+            "$5\r\nWorld\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GETRANGE", "s", "-1", "-5"]),
+            "$0\r\n\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GETRANGE", "nope", "0", "-1"]),
+            "$0\r\n\r\n"
+        );
+
+        assert_eq!(
+            run(&cache, client, &["SETRANGE", "s", "6", "Redis"]),
+            ":11\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "s"]), "$11\r\nHello Redis\r\n");
+        assert_eq!(
+            run(&cache, client, &["SETRANGE", "pad", "3", "x"]),
+            ":4\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "pad"]), "$4\r\n\0\0\0x\r\n");
+        assert_eq!(
+            run(&cache, client, &["SETRANGE", "empty", "3", ""]),
+            ":0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "empty"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["SETRANGE", "s", "-1", "x"]),
+            "-ERR offset is out of range\r\n"
+        );
+        run(&cache, client, &["SADD", "set", "a"]);
+        assert!(run(&cache, client, &["APPEND", "set", "a"]).starts_with("-WRONGTYPE"));
+    }
+
+    #[test]
+    fn should_find_longest_common_subsequence() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "key1", "ohmytext"]);
+        run(&cache, client, &["SET", "key2", "mynewtext"]);
+        assert_eq!(
+            run(&cache, client, &["LCS", "key1", "key2"]),
+            "$6\r\nmytext\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["LCS", "key1", "key2", "LEN"]),
+            ":6\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["LCS", "key1", "key2", "IDX"]),
+            "*4\r\n$7\r\nmatches\r\n*2\r\n*2\r\n*2\r\n:4\r\n:7\r\n*2\r\n:5\r\n:8\r\n\
+             *2\r\n*2\r\n:2\r\n:3\r\n*2\r\n:0\r\n:1\r\n$3\r\nlen\r\n:6\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["LCS", "key1", "key2", "IDX", "MINMATCHLEN", "4", "WITHMATCHLEN"]),
+            "*4\r\n$7\r\nmatches\r\n*1\r\n*3\r\n*2\r\n:4\r\n:7\r\n*2\r\n:5\r\n:8\r\n:4\r\n$3\r\nlen\r\n:6\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["LCS", "key1", "key2", "LEN", "IDX"]),
+            "-ERR If you want both the length and indexes, please just use IDX.\r\n"
+        );
+        run(&cache, client, &["SADD", "set", "a"]);
+        assert_eq!(
+            run(&cache, client, &["LCS", "key1", "set"]),
+            "-ERR The specified keys must contain string values\r\n"
+        );
+    }
+
+    #[test]
+    fn should_get_and_replace_or_delete() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["GETSET", "s", "one"]), "$-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["GETSET", "s", "two"]),
+            "$3\r\none\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GETDEL", "s"]), "$3\r\ntwo\r\n");
+        assert_eq!(run(&cache, client, &["GETDEL", "s"]), "$-1\r\n");
+
+        run(&cache, client, &["SET", "s", "v"]);
+        assert_eq!(
+            run(&cache, client, &["GETEX", "s", "PX", "100000"]),
+            "$1\r\nv\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GETEX", "s", "PERSIST"]),
+            "$1\r\nv\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GETEX", "s", "EXAT", "1"]),
+            "$1\r\nv\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "s"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["GETEX", "s", "EX", "0"]),
+            "-ERR invalid expire time in 'getex' command\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GETEX", "s", "EX", "1", "PERSIST"]),
+            "-ERR syntax error\r\n"
+        );
+    }
+
+    #[test]
+    fn should_increment_64_bit_counters() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "n", "2147483647"]);
+        assert_eq!(run(&cache, client, &["INCR", "n"]), ":2147483648\r\n");
+        assert_eq!(
+            run(&cache, client, &["DECRBY", "n", "2147483650"]),
+            ":-2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["INCRBY", "n", "9223372036854775807"]),
+            ":9223372036854775805\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["INCRBY", "n", "3"]),
+            "-ERR increment or decrement would overflow\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["DECRBY", "n", "-9223372036854775808"]),
+            "-ERR decrement would overflow\r\n"
+        );
+        for value in ["+1", " 1", "01"] {
+            run(&cache, client, &["SET", "s", value]);
+            assert_eq!(
+                run(&cache, client, &["INCR", "s"]),
+                "-ERR value is not an integer or out of range\r\n"
+            );
+            assert_eq!(
+                run(&cache, client, &["INCRBY", "n", value]),
+                "-ERR value is not an integer or out of range\r\n"
+            );
         }
-    } else {
-        serialize_error("-invalid INCR arguments")
+        run(&cache, client, &["SADD", "set", "x"]);
+        assert!(run(&cache, client, &["DECR", "set"]).starts_with("-WRONGTYPE"));
+    }
+
+    #[test]
+    fn should_increment_floats_like_redis() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "f", "10.50"]);
+        assert_eq!(
+            run(&cache, client, &["INCRBYFLOAT", "f", "0.1"]),
+            "$4\r\n10.6\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["INCRBYFLOAT", "f", "-5"]),
+            "$3\r\n5.6\r\n"
+        );
+        run(&cache, client, &["SET", "f", "5.0e3"]);
+        assert_eq!(
+            run(&cache, client, &["INCRBYFLOAT", "f", "2.0e2"]),
+            "$4\r\n5200\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["INCRBYFLOAT", "new", "0.1"]),
+            "$3\r\n0.1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["INCRBYFLOAT", "new", "0.2"]),
+            "$3\r\n0.3\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["INCRBYFLOAT", "f", "inf"]),
+            "-ERR increment would produce NaN or Infinity\r\n"
+        );
+        run(&cache, client, &["SET", "s", "abc"]);
+        assert_eq!(
+            run(&cache, client, &["INCRBYFLOAT", "s", "1"]),
+            "-ERR value is not a valid float\r\n"
+        );
+    }
+
+    #[test]
+    fn should_set_with_options_in_any_order() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["SET", "k", "v", "XX"]), "$-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "v", "GET", "EX", "100", "NX"]),
+            "$-1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["SET", "k", "w", "NX"]), "$-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "w", "keepttl", "get", "xx"]),
+            "$1\r\nv\r\n"
+        );
+        assert_eq!(run(&cache, client, &["SET", "k", "x"]), "+OK\r\n");
+
+        for conflicting in [
+            &["SET", "k", "v", "NX", "XX"][..],
+            &["SET", "k", "v", "IFEQ", "x", "NX"],
+            &["SET", "k", "v", "EX", "10", "PX", "10"],
+            &["SET", "k", "v", "KEEPTTL", "EXAT", "10"],
+            &["SET", "k", "v", "EX"],
+            &["SET", "k", "v", "EXATAT", "10"],
+        ] {
+            assert_eq!(run(&cache, client, conflicting), "-ERR syntax error\r\n");
+        }
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "v", "EX", "0"]),
+            "-ERR invalid expire time in 'set' command\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SET", "k"]),
+            "-ERR wrong number of arguments for 'set' command\r\n"
+        );
+    }
+
+    #[test]
+    fn should_compare_and_set_with_ifeq() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "v", "IFEQ", "old"]),
+            "$-1\r\n"
+        );
+        run(&cache, client, &["SET", "k", "old"]);
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "v", "IFEQ", "nope"]),
+            "$-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "new", "IFEQ", "old", "GET"]),
+            "$3\r\nold\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "k"]), "$3\r\nnew\r\n");
+        run(&cache, client, &["SADD", "set", "x"]);
+        assert!(run(&cache, client, &["SET", "set", "v", "IFEQ", "x"]).starts_with("-WRONGTYPE"));
+        assert!(run(&cache, client, &["SET", "set", "v", "GET"]).starts_with("-WRONGTYPE"));
+        assert_eq!(run(&cache, client, &["SET", "set", "v"]), "+OK\r\n");
+    }
+
+    #[test]
+    fn should_delete_right_away_when_expiring_in_the_past() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "k", "old"]);
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "v", "EXAT", "1", "GET"]),
+            "$3\r\nold\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "k"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "v", "PXAT", "1"]),
+            "+OK\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "k"]), ":0\r\n");
+    }
+
+    #[test]
+    fn should_set_with_the_legacy_commands() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["SETNX", "k", "v"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["SETNX", "k", "w"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["SETEX", "k", "100", "w"]), "+OK\r\n");
+        assert_eq!(
+            run(&cache, client, &["PSETEX", "p", "100000", "x"]),
+            "+OK\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "p"]), "$1\r\nx\r\n");
+        assert_eq!(
+            run(&cache, client, &["SETEX", "k", "-1", "w"]),
+            "-ERR invalid expire time in 'setex' command\r\n"
+        );
+    }
+
+    #[test]
+    fn should_set_and_get_several_keys() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["MSET", "a", "1", "b", "2"]),
+            "+OK\r\n"
+        );
+        run(&cache, client, &["SADD", "set", "x"]);
+        assert_eq!(
+            run(&cache, client, &["MGET", "a", "nope", "b", "set"]),
+            "*4\r\n$1\r\n1\r\n$-1\r\n$1\r\n2\r\n$-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["MSET", "a", "1", "b"]),
+            "-ERR wrong number of arguments for 'mset' command\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["MSETNX", "c", "3", "a", "9"]),
+            ":0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["MGET", "a", "c"]),
+            "*2\r\n$1\r\n1\r\n$-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["MSETNX", "c", "3", "d", "4"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["MGET", "c", "d"]),
+            "*2\r\n$1\r\n3\r\n$1\r\n4\r\n"
+        );
+    }
+
+    #[test]
+    fn should_set_several_keys_with_a_shared_ttl() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["MSETEX", "2", "a", "1", "b", "2", "XX", "EX", "100"]
+            ),
+            ":0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "a"]), ":0\r\n");
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["MSETEX", "2", "a", "1", "b", "2", "NX", "PX", "100000"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["MSETEX", "1", "a", "new", "XX", "KEEPTTL"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["GET", "a"]), "$3\r\nnew\r\n");
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["MSETEX", "2", "a", "1", "b", "2", "PXAT", "1"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "a", "b"]), ":0\r\n");
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["MSETEX", "1", "a", "1", "EX", "10", "KEEPTTL"]
+            ),
+            "-ERR syntax error\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["MSETEX", "2", "a", "1"]),
+            "-ERR wrong number of arguments for 'msetex' command\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["MSETEX", "0", "a", "1"]),
+            "-ERR numkeys should be greater than 0\r\n"
+        );
     }
 }
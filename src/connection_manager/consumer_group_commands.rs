@@ -0,0 +1,909 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        blocking::ServeFn,
+        consumer_group::{
+            read_group, AutoClaimOptions, DeliveredEntry, GroupRead, GroupReader, GroupStart,
+            PendingFilter, XClaimOptions, NOGROUP_ERR,
+        },
+        db::{unix_time_ms, Cache},
+        stream::{StreamEntry, StreamId},
+    },
+};
+
+use super::{
+    client::Client,
+    stream_commands::{
+        entries_variant, entry_variant, info_variant, parse_id, parse_range_bound, parse_read_args,
+        serialize_streams, ReadArgs,
+    },
+    utils::{
+        parse_integer, serialize_block_outcome, serialize_error, serialize_len,
+        throw_err_if_num_of_args_wrong,
+    },
+};
+
+/// Store errors of commands that name the key and group as XPENDING and XCLAIM do.
+fn serialize_group_error(err: &str, key: &str, group: &str) -> Cow<'static, str> {
+    if err != NOGROUP_ERR {
+        return serialize_error(concat_string!("-", err).as_str());
+    }
+    serialize_error(
+        concat_string!(
+            "-NOGROUP No such key '",
+            key,
+            "' or consumer group '",
+            group,
+            "'"
+        )
+        .as_str(),
+    )
+}
+
+/// Store errors of XGROUP and XINFO CONSUMERS, which only run against an existing stream.
+fn serialize_xgroup_error(err: &str, key: &str, group: &str) -> Cow<'static, str> {
+    if err != NOGROUP_ERR {
+        return serialize_error(concat_string!("-", err).as_str());
+    }
+    serialize_error(
+        concat_string!(
+            "-NOGROUP No such consumer group '",
+            group,
+            "' for key name '",
+            key,
+            "'"
+        )
+        .as_str(),
+    )
+}
+
+fn delivered_variant(entries: Vec<DeliveredEntry>) -> InputVariants {
+    InputVariants::Array(
+        entries
+            .into_iter()
+            .map(|(id, fields)| match fields {
+                Some(fields) => entry_variant((id, fields)),
+                None => InputVariants::Array(vec![
+                    InputVariants::StringVariant(id.to_string()),
+                    InputVariants::NullArray,
+                ]),
+            })
+            .collect(),
+    )
+}
+
+fn ids_variant(ids: impl IntoIterator<Item = StreamId>) -> InputVariants {
+    InputVariants::StringVariantArr(ids.into_iter().map(|id| id.to_string()).collect())
+}
+
+/// Milliseconds arguments where negative values count as zero.
+fn parse_millis(arg: &str) -> Result<u64, Cow<'static, str>> {
+    Ok(parse_integer(arg)?.max(0) as u64)
+}
+
+fn parse_group_start(arg: &str) -> Result<GroupStart, Cow<'static, str>> {
+    match arg {
+        "$" => Ok(GroupStart::LastEntry),
+        arg => Ok(GroupStart::Id(parse_id(arg)?)),
+    }
+}
+
+/// Parses what follows the ID of XGROUP CREATE and SETID: `[MKSTREAM] [ENTRIESREAD n]`, with
+/// MKSTREAM only valid for CREATE. An entries read count of -1 means unknown.
+fn parse_group_options(
+    args: &[String],
+    allow_mkstream: bool,
+) -> Result<(bool, Option<u64>), Cow<'static, str>> {
+    let mut mkstream = false;
+    let mut entries_read = None;
+    let mut args = args.iter();
+    while let Some(option) = args.next() {
+        match option.to_uppercase().as_str() {
+            "MKSTREAM" if allow_mkstream => mkstream = true,
+            "ENTRIESREAD" => match args.next().map(|arg| parse_integer(arg)) {
+                Some(Ok(-1)) => entries_read = None,
+                Some(Ok(read)) if read >= 0 => entries_read = Some(read as u64),
+                Some(Ok(_)) => {
+                    return Err(serialize_error(
+                        "-ERR value for ENTRIESREAD must be positive or -1",
+                    ))
+                }
+                Some(Err(err)) => return Err(err),
+                None => return Err(serialize_error("-ERR syntax error")),
+            },
+            _ => return Err(serialize_error("-ERR syntax error")),
+        }
+    }
+    Ok((mkstream, entries_read))
+}
+
+/// XGROUP CREATE key group id|$ [MKSTREAM] [ENTRIESREAD n], XGROUP SETID key group id|$
+/// [ENTRIESREAD n], XGROUP DESTROY key group, XGROUP CREATECONSUMER key group consumer and
+/// XGROUP DELCONSUMER key group consumer
+pub fn handle_xgroup(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let Some((subcommand, args)) = args.split_first() else {
+        return throw_err_if_num_of_args_wrong("xgroup");
+    };
+    let subcommand = subcommand.to_lowercase();
+    let wrong_args =
+        || throw_err_if_num_of_args_wrong(concat_string!("xgroup|", subcommand).as_str());
+    let ok = || serialize(InputVariants::StringVariant("+OK".to_string()));
+
+    match (subcommand.as_str(), args) {
+        ("create", [key, group, id, options @ ..]) | ("setid", [key, group, id, options @ ..]) => {
+            let create = subcommand == "create";
+            let (start, (mkstream, entries_read)) =
+                match (parse_group_start(id), parse_group_options(options, create)) {
+                    (Ok(start), Ok(options)) => (start, options),
+                    (Err(err), _) | (_, Err(err)) => return err,
+                };
+            let result = if create {
+                cache.xgroup_create(key, group, start, mkstream, entries_read)
+            } else {
+                cache.xgroup_setid(key, group, start, entries_read)
+            };
+            match result {
+                Ok(()) => ok(),
+                Err(err) => serialize_xgroup_error(err, key, group),
+            }
+        }
+        ("destroy", [key, group]) => match cache.xgroup_destroy(key, group) {
+            Ok(destroyed) => serialize_len(usize::from(destroyed)),
+            Err(err) => serialize_xgroup_error(err, key, group),
+        },
+        ("createconsumer", [key, group, consumer]) => {
+            match cache.xgroup_createconsumer(key, group, consumer) {
+                Ok(created) => serialize_len(usize::from(created)),
+                Err(err) => serialize_xgroup_error(err, key, group),
+            }
+        }
+        ("delconsumer", [key, group, consumer]) => {
+            match cache.xgroup_delconsumer(key, group, consumer) {
+                Ok(pending) => serialize_len(pending),
+                Err(err) => serialize_xgroup_error(err, key, group),
+            }
+        }
+        ("create" | "setid" | "destroy" | "createconsumer" | "delconsumer", _) => wrong_args(),
+        _ => serialize_error(
+            concat_string!(
+                "-ERR unknown subcommand '",
+                subcommand,
+                "'. Try XGROUP HELP."
+            )
+            .as_str(),
+        ),
+    }
+}
+
+/// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key
+/// [key ...] id [id ...]
+///
+/// `>` hands out entries never delivered to the group and adds them to the consumer's pending
+/// list; any other ID replays the consumer's own pending entries after it. Only `>` reads
+/// block.
+pub fn handle_xreadgroup(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [option, group, consumer, args @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("xreadgroup");
+    };
+    if !option.eq_ignore_ascii_case("group") {
+        return serialize_error("-ERR syntax error");
+    }
+    let ReadArgs {
+        count,
+        block,
+        no_ack,
+        keys,
+        ids,
+    } = match parse_read_args(args, "xreadgroup") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    let mut streams = Vec::with_capacity(keys.len());
+    for (key, id) in keys.iter().zip(ids) {
+        let read = match id.as_str() {
+            ">" => GroupRead::New,
+            "$" => return serialize_error("-ERR The $ ID is meaningless in the context of XREADGROUP: you want to read the history of this consumer by specifying a proper ID, or use the > ID to get new messages. The $ ID would just return an empty result set."),
+            id => match parse_id(id) {
+                Ok(id) => GroupRead::History(id),
+                Err(err) => return err,
+            },
+        };
+        streams.push((key.clone(), read));
+    }
+    let reader = GroupReader {
+        group: group.clone(),
+        consumer: consumer.clone(),
+        count,
+        no_ack,
+    };
+
+    match cache.xreadgroup(&streams, &reader) {
+        Ok(found) if !found.is_empty() => {
+            let found = found
+                .into_iter()
+                .map(|(key, entries)| (key, delivered_variant(entries)))
+                .collect();
+            return serialize_streams(found, client.protocol);
+        }
+        Ok(_) => {}
+        Err(NOGROUP_ERR) => {
+            let key = keys
+                .iter()
+                .find(|key| !cache.xgroup_exists(key, group))
+                .unwrap_or(&keys[0]);
+            return serialize_error(
+                concat_string!(
+                    "-NOGROUP No such key '",
+                    key,
+                    "' or consumer group '",
+                    group,
+                    "' in XREADGROUP with GROUP option"
+                )
+                .as_str(),
+            );
+        }
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    }
+    let Some(timeout) = block else {
+        return serialize(InputVariants::NullArray);
+    };
+    let protocol = client.protocol;
+    let serve: ServeFn = Box::new(move |data, key| {
+        match read_group(data, key, &reader, GroupRead::New, unix_time_ms()) {
+            Ok(entries) => Ok(entries.map(|entries| {
+                serialize_streams(
+                    vec![(key.to_string(), delivered_variant(entries))],
+                    protocol,
+                )
+                .into_owned()
+            })),
+            Err(NOGROUP_ERR) => Ok(Some(
+                serialize_error(
+                    "-NOGROUP the consumer group this client was blocked on no longer exists",
+                )
+                .into_owned(),
+            )),
+            Err(err) => Err(err),
+        }
+    });
+    let outcome = cache.block_on(
+        client.id,
+        keys.to_vec(),
+        timeout,
+        client.may_block(),
+        serve,
+        &|| client.is_connected(),
+    );
+    serialize_block_outcome(outcome, InputVariants::NullArray)
+}
+
+/// XACK key group id [id ...]
+pub fn handle_xack(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, group, ids @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("xack");
+    };
+    if ids.is_empty() {
+        return throw_err_if_num_of_args_wrong("xack");
+    }
+    let ids = match ids
+        .iter()
+        .map(|id| parse_id(id))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(ids) => ids,
+        Err(err) => return err,
+    };
+    match cache.xack(key, group, &ids) {
+        Ok(acked) => serialize_len(acked),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+///
+/// The short form sums up the pending entries list, the extended one lists entries with their
+/// owner, idle time and delivery count.
+pub fn handle_xpending(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, group, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("xpending");
+    };
+    if options.is_empty() {
+        let summary = match cache.xpending_summary(key, group) {
+            Ok(summary) => summary,
+            Err(err) => return serialize_group_error(err, key, group),
+        };
+        if summary.count == 0 {
+            return serialize(InputVariants::Array(vec![
+                InputVariants::NumberVariant(0),
+                InputVariants::Nullish,
+                InputVariants::Nullish,
+                InputVariants::NullArray,
+            ]));
+        }
+        let optional_id = |id: Option<StreamId>| match id {
+            Some(id) => InputVariants::StringVariant(id.to_string()),
+            None => InputVariants::Nullish,
+        };
+        return serialize(InputVariants::Array(vec![
+            InputVariants::NumberVariant(summary.count as i64),
+            optional_id(summary.first),
+            optional_id(summary.last),
+            InputVariants::Array(
+                summary
+                    .consumers
+                    .into_iter()
+                    .map(|(name, pending)| {
+                        InputVariants::StringVariantArr(vec![name, pending.to_string()])
+                    })
+                    .collect(),
+            ),
+        ]));
+    }
+
+    let (min_idle, options) = match options {
+        [option, idle, rest @ ..] if option.eq_ignore_ascii_case("idle") => {
+            match parse_millis(idle) {
+                Ok(idle) => (Some(idle), rest),
+                Err(err) => return err,
+            }
+        }
+        _ => (None, options),
+    };
+    let (start, end, count, consumer) = match options {
+        [start, end, count] => (start, end, count, None),
+        [start, end, count, consumer] => (start, end, count, Some(consumer.clone())),
+        _ => return serialize_error("-ERR syntax error"),
+    };
+    let filter = match (
+        parse_range_bound(start, true),
+        parse_range_bound(end, false),
+        parse_integer(count),
+    ) {
+        (Ok(start), Ok(end), Ok(count)) => PendingFilter {
+            min_idle,
+            start,
+            end,
+            count: count.max(0) as usize,
+            consumer,
+        },
+        (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => return err,
+    };
+    match cache.xpending(key, group, &filter) {
+        Ok(pending) => serialize(InputVariants::Array(
+            pending
+                .into_iter()
+                .map(|info| {
+                    InputVariants::Array(vec![
+                        InputVariants::StringVariant(info.id.to_string()),
+                        InputVariants::StringVariant(info.consumer),
+                        InputVariants::NumberVariant(info.idle as i64),
+                        InputVariants::NumberVariant(info.delivery_count as i64),
+                    ])
+                })
+                .collect(),
+        )),
+        Err(err) => serialize_group_error(err, key, group),
+    }
+}
+
+fn claimed_variant(claimed: Vec<StreamEntry>, just_id: bool) -> InputVariants {
+    if just_id {
+        ids_variant(claimed.into_iter().map(|(id, _)| id))
+    } else {
+        entries_variant(claimed)
+    }
+}
+
+/// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds]
+/// [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID lastid]
+pub fn handle_xclaim(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, group, consumer, min_idle, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("xclaim");
+    };
+    let min_idle = match parse_integer(min_idle) {
+        Ok(min_idle) => min_idle.max(0) as u64,
+        Err(_) => return serialize_error("-ERR Invalid min-idle-time argument for XCLAIM"),
+    };
+    let id_count = rest
+        .iter()
+        .take_while(|arg| StreamId::parse(arg, 0).is_some())
+        .count();
+    let (ids, options) = rest.split_at(id_count);
+    if ids.is_empty() {
+        return match rest.first() {
+            Some(_) => {
+                serialize_error("-ERR Invalid stream ID specified as stream command argument")
+            }
+            None => throw_err_if_num_of_args_wrong("xclaim"),
+        };
+    }
+    let ids: Vec<StreamId> = ids.iter().filter_map(|id| StreamId::parse(id, 0)).collect();
+
+    let mut claim_options = XClaimOptions::default();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let upper = option.to_uppercase();
+        let parsed = match upper.as_str() {
+            "FORCE" => {
+                claim_options.force = true;
+                Ok(())
+            }
+            "JUSTID" => {
+                claim_options.just_id = true;
+                Ok(())
+            }
+            "IDLE" | "TIME" | "RETRYCOUNT" | "LASTID" => match options.next() {
+                Some(value) => match upper.as_str() {
+                    "IDLE" => parse_millis(value).map(|idle| claim_options.idle = Some(idle)),
+                    "TIME" => parse_millis(value).map(|time| claim_options.time = Some(time)),
+                    "RETRYCOUNT" => parse_millis(value)
+                        .map(|retry_count| claim_options.retry_count = Some(retry_count)),
+                    _ => parse_id(value).map(|last_id| claim_options.last_id = Some(last_id)),
+                },
+                None => Err(serialize_error("-ERR syntax error")),
+            },
+            _ => Err(serialize_error(
+                concat_string!("-ERR Unrecognized XCLAIM option '", option, "'").as_str(),
+            )),
+        };
+        if let Err(err) = parsed {
+            return err;
+        }
+    }
+
+    match cache.xclaim(key, group, consumer, min_idle, &ids, &claim_options) {
+        Ok(claimed) => serialize(claimed_variant(claimed, claim_options.just_id)),
+        Err(err) => serialize_group_error(err, key, group),
+    }
+}
+
+/// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+pub fn handle_xautoclaim(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, group, consumer, min_idle, start, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("xautoclaim");
+    };
+    let min_idle = match parse_integer(min_idle) {
+        Ok(min_idle) => min_idle.max(0) as u64,
+        Err(_) => return serialize_error("-ERR Invalid min-idle-time argument for XAUTOCLAIM"),
+    };
+    let start = match parse_range_bound(start, true) {
+        Ok(start) => start,
+        Err(err) => return err,
+    };
+    let mut count = 100;
+    let mut just_id = false;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.to_uppercase().as_str() {
+            "JUSTID" => just_id = true,
+            "COUNT" => match options.next().map(|count| parse_integer(count)) {
+                Some(Ok(value)) if value > 0 => count = value as usize,
+                Some(Ok(_)) => return serialize_error("-ERR COUNT must be > 0"),
+                Some(Err(err)) => return err,
+                None => return serialize_error("-ERR syntax error"),
+            },
+            _ => return serialize_error("-ERR syntax error"),
+        }
+    }
+    let options = AutoClaimOptions {
+        min_idle,
+        start,
+        count,
+        just_id,
+    };
+
+    match cache.xautoclaim(key, group, consumer, &options) {
+        Ok(claimed) => serialize(InputVariants::Array(vec![
+            InputVariants::StringVariant(claimed.next.to_string()),
+            claimed_variant(claimed.claimed, just_id),
+            ids_variant(claimed.deleted),
+        ])),
+        Err(err) => serialize_group_error(err, key, group),
+    }
+}
+
+/// XINFO GROUPS key
+pub(super) fn handle_xinfo_groups(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("xinfo|groups");
+    };
+    let optional_number = |value: Option<u64>| match value {
+        Some(value) => InputVariants::NumberVariant(value as i64),
+        None => InputVariants::Nullish,
+    };
+    match cache.xinfo_groups(key) {
+        Ok(Some(groups)) => serialize(InputVariants::Array(
+            groups
+                .into_iter()
+                .map(|info| {
+                    info_variant(
+                        vec![
+                            ("name", InputVariants::StringVariant(info.name)),
+                            (
+                                "consumers",
+                                InputVariants::NumberVariant(info.consumers as i64),
+                            ),
+                            ("pending", InputVariants::NumberVariant(info.pending as i64)),
+                            (
+                                "last-delivered-id",
+                                InputVariants::StringVariant(info.last_delivered_id.to_string()),
+                            ),
+                            ("entries-read", optional_number(info.entries_read)),
+                            ("lag", optional_number(info.lag)),
+                        ],
+                        client.protocol,
+                    )
+                })
+                .collect(),
+        )),
+        Ok(None) => serialize_error("-ERR no such key"),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// XINFO CONSUMERS key group
+pub(super) fn handle_xinfo_consumers(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+) -> Cow<'static, str> {
+    let [key, group] = args else {
+        return throw_err_if_num_of_args_wrong("xinfo|consumers");
+    };
+    match cache.xinfo_consumers(key, group) {
+        Ok(consumers) => serialize(InputVariants::Array(
+            consumers
+                .into_iter()
+                .map(|info| {
+                    info_variant(
+                        vec![
+                            ("name", InputVariants::StringVariant(info.name)),
+                            ("pending", InputVariants::NumberVariant(info.pending as i64)),
+                            ("idle", InputVariants::NumberVariant(info.idle as i64)),
+                            (
+                                "inactive",
+                                InputVariants::NumberVariant(
+                                    info.inactive.map_or(-1, |inactive| inactive as i64),
+                                ),
+                            ),
+                        ],
+                        client.protocol,
+                    )
+                })
+                .collect(),
+        )),
+        Err(err) => serialize_xgroup_error(err, key, group),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    fn entry(id: &str, field: &str, value: &str) -> String {
+        concat_string!(
+            "*2\r\n$",
+            id.len().to_string(),
+            "\r\n",
+            id,
+            "\r\n*2\r\n$1\r\n",
+            field,
+            "\r\n$1\r\n",
+            value,
+            "\r\n"
+        )
+    }
+
+    fn filled(cache: &Cache, client: &mut Client) {
+        for (id, value) in [("1-1", "a"), ("2-1", "b"), ("3-1", "c")] {
+            run(cache, client, &["XADD", "s", id, "f", value]);
+        }
+    }
+
+    #[test]
+    fn should_manage_groups() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["XGROUP", "CREATE", "s", "g", "$"]),
+            "-ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"]
+            ),
+            "+OK\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XGROUP", "CREATE", "s", "g", "0"]),
+            "-BUSYGROUP Consumer Group name already exists\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XGROUP", "SETID", "s", "h", "0"]),
+            "-NOGROUP No such consumer group 'h' for key name 's'\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XGROUP", "CREATECONSUMER", "s", "g", "bob"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XGROUP", "CREATECONSUMER", "s", "g", "bob"]
+            ),
+            ":0\r\n"
+        );
+        assert!(run(&cache, client, &["XINFO", "STREAM", "s"]).contains("$6\r\ngroups\r\n:1\r\n"));
+        assert_eq!(
+            run(&cache, client, &["XGROUP", "DELCONSUMER", "s", "g", "bob"]),
+            ":0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XGROUP", "DESTROY", "s", "g"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XGROUP", "DESTROY", "s", "g"]),
+            ":0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["XINFO", "GROUPS", "s"]), "*0\r\n");
+    }
+
+    #[test]
+    fn should_track_pending_entries() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        filled(&cache, client);
+        run(&cache, client, &["XGROUP", "CREATE", "s", "g", "0"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "XREADGROUP",
+                    "GROUP",
+                    "g",
+                    "alice",
+                    "COUNT",
+                    "2",
+                    "STREAMS",
+                    "s",
+                    ">"
+                ]
+            ),
+            concat_string!(
+                "*1\r\n*2\r\n$1\r\ns\r\n*2\r\n",
+                entry("1-1", "f", "a"),
+                entry("2-1", "f", "b")
+            )
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XREADGROUP", "GROUP", "x", "alice", "STREAMS", "s", ">"]
+            ),
+            "-NOGROUP No such key 's' or consumer group 'x' in XREADGROUP with GROUP option\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XPENDING", "s", "g"]),
+            "*4\r\n:2\r\n$3\r\n1-1\r\n$3\r\n2-1\r\n*1\r\n*2\r\n$5\r\nalice\r\n$1\r\n2\r\n"
+        );
+
+        run(&cache, client, &["XDEL", "s", "2-1"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XREADGROUP", "GROUP", "g", "alice", "STREAMS", "s", "0"]
+            ),
+            concat_string!(
+                "*1\r\n*2\r\n$1\r\ns\r\n*2\r\n",
+                entry("1-1", "f", "a"),
+                "*2\r\n$3\r\n2-1\r\n*-1\r\n"
+            )
+        );
+        let pending = run(
+            &cache,
+            client,
+            &["XPENDING", "s", "g", "-", "+", "10", "alice"],
+        );
+        assert!(pending.starts_with("*2\r\n*4\r\n$3\r\n1-1\r\n$5\r\nalice\r\n:"));
+        assert!(pending.ends_with(":2\r\n"));
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XPENDING", "s", "g", "IDLE", "60000", "-", "+", "10"]
+            ),
+            "*0\r\n"
+        );
+
+        assert_eq!(
+            run(&cache, client, &["XACK", "s", "g", "1-1", "2-1", "9-9"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XPENDING", "s", "g"]),
+            "*4\r\n:0\r\n$-1\r\n$-1\r\n*-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XINFO", "GROUPS", "s"]),
+            concat_string!(
+                "*1\r\n*12\r\n",
+                "$4\r\nname\r\n$1\r\ng\r\n",
+                "$9\r\nconsumers\r\n:1\r\n",
+                "$7\r\npending\r\n:0\r\n",
+                "$17\r\nlast-delivered-id\r\n$3\r\n2-1\r\n",
+                "$12\r\nentries-read\r\n:2\r\n",
+                "$3\r\nlag\r\n$-1\r\n"
+            )
+        );
+        run(
+            &cache,
+            client,
+            &["XREADGROUP", "GROUP", "g", "alice", "STREAMS", "s", ">"],
+        );
+        let groups = run(&cache, client, &["XINFO", "GROUPS", "s"]);
+        assert!(groups.ends_with("$12\r\nentries-read\r\n:3\r\n$3\r\nlag\r\n:0\r\n"));
+    }
+
+    #[test]
+    fn should_claim_idle_entries() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        filled(&cache, client);
+        run(&cache, client, &["XGROUP", "CREATE", "s", "g", "0"]);
+        run(
+            &cache,
+            client,
+            &["XREADGROUP", "GROUP", "g", "alice", "STREAMS", "s", ">"],
+        );
+        assert_eq!(
+            run(&cache, client, &["XCLAIM", "s", "g", "bob", "60000", "1-1"]),
+            "*0\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XCLAIM", "s", "g", "bob", "0", "1-1", "IDLE", "120000", "JUSTID"]
+            ),
+            "*1\r\n$3\r\n1-1\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XCLAIM", "s", "g", "bob", "60000", "1-1", "BOGUS"]
+            ),
+            "-ERR Unrecognized XCLAIM option 'BOGUS'\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XCLAIM", "s", "g", "carol", "60000", "1-1"]
+            ),
+            concat_string!("*1\r\n", entry("1-1", "f", "a"))
+        );
+
+        run(&cache, client, &["XDEL", "s", "2-1"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XAUTOCLAIM", "s", "g", "dave", "0", "0", "COUNT", "1"]
+            ),
+            concat_string!(
+                "*3\r\n$3\r\n2-1\r\n*1\r\n",
+                entry("1-1", "f", "a"),
+                "*0\r\n"
+            )
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XAUTOCLAIM", "s", "g", "dave", "0", "2-1", "JUSTID"]
+            ),
+            "*3\r\n$3\r\n0-0\r\n*1\r\n$3\r\n3-1\r\n*1\r\n$3\r\n2-1\r\n"
+        );
+        let pending = run(&cache, client, &["XPENDING", "s", "g", "-", "+", "10"]);
+        assert!(pending.starts_with("*2\r\n*4\r\n$3\r\n1-1\r\n$4\r\ndave\r\n"));
+        assert!(pending.contains(":3\r\n*4\r\n$3\r\n3-1\r\n$4\r\ndave\r\n"));
+        assert!(pending.ends_with(":1\r\n"));
+
+        let consumers = run(&cache, client, &["XINFO", "CONSUMERS", "s", "g"]);
+        assert!(consumers
+            .starts_with("*4\r\n*8\r\n$4\r\nname\r\n$5\r\nalice\r\n$7\r\npending\r\n:0\r\n"));
+        assert_eq!(
+            run(&cache, client, &["XGROUP", "DELCONSUMER", "s", "g", "dave"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XPENDING", "s", "g"]),
+            "*4\r\n:0\r\n$-1\r\n$-1\r\n*-1\r\n"
+        );
+    }
+
+    #[test]
+    fn should_hand_new_entries_to_one_blocked_consumer() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(
+            &cache,
+            client,
+            &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"],
+        );
+        let readers: Vec<_> = ["alice", "bob"]
+            .into_iter()
+            .map(|consumer| {
+                let blocked = cache.clone();
+                let mut reader = Client::new(None);
+                let id = reader.id;
+                let handle = thread::spawn(move || {
+                    run(
+                        &blocked,
+                        &mut reader,
+                        &[
+                            "XREADGROUP",
+                            "GROUP",
+                            "g",
+                            consumer,
+                            "BLOCK",
+                            "0",
+                            "STREAMS",
+                            "s",
+                            ">",
+                        ],
+                    )
+                });
+                (id, handle)
+            })
+            .collect();
+        let started = Instant::now();
+        for (id, _) in &readers {
+            while !cache.is_client_blocked(*id) {
+                assert!(started.elapsed() < Duration::from_secs(5));
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+
+        run(&cache, client, &["XADD", "s", "1-1", "f", "a"]);
+        run(&cache, client, &["XGROUP", "DESTROY", "s", "g"]);
+        let mut replies: Vec<String> = readers
+            .into_iter()
+            .map(|(_, handle)| handle.join().unwrap())
+            .collect();
+        replies.sort();
+        assert_eq!(
+            replies,
+            [
+                concat_string!("*1\r\n*2\r\n$1\r\ns\r\n*1\r\n", entry("1-1", "f", "a")),
+                "-NOGROUP the consumer group this client was blocked on no longer exists\r\n"
+                    .to_string(),
+            ]
+        );
+    }
+}
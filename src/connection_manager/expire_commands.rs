@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        db::{unix_time_ms, Cache},
+        expire::{KEY_MISSING, KEY_WITHOUT_TTL},
+        hash::ExpireCondition,
+    },
+};
+
+use super::utils::{parse_integer, serialize_error, throw_err_if_num_of_args_wrong};
+
+/// EXPIRE/PEXPIRE/EXPIREAT/PEXPIREAT key time [NX | XX | GT | LT]
+pub fn handle_expire(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, time, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let time = match parse_integer(time) {
+        Ok(time) => time,
+        Err(err) => return err,
+    };
+    let mut conditions = Vec::with_capacity(options.len());
+    for option in options {
+        conditions.push(match option.to_uppercase().as_str() {
+            "NX" => ExpireCondition::Nx,
+            "XX" => ExpireCondition::Xx,
+            "GT" => ExpireCondition::Gt,
+            "LT" => ExpireCondition::Lt,
+            _ => {
+                return serialize_error(concat_string!("-ERR Unsupported option ", option).as_str())
+            }
+        });
+    }
+    let has = |condition| conditions.contains(&condition);
+    if has(ExpireCondition::Nx)
+        && (has(ExpireCondition::Xx) || has(ExpireCondition::Gt) || has(ExpireCondition::Lt))
+    {
+        return serialize_error(
+            "-ERR NX and XX, GT or LT options at the same time are not compatible",
+        );
+    }
+    if has(ExpireCondition::Gt) && has(ExpireCondition::Lt) {
+        return serialize_error("-ERR GT and LT options at the same time are not compatible");
+    }
+
+    let now = unix_time_ms() as i64;
+    let at = match command {
+        "expire" => time.checked_mul(1000).and_then(|ms| ms.checked_add(now)),
+        "pexpire" => time.checked_add(now),
+        "expireat" => time.checked_mul(1000),
+        _ => Some(time),
+    };
+    let Some(at) = at else {
+        return serialize_error(
+            concat_string!("-ERR invalid expire time in '", command, "' command").as_str(),
+        );
+    };
+    match cache.expire(key, at, &conditions) {
+        Ok(updated) => serialize(InputVariants::NumberVariant(i64::from(updated))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// TTL/PTTL/EXPIRETIME/PEXPIRETIME key
+pub fn handle_ttl(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let at = match cache.expire_time(key) {
+        Ok(at) => at,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    let now = unix_time_ms() as i64;
+    let reply = match (at, command) {
+        (KEY_MISSING | KEY_WITHOUT_TTL, _) => at,
+        (at, "ttl") => ((at - now).max(0) + 500) / 1000,
+        (at, "pttl") => (at - now).max(0),
+        (at, "expiretime") => (at + 500) / 1000,
+        (at, _) => at,
+    };
+    serialize(InputVariants::NumberVariant(reply))
+}
+
+/// PERSIST key
+pub fn handle_persist(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("persist");
+    };
+    match cache.persist(key) {
+        Ok(removed) => serialize(InputVariants::NumberVariant(i64::from(removed))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection_manager::{client::Client, test_utils::run};
+
+    use super::*;
+
+    #[test]
+    fn should_set_and_read_key_ttls() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["EXPIRE", "k", "100"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["TTL", "k"]), ":-2\r\n");
+        run(&cache, client, &["SET", "k", "v"]);
+        assert_eq!(run(&cache, client, &["TTL", "k"]), ":-1\r\n");
+        assert_eq!(run(&cache, client, &["PEXPIRETIME", "k"]), ":-1\r\n");
+
+        assert_eq!(run(&cache, client, &["EXPIRE", "k", "100"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["TTL", "k"]), ":100\r\n");
+        let pttl: i64 = run(&cache, client, &["PTTL", "k"])[1..]
+            .trim()
+            .parse()
+            .unwrap();
+        assert!((99_000..=100_000).contains(&pttl));
+
+        assert_eq!(
+            run(&cache, client, &["EXPIREAT", "k", "33177117420"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["EXPIRETIME", "k"]),
+            ":33177117420\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["PEXPIRETIME", "k"]),
+            ":33177117420000\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["PEXPIREAT", "k", "33177117420001", "lt"]),
+            ":0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["PEXPIREAT", "k", "33177117420001", "gt"]),
+            ":1\r\n"
+        );
+
+        assert_eq!(run(&cache, client, &["PERSIST", "k"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["PERSIST", "k"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["TTL", "k"]), ":-1\r\n");
+
+        assert_eq!(run(&cache, client, &["PEXPIRE", "k", "-1"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["EXISTS", "k"]), ":0\r\n");
+    }
+
+    #[test]
+    fn should_reject_bad_expire_options() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["EXPIRE", "k", "10", "NX", "XX"]),
+            "-ERR NX and XX, GT or LT options at the same time are not compatible\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["EXPIRE", "k", "10", "GT", "LT"]),
+            "-ERR GT and LT options at the same time are not compatible\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["EXPIRE", "k", "10", "YY"]),
+            "-ERR Unsupported option YY\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["EXPIRE", "k", "9223372036854775807"]),
+            "-ERR invalid expire time in 'expire' command\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["EXPIRE", "k", "ten"]),
+            "-ERR value is not an integer or out of range\r\n"
+        );
+    }
+}
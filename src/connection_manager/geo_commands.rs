@@ -0,0 +1,755 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        db::Cache,
+        geo::{
+            decode_score, distance, geohash_score, geohash_string, valid_coordinates, GeoCenter,
+            GeoOrder, GeoSearch, GeoShape,
+        },
+        zset::ZAddOptions,
+    },
+};
+
+use super::{
+    client::Client,
+    utils::{
+        parse_float, parse_integer, serialize_error, serialize_len, throw_err_if_num_of_args_wrong,
+    },
+    zset_commands::score_variant,
+};
+
+/// Meters per unit of M, KM, FT or MI.
+fn parse_unit(arg: &str) -> Result<f64, Cow<'static, str>> {
+    match arg.to_lowercase().as_str() {
+        "m" => Ok(1.0),
+        "km" => Ok(1000.0),
+        "ft" => Ok(0.3048),
+        "mi" => Ok(1609.34),
+        _ => Err(serialize_error(
+            "-ERR unsupported unit provided. please use M, KM, FT, MI",
+        )),
+    }
+}
+
+fn parse_coordinates(longitude: &str, latitude: &str) -> Result<(f64, f64), Cow<'static, str>> {
+    let (longitude, latitude) = (parse_float(longitude)?, parse_float(latitude)?);
+    if !valid_coordinates(longitude, latitude) {
+        let message = format!("-ERR invalid longitude,latitude pair {longitude:.6},{latitude:.6}");
+        return Err(serialize_error(&message));
+    }
+    Ok((longitude, latitude))
+}
+
+/// Distances are always bulk strings with four decimals.
+fn distance_variant(meters: f64, unit: f64) -> InputVariants {
+    InputVariants::StringVariant(format!("{:.4}", meters / unit))
+}
+
+fn coordinates_variant(longitude: f64, latitude: f64, protocol: u8) -> InputVariants {
+    InputVariants::Array(vec![
+        score_variant(longitude, protocol),
+        score_variant(latitude, protocol),
+    ])
+}
+
+/// GEOADD key [NX|XX] [CH] longitude latitude member [longitude latitude member ...]
+pub fn handle_geoadd(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("geoadd");
+    };
+    let mut options = ZAddOptions::default();
+    let mut changed = false;
+    let mut flags = 0;
+    for option in rest {
+        match option.to_uppercase().as_str() {
+            "NX" => options.nx = true,
+            "XX" => options.xx = true,
+            "CH" => changed = true,
+            _ => break,
+        }
+        flags += 1;
+    }
+    let triples = &rest[flags..];
+    if triples.is_empty() {
+        return throw_err_if_num_of_args_wrong("geoadd");
+    }
+    if triples.len() % 3 != 0 || (options.nx && options.xx) {
+        return serialize_error("-ERR syntax error");
+    }
+    let mut elements = Vec::with_capacity(triples.len() / 3);
+    for triple in triples.chunks(3) {
+        match parse_coordinates(&triple[0], &triple[1]) {
+            Ok((longitude, latitude)) => {
+                elements.push((geohash_score(longitude, latitude), triple[2].clone()))
+            }
+            Err(err) => return err,
+        }
+    }
+    match cache.zadd(key, &options, &elements) {
+        Ok(outcome) if changed => serialize_len(outcome.added + outcome.updated),
+        Ok(outcome) => serialize_len(outcome.added),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// GEOPOS key [member ...]
+pub fn handle_geopos(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [key, members @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("geopos");
+    };
+    match cache.zmscore(key, members) {
+        Ok(scores) => serialize(InputVariants::Array(
+            scores
+                .into_iter()
+                .map(|score| match score.map(decode_score) {
+                    Some((longitude, latitude)) => {
+                        coordinates_variant(longitude, latitude, client.protocol)
+                    }
+                    None => InputVariants::NullArray,
+                })
+                .collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// GEODIST key member1 member2 [M|KM|FT|MI]
+pub fn handle_geodist(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let (key, members, unit) = match args {
+        [key, first, second] => (key, [first.clone(), second.clone()], Ok(1.0)),
+        [key, first, second, unit] => (key, [first.clone(), second.clone()], parse_unit(unit)),
+        [_, _, _, _, ..] => return serialize_error("-ERR syntax error"),
+        _ => return throw_err_if_num_of_args_wrong("geodist"),
+    };
+    let unit = match unit {
+        Ok(unit) => unit,
+        Err(err) => return err,
+    };
+    match cache.zmscore(key, &members) {
+        Ok(scores) => match (scores[0], scores[1]) {
+            (Some(first), Some(second)) => {
+                let (long1, lat1) = decode_score(first);
+                let (long2, lat2) = decode_score(second);
+                serialize(distance_variant(distance(long1, lat1, long2, lat2), unit))
+            }
+            _ => serialize(InputVariants::Nullish),
+        },
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// GEOHASH key [member ...]
+pub fn handle_geohash(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, members @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("geohash");
+    };
+    match cache.zmscore(key, members) {
+        Ok(scores) => serialize(InputVariants::Array(
+            scores
+                .into_iter()
+                .map(|score| match score {
+                    Some(score) => InputVariants::StringVariant(geohash_string(score)),
+                    None => InputVariants::Nullish,
+                })
+                .collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// Options of GEOSEARCH and GEOSEARCHSTORE besides the keys.
+struct SearchArgs {
+    search: GeoSearch,
+    /// Meters per unit of the shape, which distances are reported in.
+    unit: f64,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+    store_dist: bool,
+}
+
+/// Parses `FROMMEMBER member | FROMLONLAT longitude latitude`,
+/// `BYRADIUS radius unit | BYBOX width height unit`, `[ASC|DESC]`, `[COUNT count [ANY]]` and
+/// either the WITH* flags or, for GEOSEARCHSTORE, `STOREDIST`.
+fn parse_search_args(
+    args: &[String],
+    command: &str,
+    store: bool,
+) -> Result<SearchArgs, Cow<'static, str>> {
+    let mut centers = Vec::new();
+    let mut shapes = Vec::new();
+    let mut order = None;
+    let mut count = None;
+    let mut any = false;
+    let (mut with_coord, mut with_dist, mut with_hash, mut store_dist) =
+        (false, false, false, false);
+    let mut rest = args;
+    while let [option, tail @ ..] = rest {
+        rest = match (option.to_uppercase().as_str(), tail) {
+            ("FROMMEMBER", [member, tail @ ..]) => {
+                centers.push(GeoCenter::Member(member.clone()));
+                tail
+            }
+            ("FROMLONLAT", [longitude, latitude, tail @ ..]) => {
+                let (longitude, latitude) = parse_coordinates(longitude, latitude)?;
+                centers.push(GeoCenter::Coordinates(longitude, latitude));
+                tail
+            }
+            ("BYRADIUS", [radius, unit, tail @ ..]) => {
+                let radius = radius
+                    .parse::<f64>()
+                    .map_err(|_| serialize_error("-ERR need numeric radius"))?;
+                if radius < 0.0 {
+                    return Err(serialize_error("-ERR radius cannot be negative"));
+                }
+                let unit = parse_unit(unit)?;
+                shapes.push((GeoShape::Radius(radius * unit), unit));
+                tail
+            }
+            ("BYBOX", [width, height, unit, tail @ ..]) => {
+                let width = width
+                    .parse::<f64>()
+                    .map_err(|_| serialize_error("-ERR need numeric width"))?;
+                let height = height
+                    .parse::<f64>()
+                    .map_err(|_| serialize_error("-ERR need numeric height"))?;
+                if width < 0.0 || height < 0.0 {
+                    return Err(serialize_error("-ERR height or width cannot be negative"));
+                }
+                let unit = parse_unit(unit)?;
+                let shape = GeoShape::Box {
+                    width: width * unit,
+                    height: height * unit,
+                };
+                shapes.push((shape, unit));
+                tail
+            }
+            ("ASC", _) => {
+                order = Some(GeoOrder::Asc);
+                tail
+            }
+            ("DESC", _) => {
+                order = Some(GeoOrder::Desc);
+                tail
+            }
+            ("COUNT", [value, tail @ ..]) => {
+                let value = parse_integer(value)?;
+                if value <= 0 {
+                    return Err(serialize_error("-ERR COUNT must be > 0"));
+                }
+                count = Some(value as usize);
+                match tail {
+                    [flag, tail @ ..] if flag.eq_ignore_ascii_case("ANY") => {
+                        any = true;
+                        tail
+                    }
+                    _ => tail,
+                }
+            }
+            ("WITHCOORD", _) => {
+                with_coord = true;
+                tail
+            }
+            ("WITHDIST", _) => {
+                with_dist = true;
+                tail
+            }
+            ("WITHHASH", _) => {
+                with_hash = true;
+                tail
+            }
+            ("STOREDIST", _) if store => {
+                store_dist = true;
+                tail
+            }
+            _ => return Err(serialize_error("-ERR syntax error")),
+        };
+    }
+
+    let upper = command.to_uppercase();
+    if store && (with_coord || with_dist || with_hash) {
+        return Err(serialize_error(&concat_string!(
+            "-ERR STORE option in ",
+            upper,
+            " is not compatible with WITHDIST, WITHHASH and WITHCOORD options"
+        )));
+    }
+    let ([center], [(shape, unit)]) = (centers.as_slice(), shapes.as_slice()) else {
+        let message = if centers.len() != 1 {
+            concat_string!(
+                "-ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for ",
+                upper
+            )
+        } else {
+            concat_string!(
+                "-ERR exactly one of BYRADIUS and BYBOX arguments must be provided for ",
+                upper
+            )
+        };
+        return Err(serialize_error(&message));
+    };
+    Ok(SearchArgs {
+        search: GeoSearch {
+            center: center.clone(),
+            shape: *shape,
+            order,
+            count,
+            any,
+        },
+        unit: *unit,
+        with_coord,
+        with_dist,
+        with_hash,
+        store_dist,
+    })
+}
+
+/// GEOSEARCH key FROMMEMBER member | FROMLONLAT longitude latitude
+/// BYRADIUS radius unit | BYBOX width height unit [ASC|DESC] [COUNT count [ANY]]
+/// [WITHCOORD] [WITHDIST] [WITHHASH]
+pub fn handle_geosearch(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [key, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("geosearch");
+    };
+    if options.len() < 4 {
+        return throw_err_if_num_of_args_wrong("geosearch");
+    }
+    let parsed = match parse_search_args(options, "geosearch", false) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    let matches = match cache.geosearch(key, &parsed.search) {
+        Ok(matches) => matches,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    if !(parsed.with_coord || parsed.with_dist || parsed.with_hash) {
+        return serialize(InputVariants::StringVariantArr(
+            matches.into_iter().map(|found| found.member).collect(),
+        ));
+    }
+    serialize(InputVariants::Array(
+        matches
+            .into_iter()
+            .map(|found| {
+                let mut item = vec![InputVariants::StringVariant(found.member)];
+                if parsed.with_dist {
+                    item.push(distance_variant(found.distance, parsed.unit));
+                }
+                if parsed.with_hash {
+                    item.push(InputVariants::NumberVariant(found.score as i64));
+                }
+                if parsed.with_coord {
+                    item.push(coordinates_variant(
+                        found.longitude,
+                        found.latitude,
+                        client.protocol,
+                    ));
+                }
+                InputVariants::Array(item)
+            })
+            .collect(),
+    ))
+}
+
+/// GEOSEARCHSTORE destination source FROMMEMBER member | FROMLONLAT longitude latitude
+/// BYRADIUS radius unit | BYBOX width height unit [ASC|DESC] [COUNT count [ANY]] [STOREDIST]
+pub fn handle_geosearchstore(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [destination, source, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("geosearchstore");
+    };
+    if options.len() < 4 {
+        return throw_err_if_num_of_args_wrong("geosearchstore");
+    }
+    let parsed = match parse_search_args(options, "geosearchstore", true) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    let distance_unit = parsed.store_dist.then_some(parsed.unit);
+    match cache.geosearchstore(destination, source, &parsed.search, distance_unit) {
+        Ok(stored) => serialize_len(stored),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    fn sicily(cache: &Cache, client: &mut Client) {
+        let reply = run(
+            cache,
+            client,
+            &[
+                "GEOADD",
+                "Sicily",
+                "13.361389",
+                "38.115556",
+                "Palermo",
+                "15.087269",
+                "37.502669",
+                "Catania",
+            ],
+        );
+        assert_eq!(reply, ":2\r\n");
+    }
+
+    #[test]
+    fn should_add_members_and_measure_them() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        sicily(&cache, client);
+        assert_eq!(
+            run(&cache, client, &["ZSCORE", "Sicily", "Palermo"]),
+            "$16\r\n3479099956230698\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GEODIST", "Sicily", "Palermo", "Catania"]),
+            "$11\r\n166274.1516\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["GEODIST", "Sicily", "Palermo", "Catania", "km"]
+            ),
+            "$8\r\n166.2742\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["GEODIST", "Sicily", "Palermo", "Catania", "mi"]
+            ),
+            "$8\r\n103.3182\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GEODIST", "Sicily", "Palermo", "Nope"]),
+            "$-1\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["GEOHASH", "Sicily", "Palermo", "Catania", "Nope"]
+            ),
+            "*3\r\n$11\r\nsqc8b49rny0\r\n$11\r\nsqdtr74hyu0\r\n$-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GEOPOS", "Sicily", "Palermo", "Nope"]),
+            "*2\r\n*2\r\n$18\r\n13.361389338970184\r\n$16\r\n38.1155563954963\r\n*-1\r\n"
+        );
+
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["GEOADD", "Sicily", "XX", "CH", "13.5", "38.1", "Palermo", "1", "1", "Nope"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GEOADD", "Sicily", "200", "10", "Bad"]),
+            "-ERR invalid longitude,latitude pair 200.000000,10.000000\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["GEOADD", "Sicily", "NX", "XX", "1", "1", "a"]
+            ),
+            "-ERR syntax error\r\n"
+        );
+    }
+
+    #[test]
+    fn should_search_by_radius_and_box() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        sicily(&cache, client);
+        run(
+            &cache,
+            client,
+            &[
+                "GEOADD",
+                "Sicily",
+                "12.758489",
+                "38.788135",
+                "edge1",
+                "17.241510",
+                "38.788135",
+                "edge2",
+            ],
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "200",
+                    "km",
+                    "ASC"
+                ]
+            ),
+            "*2\r\n$7\r\nCatania\r\n$7\r\nPalermo\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GEOSEARCH", "Sicily", "FROMLONLAT", "15", "37", "BYBOX", "400", "400", "km", "ASC", "WITHDIST"]),
+            "*4\r\n*2\r\n$7\r\nCatania\r\n$7\r\n56.4413\r\n*2\r\n$7\r\nPalermo\r\n$8\r\n190.4424\r\n\
+             *2\r\n$5\r\nedge2\r\n$8\r\n279.7403\r\n*2\r\n$5\r\nedge1\r\n$8\r\n279.7405\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "BYRADIUS",
+                    "200",
+                    "km",
+                    "DESC",
+                    "COUNT",
+                    "1",
+                    "WITHHASH"
+                ]
+            ),
+            "*1\r\n*2\r\n$7\r\nCatania\r\n:3479447370796909\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Nope",
+                    "BYRADIUS",
+                    "1",
+                    "km"
+                ]
+            ),
+            "-ERR could not decode requested zset member\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "BYRADIUS",
+                    "1",
+                    "km",
+                    "BYBOX",
+                    "1",
+                    "1",
+                    "km"
+                ]
+            ),
+            "-ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "1",
+                    "yd"
+                ]
+            ),
+            "-ERR unsupported unit provided. please use M, KM, FT, MI\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "1",
+                    "km",
+                    "ANY"
+                ]
+            ),
+            "-ERR syntax error\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCH",
+                    "Nope",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "1",
+                    "km"
+                ]
+            ),
+            "*0\r\n"
+        );
+    }
+
+    #[test]
+    fn should_store_search_results() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        sicily(&cache, client);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCHSTORE",
+                    "near",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "100",
+                    "km"
+                ]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["GEOHASH", "near", "Catania"]),
+            "*1\r\n$11\r\nsqdtr74hyu0\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCHSTORE",
+                    "dist",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "200",
+                    "km",
+                    "STOREDIST"
+                ]
+            ),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "dist", "0", "-1"]),
+            "*2\r\n$7\r\nCatania\r\n$7\r\nPalermo\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCHSTORE",
+                    "dist",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "0",
+                    "0",
+                    "BYRADIUS",
+                    "1",
+                    "km"
+                ]
+            ),
+            ":0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "dist"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["GEOSEARCHSTORE", "dist", "Sicily", "FROMLONLAT", "15", "37", "BYRADIUS", "1", "km", "WITHDIST"]),
+            "-ERR STORE option in GEOSEARCHSTORE is not compatible with WITHDIST, WITHHASH and WITHCOORD options\r\n"
+        );
+    }
+
+    #[test]
+    fn should_drop_the_ttl_of_a_stored_destination() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        sicily(&cache, client);
+        run(&cache, client, &["SET", "near", "x", "EX", "1000"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "GEOSEARCHSTORE",
+                    "near",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "100",
+                    "km"
+                ]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["TTL", "near"]), ":-1\r\n");
+    }
+
+    #[test]
+    fn should_wake_clients_blocked_on_a_stored_destination() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        sicily(&cache, client);
+        let blocked = cache.clone();
+        let mut waiter = Client::new(None);
+        let id = waiter.id;
+        let handle = thread::spawn(move || run(&blocked, &mut waiter, &["BZPOPMIN", "near", "0"]));
+        while !cache.is_client_blocked(id) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        run(
+            &cache,
+            client,
+            &[
+                "GEOSEARCHSTORE",
+                "near",
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "100",
+                "km",
+            ],
+        );
+        assert!(handle
+            .join()
+            .unwrap()
+            .starts_with("*3\r\n$4\r\nnear\r\n$7\r\nCatania\r\n"));
+        assert_eq!(run(&cache, client, &["EXISTS", "near"]), ":0\r\n");
+    }
+}
@@ -0,0 +1,579 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        db::{unix_time_ms, Cache},
+        hash::{format_float, ExpireCondition, FIELD_MISSING, FIELD_WITHOUT_TTL},
+        pattern::glob_match,
+    },
+};
+
+use super::{
+    client::Client,
+    utils::{
+        optional_string, parse_float, parse_integer, parse_scan_args, serialize_error,
+        serialize_len, serialize_pairs, serialize_scan_page, throw_err_if_num_of_args_wrong,
+    },
+};
+
+/// HSET key field value [field value ...] and the deprecated HMSET, which answers +OK instead.
+pub fn handle_hset(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, pairs @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    if pairs.is_empty() || pairs.len() % 2 != 0 {
+        return throw_err_if_num_of_args_wrong(command);
+    }
+    let pairs: Vec<(String, String)> = pairs
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    match cache.hset(key, &pairs) {
+        Ok(_) if command == "hmset" => serialize(InputVariants::StringVariant("+OK".to_string())),
+        Ok(added) => serialize_len(added),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hsetnx(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, field, value] = args else {
+        return throw_err_if_num_of_args_wrong("hsetnx");
+    };
+    match cache.hsetnx(key, field, value) {
+        Ok(set) => serialize(InputVariants::NumberVariant(i64::from(set))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hget(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, field] = args else {
+        return throw_err_if_num_of_args_wrong("hget");
+    };
+    match cache.hget(key, field) {
+        Ok(value) => serialize(optional_string(value)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hmget(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, fields @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("hmget");
+    };
+    if fields.is_empty() {
+        return throw_err_if_num_of_args_wrong("hmget");
+    }
+    match cache.hmget(key, fields) {
+        Ok(values) => serialize(InputVariants::Array(
+            values.into_iter().map(optional_string).collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hdel(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, fields @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("hdel");
+    };
+    if fields.is_empty() {
+        return throw_err_if_num_of_args_wrong("hdel");
+    }
+    match cache.hdel(key, fields) {
+        Ok(removed) => serialize_len(removed),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hexists(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, field] = args else {
+        return throw_err_if_num_of_args_wrong("hexists");
+    };
+    match cache.hexists(key, field) {
+        Ok(exists) => serialize(InputVariants::NumberVariant(i64::from(exists))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hlen(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("hlen");
+    };
+    match cache.hlen(key) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hstrlen(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, field] = args else {
+        return throw_err_if_num_of_args_wrong("hstrlen");
+    };
+    match cache.hstrlen(key, field) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// HKEYS, HVALS and HGETALL, which only differ in which half of each pair they return.
+pub fn handle_hgetall(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+    command: &str,
+) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let pairs = match cache.hgetall(key) {
+        Ok(pairs) => pairs,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    match command {
+        "hkeys" => serialize(InputVariants::StringVariantArr(
+            pairs.into_iter().map(|(field, _)| field).collect(),
+        )),
+        "hvals" => serialize(InputVariants::StringVariantArr(
+            pairs.into_iter().map(|(_, value)| value).collect(),
+        )),
+        _ => serialize_pairs(pairs, client.protocol),
+    }
+}
+
+pub fn handle_hincrby(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, field, delta] = args else {
+        return throw_err_if_num_of_args_wrong("hincrby");
+    };
+    let delta = match parse_integer(delta) {
+        Ok(delta) => delta,
+        Err(err) => return err,
+    };
+    match cache.hincrby(key, field, delta) {
+        Ok(updated) => serialize(InputVariants::NumberVariant(updated)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_hincrbyfloat(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, field, delta] = args else {
+        return throw_err_if_num_of_args_wrong("hincrbyfloat");
+    };
+    let delta = match parse_float(delta) {
+        Ok(delta) => delta,
+        Err(err) => return err,
+    };
+    match cache.hincrbyfloat(key, field, delta) {
+        Ok(updated) => serialize(InputVariants::StringVariant(format_float(updated))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// HRANDFIELD key [count [WITHVALUES]]
+pub fn handle_hrandfield(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let (key, count, with_values) = match args {
+        [key] => (key, None, false),
+        [key, count] => (key, Some(count), false),
+        [key, count, option] if option.eq_ignore_ascii_case("withvalues") => {
+            (key, Some(count), true)
+        }
+        [_, _, _] => return serialize_error("-ERR syntax error"),
+        _ => return throw_err_if_num_of_args_wrong("hrandfield"),
+    };
+    let count = match count.map(|count| parse_integer(count)).transpose() {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    let picked = match cache.hrandfield(key, count.unwrap_or(1)) {
+        Ok(picked) => picked,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+
+    match (count, with_values) {
+        (None, _) => serialize(optional_string(
+            picked.into_iter().next().map(|(field, _)| field),
+        )),
+        (Some(_), false) => serialize(InputVariants::StringVariantArr(
+            picked.into_iter().map(|(field, _)| field).collect(),
+        )),
+        (Some(_), true) if client.protocol >= 3 => serialize(InputVariants::Array(
+            picked
+                .into_iter()
+                .map(|(field, value)| InputVariants::StringVariantArr(vec![field, value]))
+                .collect(),
+        )),
+        (Some(_), true) => serialize_pairs(picked, 2),
+    }
+}
+
+/// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+pub fn handle_hscan(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, cursor, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("hscan");
+    };
+    let (cursor, options) = match parse_scan_args(cursor, options, "hscan") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+
+    let (cursor, pairs) = match cache.hscan(key, cursor, options.count) {
+        Ok(page) => page,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    let items: Vec<String> = pairs
+        .into_iter()
+        .filter(|(field, _)| {
+            options
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, field))
+        })
+        .flat_map(|(field, value)| {
+            if options.no_values {
+                vec![field]
+            } else {
+                vec![field, value]
+            }
+        })
+        .collect();
+    serialize_scan_page(cursor, items)
+}
+
+/// Parses the trailing `FIELDS numfields field [field ...]` block of the field TTL commands.
+fn parse_fields(args: &[String]) -> Result<&[String], Cow<'static, str>> {
+    let [keyword, numfields, fields @ ..] = args else {
+        return Err(serialize_error(
+            "-ERR Mandatory argument FIELDS is missing or not at the right position",
+        ));
+    };
+    if !keyword.eq_ignore_ascii_case("fields") {
+        return Err(serialize_error(
+            "-ERR Mandatory argument FIELDS is missing or not at the right position",
+        ));
+    }
+    match parse_integer(numfields)? {
+        numfields if numfields <= 0 => Err(serialize_error(
+            "-ERR Parameter `numFields` should be greater than 0",
+        )),
+        numfields if numfields as usize != fields.len() => Err(serialize_error(
+            "-ERR The `numfields` parameter must match the number of arguments",
+        )),
+        _ => Ok(fields),
+    }
+}
+
+fn serialize_statuses(statuses: Vec<i64>) -> Cow<'static, str> {
+    serialize(InputVariants::Array(
+        statuses
+            .into_iter()
+            .map(InputVariants::NumberVariant)
+            .collect(),
+    ))
+}
+
+/// HEXPIRE/HPEXPIRE/HEXPIREAT/HPEXPIREAT key time [NX|XX|GT|LT] FIELDS numfields field [...]
+pub fn handle_hexpire(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, time, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let time = match parse_integer(time) {
+        Ok(time) if time >= 0 => time as u64,
+        Ok(_) => return serialize_error("-ERR invalid expire time, must be >= 0"),
+        Err(err) => return err,
+    };
+    let (condition, rest) = match rest.first().map(|arg| arg.to_uppercase()).as_deref() {
+        Some("NX") => (ExpireCondition::Nx, &rest[1..]),
+        Some("XX") => (ExpireCondition::Xx, &rest[1..]),
+        Some("GT") => (ExpireCondition::Gt, &rest[1..]),
+        Some("LT") => (ExpireCondition::Lt, &rest[1..]),
+        _ => (ExpireCondition::Always, rest),
+    };
+    let fields = match parse_fields(rest) {
+        Ok(fields) => fields,
+        Err(err) => return err,
+    };
+    let at = match command {
+        "hexpire" => time
+            .checked_mul(1000)
+            .and_then(|ms| ms.checked_add(unix_time_ms())),
+        "hpexpire" => time.checked_add(unix_time_ms()),
+        "hexpireat" => time.checked_mul(1000),
+        _ => Some(time),
+    };
+    let Some(at) = at.filter(|&at| at <= i64::MAX as u64) else {
+        return serialize_error(
+            concat_string!("-ERR invalid expire time in '", command, "' command").as_str(),
+        );
+    };
+    match cache.hexpire(key, fields, at, condition) {
+        Ok(statuses) => serialize_statuses(statuses),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// HTTL/HPTTL/HEXPIRETIME/HPEXPIRETIME key FIELDS numfields field [field ...]
+pub fn handle_httl(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let fields = match parse_fields(rest) {
+        Ok(fields) => fields,
+        Err(err) => return err,
+    };
+    let expire_times = match cache.hexpiretime(key, fields) {
+        Ok(expire_times) => expire_times,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    let now = unix_time_ms() as i64;
+    serialize_statuses(
+        expire_times
+            .into_iter()
+            .map(|at| match (at, command) {
+                (FIELD_MISSING | FIELD_WITHOUT_TTL, _) => at,
+                (at, "httl") => (at - now + 999).max(0) / 1000,
+                (at, "hpttl") => (at - now).max(0),
+                (at, "hexpiretime") => at / 1000,
+                (at, _) => at,
+            })
+            .collect(),
+    )
+}
+
+/// HPERSIST key FIELDS numfields field [field ...]
+pub fn handle_hpersist(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("hpersist");
+    };
+    let fields = match parse_fields(rest) {
+        Ok(fields) => fields,
+        Err(err) => return err,
+    };
+    match cache.hpersist(key, fields) {
+        Ok(statuses) => serialize_statuses(statuses),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    #[test]
+    fn should_set_get_and_delete_fields() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["HSET", "user:1", "name", "oz", "city", "emerald"]
+            ),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HSET", "user:1", "name", "dorothy"]),
+            ":0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HGET", "user:1", "name"]),
+            "$7\r\ndorothy\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HMGET", "user:1", "name", "missing"]),
+            "*2\r\n$7\r\ndorothy\r\n$-1\r\n"
+        );
+        run(&cache, client, &["HSET", "user:1", "motto", "+y"]);
+        assert_eq!(
+            run(&cache, client, &["HGET", "user:1", "motto"]),
+            "$2\r\n+y\r\n"
+        );
+        run(&cache, client, &["HDEL", "user:1", "motto"]);
+        assert_eq!(
+            run(&cache, client, &["HSETNX", "user:1", "name", "x"]),
+            ":0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HSTRLEN", "user:1", "city"]),
+            ":7\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HDEL", "user:1", "name", "nope"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HEXISTS", "user:1", "name"]),
+            ":0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["HLEN", "user:1"]), ":1\r\n");
+        assert_eq!(
+            run(&cache, client, &["HSET", "user:1", "name"]),
+            "-ERR wrong number of arguments for 'hset' command\r\n"
+        );
+    }
+
+    #[test]
+    fn should_encode_hgetall_as_map_after_hello_3() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["HSET", "h", "f", "v"]);
+        assert_eq!(
+            run(&cache, client, &["HGETALL", "h"]),
+            "*2\r\n$1\r\nf\r\n$1\r\nv\r\n"
+        );
+
+        assert!(run(&cache, client, &["HELLO", "3"]).starts_with("%7\r\n"));
+        assert_eq!(
+            run(&cache, client, &["HGETALL", "h"]),
+            "%1\r\n$1\r\nf\r\n$1\r\nv\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HELLO", "4"]),
+            "-NOPROTO unsupported protocol version\r\n"
+        );
+    }
+
+    #[test]
+    fn should_increment_fields() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["HINCRBY", "h", "n", "5"]), ":5\r\n");
+        assert_eq!(run(&cache, client, &["HINCRBY", "h", "n", "-7"]), ":-2\r\n");
+        assert_eq!(
+            run(&cache, client, &["HINCRBYFLOAT", "h", "f", "10.50"]),
+            "$4\r\n10.5\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HINCRBYFLOAT", "h", "f", "-10.5"]),
+            "$1\r\n0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HINCRBY", "h", "f", "x"]),
+            "-ERR value is not an integer or out of range\r\n"
+        );
+    }
+
+    #[test]
+    fn should_sample_random_fields() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["HSET", "h", "a", "1", "b", "2"]);
+        assert!(run(&cache, client, &["HRANDFIELD", "h", "5"]).starts_with("*2\r\n"));
+        assert!(run(&cache, client, &["HRANDFIELD", "h", "-5"]).starts_with("*5\r\n"));
+        assert!(run(&cache, client, &["HRANDFIELD", "h", "1", "WITHVALUES"]).starts_with("*2\r\n"));
+        assert_eq!(run(&cache, client, &["HRANDFIELD", "missing"]), "$-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["HRANDFIELD", "h", "-10000000000000"]),
+            "-ERR value is out of range\r\n"
+        );
+    }
+
+    #[test]
+    fn should_expire_individual_fields() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(
+            &cache,
+            client,
+            &["HSET", "user:1", "token", "a", "name", "oz"],
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["HPEXPIRE", "user:1", "50", "FIELDS", "2", "token", "nope"]
+            ),
+            "*2\r\n:1\r\n:-2\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["HTTL", "user:1", "FIELDS", "2", "token", "name"]
+            ),
+            "*2\r\n:1\r\n:-1\r\n"
+        );
+        thread::sleep(Duration::from_millis(80));
+        assert_eq!(run(&cache, client, &["HGET", "user:1", "token"]), "$-1\r\n");
+        assert_eq!(run(&cache, client, &["HLEN", "user:1"]), ":1\r\n");
+        assert_eq!(
+            run(&cache, client, &["HGETALL", "user:1"]),
+            "*2\r\n$4\r\nname\r\n$2\r\noz\r\n"
+        );
+    }
+
+    #[test]
+    fn should_honour_conditions_and_persist() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["HSET", "h", "f", "v"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["HEXPIRE", "h", "100", "XX", "FIELDS", "1", "f"]
+            ),
+            "*1\r\n:0\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["HEXPIRE", "h", "100", "NX", "FIELDS", "1", "f"]
+            ),
+            "*1\r\n:1\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["HEXPIRE", "h", "50", "GT", "FIELDS", "1", "f"]
+            ),
+            "*1\r\n:0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HPERSIST", "h", "FIELDS", "1", "f"]),
+            "*1\r\n:1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HPEXPIRETIME", "h", "FIELDS", "1", "f"]),
+            "*1\r\n:-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["HEXPIRE", "h", "10", "FIELDS", "2", "f"]),
+            "-ERR The `numfields` parameter must match the number of arguments\r\n"
+        );
+    }
+
+    #[test]
+    fn should_delete_key_when_last_field_expires_immediately() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["HSET", "h", "f", "v"]);
+        assert_eq!(
+            run(&cache, client, &["HEXPIREAT", "h", "1", "FIELDS", "1", "f"]),
+            "*1\r\n:2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "h"]), ":0\r\n");
+    }
+
+    #[test]
+    fn should_scan_with_match() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["HSET", "h", "device:1", "a", "user", "b"]);
+        assert_eq!(
+            run(&cache, client, &["HSCAN", "h", "0", "MATCH", "device:*"]),
+            "*2\r\n$1\r\n0\r\n*2\r\n$8\r\ndevice:1\r\n$1\r\na\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["HSCAN", "h", "0", "MATCH", "u*", "NOVALUES"]
+            ),
+            "*2\r\n$1\r\n0\r\n*1\r\n$4\r\nuser\r\n"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::db::Cache,
+};
+
+use super::utils::{serialize_error, throw_err_if_num_of_args_wrong};
+
+/// PFADD key [element [element ...]]
+pub fn handle_pfadd(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, elements @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("pfadd");
+    };
+    match cache.pfadd(key, elements) {
+        Ok(updated) => serialize(InputVariants::NumberVariant(i64::from(updated))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// PFCOUNT key [key ...]
+pub fn handle_pfcount(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    if args.is_empty() {
+        return throw_err_if_num_of_args_wrong("pfcount");
+    }
+    match cache.pfcount(args) {
+        Ok(count) => serialize(InputVariants::NumberVariant(count as i64)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// PFMERGE destkey [sourcekey [sourcekey ...]]
+pub fn handle_pfmerge(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [destination, sources @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("pfmerge");
+    };
+    match cache.pfmerge(destination, sources) {
+        Ok(()) => serialize(InputVariants::StringVariant("+OK".to_string())),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection_manager::{client::Client, test_utils::run};
+
+    use super::*;
+
+    fn count(cache: &Cache, client: &mut Client, keys: &[&str]) -> i64 {
+        let command: Vec<&str> = ["PFCOUNT"].iter().chain(keys).copied().collect();
+        let reply = run(cache, client, &command);
+        reply[1..reply.len() - 2].parse().unwrap()
+    }
+
+    #[test]
+    fn should_add_and_count_elements() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["PFADD", "h"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["PFADD", "h"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["PFADD", "h", "a", "b", "c"]),
+            ":1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["PFADD", "h", "a", "b"]), ":0\r\n");
+        assert_eq!(count(&cache, client, &["h"]), 3);
+        assert_eq!(count(&cache, client, &["missing"]), 0);
+
+        let elements: Vec<String> = (0..5000).map(|i| i.to_string()).collect();
+        let mut command = vec!["PFADD", "big"];
+        command.extend(elements.iter().map(String::as_str));
+        assert_eq!(run(&cache, client, &command), ":1\r\n");
+        let estimate = count(&cache, client, &["big"]);
+        assert!((estimate - 5000).abs() < 150, "{estimate}");
+        assert_eq!(count(&cache, client, &["big"]), estimate);
+        let union = count(&cache, client, &["big", "h"]);
+        assert!((union - 5003).abs() < 150, "{union}");
+    }
+
+    #[test]
+    fn should_merge_counters() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["PFADD", "a", "x", "y"]);
+        run(&cache, client, &["PFADD", "b", "y", "z"]);
+        run(&cache, client, &["PFADD", "dest", "w"]);
+        assert_eq!(
+            run(&cache, client, &["PFMERGE", "dest", "a", "b", "nope"]),
+            "+OK\r\n"
+        );
+        assert_eq!(count(&cache, client, &["dest"]), 4);
+        assert_eq!(run(&cache, client, &["PFMERGE", "empty"]), "+OK\r\n");
+        assert_eq!(count(&cache, client, &["empty"]), 0);
+    }
+
+    #[test]
+    fn should_round_trip_through_get_and_set() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["PFADD", "h", "a", "b", "c", "d"]);
+        let reply = run(&cache, client, &["GET", "h"]);
+        let header_end = reply.find("\r\n").unwrap() + 2;
+        let value = &reply[header_end..reply.len() - 2];
+        assert!(value.starts_with("HYLL\u{1}"));
+        assert_eq!(reply[1..header_end - 2], value.chars().count().to_string());
+
+        run(&cache, client, &["SET", "copy", value]);
+        assert_eq!(count(&cache, client, &["copy"]), 4);
+        assert_eq!(run(&cache, client, &["PFADD", "copy", "a"]), ":0\r\n");
+    }
+
+    #[test]
+    fn should_reject_values_that_are_not_counters() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "s", "hello"]);
+        run(&cache, client, &["SADD", "set", "a"]);
+        assert_eq!(
+            run(&cache, client, &["PFADD", "s", "a"]),
+            "-WRONGTYPE Key is not a valid HyperLogLog string value.\r\n"
+        );
+        assert!(run(&cache, client, &["PFCOUNT", "set"]).starts_with("-WRONGTYPE Operation"));
+        assert_eq!(
+            run(&cache, client, &["PFCOUNT"]),
+            "-ERR wrong number of arguments for 'pfcount' command\r\n"
+        );
+    }
+}
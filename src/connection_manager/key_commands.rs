@@ -0,0 +1,453 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{db::Cache, keys::FlushMode},
+};
+
+use super::utils::{
+    optional_string, parse_db_index, parse_scan_args, serialize_error, serialize_scan_page,
+    throw_err_if_num_of_args_wrong,
+};
+
+fn count_reply(count: Result<usize, &'static str>) -> Cow<'static, str> {
+    match count {
+        Ok(count) => serialize(InputVariants::NumberVariant(count as i64)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// KEYS pattern
+pub fn handle_keys(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [pattern] = args else {
+        return throw_err_if_num_of_args_wrong("keys");
+    };
+    match cache.keys(pattern) {
+        Ok(keys) => serialize(InputVariants::StringVariantArr(keys)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+pub fn handle_scan(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [cursor, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("scan");
+    };
+    let (cursor, options) = match parse_scan_args(cursor, options, "scan") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.scan(
+        cursor,
+        options.count,
+        options.pattern.as_deref(),
+        options.type_name.as_deref(),
+    ) {
+        Ok((cursor, keys)) => serialize_scan_page(cursor, keys),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// RENAME key newkey and RENAMENX key newkey
+pub fn handle_rename(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [source, destination] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let only_if_missing = command == "renamenx";
+    match cache.rename(source, destination, only_if_missing) {
+        Ok(_) if !only_if_missing => serialize(InputVariants::StringVariant("+OK".to_string())),
+        Ok(renamed) => serialize(InputVariants::NumberVariant(i64::from(renamed))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// COPY source destination [DB destination-db] [REPLACE]
+pub fn handle_copy(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [source, destination, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("copy");
+    };
+    let mut options = options;
+    let mut replace = false;
+    let mut db = cache.index();
+    while let [option, tail @ ..] = options {
+        options = match (option.to_uppercase().as_str(), tail) {
+            ("REPLACE", tail) => {
+                replace = true;
+                tail
+            }
+            ("DB", [index, tail @ ..]) => match parse_db_index(index, cache) {
+                Ok(index) => {
+                    db = index;
+                    tail
+                }
+                Err(err) => return err,
+            },
+            _ => return serialize_error("-ERR syntax error"),
+        };
+    }
+    match cache.copy(source, destination, db, replace) {
+        Ok(copied) => serialize(InputVariants::NumberVariant(i64::from(copied))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// MOVE key db
+pub fn handle_move(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, db] = args else {
+        return throw_err_if_num_of_args_wrong("move");
+    };
+    let db = match parse_db_index(db, cache) {
+        Ok(db) => db,
+        Err(err) => return err,
+    };
+    match cache.move_key(key, db) {
+        Ok(moved) => serialize(InputVariants::NumberVariant(i64::from(moved))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// TYPE key
+pub fn handle_type(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("type");
+    };
+    match cache.key_type(key) {
+        Ok(name) => serialize(InputVariants::StringVariant(concat_string!("+", name))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// TOUCH key [key ...]
+pub fn handle_touch(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    if args.is_empty() {
+        return throw_err_if_num_of_args_wrong("touch");
+    }
+    count_reply(cache.touch(args))
+}
+
+/// OBJECT ENCODING | FREQ | IDLETIME | REFCOUNT key
+pub fn handle_object(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let Some(subcommand) = args.first().map(|s| s.to_lowercase()) else {
+        return throw_err_if_num_of_args_wrong("object");
+    };
+    let subcommands = ["encoding", "freq", "idletime", "refcount"];
+    if !subcommands.contains(&subcommand.as_str()) {
+        return serialize_error(
+            concat_string!(
+                "-ERR unknown subcommand '",
+                subcommand,
+                "'. Try OBJECT HELP."
+            )
+            .as_str(),
+        );
+    }
+    let [key] = &args[1..] else {
+        return throw_err_if_num_of_args_wrong(concat_string!("object|", subcommand).as_str());
+    };
+    let info = match cache.object(key) {
+        Ok(Some(info)) => info,
+        Ok(None) => return serialize(optional_string(None)),
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    // Like Redis, only the data the current policy keeps up to date is reported.
+    let lfu = cache.maxmemory_config().policy.is_lfu();
+    match subcommand.as_str() {
+        "encoding" => serialize(InputVariants::StringVariant(info.encoding.to_string())),
+        "freq" if !lfu => serialize_error(
+            "-ERR An LFU maxmemory policy is not selected, access frequency not tracked. Please \
+             note that when switching between policies at runtime LRU and LFU data will take \
+             some time to adjust.",
+        ),
+        "freq" => serialize(InputVariants::NumberVariant(i64::from(info.frequency))),
+        "idletime" if lfu => serialize_error(
+            "-ERR An LFU maxmemory policy is selected, idle time not tracked. Please note that \
+             when switching between policies at runtime LRU and LFU data will take some time to \
+             adjust.",
+        ),
+        "idletime" => serialize(InputVariants::NumberVariant((info.idle_time / 1000) as i64)),
+        // Values are never shared between keys.
+        _ => serialize(InputVariants::NumberVariant(1)),
+    }
+}
+
+/// UNLINK key [key ...]
+pub fn handle_unlink(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    if args.is_empty() {
+        return throw_err_if_num_of_args_wrong("unlink");
+    }
+    count_reply(cache.unlink(args))
+}
+
+/// RANDOMKEY
+pub fn handle_randomkey(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    if !args.is_empty() {
+        return throw_err_if_num_of_args_wrong("randomkey");
+    }
+    match cache.random_key() {
+        Ok(key) => serialize(optional_string(key)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// DBSIZE
+pub fn handle_dbsize(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    if !args.is_empty() {
+        return throw_err_if_num_of_args_wrong("dbsize");
+    }
+    count_reply(cache.dbsize())
+}
+
+/// FLUSHDB [ASYNC | SYNC] and FLUSHALL [ASYNC | SYNC]
+pub fn handle_flush(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let mode = match args {
+        [] => FlushMode::default(),
+        [mode] => match mode.to_uppercase().as_str() {
+            "SYNC" => FlushMode::Sync,
+            "ASYNC" => FlushMode::Async,
+            _ => return serialize_error("-ERR syntax error"),
+        },
+        _ => return throw_err_if_num_of_args_wrong(command),
+    };
+    let flushed = if command == "flushall" {
+        cache.flushall(mode)
+    } else {
+        cache.flushdb(mode)
+    };
+    match flushed {
+        Ok(()) => serialize(InputVariants::StringVariant("+OK".to_string())),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::connection_manager::{client::Client, test_utils::run};
+
+    use super::*;
+
+    /// Splits a SCAN reply into its cursor and keys.
+    fn parse_page(reply: &str) -> (String, Vec<String>) {
+        let lines: Vec<&str> = reply.split("\r\n").collect();
+        let cursor = lines[2].to_string();
+        let keys = lines[4..]
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .map(|key| key.to_string())
+            .collect();
+        (cursor, keys)
+    }
+
+    #[test]
+    fn should_list_keys_matching_a_glob() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        for key in ["hello", "hallo", "hxllo", "hllo", "heeeello", "h*llo"] {
+            run(&cache, client, &["SET", key, "v"]);
+        }
+        let keys = |pattern: &str| -> HashSet<String> {
+            let reply = run(&cache, &mut Client::new(None), &["KEYS", pattern]);
+            reply
+                .split("\r\n")
+                .skip(2)
+                .step_by(2)
+                .filter(|key| !key.is_empty())
+                .map(|key| key.to_string())
+                .collect()
+        };
+        let set = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+        assert_eq!(keys("h?llo"), set(&["hello", "hallo", "hxllo", "h*llo"]));
+        assert_eq!(keys("h*llo").len(), 6);
+        assert_eq!(keys("h[ae]llo"), set(&["hello", "hallo"]));
+        assert_eq!(keys("h[^e]llo"), set(&["hallo", "hxllo", "h*llo"]));
+        assert_eq!(keys("h[a-b]llo"), set(&["hallo"]));
+        assert_eq!(keys("h\\*llo"), set(&["h*llo"]));
+        assert_eq!(run(&cache, client, &["KEYS", "nothing*"]), "*0\r\n");
+    }
+
+    #[test]
+    fn should_scan_every_key_with_filters() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        for i in 0..100 {
+            run(&cache, client, &["SET", &format!("key:{}", i), "v"]);
+        }
+        run(&cache, client, &["RPUSH", "list", "a"]);
+
+        let mut cursor = "0".to_string();
+        let mut seen = HashSet::new();
+        let mut strings = HashSet::new();
+        loop {
+            let reply = run(&cache, client, &["SCAN", &cursor, "COUNT", "7"]);
+            let (next, keys) = parse_page(&reply);
+            seen.extend(keys);
+            let reply = run(
+                &cache,
+                client,
+                &["SCAN", &cursor, "COUNT", "7", "TYPE", "STRING"],
+            );
+            strings.extend(parse_page(&reply).1);
+            if next == "0" {
+                break;
+            }
+            cursor = next;
+        }
+        assert_eq!(seen.len(), 101);
+        assert_eq!(strings.len(), 100);
+        assert!(!strings.contains("list"));
+
+        let reply = run(
+            &cache,
+            client,
+            &["SCAN", "0", "MATCH", "l*", "COUNT", "1000"],
+        );
+        assert_eq!(
+            parse_page(&reply),
+            ("0".to_string(), vec!["list".to_string()])
+        );
+        assert_eq!(
+            run(&cache, client, &["SCAN", "0", "TYPE", "blob"]),
+            "-ERR unknown type name 'blob'\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SCAN", "x"]),
+            "-ERR invalid cursor\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SCAN", "0", "NOVALUES"]),
+            "-ERR syntax error\r\n"
+        );
+    }
+
+    #[test]
+    fn should_manage_keys_generically() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["RPUSH", "list", "a", "b"]);
+        run(&cache, client, &["SET", "s", "v"]);
+        assert_eq!(run(&cache, client, &["TYPE", "list"]), "+list\r\n");
+        assert_eq!(run(&cache, client, &["TYPE", "nope"]), "+none\r\n");
+        assert_eq!(
+            run(&cache, client, &["TOUCH", "list", "s", "nope"]),
+            ":2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["DBSIZE"]), ":2\r\n");
+
+        assert_eq!(run(&cache, client, &["RENAME", "list", "l"]), "+OK\r\n");
+        assert_eq!(
+            run(&cache, client, &["RENAME", "list", "l"]),
+            "-ERR no such key\r\n"
+        );
+        assert_eq!(run(&cache, client, &["RENAMENX", "s", "l"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["COPY", "l", "s"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["COPY", "l", "s", "DB", "0", "REPLACE"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["LRANGE", "s", "0", "-1"]),
+            "*2\r\n$1\r\na\r\n$1\r\nb\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["COPY", "l", "s", "DB", "16"]),
+            "-ERR DB index is out of range\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["COPY", "l", "l"]),
+            "-ERR source and destination objects are the same\r\n"
+        );
+
+        assert_eq!(run(&cache, client, &["UNLINK", "s", "nope"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["RANDOMKEY"]), "$1\r\nl\r\n");
+        assert_eq!(
+            run(&cache, client, &["FLUSHDB", "LAZY"]),
+            "-ERR syntax error\r\n"
+        );
+        assert_eq!(run(&cache, client, &["FLUSHALL", "ASYNC"]), "+OK\r\n");
+        assert_eq!(run(&cache, client, &["DBSIZE"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["RANDOMKEY"]), "$-1\r\n");
+    }
+
+    #[test]
+    fn should_report_compact_encodings_until_a_threshold_is_crossed() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        let long = "x".repeat(65);
+        run(&cache, client, &["HSET", "hash", "f", "v"]);
+        run(&cache, client, &["ZADD", "zset", "1", "m"]);
+        run(&cache, client, &["RPUSH", "list", "a"]);
+        let encoding = |client: &mut Client, key| {
+            let reply = run(&cache, client, &["OBJECT", "ENCODING", key]);
+            reply.trim_end().rsplit('\n').next().unwrap().to_string()
+        };
+        for key in ["hash", "zset", "list"] {
+            assert_eq!(encoding(client, key), "listpack");
+        }
+
+        run(&cache, client, &["HSET", "hash", "f", &long]);
+        run(&cache, client, &["ZADD", "zset", "2", &long]);
+        run(&cache, client, &["RPUSH", "list", &"x".repeat(8192)]);
+        assert_eq!(encoding(client, "hash"), "hashtable");
+        assert_eq!(encoding(client, "zset"), "skiplist");
+        assert_eq!(encoding(client, "list"), "quicklist");
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "zset", "0", "0"]),
+            "*1\r\n$1\r\nm\r\n"
+        );
+    }
+
+    #[test]
+    fn should_describe_objects_without_touching_them() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SET", "int", "12345"]);
+        run(&cache, client, &["SET", "padded", "012"]);
+        run(&cache, client, &["SET", "long", &"x".repeat(45)]);
+        run(&cache, client, &["SADD", "ints", "1", "2"]);
+        run(&cache, client, &["SADD", "words", "a"]);
+        run(&cache, client, &["RPUSH", "list", "a"]);
+        for (key, encoding) in [
+            ("int", "int"),
+            ("padded", "embstr"),
+            ("long", "raw"),
+            ("ints", "intset"),
+            ("words", "listpack"),
+            ("list", "listpack"),
+        ] {
+            let expected = format!("${}\r\n{}\r\n", encoding.len(), encoding);
+            assert_eq!(run(&cache, client, &["OBJECT", "ENCODING", key]), expected);
+        }
+        assert_eq!(
+            run(&cache, client, &["OBJECT", "ENCODING", "nope"]),
+            "$-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["OBJECT", "REFCOUNT", "int"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["OBJECT", "IDLETIME", "int"]),
+            ":0\r\n"
+        );
+        assert!(run(&cache, client, &["OBJECT", "FREQ", "int"])
+            .starts_with("-ERR An LFU maxmemory policy is not selected"));
+        assert_eq!(
+            run(&cache, client, &["OBJECT", "NOPE", "int"]),
+            "-ERR unknown subcommand 'nope'. Try OBJECT HELP.\r\n"
+        );
+
+        run(
+            &cache,
+            client,
+            &["CONFIG", "SET", "maxmemory-policy", "allkeys-lfu"],
+        );
+        assert_eq!(run(&cache, client, &["OBJECT", "FREQ", "int"]), ":5\r\n");
+        assert!(run(&cache, client, &["OBJECT", "IDLETIME", "int"])
+            .starts_with("-ERR An LFU maxmemory policy is selected"));
+        // The first access past the initial value always counts, later ones less and less.
+        assert_eq!(run(&cache, client, &["TOUCH", "int", "nope"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["OBJECT", "FREQ", "int"]), ":6\r\n");
+    }
+}
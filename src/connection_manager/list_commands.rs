@@ -0,0 +1,454 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        blocking::ServeFn,
+        db::Cache,
+        list::{move_between, pop_from, ListEnd},
+    },
+};
+
+use super::{
+    client::Client,
+    utils::{
+        parse_count, parse_integer, parse_timeout, serialize_block_outcome, serialize_error,
+        serialize_len, throw_err_if_num_of_args_wrong,
+    },
+};
+
+fn parse_end(arg: &str) -> Result<ListEnd, Cow<'static, str>> {
+    match arg.to_uppercase().as_str() {
+        "LEFT" => Ok(ListEnd::Left),
+        "RIGHT" => Ok(ListEnd::Right),
+        _ => Err(serialize_error("-ERR syntax error")),
+    }
+}
+
+/// Parses `numkeys key [key ...] LEFT|RIGHT [COUNT count]` shared by LMPOP and BLMPOP.
+fn parse_mpop_args(args: &[String]) -> Result<(Vec<String>, ListEnd, usize), Cow<'static, str>> {
+    let numkeys = match args.first().map(|arg| parse_integer(arg)).transpose()? {
+        Some(numkeys) if numkeys > 0 => numkeys as usize,
+        Some(_) => return Err(serialize_error("-ERR numkeys should be greater than 0")),
+        None => return Err(serialize_error("-ERR syntax error")),
+    };
+    let keys = args
+        .get(1..=numkeys)
+        .ok_or_else(|| serialize_error("-ERR syntax error"))?
+        .to_vec();
+    let end = parse_end(
+        args.get(numkeys + 1)
+            .ok_or_else(|| serialize_error("-ERR syntax error"))?,
+    )?;
+    let count = match &args[numkeys + 2..] {
+        [] => 1,
+        [option, count] if option.eq_ignore_ascii_case("count") => parse_count(count)?,
+        _ => return Err(serialize_error("-ERR syntax error")),
+    };
+    Ok((keys, end, count))
+}
+
+fn serialize_mpop(key: String, values: Vec<String>) -> Cow<'static, str> {
+    serialize(InputVariants::Array(vec![
+        InputVariants::StringVariant(key),
+        InputVariants::StringVariantArr(values),
+    ]))
+}
+
+fn serialize_moved(moved: Option<String>) -> Cow<'static, str> {
+    match moved {
+        Some(value) => serialize(InputVariants::StringVariant(value)),
+        None => serialize(InputVariants::Nullish),
+    }
+}
+
+fn move_serve_fn(destination: String, from: ListEnd, to: ListEnd) -> ServeFn {
+    Box::new(move |data, key| {
+        Ok(move_between(data, key, &destination, from, to)?
+            .map(|value| serialize(InputVariants::StringVariant(value)).into_owned()))
+    })
+}
+
+fn block_and_move(
+    client: &Client,
+    cache: &Cache,
+    source: &str,
+    destination: &str,
+    (from, to): (ListEnd, ListEnd),
+    timeout: &str,
+) -> Cow<'static, str> {
+    let timeout = match parse_timeout(timeout) {
+        Ok(timeout) => timeout,
+        Err(err) => return err,
+    };
+    let outcome = cache.block_on(
+        client.id,
+        vec![source.to_string()],
+        timeout,
+        client.may_block(),
+        move_serve_fn(destination.to_string(), from, to),
+        &|| client.is_connected(),
+    );
+    serialize_block_outcome(outcome, InputVariants::Nullish)
+}
+
+/// LPUSH/RPUSH and their X variants, which only push onto existing lists.
+pub fn handle_push(
+    args: &[String],
+    cache: &Cache,
+    end: ListEnd,
+    only_if_exists: bool,
+    command: &str,
+) -> Cow<'static, str> {
+    match args {
+        [key, values @ ..] if !values.is_empty() => {
+            match cache.push(key, values, end, only_if_exists) {
+                Ok(len) => serialize_len(len),
+                Err(err) => serialize_error(concat_string!("-", err).as_str()),
+            }
+        }
+        _ => throw_err_if_num_of_args_wrong(command),
+    }
+}
+
+/// LPOP/RPOP key [count]. With a count the reply is always an array.
+pub fn handle_pop(
+    args: &[String],
+    cache: &Cache,
+    end: ListEnd,
+    command: &str,
+) -> Cow<'static, str> {
+    let (key, count) = match args {
+        [key] => (key, None),
+        [key, count] => match parse_integer(count) {
+            Ok(count) if count >= 0 => (key, Some(count as usize)),
+            Ok(_) => return serialize_error("-ERR value is out of range, must be positive"),
+            Err(err) => return err,
+        },
+        _ => return throw_err_if_num_of_args_wrong(command),
+    };
+    match (cache.pop(key, end, count.unwrap_or(1)), count) {
+        (Ok(Some(mut values)), None) => serialize(InputVariants::StringVariant(values.remove(0))),
+        (Ok(Some(values)), Some(_)) => serialize(InputVariants::StringVariantArr(values)),
+        (Ok(None), None) => serialize(InputVariants::Nullish),
+        (Ok(None), Some(_)) => serialize(InputVariants::NullArray),
+        (Err(err), _) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_lrange(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, start, stop] = args else {
+        return throw_err_if_num_of_args_wrong("lrange");
+    };
+    let (start, stop) = match (parse_integer(start), parse_integer(stop)) {
+        (Ok(start), Ok(stop)) => (start, stop),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    match cache.list_range(key, start, stop) {
+        Ok(values) => serialize(InputVariants::StringVariantArr(values)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_llen(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("llen");
+    };
+    match cache.list_len(key) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_lmove(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [source, destination, from, to] = args else {
+        return throw_err_if_num_of_args_wrong("lmove");
+    };
+    let (from, to) = match (parse_end(from), parse_end(to)) {
+        (Ok(from), Ok(to)) => (from, to),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    match cache.list_move(source, destination, from, to) {
+        Ok(moved) => serialize_moved(moved),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_rpoplpush(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [source, destination] = args else {
+        return throw_err_if_num_of_args_wrong("rpoplpush");
+    };
+    match cache.list_move(source, destination, ListEnd::Right, ListEnd::Left) {
+        Ok(moved) => serialize_moved(moved),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_lmpop(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let (keys, end, count) = match parse_mpop_args(args) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.pop_first(&keys, end, count) {
+        Ok(Some((key, values))) => serialize_mpop(key, values),
+        Ok(None) => serialize(InputVariants::NullArray),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// BLPOP/BRPOP key [key ...] timeout
+pub fn handle_blocking_pop(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+    end: ListEnd,
+    command: &str,
+) -> Cow<'static, str> {
+    let [keys @ .., timeout] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    if keys.is_empty() {
+        return throw_err_if_num_of_args_wrong(command);
+    }
+    let timeout = match parse_timeout(timeout) {
+        Ok(timeout) => timeout,
+        Err(err) => return err,
+    };
+    let serve: ServeFn = Box::new(move |data, key| {
+        Ok(pop_from(data, key, end, 1)?.map(|mut values| {
+            serialize(InputVariants::StringVariantArr(vec![
+                key.to_string(),
+                values.remove(0),
+            ]))
+            .into_owned()
+        }))
+    });
+    let outcome = cache.block_on(
+        client.id,
+        keys.to_vec(),
+        timeout,
+        client.may_block(),
+        serve,
+        &|| client.is_connected(),
+    );
+    serialize_block_outcome(outcome, InputVariants::NullArray)
+}
+
+/// BLMOVE source destination LEFT|RIGHT LEFT|RIGHT timeout
+pub fn handle_blmove(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [source, destination, from, to, timeout] = args else {
+        return throw_err_if_num_of_args_wrong("blmove");
+    };
+    match (parse_end(from), parse_end(to)) {
+        (Ok(from), Ok(to)) => {
+            block_and_move(client, cache, source, destination, (from, to), timeout)
+        }
+        (Err(err), _) | (_, Err(err)) => err,
+    }
+}
+
+/// BRPOPLPUSH source destination timeout
+pub fn handle_brpoplpush(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [source, destination, timeout] = args else {
+        return throw_err_if_num_of_args_wrong("brpoplpush");
+    };
+    block_and_move(
+        client,
+        cache,
+        source,
+        destination,
+        (ListEnd::Right, ListEnd::Left),
+        timeout,
+    )
+}
+
+/// BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count]
+pub fn handle_blmpop(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let Some((timeout, args)) = args.split_first() else {
+        return throw_err_if_num_of_args_wrong("blmpop");
+    };
+    let (timeout, (keys, end, count)) = match (parse_timeout(timeout), parse_mpop_args(args)) {
+        (Ok(timeout), Ok(parsed)) => (timeout, parsed),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    let serve: ServeFn = Box::new(move |data, key| {
+        Ok(pop_from(data, key, end, count)?
+            .map(|values| serialize_mpop(key.to_string(), values).into_owned()))
+    });
+    let outcome = cache.block_on(client.id, keys, timeout, client.may_block(), serve, &|| {
+        client.is_connected()
+    });
+    serialize_block_outcome(outcome, InputVariants::NullArray)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    fn spawn_blocked(
+        cache: &Cache,
+        command: &'static [&'static str],
+    ) -> (u64, thread::JoinHandle<String>) {
+        let cache = cache.clone();
+        let mut client = Client::new(None);
+        let id = client.id;
+        let handle = thread::spawn(move || run(&cache, &mut client, command));
+        (id, handle)
+    }
+
+    fn wait_until_blocked(cache: &Cache, id: u64) {
+        while !cache.is_client_blocked(id) {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn should_push_pop_and_range() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["RPUSH", "list", "a", "b", "c"]),
+            ":3\r\n"
+        );
+        assert_eq!(run(&cache, client, &["LPUSH", "list", "z"]), ":4\r\n");
+        assert_eq!(
+            run(&cache, client, &["LRANGE", "list", "0", "-2"]),
+            "*3\r\n$1\r\nz\r\n$1\r\na\r\n$1\r\nb\r\n"
+        );
+        assert_eq!(run(&cache, client, &["RPOP", "list"]), "$1\r\nc\r\n");
+        assert_eq!(
+            run(&cache, client, &["LPOP", "list", "5"]),
+            "*3\r\n$1\r\nz\r\n$1\r\na\r\n$1\r\nb\r\n"
+        );
+        assert_eq!(run(&cache, client, &["LLEN", "list"]), ":0\r\n");
+        assert_eq!(run(&cache, client, &["LPOP", "list"]), "$-1\r\n");
+    }
+
+    #[test]
+    fn should_reject_list_commands_on_strings() {
+        let cache = Cache::new();
+        cache.set("name".to_string(), "value".to_string()).unwrap();
+        assert_eq!(
+            run(&cache, &mut Client::new(None), &["LPUSH", "name", "a"]),
+            "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+        );
+    }
+
+    #[test]
+    fn should_pop_immediately_when_list_has_elements() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["RPUSH", "jobs", "1"]);
+        assert_eq!(
+            run(&cache, client, &["BLPOP", "empty", "jobs", "0"]),
+            "*2\r\n$4\r\njobs\r\n$1\r\n1\r\n"
+        );
+    }
+
+    #[test]
+    fn should_time_out_with_sub_second_precision() {
+        let cache = Cache::new();
+        let started = Instant::now();
+        assert_eq!(
+            run(&cache, &mut Client::new(None), &["BRPOP", "jobs", "0.1"]),
+            "*-1\r\n"
+        );
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn should_wake_blocked_clients_in_fifo_order() {
+        let cache = Cache::new();
+        let (first_id, first) = spawn_blocked(&cache, &["BLPOP", "jobs", "0"]);
+        wait_until_blocked(&cache, first_id);
+        let (second_id, second) = spawn_blocked(&cache, &["BLPOP", "jobs", "0"]);
+        wait_until_blocked(&cache, second_id);
+
+        run(&cache, &mut Client::new(None), &["RPUSH", "jobs", "a", "b"]);
+        assert_eq!(first.join().unwrap(), "*2\r\n$4\r\njobs\r\n$1\r\na\r\n");
+        assert_eq!(second.join().unwrap(), "*2\r\n$4\r\njobs\r\n$1\r\nb\r\n");
+    }
+
+    #[test]
+    fn should_hand_pushed_element_to_blocked_client_before_others() {
+        let cache = Cache::new();
+        let (id, blocked) = spawn_blocked(&cache, &["BRPOP", "jobs", "0"]);
+        wait_until_blocked(&cache, id);
+
+        let client = &mut Client::new(None);
+        run(&cache, client, &["LPUSH", "jobs", "a"]);
+        assert_eq!(run(&cache, client, &["LPOP", "jobs"]), "$-1\r\n");
+        assert_eq!(blocked.join().unwrap(), "*2\r\n$4\r\njobs\r\n$1\r\na\r\n");
+    }
+
+    #[test]
+    fn should_chain_blmove_into_clients_blocked_on_destination() {
+        let cache = Cache::new();
+        let (mover_id, mover) = spawn_blocked(
+            &cache,
+            &["BLMOVE", "incoming", "work", "LEFT", "RIGHT", "0"],
+        );
+        wait_until_blocked(&cache, mover_id);
+        let (worker_id, worker) = spawn_blocked(&cache, &["BLMPOP", "0", "1", "work", "LEFT"]);
+        wait_until_blocked(&cache, worker_id);
+
+        run(
+            &cache,
+            &mut Client::new(None),
+            &["RPUSH", "incoming", "job"],
+        );
+        assert_eq!(mover.join().unwrap(), "$3\r\njob\r\n");
+        assert_eq!(
+            worker.join().unwrap(),
+            "*2\r\n$4\r\nwork\r\n*1\r\n$3\r\njob\r\n"
+        );
+    }
+
+    #[test]
+    fn should_unblock_client_with_timeout_or_error() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+
+        let (id, blocked) = spawn_blocked(&cache, &["BRPOPLPUSH", "jobs", "done", "0"]);
+        wait_until_blocked(&cache, id);
+        let unblock = ["CLIENT", "UNBLOCK", &id.to_string()].map(str::to_string);
+        let unblock: Vec<&str> = unblock.iter().map(String::as_str).collect();
+        assert_eq!(run(&cache, client, &unblock), ":1\r\n");
+        assert_eq!(blocked.join().unwrap(), "$-1\r\n");
+        assert_eq!(run(&cache, client, &unblock), ":0\r\n");
+
+        let (id, blocked) = spawn_blocked(&cache, &["BLPOP", "jobs", "0"]);
+        wait_until_blocked(&cache, id);
+        let id = id.to_string();
+        assert_eq!(
+            run(&cache, client, &["CLIENT", "UNBLOCK", &id, "ERROR"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            blocked.join().unwrap(),
+            "-UNBLOCKED client unblocked via CLIENT UNBLOCK\r\n"
+        );
+    }
+
+    #[test]
+    fn should_not_block_inside_multi() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["MULTI"]), "+OK\r\n");
+        assert_eq!(run(&cache, client, &["BLPOP", "jobs", "0"]), "+QUEUED\r\n");
+        assert_eq!(run(&cache, client, &["RPUSH", "jobs", "a"]), "+QUEUED\r\n");
+        assert_eq!(run(&cache, client, &["BLPOP", "jobs", "0"]), "+QUEUED\r\n");
+        assert_eq!(
+            run(&cache, client, &["EXEC"]),
+            "*3\r\n*-1\r\n:1\r\n*2\r\n$4\r\njobs\r\n$1\r\na\r\n"
+        );
+    }
+}
@@ -1,4 +1,19 @@
+mod bitmap_commands;
+mod client;
 pub mod client_handler;
 mod command_handler;
 mod commands;
+mod consumer_group_commands;
+mod expire_commands;
+mod geo_commands;
+mod hash_commands;
+mod hyperloglog_commands;
+mod key_commands;
+mod list_commands;
+mod server_commands;
+mod set_commands;
+mod stream_commands;
+#[cfg(test)]
+mod test_utils;
 mod utils;
+mod zset_commands;
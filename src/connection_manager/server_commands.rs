@@ -0,0 +1,602 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        db::Cache,
+        eviction::{parse_memory, EvictionPolicy, MaxmemoryConfig, OOM_ERR},
+        hash::{HASH_MAX_LISTPACK_ENTRIES, HASH_MAX_LISTPACK_VALUE},
+        memory::{human_bytes, DatabaseMemory, DEFAULT_SAMPLES},
+        pattern::glob_match,
+        set::{SET_MAX_INTSET_ENTRIES, SET_MAX_LISTPACK_ENTRIES, SET_MAX_LISTPACK_VALUE},
+        zset::{ZSET_MAX_LISTPACK_ENTRIES, ZSET_MAX_LISTPACK_VALUE},
+    },
+};
+
+use super::{
+    client::Client,
+    stream_commands::info_variant,
+    utils::{
+        optional_string, parse_db_index, parse_integer, serialize_error, serialize_pairs,
+        throw_err_if_num_of_args_wrong,
+    },
+    zset_commands::score_variant,
+};
+
+/// Sections INFO knows about, in the order it prints them.
+const INFO_SECTIONS: [&str; 3] = ["memory", "stats", "keyspace"];
+
+/// Keys MEMORY DOCTOR lists.
+const DOCTOR_LARGEST_KEYS: usize = 10;
+
+/// Parameters CONFIG GET reports. Only the maxmemory ones can be changed with CONFIG SET, the
+/// encoding thresholds are fixed at build time.
+const CONFIG_PARAMETERS: [&str; 12] = [
+    "maxmemory",
+    "maxmemory-policy",
+    "maxmemory-samples",
+    "databases",
+    "hash-max-listpack-entries",
+    "hash-max-listpack-value",
+    "list-max-listpack-size",
+    "set-max-intset-entries",
+    "set-max-listpack-entries",
+    "set-max-listpack-value",
+    "zset-max-listpack-entries",
+    "zset-max-listpack-value",
+];
+
+/// SELECT index
+pub fn handle_select(args: &[String], cache: &Cache, client: &mut Client) -> Cow<'static, str> {
+    let [index] = args else {
+        return throw_err_if_num_of_args_wrong("select");
+    };
+    match parse_db_index(index, cache) {
+        Ok(index) => {
+            client.db = index;
+            serialize(InputVariants::StringVariant("+OK".to_string()))
+        }
+        Err(err) => err,
+    }
+}
+
+/// SWAPDB index1 index2
+pub fn handle_swapdb(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [first, second] = args else {
+        return throw_err_if_num_of_args_wrong("swapdb");
+    };
+    if parse_integer(first).is_err() {
+        return serialize_error("-ERR invalid first DB index");
+    }
+    if parse_integer(second).is_err() {
+        return serialize_error("-ERR invalid second DB index");
+    }
+    let (first, second) = match (parse_db_index(first, cache), parse_db_index(second, cache)) {
+        (Ok(first), Ok(second)) => (first, second),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    match cache.swapdb(first, second) {
+        Ok(()) => serialize(InputVariants::StringVariant("+OK".to_string())),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+fn config_value(parameter: &str, config: &MaxmemoryConfig, cache: &Cache) -> String {
+    match parameter {
+        "maxmemory" => config.maxmemory.to_string(),
+        "maxmemory-policy" => config.policy.name().to_string(),
+        "maxmemory-samples" => config.samples.to_string(),
+        "hash-max-listpack-entries" => HASH_MAX_LISTPACK_ENTRIES.to_string(),
+        "hash-max-listpack-value" => HASH_MAX_LISTPACK_VALUE.to_string(),
+        // Redis' way of saying 8kb per listpack, which is `LIST_MAX_LISTPACK_SIZE`.
+        "list-max-listpack-size" => "-2".to_string(),
+        "set-max-intset-entries" => SET_MAX_INTSET_ENTRIES.to_string(),
+        "set-max-listpack-entries" => SET_MAX_LISTPACK_ENTRIES.to_string(),
+        "set-max-listpack-value" => SET_MAX_LISTPACK_VALUE.to_string(),
+        "zset-max-listpack-entries" => ZSET_MAX_LISTPACK_ENTRIES.to_string(),
+        "zset-max-listpack-value" => ZSET_MAX_LISTPACK_VALUE.to_string(),
+        _ => cache.database_count().to_string(),
+    }
+}
+
+/// Applies `value` to `config`, returning the reason it is invalid otherwise.
+fn set_config_value(
+    config: &mut MaxmemoryConfig,
+    parameter: &str,
+    value: &str,
+) -> Result<(), &'static str> {
+    match parameter {
+        "maxmemory" => {
+            config.maxmemory = parse_memory(value).ok_or("argument must be a memory value")?;
+        }
+        "maxmemory-policy" => {
+            config.policy = EvictionPolicy::parse(value).ok_or("argument must be a policy")?;
+        }
+        "maxmemory-samples" => {
+            config.samples = value
+                .parse()
+                .ok()
+                .filter(|samples| (1..=64).contains(samples))
+                .ok_or("argument must be between 1 and 64 inclusive")?;
+        }
+        _ => return Err("can't set immutable config"),
+    }
+    Ok(())
+}
+
+/// CONFIG GET pattern [pattern ...] | SET parameter value [parameter value ...]
+pub fn handle_config(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let Some(subcommand) = args.first().map(|s| s.to_lowercase()) else {
+        return throw_err_if_num_of_args_wrong("config");
+    };
+    match (subcommand.as_str(), &args[1..]) {
+        ("get", patterns) if !patterns.is_empty() => {
+            let config = cache.maxmemory_config();
+            let pairs = CONFIG_PARAMETERS
+                .iter()
+                .filter(|parameter| {
+                    patterns
+                        .iter()
+                        .any(|pattern| glob_match(&pattern.to_lowercase(), parameter))
+                })
+                .map(|parameter| {
+                    (
+                        parameter.to_string(),
+                        config_value(parameter, &config, cache),
+                    )
+                })
+                .collect();
+            serialize_pairs(pairs, client.protocol)
+        }
+        ("set", pairs) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+            // Every parameter is validated before any of them is applied.
+            let mut config = cache.maxmemory_config();
+            for pair in pairs.chunks(2) {
+                let parameter = pair[0].to_lowercase();
+                if !CONFIG_PARAMETERS.contains(&parameter.as_str()) {
+                    return serialize_error(
+                        concat_string!(
+                            "-ERR Unknown option or number of arguments for CONFIG SET - '",
+                            pair[0],
+                            "'"
+                        )
+                        .as_str(),
+                    );
+                }
+                if let Err(reason) = set_config_value(&mut config, &parameter, &pair[1]) {
+                    return serialize_error(
+                        concat_string!(
+                            "-ERR CONFIG SET failed (possibly related to argument '",
+                            parameter,
+                            "') - ",
+                            reason
+                        )
+                        .as_str(),
+                    );
+                }
+            }
+            let result = cache
+                .set_maxmemory_config(config)
+                .and_then(|()| cache.free_memory_if_needed());
+            match result {
+                // Like Redis, lowering the limit below the data set is not an error.
+                Ok(()) | Err(OOM_ERR) => serialize(InputVariants::StringVariant("+OK".to_string())),
+                Err(err) => serialize_error(concat_string!("-", err).as_str()),
+            }
+        }
+        ("get" | "set", _) => {
+            throw_err_if_num_of_args_wrong(concat_string!("config|", subcommand).as_str())
+        }
+        _ => serialize_error(
+            concat_string!(
+                "-ERR unknown subcommand '",
+                subcommand,
+                "'. Try CONFIG HELP."
+            )
+            .as_str(),
+        ),
+    }
+}
+
+/// MEMORY STATS: totals, then the overheads of every database holding keys.
+fn memory_stats_reply(cache: &Cache, protocol: u8) -> Result<Cow<'static, str>, &'static str> {
+    let databases = cache.memory_stats()?;
+    let total: usize = databases.iter().map(|db| db.used).sum();
+    let keys: usize = databases.iter().map(|db| db.keys).sum();
+    let overhead: usize = databases
+        .iter()
+        .map(|db| db.overhead_main + db.overhead_expires)
+        .sum();
+    let dataset = total - overhead;
+    let number = |value: usize| InputVariants::NumberVariant(value as i64);
+    let mut fields = vec![
+        ("total.allocated", number(total)),
+        ("overhead.total", number(overhead)),
+        ("keys.count", number(keys)),
+        (
+            "keys.bytes-per-key",
+            number(total.checked_div(keys).unwrap_or(0)),
+        ),
+        ("dataset.bytes", number(dataset)),
+        (
+            "dataset.percentage",
+            score_variant(dataset as f64 * 100.0 / total.max(1) as f64, protocol),
+        ),
+    ];
+    let names: Vec<String> = databases
+        .iter()
+        .map(|db| concat_string!("db.", db.index.to_string()))
+        .collect();
+    for (name, db) in names.iter().zip(&databases) {
+        let overheads = vec![
+            ("overhead.hashtable.main", number(db.overhead_main)),
+            ("overhead.hashtable.expires", number(db.overhead_expires)),
+        ];
+        fields.push((name.as_str(), info_variant(overheads, protocol)));
+    }
+    Ok(serialize(info_variant(fields, protocol)))
+}
+
+/// MEMORY DOCTOR: where the memory goes and the largest keys, as text for people.
+fn memory_doctor_report(cache: &Cache) -> Result<String, &'static str> {
+    let databases: Vec<DatabaseMemory> = cache.memory_stats()?;
+    if databases.is_empty() {
+        return Ok("The instance is empty, there is nothing to report.".to_string());
+    }
+    let config = cache.maxmemory_config();
+    let total: usize = databases.iter().map(|db| db.used).sum();
+    let main: usize = databases.iter().map(|db| db.overhead_main).sum();
+    let expires: usize = databases.iter().map(|db| db.overhead_expires).sum();
+    let share = |bytes: usize| bytes as f64 * 100.0 / total.max(1) as f64;
+
+    let mut lines = vec![
+        format!(
+            "Used memory: {} in {} keys.",
+            human_bytes(total),
+            databases.iter().map(|db| db.keys).sum::<usize>()
+        ),
+        format!(
+            "Dataset: {} ({:.2}%), key overhead: {} ({:.2}%), expiry overhead: {} ({:.2}%).",
+            human_bytes(total - main - expires),
+            share(total - main - expires),
+            human_bytes(main),
+            share(main),
+            human_bytes(expires),
+            share(expires)
+        ),
+    ];
+    for db in &databases {
+        lines.push(format!(
+            "db{}: {} in {} keys, {} of it overhead.",
+            db.index,
+            human_bytes(db.used),
+            db.keys,
+            human_bytes(db.overhead_main + db.overhead_expires)
+        ));
+    }
+    if config.maxmemory > 0 && total * 10 >= config.maxmemory * 9 {
+        lines.push(format!(
+            "Used memory is at {:.2}% of maxmemory ({}), writes will {}.",
+            total as f64 * 100.0 / config.maxmemory as f64,
+            human_bytes(config.maxmemory),
+            match config.policy {
+                EvictionPolicy::NoEviction => "fail with OOM once it is reached",
+                _ => "evict keys once it is reached",
+            }
+        ));
+    }
+    lines.push("Largest keys:".to_string());
+    for large in cache.largest_keys(DOCTOR_LARGEST_KEYS)? {
+        lines.push(format!(
+            "  db{} \"{}\" ({}): {} ({:.2}%)",
+            large.db,
+            large.key,
+            large.type_name,
+            human_bytes(large.size),
+            share(large.size)
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// MEMORY USAGE key [SAMPLES count] | STATS | DOCTOR
+pub fn handle_memory(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let Some(subcommand) = args.first().map(|s| s.to_lowercase()) else {
+        return throw_err_if_num_of_args_wrong("memory");
+    };
+    match (subcommand.as_str(), &args[1..]) {
+        ("usage", [key, options @ ..]) => {
+            let samples = match options {
+                [] => DEFAULT_SAMPLES,
+                [option, samples] if option.eq_ignore_ascii_case("samples") => {
+                    // Zero samples every element.
+                    match parse_integer(samples) {
+                        Ok(samples) if samples >= 0 => samples as usize,
+                        Ok(_) => return serialize_error("-ERR syntax error"),
+                        Err(err) => return err,
+                    }
+                }
+                _ => return serialize_error("-ERR syntax error"),
+            };
+            match cache.memory_usage(key, samples) {
+                Ok(Some(bytes)) => serialize(InputVariants::NumberVariant(bytes as i64)),
+                Ok(None) => serialize(optional_string(None)),
+                Err(err) => serialize_error(concat_string!("-", err).as_str()),
+            }
+        }
+        ("stats", []) => match memory_stats_reply(cache, client.protocol) {
+            Ok(reply) => reply,
+            Err(err) => serialize_error(concat_string!("-", err).as_str()),
+        },
+        ("doctor", []) => match memory_doctor_report(cache) {
+            Ok(report) => serialize(InputVariants::StringVariant(report)),
+            Err(err) => serialize_error(concat_string!("-", err).as_str()),
+        },
+        ("usage" | "stats" | "doctor", _) => {
+            throw_err_if_num_of_args_wrong(concat_string!("memory|", subcommand).as_str())
+        }
+        _ => serialize_error(
+            concat_string!(
+                "-ERR unknown subcommand '",
+                subcommand,
+                "'. Try MEMORY HELP."
+            )
+            .as_str(),
+        ),
+    }
+}
+
+fn info_section(section: &str, cache: &Cache) -> Result<String, &'static str> {
+    match section {
+        "memory" => {
+            let used = cache.used_memory()?;
+            let config = cache.maxmemory_config();
+            Ok(format!(
+                "# Memory\r\nused_memory:{}\r\nused_memory_human:{}\r\nmaxmemory:{}\r\n\
+                 maxmemory_human:{}\r\nmaxmemory_policy:{}\r\n",
+                used,
+                human_bytes(used),
+                config.maxmemory,
+                human_bytes(config.maxmemory),
+                config.policy.name()
+            ))
+        }
+        "stats" => Ok(format!(
+            "# Stats\r\nevicted_keys:{}\r\n",
+            cache.evicted_keys()
+        )),
+        "keyspace" => {
+            let mut lines = String::from("# Keyspace\r\n");
+            for (index, stats) in cache.keyspace_stats()? {
+                lines.push_str(&format!(
+                    "db{}:keys={},expires={},avg_ttl={}\r\n",
+                    index, stats.keys, stats.expires, stats.avg_ttl
+                ));
+            }
+            Ok(lines)
+        }
+        _ => Ok(String::new()),
+    }
+}
+
+/// INFO [section [section ...]]
+pub fn handle_info(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let requested: Vec<String> = args.iter().map(|arg| arg.to_lowercase()).collect();
+    let everything = requested.is_empty()
+        || requested
+            .iter()
+            .any(|section| ["default", "all", "everything"].contains(&section.as_str()));
+    let mut sections = Vec::new();
+    for section in INFO_SECTIONS {
+        if everything || requested.iter().any(|requested| requested == section) {
+            match info_section(section, cache) {
+                Ok(lines) => sections.push(lines),
+                Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+            }
+        }
+    }
+    serialize(InputVariants::StringVariant(sections.join("\r\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    #[test]
+    fn should_keep_databases_apart() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        let other = &mut Client::new(None);
+        run(&cache, client, &["SET", "k", "zero"]);
+        assert_eq!(run(&cache, client, &["SELECT", "1"]), "+OK\r\n");
+        assert_eq!(run(&cache, client, &["GET", "k"]), "+(nil)\r\n");
+        run(&cache, client, &["SET", "k", "one"]);
+        assert_eq!(run(&cache, other, &["GET", "k"]), "$4\r\nzero\r\n");
+
+        assert_eq!(run(&cache, other, &["SWAPDB", "0", "1"]), "+OK\r\n");
+        assert_eq!(run(&cache, other, &["GET", "k"]), "$3\r\none\r\n");
+        assert_eq!(run(&cache, client, &["GET", "k"]), "$4\r\nzero\r\n");
+
+        assert_eq!(run(&cache, client, &["MOVE", "k", "2"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["MOVE", "k", "2"]), ":0\r\n");
+        run(&cache, client, &["MULTI"]);
+        run(&cache, client, &["SELECT", "2"]);
+        run(&cache, client, &["GET", "k"]);
+        assert_eq!(
+            run(&cache, client, &["EXEC"]),
+            "*2\r\n+OK\r\n$4\r\nzero\r\n"
+        );
+
+        assert_eq!(
+            run(&cache, client, &["SELECT", "16"]),
+            "-ERR DB index is out of range\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SWAPDB", "x", "1"]),
+            "-ERR invalid first DB index\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SWAPDB", "0", "-1"]),
+            "-ERR DB index is out of range\r\n"
+        );
+    }
+
+    #[test]
+    fn should_serve_blocked_clients_after_swapdb() {
+        let cache = Cache::new();
+        let mut blocked = Client::new(None);
+        let id = blocked.id;
+        let handle = {
+            let cache = cache.clone();
+            thread::spawn(move || run(&cache, &mut blocked, &["BLPOP", "jobs", "0"]))
+        };
+        while !cache.is_client_blocked(id) {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SELECT", "1"]);
+        run(&cache, client, &["RPUSH", "jobs", "a", "b"]);
+        assert_eq!(run(&cache, client, &["SWAPDB", "1", "0"]), "+OK\r\n");
+        assert_eq!(handle.join().unwrap(), "*2\r\n$4\r\njobs\r\n$1\r\na\r\n");
+        assert_eq!(run(&cache, client, &["LLEN", "jobs"]), ":0\r\n");
+        run(&cache, client, &["SELECT", "0"]);
+        assert_eq!(run(&cache, client, &["LLEN", "jobs"]), ":1\r\n");
+    }
+
+    #[test]
+    fn should_report_the_keyspace_per_database() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["INFO", "keyspace"]),
+            "$12\r\n# Keyspace\r\n\r\n"
+        );
+        run(&cache, client, &["SET", "a", "1"]);
+        run(&cache, client, &["SET", "b", "1", "EX", "100"]);
+        run(&cache, client, &["SELECT", "3"]);
+        run(&cache, client, &["SET", "c", "1"]);
+        let info = run(&cache, client, &["INFO"]);
+        assert!(info.contains("db0:keys=2,expires=1,avg_ttl="));
+        assert!(info.contains("db3:keys=1,expires=0,avg_ttl=0\r\n"));
+        assert!(!info.contains("db1:"));
+        assert_eq!(run(&cache, client, &["INFO", "nothing"]), "$0\r\n\r\n");
+    }
+
+    #[test]
+    fn should_configure_maxmemory_and_refuse_writes_over_it() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["CONFIG", "GET", "maxmemory*"]),
+            "*6\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n$16\r\nmaxmemory-policy\r\n\
+             $10\r\nnoeviction\r\n$17\r\nmaxmemory-samples\r\n$1\r\n5\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["CONFIG", "SET", "maxmemory-policy", "lru"]
+            ),
+            "-ERR CONFIG SET failed (possibly related to argument 'maxmemory-policy') - \
+             argument must be a policy\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["CONFIG", "SET", "databases", "4"]),
+            "-ERR CONFIG SET failed (possibly related to argument 'databases') - \
+             can't set immutable config\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["CONFIG", "GET", "zset-*"]),
+            "*4\r\n$25\r\nzset-max-listpack-entries\r\n$3\r\n128\r\n\
+             $23\r\nzset-max-listpack-value\r\n$2\r\n64\r\n"
+        );
+        assert!(run(
+            &cache,
+            client,
+            &["CONFIG", "SET", "hash-max-listpack-value", "1"]
+        )
+        .ends_with("can't set immutable config\r\n"));
+        assert_eq!(
+            run(&cache, client, &["CONFIG", "SET", "nope", "1"]),
+            "-ERR Unknown option or number of arguments for CONFIG SET - 'nope'\r\n"
+        );
+
+        run(&cache, client, &["SET", "big", &"x".repeat(4096)]);
+        assert_eq!(
+            run(&cache, client, &["CONFIG", "SET", "maxmemory", "1kb"]),
+            "+OK\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SET", "k", "v"]),
+            "-OOM command not allowed when used memory > 'maxmemory'.\r\n"
+        );
+        // Reads and deletes still work.
+        assert_eq!(run(&cache, client, &["STRLEN", "big"]), ":4096\r\n");
+        assert!(run(&cache, client, &["INFO", "memory"]).contains("maxmemory_human:1.00K\r\n"));
+
+        run(
+            &cache,
+            client,
+            &["CONFIG", "SET", "maxmemory-policy", "allkeys-lru"],
+        );
+        assert_eq!(run(&cache, client, &["SET", "k", "v"]), "+OK\r\n");
+        assert_eq!(run(&cache, client, &["EXISTS", "big"]), ":0\r\n");
+        assert!(run(&cache, client, &["INFO", "stats"]).contains("evicted_keys:1\r\n"));
+    }
+
+    #[test]
+    fn should_report_memory_usage_and_stats() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["MEMORY", "USAGE", "nope"]), "$-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["MEMORY", "DOCTOR"]),
+            "$50\r\nThe instance is empty, there is nothing to report.\r\n"
+        );
+
+        let values: Vec<String> = (0..100).map(|i| "v".repeat(i * 10)).collect();
+        let mut push = vec!["RPUSH", "list"];
+        push.extend(values.iter().map(String::as_str));
+        run(&cache, client, &push);
+        run(&cache, client, &["SET", "small", "v", "EX", "100"]);
+        let usage = |reply: String| -> usize { reply[1..reply.len() - 2].parse().unwrap() };
+        let sampled = usage(run(&cache, client, &["MEMORY", "USAGE", "list"]));
+        let exact = usage(run(
+            &cache,
+            client,
+            &["MEMORY", "USAGE", "list", "SAMPLES", "0"],
+        ));
+        // The first five elements are the shortest, so sampling them underestimates.
+        assert!(
+            exact > 50_000 && sampled < exact / 5,
+            "{} {}",
+            sampled,
+            exact
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["MEMORY", "USAGE", "list", "SAMPLES", "-1"]
+            ),
+            "-ERR syntax error\r\n"
+        );
+
+        let stats = run(&cache, client, &["MEMORY", "STATS"]);
+        assert!(stats.starts_with("*14\r\n$15\r\ntotal.allocated\r\n"));
+        assert!(stats.contains("$10\r\nkeys.count\r\n:2\r\n"));
+        assert!(stats.contains(
+            "$4\r\ndb.0\r\n*4\r\n$23\r\noverhead.hashtable.main\r\n:272\r\n\
+             $26\r\noverhead.hashtable.expires\r\n:64\r\n"
+        ));
+
+        let doctor = run(&cache, client, &["MEMORY", "DOCTOR"]);
+        assert!(doctor.contains("Largest keys:\n  db0 \"list\" (list): "));
+        assert!(doctor.contains("\n  db0 \"small\" (string): "));
+    }
+}
@@ -0,0 +1,351 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{db::Cache, pattern::glob_match, set::SetOperation},
+};
+
+use super::utils::{
+    optional_string, parse_integer, parse_intercard_args, parse_scan_args, serialize_error,
+    serialize_len, serialize_scan_page, throw_err_if_num_of_args_wrong,
+};
+
+/// SADD key member [member ...] and SREM key member [member ...]
+pub fn handle_sadd_srem(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, members @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    if members.is_empty() {
+        return throw_err_if_num_of_args_wrong(command);
+    }
+    let result = if command == "sadd" {
+        cache.sadd(key, members)
+    } else {
+        cache.srem(key, members)
+    };
+    match result {
+        Ok(changed) => serialize_len(changed),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_sismember(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, member] = args else {
+        return throw_err_if_num_of_args_wrong("sismember");
+    };
+    match cache.smismember(key, std::slice::from_ref(member)) {
+        Ok(found) => serialize(InputVariants::NumberVariant(i64::from(found[0]))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_smismember(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, members @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("smismember");
+    };
+    if members.is_empty() {
+        return throw_err_if_num_of_args_wrong("smismember");
+    }
+    match cache.smismember(key, members) {
+        Ok(found) => serialize(InputVariants::Array(
+            found
+                .into_iter()
+                .map(|found| InputVariants::NumberVariant(i64::from(found)))
+                .collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_smembers(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("smembers");
+    };
+    match cache.smembers(key) {
+        Ok(members) => serialize(InputVariants::StringVariantArr(members)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_scard(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("scard");
+    };
+    match cache.scard(key) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SPOP key [count]. Without a count a single member (or nil) is returned, with one an array.
+pub fn handle_spop(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let (key, count) = match args {
+        [key] => (key, None),
+        [key, count] => match parse_integer(count) {
+            Ok(count) if count >= 0 => (key, Some(count as usize)),
+            Ok(_) => return serialize_error("-ERR value is out of range, must be positive"),
+            Err(err) => return err,
+        },
+        _ => return throw_err_if_num_of_args_wrong("spop"),
+    };
+    match (count, cache.spop(key, count.unwrap_or(1))) {
+        (None, Ok(popped)) => serialize(optional_string(popped.into_iter().next())),
+        (Some(_), Ok(popped)) => serialize(InputVariants::StringVariantArr(popped)),
+        (_, Err(err)) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SRANDMEMBER key [count]. A negative count may return the same member more than once.
+pub fn handle_srandmember(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let (key, count) = match args {
+        [key] => (key, None),
+        [key, count] => match parse_integer(count) {
+            Ok(count) => (key, Some(count)),
+            Err(err) => return err,
+        },
+        _ => return throw_err_if_num_of_args_wrong("srandmember"),
+    };
+    match (count, cache.srandmember(key, count.unwrap_or(1))) {
+        (None, Ok(picked)) => serialize(optional_string(picked.into_iter().next())),
+        (Some(_), Ok(picked)) => serialize(InputVariants::StringVariantArr(picked)),
+        (_, Err(err)) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_smove(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [source, destination, member] = args else {
+        return throw_err_if_num_of_args_wrong("smove");
+    };
+    match cache.smove(source, destination, member) {
+        Ok(moved) => serialize(InputVariants::NumberVariant(i64::from(moved))),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SINTER, SUNION and SDIFF key [key ...]
+pub fn handle_set_combine(
+    args: &[String],
+    cache: &Cache,
+    operation: SetOperation,
+    command: &str,
+) -> Cow<'static, str> {
+    if args.is_empty() {
+        return throw_err_if_num_of_args_wrong(command);
+    }
+    match cache.set_combine(operation, args) {
+        Ok(members) => serialize(InputVariants::StringVariantArr(members)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SINTERSTORE, SUNIONSTORE and SDIFFSTORE destination key [key ...]
+pub fn handle_set_combine_store(
+    args: &[String],
+    cache: &Cache,
+    operation: SetOperation,
+    command: &str,
+) -> Cow<'static, str> {
+    let [destination, keys @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    if keys.is_empty() {
+        return throw_err_if_num_of_args_wrong(command);
+    }
+    match cache.set_combine_store(operation, destination, keys) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SINTERCARD numkeys key [key ...] [LIMIT limit]
+pub fn handle_sintercard(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let (keys, limit) = match parse_intercard_args(args, "sintercard") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.sintercard(keys, limit) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// SSCAN key cursor [MATCH pattern] [COUNT count]
+pub fn handle_sscan(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, cursor, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("sscan");
+    };
+    let (cursor, options) = match parse_scan_args(cursor, options, "sscan") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+
+    let (cursor, members) = match cache.sscan(key, cursor, options.count) {
+        Ok(page) => page,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    let items: Vec<String> = members
+        .into_iter()
+        .filter(|member| {
+            options
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, member))
+        })
+        .collect();
+    serialize_scan_page(cursor, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection_manager::{client::Client, test_utils::run};
+
+    use super::*;
+
+    #[test]
+    fn should_add_remove_and_check_members() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(run(&cache, client, &["SADD", "s", "a", "b", "a"]), ":2\r\n");
+        assert_eq!(run(&cache, client, &["SCARD", "s"]), ":2\r\n");
+        assert_eq!(run(&cache, client, &["SISMEMBER", "s", "a"]), ":1\r\n");
+        assert_eq!(
+            run(&cache, client, &["SMISMEMBER", "s", "a", "c"]),
+            "*2\r\n:1\r\n:0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["SREM", "s", "a", "b", "c"]), ":2\r\n");
+        assert_eq!(run(&cache, client, &["EXISTS", "s"]), ":0\r\n");
+
+        run(&cache, client, &["LPUSH", "l", "x"]);
+        assert_eq!(
+            run(&cache, client, &["SADD", "l", "a"]),
+            "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+        );
+    }
+
+    #[test]
+    fn should_pop_and_sample_members() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SADD", "s", "1", "2", "3"]);
+        assert_eq!(
+            run(&cache, client, &["SRANDMEMBER", "s", "5"])
+                .lines()
+                .next(),
+            Some("*3")
+        );
+        assert_eq!(
+            run(&cache, client, &["SRANDMEMBER", "s", "-5"])
+                .lines()
+                .next(),
+            Some("*5")
+        );
+        assert_eq!(
+            run(&cache, client, &["SRANDMEMBER", "s", "-10000000000000"]),
+            "-ERR value is out of range\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SPOP", "s", "2"]).lines().next(),
+            Some("*2")
+        );
+        assert_eq!(run(&cache, client, &["SCARD", "s"]), ":1\r\n");
+        run(&cache, client, &["SPOP", "s"]);
+        assert_eq!(run(&cache, client, &["SPOP", "s"]), "$-1\r\n");
+        assert_eq!(run(&cache, client, &["SPOP", "s", "3"]), "*0\r\n");
+    }
+
+    #[test]
+    fn should_move_and_store_combinations() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SADD", "a", "1", "2", "3"]);
+        run(&cache, client, &["SADD", "b", "2", "3", "4"]);
+        assert_eq!(run(&cache, client, &["SMOVE", "a", "b", "1"]), ":1\r\n");
+        assert_eq!(run(&cache, client, &["SMOVE", "a", "b", "1"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["SINTERSTORE", "c", "a", "b"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SDIFFSTORE", "c", "b", "a"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SINTERCARD", "2", "a", "b", "LIMIT", "1"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SINTERCARD", "2", "a", "b"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SINTERCARD", "2", "a", "missing"]),
+            ":0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SINTERCARD", "3", "a", "b"]),
+            "-ERR Number of keys can't be greater than number of args\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SDIFFSTORE", "c", "a", "b"]),
+            ":0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "c"]), ":0\r\n");
+    }
+
+    #[test]
+    fn should_drop_the_ttl_of_a_stored_destination() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SADD", "a", "1"]);
+        for command in ["SINTERSTORE", "SUNIONSTORE", "SDIFFSTORE"] {
+            run(&cache, client, &["SET", "d", "x", "EX", "1000"]);
+            assert_eq!(run(&cache, client, &[command, "d", "a"]), ":1\r\n");
+            assert_eq!(run(&cache, client, &["TTL", "d"]), ":-1\r\n");
+        }
+    }
+
+    #[test]
+    fn should_scan_with_match() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["SADD", "s", "apple", "banana"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["SSCAN", "s", "0", "MATCH", "a*", "COUNT", "5"]
+            ),
+            "*2\r\n$1\r\n0\r\n*1\r\n$5\r\napple\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["SSCAN", "s", "0", "NOVALUES"]),
+            "-ERR syntax error\r\n"
+        );
+    }
+
+    #[test]
+    fn should_page_through_large_sets() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        let members: Vec<String> = (0..200).map(|i| format!("m{}", i)).collect();
+        let mut command = vec!["SADD", "s"];
+        command.extend(members.iter().map(String::as_str));
+        run(&cache, client, &command);
+
+        let mut cursor = "0".to_string();
+        let mut seen = std::collections::HashSet::new();
+        let mut pages = 0;
+        loop {
+            let reply = run(&cache, client, &["SSCAN", "s", &cursor, "COUNT", "20"]);
+            let lines: Vec<&str> = reply.split("\r\n").collect();
+            seen.extend(lines[4..].iter().skip(1).step_by(2).map(|m| m.to_string()));
+            pages += 1;
+            if lines[2] == "0" {
+                break;
+            }
+            cursor = lines[2].to_string();
+        }
+        assert!(pages > 1);
+        assert_eq!(seen.len(), 200);
+    }
+}
@@ -0,0 +1,693 @@
+use std::{borrow::Cow, collections::HashMap, time::Duration};
+
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        blocking::ServeFn,
+        db::Cache,
+        stream::{
+            read_after, IdSpec, StreamEntry, StreamId, StreamInfo, TrimOptions, TrimStrategy,
+            XAddOptions, STREAM_DEFAULT_TRIM_LIMIT,
+        },
+    },
+};
+
+use super::{
+    client::Client,
+    consumer_group_commands::{handle_xinfo_consumers, handle_xinfo_groups},
+    utils::{
+        parse_integer, serialize_block_outcome, serialize_error, serialize_len,
+        throw_err_if_num_of_args_wrong,
+    },
+};
+
+const INVALID_ID_ERR: &str = "-ERR Invalid stream ID specified as stream command argument";
+
+pub(super) fn parse_id(arg: &str) -> Result<StreamId, Cow<'static, str>> {
+    StreamId::parse(arg, 0).ok_or_else(|| serialize_error(INVALID_ID_ERR))
+}
+
+/// `[id, [field, value, ...]]`
+pub(super) fn entry_variant((id, fields): StreamEntry) -> InputVariants {
+    InputVariants::Array(vec![
+        InputVariants::StringVariant(id.to_string()),
+        InputVariants::StringVariantArr(
+            fields
+                .into_iter()
+                .flat_map(|(field, value)| [field, value])
+                .collect(),
+        ),
+    ])
+}
+
+pub(super) fn entries_variant(entries: Vec<StreamEntry>) -> InputVariants {
+    InputVariants::Array(entries.into_iter().map(entry_variant).collect())
+}
+
+/// XREAD and XREADGROUP replies: a map of key to entries in RESP3, `[[key, entries], ...]`
+/// in RESP2.
+pub(super) fn serialize_streams(
+    streams: Vec<(String, InputVariants)>,
+    protocol: u8,
+) -> Cow<'static, str> {
+    if protocol >= 3 {
+        return serialize(InputVariants::Map(
+            streams
+                .into_iter()
+                .map(|(key, entries)| (InputVariants::StringVariant(key), entries))
+                .collect(),
+        ));
+    }
+    serialize(InputVariants::Array(
+        streams
+            .into_iter()
+            .map(|(key, entries)| {
+                InputVariants::Array(vec![InputVariants::StringVariant(key), entries])
+            })
+            .collect(),
+    ))
+}
+
+/// XINFO replies: a map in RESP3 and a flat array of names and values in RESP2.
+pub(super) fn info_variant(fields: Vec<(&str, InputVariants)>, protocol: u8) -> InputVariants {
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| (InputVariants::StringVariant(name.to_string()), value));
+    if protocol >= 3 {
+        InputVariants::Map(fields.collect())
+    } else {
+        InputVariants::Array(fields.flat_map(|(name, value)| [name, value]).collect())
+    }
+}
+
+/// Options shared by XREAD and XREADGROUP, with the STREAMS keys and IDs split in halves.
+pub(super) struct ReadArgs<'a> {
+    pub count: Option<usize>,
+    /// `Some` with BLOCK, holding `None` for BLOCK 0 (wait forever).
+    pub block: Option<Option<Duration>>,
+    pub no_ack: bool,
+    pub keys: &'a [String],
+    pub ids: &'a [String],
+}
+
+/// Parses `[COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]`, with
+/// NOACK only valid for XREADGROUP.
+pub(super) fn parse_read_args<'a>(
+    mut args: &'a [String],
+    command: &str,
+) -> Result<ReadArgs<'a>, Cow<'static, str>> {
+    let mut count = None;
+    let mut block = None;
+    let mut no_ack = false;
+    let streams = loop {
+        let Some((option, tail)) = args.split_first() else {
+            return Err(serialize_error("-ERR syntax error"));
+        };
+        args = match (option.to_uppercase().as_str(), tail.first()) {
+            ("STREAMS", _) => break tail,
+            ("NOACK", _) if command == "xreadgroup" => {
+                no_ack = true;
+                tail
+            }
+            ("COUNT", Some(value)) => {
+                let value = parse_integer(value)?;
+                count = (value > 0).then_some(value as usize);
+                &tail[1..]
+            }
+            ("BLOCK", Some(value)) => {
+                match parse_integer(value) {
+                    Ok(ms) if ms < 0 => return Err(serialize_error("-ERR timeout is negative")),
+                    Ok(ms) => block = Some((ms > 0).then(|| Duration::from_millis(ms as u64))),
+                    Err(_) => {
+                        return Err(serialize_error(
+                            "-ERR timeout is not an integer or out of range",
+                        ))
+                    }
+                }
+                &tail[1..]
+            }
+            _ => return Err(serialize_error("-ERR syntax error")),
+        };
+    };
+    if streams.is_empty() || streams.len() % 2 != 0 {
+        return Err(serialize_error(
+            concat_string!(
+                "-ERR Unbalanced '",
+                command,
+                "' list of streams: for each stream key an ID or '$' must be specified."
+            )
+            .as_str(),
+        ));
+    }
+    let (keys, ids) = streams.split_at(streams.len() / 2);
+    Ok(ReadArgs {
+        count,
+        block,
+        no_ack,
+        keys,
+        ids,
+    })
+}
+
+/// Parses `MAXLEN|MINID [=|~] threshold [LIMIT count]` at the start of `args` and returns the
+/// options along with how many arguments they took.
+fn parse_trim(args: &[String]) -> Result<(TrimOptions, usize), Cow<'static, str>> {
+    let syntax_err = || serialize_error("-ERR syntax error");
+    let strategy = args.first().ok_or_else(syntax_err)?.to_uppercase();
+    let mut position = 1;
+    let approximate = match args.get(position).map(String::as_str) {
+        Some("~") => true,
+        Some("=") => false,
+        _ => {
+            position -= 1;
+            false
+        }
+    };
+    position += 1;
+    let threshold = args.get(position).ok_or_else(syntax_err)?;
+    let strategy = match strategy.as_str() {
+        "MAXLEN" => match parse_integer(threshold)? {
+            max_len if max_len >= 0 => TrimStrategy::MaxLen(max_len as usize),
+            _ => return Err(serialize_error("-ERR The MAXLEN argument must be >= 0.")),
+        },
+        "MINID" => TrimStrategy::MinId(parse_id(threshold)?),
+        _ => return Err(syntax_err()),
+    };
+    position += 1;
+
+    let mut limit = approximate.then_some(STREAM_DEFAULT_TRIM_LIMIT);
+    if args
+        .get(position)
+        .is_some_and(|arg| arg.eq_ignore_ascii_case("limit"))
+    {
+        let count = args.get(position + 1).ok_or_else(syntax_err)?;
+        let count = match parse_integer(count)? {
+            count if count >= 0 => count as usize,
+            _ => return Err(serialize_error("-ERR The LIMIT argument must be >= 0.")),
+        };
+        if !approximate {
+            return Err(serialize_error(
+                "-ERR syntax error, LIMIT cannot be used without the special ~ option",
+            ));
+        }
+        limit = (count > 0).then_some(count);
+        position += 2;
+    }
+    Ok((
+        TrimOptions {
+            strategy,
+            approximate,
+            limit,
+        },
+        position,
+    ))
+}
+
+/// XADD key [NOMKSTREAM] [MAXLEN|MINID [=|~] threshold [LIMIT count]] *|id field value
+/// [field value ...]
+pub fn handle_xadd(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let Some((key, mut rest)) = args.split_first() else {
+        return throw_err_if_num_of_args_wrong("xadd");
+    };
+    let mut no_mkstream = false;
+    let mut trim = None;
+    while let Some(option) = rest.first() {
+        match option.to_uppercase().as_str() {
+            "NOMKSTREAM" => {
+                no_mkstream = true;
+                rest = &rest[1..];
+            }
+            "MAXLEN" | "MINID" => match parse_trim(rest) {
+                Ok((options, used)) => {
+                    trim = Some(options);
+                    rest = &rest[used..];
+                }
+                Err(err) => return err,
+            },
+            _ => break,
+        }
+    }
+    let Some((id, fields)) = rest.split_first() else {
+        return throw_err_if_num_of_args_wrong("xadd");
+    };
+    if fields.is_empty() || fields.len() % 2 != 0 {
+        return throw_err_if_num_of_args_wrong("xadd");
+    }
+    let id = match id.as_str() {
+        "*" => IdSpec::Auto,
+        id => match id.strip_suffix("-*") {
+            Some(ms) => match ms.parse() {
+                Ok(ms) => IdSpec::AutoSeq(ms),
+                Err(_) => return serialize_error(INVALID_ID_ERR),
+            },
+            None => match parse_id(id) {
+                Ok(id) => IdSpec::Explicit(id),
+                Err(err) => return err,
+            },
+        },
+    };
+    let fields: Vec<(String, String)> = fields
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    let options = XAddOptions {
+        no_mkstream,
+        id,
+        trim,
+    };
+
+    match cache.xadd(key, &options, &fields) {
+        Ok(Some(id)) => serialize(InputVariants::StringVariant(id.to_string())),
+        Ok(None) => serialize(InputVariants::Nullish),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// XLEN key
+pub fn handle_xlen(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("xlen");
+    };
+    match cache.xlen(key) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// Parses an XRANGE bound: `-`, `+`, a full or incomplete ID, or either of them prefixed with
+/// `(` to exclude it. An incomplete ID covers its whole millisecond.
+pub(super) fn parse_range_bound(arg: &str, is_start: bool) -> Result<StreamId, Cow<'static, str>> {
+    let (arg, exclusive) = match arg.strip_prefix('(') {
+        Some(arg) => (arg, true),
+        None => (arg, false),
+    };
+    let id = match arg {
+        "-" => StreamId::MIN,
+        "+" => StreamId::MAX,
+        arg => StreamId::parse(arg, if is_start { 0 } else { u64::MAX })
+            .ok_or_else(|| serialize_error(INVALID_ID_ERR))?,
+    };
+    match (exclusive, is_start) {
+        (false, _) => Ok(id),
+        (true, true) => id
+            .next()
+            .ok_or_else(|| serialize_error("-ERR invalid start ID for the interval")),
+        (true, false) => id
+            .prev()
+            .ok_or_else(|| serialize_error("-ERR invalid end ID for the interval")),
+    }
+}
+
+/// XRANGE key start end [COUNT count], and XREVRANGE key end start [COUNT count]
+pub fn handle_xrange(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let reverse = command == "xrevrange";
+    let (key, first, second, count) = match args {
+        [key, first, second] => (key, first, second, None),
+        [key, first, second, option, count] if option.eq_ignore_ascii_case("count") => {
+            match parse_integer(count) {
+                Ok(count) => (key, first, second, Some(count.max(0) as usize)),
+                Err(err) => return err,
+            }
+        }
+        [_, _, _, _, _] => return serialize_error("-ERR syntax error"),
+        _ => return throw_err_if_num_of_args_wrong(command),
+    };
+    let (start, end) = if reverse {
+        (second, first)
+    } else {
+        (first, second)
+    };
+    let (start, end) = match (
+        parse_range_bound(start, true),
+        parse_range_bound(end, false),
+    ) {
+        (Ok(start), Ok(end)) => (start, end),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+
+    match cache.xrange(key, start, end, count, reverse) {
+        Ok(entries) => serialize(entries_variant(entries)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// XDEL key id [id ...]
+pub fn handle_xdel(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, ids @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("xdel");
+    };
+    if ids.is_empty() {
+        return throw_err_if_num_of_args_wrong("xdel");
+    }
+    let ids = match ids
+        .iter()
+        .map(|id| parse_id(id))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(ids) => ids,
+        Err(err) => return err,
+    };
+    match cache.xdel(key, &ids) {
+        Ok(deleted) => serialize_len(deleted),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// XTRIM key MAXLEN|MINID [=|~] threshold [LIMIT count]
+pub fn handle_xtrim(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("xtrim");
+    };
+    if options.is_empty() {
+        return throw_err_if_num_of_args_wrong("xtrim");
+    }
+    let options = match parse_trim(options) {
+        Ok((trim, used)) if used == options.len() => trim,
+        Ok(_) => return serialize_error("-ERR syntax error"),
+        Err(err) => return err,
+    };
+    match cache.xtrim(key, &options) {
+        Ok(deleted) => serialize_len(deleted),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+fn serialize_info(info: StreamInfo, protocol: u8) -> Cow<'static, str> {
+    let optional_entry = |entry: Option<StreamEntry>| match entry {
+        Some(entry) => entry_variant(entry),
+        None => InputVariants::Nullish,
+    };
+    let recorded_first_entry_id = info
+        .first_entry
+        .as_ref()
+        .map_or(StreamId::MIN, |(id, _)| *id);
+    let fields = vec![
+        ("length", InputVariants::NumberVariant(info.length as i64)),
+        (
+            "radix-tree-keys",
+            InputVariants::NumberVariant(info.radix_tree_keys as i64),
+        ),
+        (
+            "radix-tree-nodes",
+            InputVariants::NumberVariant(info.radix_tree_nodes as i64),
+        ),
+        (
+            "last-generated-id",
+            InputVariants::StringVariant(info.last_generated_id.to_string()),
+        ),
+        (
+            "max-deleted-entry-id",
+            InputVariants::StringVariant(info.max_deleted_entry_id.to_string()),
+        ),
+        (
+            "entries-added",
+            InputVariants::NumberVariant(info.entries_added as i64),
+        ),
+        (
+            "recorded-first-entry-id",
+            InputVariants::StringVariant(recorded_first_entry_id.to_string()),
+        ),
+        ("groups", InputVariants::NumberVariant(info.groups as i64)),
+        ("first-entry", optional_entry(info.first_entry)),
+        ("last-entry", optional_entry(info.last_entry)),
+    ];
+    serialize(info_variant(fields, protocol))
+}
+
+/// XINFO STREAM key, XINFO GROUPS key and XINFO CONSUMERS key group
+pub fn handle_xinfo(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let Some((subcommand, args)) = args.split_first() else {
+        return throw_err_if_num_of_args_wrong("xinfo");
+    };
+    match subcommand.to_lowercase().as_str() {
+        "stream" => {
+            let [key] = args else {
+                return throw_err_if_num_of_args_wrong("xinfo|stream");
+            };
+            match cache.xinfo_stream(key) {
+                Ok(Some(info)) => serialize_info(info, client.protocol),
+                Ok(None) => serialize_error("-ERR no such key"),
+                Err(err) => serialize_error(concat_string!("-", err).as_str()),
+            }
+        }
+        "groups" => handle_xinfo_groups(args, cache, client),
+        "consumers" => handle_xinfo_consumers(args, cache, client),
+        _ => serialize_error(
+            concat_string!(
+                "-ERR unknown subcommand '",
+                subcommand,
+                "'. Try XINFO HELP."
+            )
+            .as_str(),
+        ),
+    }
+}
+
+/// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+///
+/// `$` stands for the last ID of the stream at the time of the call. Without BLOCK, or when the
+/// streams already have something newer, the reply is immediate; otherwise the client waits
+/// for the first stream to get a new entry and only that stream is reported.
+pub fn handle_xread(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let ReadArgs {
+        count,
+        block,
+        keys,
+        ids,
+        ..
+    } = match parse_read_args(args, "xread") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    let last_ids = match cache.xlast_ids(keys) {
+        Ok(last_ids) => last_ids,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    let mut after = Vec::with_capacity(keys.len());
+    for ((key, id), last_id) in keys.iter().zip(ids).zip(last_ids) {
+        let id = match id.as_str() {
+            "$" => last_id,
+            id => match parse_id(id) {
+                Ok(id) => id,
+                Err(err) => return err,
+            },
+        };
+        after.push((key.clone(), id));
+    }
+
+    match cache.xread(&after, count) {
+        Ok(found) if !found.is_empty() => {
+            let found = found
+                .into_iter()
+                .map(|(key, entries)| (key, entries_variant(entries)))
+                .collect();
+            return serialize_streams(found, client.protocol);
+        }
+        Ok(_) => {}
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    }
+    let Some(timeout) = block else {
+        return serialize(InputVariants::NullArray);
+    };
+    let protocol = client.protocol;
+    let after: HashMap<String, StreamId> = after.into_iter().collect();
+    let serve: ServeFn = Box::new(move |data, key| {
+        let Some(id) = after.get(key) else {
+            return Ok(None);
+        };
+        Ok(read_after(data, key, *id, count)?.map(|entries| {
+            serialize_streams(vec![(key.to_string(), entries_variant(entries))], protocol)
+                .into_owned()
+        }))
+    });
+    let outcome = cache.block_on(
+        client.id,
+        keys.to_vec(),
+        timeout,
+        client.may_block(),
+        serve,
+        &|| client.is_connected(),
+    );
+    serialize_block_outcome(outcome, InputVariants::NullArray)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    #[test]
+    fn should_add_range_and_delete() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["XADD", "s", "1-1", "a", "1"]),
+            "$3\r\n1-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XADD", "s", "1-*", "b", "2"]),
+            "$3\r\n1-2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XADD", "s", "1-2", "c", "3"]),
+            "-ERR The ID specified in XADD is equal or smaller than the target stream top item\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XADD", "t", "0-0", "c", "3"]),
+            "-ERR The ID specified in XADD must be greater than 0-0\r\n"
+        );
+        assert_eq!(run(&cache, client, &["XLEN", "t"]), ":0\r\n");
+        assert_eq!(
+            run(&cache, client, &["XADD", "t", "NOMKSTREAM", "*", "c", "3"]),
+            "$-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XADD", "s", "5", "c", "3", "d"]),
+            "-ERR wrong number of arguments for 'xadd' command\r\n"
+        );
+        run(&cache, client, &["XADD", "s", "5", "c", "3", "d", "4"]);
+        assert_eq!(run(&cache, client, &["XLEN", "s"]), ":3\r\n");
+
+        assert_eq!(
+            run(&cache, client, &["XRANGE", "s", "-", "+", "COUNT", "1"]),
+            "*1\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\na\r\n$1\r\n1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XRANGE", "s", "(1-1", "1"]),
+            "*1\r\n*2\r\n$3\r\n1-2\r\n*2\r\n$1\r\nb\r\n$1\r\n2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XREVRANGE", "s", "+", "(1-2"]),
+            "*1\r\n*2\r\n$3\r\n5-0\r\n*4\r\n$1\r\nc\r\n$1\r\n3\r\n$1\r\nd\r\n$1\r\n4\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XRANGE", "s", "x", "+"]),
+            "-ERR Invalid stream ID specified as stream command argument\r\n"
+        );
+
+        assert_eq!(run(&cache, client, &["XDEL", "s", "1-2", "9-9"]), ":1\r\n");
+        assert_eq!(
+            run(&cache, client, &["XTRIM", "s", "MAXLEN", "1", "LIMIT", "5"]),
+            "-ERR syntax error, LIMIT cannot be used without the special ~ option\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XTRIM", "s", "MAXLEN", "=", "1"]),
+            ":1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["XLEN", "s"]), ":1\r\n");
+        assert_eq!(
+            run(&cache, client, &["XADD", "s", "MINID", "6", "*", "e", "5"]).len(),
+            "$15\r\n0000000000000-0\r\n".len()
+        );
+        assert_eq!(run(&cache, client, &["XLEN", "s"]), ":1\r\n");
+    }
+
+    #[test]
+    fn should_describe_stream() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["XINFO", "STREAM", "s"]),
+            "-ERR no such key\r\n"
+        );
+        run(&cache, client, &["XADD", "s", "1-1", "a", "1"]);
+        run(&cache, client, &["XADD", "s", "2-1", "b", "2"]);
+        run(&cache, client, &["XDEL", "s", "2-1"]);
+        assert_eq!(
+            run(&cache, client, &["XINFO", "STREAM", "s"]),
+            concat_string!(
+                "*20\r\n",
+                "$6\r\nlength\r\n:1\r\n",
+                "$15\r\nradix-tree-keys\r\n:1\r\n",
+                "$16\r\nradix-tree-nodes\r\n:2\r\n",
+                "$17\r\nlast-generated-id\r\n$3\r\n2-1\r\n",
+                "$20\r\nmax-deleted-entry-id\r\n$3\r\n2-1\r\n",
+                "$13\r\nentries-added\r\n:2\r\n",
+                "$23\r\nrecorded-first-entry-id\r\n$3\r\n1-1\r\n",
+                "$6\r\ngroups\r\n:0\r\n",
+                "$11\r\nfirst-entry\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\na\r\n$1\r\n1\r\n",
+                "$10\r\nlast-entry\r\n*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\na\r\n$1\r\n1\r\n"
+            )
+        );
+    }
+
+    #[test]
+    fn should_read_from_multiple_streams() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["XADD", "a", "1-1", "f", "1"]);
+        run(&cache, client, &["XADD", "a", "1-2", "f", "2"]);
+        run(&cache, client, &["XADD", "b", "3-1", "g", "3"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XREAD", "COUNT", "1", "STREAMS", "a", "b", "c", "1-1", "0", "0"]
+            ),
+            concat_string!(
+                "*2\r\n",
+                "*2\r\n$1\r\na\r\n*1\r\n*2\r\n$3\r\n1-2\r\n*2\r\n$1\r\nf\r\n$1\r\n2\r\n",
+                "*2\r\n$1\r\nb\r\n*1\r\n*2\r\n$3\r\n3-1\r\n*2\r\n$1\r\ng\r\n$1\r\n3\r\n"
+            )
+        );
+        assert_eq!(
+            run(&cache, client, &["XREAD", "STREAMS", "a", "b", "$", "$"]),
+            "*-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["XREAD", "STREAMS", "a", "b", "0"]),
+            "-ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["XREAD", "BLOCK", "20", "STREAMS", "a", "$"]
+            ),
+            "*-1\r\n"
+        );
+    }
+
+    #[test]
+    fn should_wake_every_blocked_reader() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["XADD", "s", "1-1", "f", "1"]);
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let blocked = cache.clone();
+                let mut reader = Client::new(None);
+                let id = reader.id;
+                let handle = thread::spawn(move || {
+                    run(
+                        &blocked,
+                        &mut reader,
+                        &["XREAD", "BLOCK", "0", "STREAMS", "s", "$"],
+                    )
+                });
+                (id, handle)
+            })
+            .collect();
+        let started = Instant::now();
+        for (id, _) in &handles {
+            while !cache.is_client_blocked(*id) {
+                assert!(started.elapsed() < Duration::from_secs(5));
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+        run(&cache, client, &["XADD", "s", "2-1", "f", "2"]);
+        for (_, handle) in handles {
+            assert_eq!(
+                handle.join().unwrap(),
+                "*1\r\n*2\r\n$1\r\ns\r\n*1\r\n*2\r\n$3\r\n2-1\r\n*2\r\n$1\r\nf\r\n$1\r\n2\r\n"
+            );
+        }
+        assert_eq!(run(&cache, client, &["XLEN", "s"]), ":2\r\n");
+    }
+}
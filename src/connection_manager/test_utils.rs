@@ -0,0 +1,14 @@
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::db::Cache,
+};
+
+use super::{client::Client, command_handler::handle_command};
+
+/// Sends `command` through the dispatcher as a client would and returns the raw reply.
+pub fn run(cache: &Cache, client: &mut Client, command: &[&str]) -> String {
+    let input = serialize(InputVariants::StringVariantArr(
+        command.iter().map(|part| part.to_string()).collect(),
+    ));
+    handle_command(input, cache, client).into_owned()
+}
@@ -1,12 +1,20 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Duration};
 
-use crate::resp::serialize::{serialize, InputVariants};
+use crate::{
+    resp::serialize::{serialize, InputVariants},
+    store::{
+        blocking::BlockOutcome,
+        db::{unix_time_ms, Cache},
+        scan::is_type_name,
+        string::parse_strict_integer,
+    },
+};
 
 pub fn throw_err_if_num_of_args_wrong(variant: &str) -> Cow<'static, str> {
-    serialize(InputVariants::StringVariant(concat_string!(
-        "-ERR wrong number of arguments for ",
+    serialize(InputVariants::ErrorVariant(concat_string!(
+        "-ERR wrong number of arguments for '",
         variant,
-        " command"
+        "' command"
     )))
 }
 
@@ -14,3 +22,215 @@ pub fn serialize_error(message: &str) -> Cow<'static, str> {
     println!("{}", message);
     serialize(InputVariants::ErrorVariant(message.to_string()))
 }
+
+pub fn serialize_len(len: usize) -> Cow<'static, str> {
+    match i64::try_from(len) {
+        Ok(len_i64) => serialize(InputVariants::NumberVariant(len_i64)),
+        Err(_) => serialize_error("-ERR length does not fit in a RESP integer"),
+    }
+}
+
+/// Bulk string for `Some`, null bulk string for `None`.
+pub fn optional_string(value: Option<String>) -> InputVariants {
+    match value {
+        Some(value) => InputVariants::BulkString(value),
+        None => InputVariants::Nullish,
+    }
+}
+
+pub fn parse_integer(arg: &str) -> Result<i64, Cow<'static, str>> {
+    parse_strict_integer(arg)
+        .ok_or_else(|| serialize_error("-ERR value is not an integer or out of range"))
+}
+
+pub fn parse_float(arg: &str) -> Result<f64, Cow<'static, str>> {
+    arg.parse::<f64>()
+        .ok()
+        .filter(|value| !value.is_nan())
+        .ok_or_else(|| serialize_error("-ERR value is not a valid float"))
+}
+
+/// Field/value style replies are maps in RESP3 and flat arrays in RESP2.
+pub fn serialize_pairs(pairs: Vec<(String, String)>, protocol: u8) -> Cow<'static, str> {
+    if protocol >= 3 {
+        serialize(InputVariants::Map(
+            pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    (
+                        InputVariants::StringVariant(key),
+                        InputVariants::StringVariant(value),
+                    )
+                })
+                .collect(),
+        ))
+    } else {
+        serialize(InputVariants::StringVariantArr(
+            pairs
+                .into_iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+        ))
+    }
+}
+
+/// MATCH/COUNT/NOVALUES/TYPE options of the SCAN family.
+pub struct ScanOptions {
+    pub pattern: Option<String>,
+    pub count: usize,
+    pub no_values: bool,
+    pub type_name: Option<String>,
+}
+
+/// Parses `cursor [MATCH pattern] [COUNT count] [NOVALUES] [TYPE type]`, accepting NOVALUES
+/// only for HSCAN and TYPE only for SCAN.
+pub fn parse_scan_args(
+    cursor: &str,
+    options: &[String],
+    command: &str,
+) -> Result<(u64, ScanOptions), Cow<'static, str>> {
+    let cursor = cursor
+        .parse::<u64>()
+        .map_err(|_| serialize_error("-ERR invalid cursor"))?;
+    let mut scan_options = ScanOptions {
+        pattern: None,
+        count: 10,
+        no_values: false,
+        type_name: None,
+    };
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.to_uppercase().as_str() {
+            "MATCH" => match options.next() {
+                Some(pattern) => scan_options.pattern = Some(pattern.clone()),
+                None => return Err(serialize_error("-ERR syntax error")),
+            },
+            "COUNT" => match options.next().map(|count| parse_integer(count)) {
+                Some(Ok(count)) if count > 0 => scan_options.count = count as usize,
+                Some(Ok(_)) | None => return Err(serialize_error("-ERR syntax error")),
+                Some(Err(err)) => return Err(err),
+            },
+            "NOVALUES" if command == "hscan" => scan_options.no_values = true,
+            "TYPE" if command == "scan" => match options.next() {
+                Some(type_name) if is_type_name(&type_name.to_lowercase()) => {
+                    scan_options.type_name = Some(type_name.to_lowercase())
+                }
+                Some(type_name) => {
+                    return Err(serialize_error(
+                        concat_string!("-ERR unknown type name '", type_name, "'").as_str(),
+                    ))
+                }
+                None => return Err(serialize_error("-ERR syntax error")),
+            },
+            _ => return Err(serialize_error("-ERR syntax error")),
+        }
+    }
+    Ok((cursor, scan_options))
+}
+
+/// Parses the index of a database that exists on this server.
+pub fn parse_db_index(arg: &str, cache: &Cache) -> Result<usize, Cow<'static, str>> {
+    let index = parse_integer(arg)?;
+    usize::try_from(index)
+        .ok()
+        .filter(|&index| index < cache.database_count())
+        .ok_or_else(|| serialize_error("-ERR DB index is out of range"))
+}
+
+/// Reply of the SCAN family: the next cursor followed by the page of items.
+pub fn serialize_scan_page(cursor: u64, items: Vec<String>) -> Cow<'static, str> {
+    serialize(InputVariants::Array(vec![
+        InputVariants::StringVariant(cursor.to_string()),
+        InputVariants::StringVariantArr(items),
+    ]))
+}
+
+/// Parses `numkeys key [key ...] [LIMIT limit]` as taken by SINTERCARD and ZINTERCARD. A zero
+/// limit means no limit.
+pub fn parse_intercard_args<'a>(
+    args: &'a [String],
+    command: &str,
+) -> Result<(&'a [String], usize), Cow<'static, str>> {
+    let [numkeys, rest @ ..] = args else {
+        return Err(throw_err_if_num_of_args_wrong(command));
+    };
+    let numkeys = match parse_integer(numkeys)? {
+        numkeys if numkeys > 0 => numkeys as usize,
+        _ => return Err(serialize_error("-ERR numkeys should be greater than 0")),
+    };
+    if rest.len() < numkeys {
+        return Err(serialize_error(
+            "-ERR Number of keys can't be greater than number of args",
+        ));
+    }
+    let (keys, options) = rest.split_at(numkeys);
+    let limit = match options {
+        [] => 0,
+        [option, limit] if option.eq_ignore_ascii_case("limit") => match parse_integer(limit)? {
+            limit if limit >= 0 => limit as usize,
+            _ => return Err(serialize_error("-ERR LIMIT can't be negative")),
+        },
+        _ => return Err(serialize_error("-ERR syntax error")),
+    };
+    Ok((keys, limit))
+}
+
+/// Parses a positive COUNT argument as used by LPOP, LMPOP and friends.
+pub fn parse_count(arg: &str) -> Result<usize, Cow<'static, str>> {
+    match parse_integer(arg)? {
+        count if count > 0 => Ok(count as usize),
+        _ => Err(serialize_error("-ERR count should be greater than 0")),
+    }
+}
+
+/// Turns an `EX`, `PX`, `EXAT` or `PXAT` option into an absolute Unix time in milliseconds.
+/// The time must be positive.
+pub fn parse_expire_at(option: &str, value: &str, command: &str) -> Result<u64, Cow<'static, str>> {
+    let invalid = || {
+        serialize_error(
+            concat_string!("-ERR invalid expire time in '", command, "' command").as_str(),
+        )
+    };
+    let value = match parse_integer(value)? {
+        value if value > 0 => value as u64,
+        _ => return Err(invalid()),
+    };
+    let at = match option {
+        "EX" => value
+            .checked_mul(1000)
+            .and_then(|ms| ms.checked_add(unix_time_ms())),
+        "PX" => value.checked_add(unix_time_ms()),
+        "EXAT" => value.checked_mul(1000),
+        _ => Some(value),
+    };
+    at.filter(|&at| at <= i64::MAX as u64).ok_or_else(invalid)
+}
+
+/// Parses a blocking timeout in (fractional) seconds. Zero means wait forever.
+pub fn parse_timeout(arg: &str) -> Result<Option<Duration>, Cow<'static, str>> {
+    match arg.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs < 0.0 => {
+            Err(serialize_error("-ERR timeout is negative"))
+        }
+        Ok(0.0) => Ok(None),
+        Ok(secs) if secs.is_finite() => Ok(Some(Duration::from_secs_f64(secs))),
+        _ => Err(serialize_error(
+            "-ERR timeout is not a float or out of range",
+        )),
+    }
+}
+
+/// Turns the outcome of `Cache::block_on` into a reply, answering `timed_out` on timeout.
+pub fn serialize_block_outcome(
+    outcome: Result<BlockOutcome, &'static str>,
+    timed_out: InputVariants,
+) -> Cow<'static, str> {
+    match outcome {
+        Ok(BlockOutcome::Served(reply)) => Cow::Owned(reply),
+        Ok(BlockOutcome::TimedOut) => serialize(timed_out),
+        Ok(BlockOutcome::Aborted) => {
+            serialize_error("-UNBLOCKED client unblocked via CLIENT UNBLOCK")
+        }
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
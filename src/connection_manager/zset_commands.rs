@@ -0,0 +1,1116 @@
+use std::borrow::Cow;
+
+use crate::{
+    resp::serialize::{format_double, serialize, InputVariants},
+    store::{
+        blocking::ServeFn,
+        db::Cache,
+        pattern::glob_match,
+        set::SetOperation,
+        skiplist::{LexBound, LexRange, ScoreRange},
+        zset::{zpop_from, Aggregate, RangeQuery, RangeSpec, ZAddOptions},
+    },
+};
+
+use super::{
+    client::Client,
+    utils::{
+        parse_count, parse_float, parse_integer, parse_intercard_args, parse_scan_args,
+        parse_timeout, serialize_block_outcome, serialize_error, serialize_len,
+        serialize_scan_page, throw_err_if_num_of_args_wrong,
+    },
+};
+
+/// Scores are doubles in RESP3 and bulk strings in RESP2.
+pub(super) fn score_variant(score: f64, protocol: u8) -> InputVariants {
+    if protocol >= 3 {
+        InputVariants::DoubleVariant(score)
+    } else {
+        InputVariants::StringVariant(format_double(score))
+    }
+}
+
+fn optional_score(score: Option<f64>, protocol: u8) -> InputVariants {
+    match score {
+        Some(score) => score_variant(score, protocol),
+        None => InputVariants::Nullish,
+    }
+}
+
+/// Member/score pairs are a flat array in RESP2 and an array of pairs in RESP3, unless `flat`
+/// asks for the single pair shape ZPOPMIN uses without a count.
+fn serialize_scored(entries: Vec<(String, f64)>, protocol: u8, flat: bool) -> Cow<'static, str> {
+    if protocol >= 3 && !flat {
+        return serialize(InputVariants::Array(
+            entries
+                .into_iter()
+                .map(|(member, score)| {
+                    InputVariants::Array(vec![
+                        InputVariants::StringVariant(member),
+                        score_variant(score, protocol),
+                    ])
+                })
+                .collect(),
+        ));
+    }
+    serialize(InputVariants::Array(
+        entries
+            .into_iter()
+            .flat_map(|(member, score)| {
+                [
+                    InputVariants::StringVariant(member),
+                    score_variant(score, protocol),
+                ]
+            })
+            .collect(),
+    ))
+}
+
+/// `[key, [[member, score], ...]]`, the reply of ZMPOP and BZMPOP.
+fn serialize_mpop(key: String, popped: Vec<(String, f64)>, protocol: u8) -> Cow<'static, str> {
+    serialize(InputVariants::Array(vec![
+        InputVariants::StringVariant(key),
+        InputVariants::Array(
+            popped
+                .into_iter()
+                .map(|(member, score)| {
+                    InputVariants::Array(vec![
+                        InputVariants::StringVariant(member),
+                        score_variant(score, protocol),
+                    ])
+                })
+                .collect(),
+        ),
+    ]))
+}
+
+/// Parses `numkeys key [key ...] MIN|MAX [COUNT count]` shared by ZMPOP and BZMPOP. The flag is
+/// true for MAX.
+fn parse_mpop_args(args: &[String]) -> Result<(Vec<String>, bool, usize), Cow<'static, str>> {
+    let numkeys = match args.first().map(|arg| parse_integer(arg)).transpose()? {
+        Some(numkeys) if numkeys > 0 => numkeys as usize,
+        Some(_) => return Err(serialize_error("-ERR numkeys should be greater than 0")),
+        None => return Err(serialize_error("-ERR syntax error")),
+    };
+    let keys = args
+        .get(1..=numkeys)
+        .ok_or_else(|| serialize_error("-ERR syntax error"))?
+        .to_vec();
+    let max = match args.get(numkeys + 1).map(|arg| arg.to_uppercase()) {
+        Some(end) if end == "MIN" => false,
+        Some(end) if end == "MAX" => true,
+        _ => return Err(serialize_error("-ERR syntax error")),
+    };
+    let count = match &args[numkeys + 2..] {
+        [] => 1,
+        [option, count] if option.eq_ignore_ascii_case("count") => parse_count(count)?,
+        _ => return Err(serialize_error("-ERR syntax error")),
+    };
+    Ok((keys, max, count))
+}
+
+/// Parses a ZRANGEBYSCORE style bound: a float, `-inf`/`+inf`, optionally prefixed with `(`
+/// to exclude it.
+fn parse_score_bound(arg: &str) -> Result<(f64, bool), Cow<'static, str>> {
+    let (value, exclusive) = match arg.strip_prefix('(') {
+        Some(value) => (value, true),
+        None => (arg, false),
+    };
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|value| !value.is_nan())
+        .map(|value| (value, exclusive))
+        .ok_or_else(|| serialize_error("-ERR min or max is not a float"))
+}
+
+fn parse_score_range(min: &str, max: &str) -> Result<ScoreRange, Cow<'static, str>> {
+    let (min, min_exclusive) = parse_score_bound(min)?;
+    let (max, max_exclusive) = parse_score_bound(max)?;
+    Ok(ScoreRange {
+        min,
+        max,
+        min_exclusive,
+        max_exclusive,
+    })
+}
+
+/// Parses a ZRANGEBYLEX style bound: `-`, `+`, `[member` or `(member`.
+fn parse_lex_bound(arg: &str) -> Result<LexBound, Cow<'static, str>> {
+    match arg {
+        "-" => Ok(LexBound::NegativeInfinity),
+        "+" => Ok(LexBound::PositiveInfinity),
+        _ => match (arg.strip_prefix('['), arg.strip_prefix('(')) {
+            (Some(member), _) => Ok(LexBound::Inclusive(member.to_string())),
+            (_, Some(member)) => Ok(LexBound::Exclusive(member.to_string())),
+            _ => Err(serialize_error(
+                "-ERR min or max not valid string range item",
+            )),
+        },
+    }
+}
+
+fn parse_lex_range(min: &str, max: &str) -> Result<LexRange, Cow<'static, str>> {
+    Ok(LexRange {
+        min: parse_lex_bound(min)?,
+        max: parse_lex_bound(max)?,
+    })
+}
+
+/// Parses `start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count] [WITHSCORES]` and returns the
+/// query along with whether scores were requested.
+fn parse_zrange(args: &[String], command: &str) -> Result<(RangeQuery, bool), Cow<'static, str>> {
+    let [start, stop, options @ ..] = args else {
+        return Err(throw_err_if_num_of_args_wrong(command));
+    };
+    let (mut by_score, mut by_lex, mut reverse, mut with_scores) = (false, false, false, false);
+    let mut limit = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.to_uppercase().as_str() {
+            "BYSCORE" => by_score = true,
+            "BYLEX" => by_lex = true,
+            "REV" => reverse = true,
+            "WITHSCORES" => with_scores = true,
+            "LIMIT" => match (options.next(), options.next()) {
+                (Some(offset), Some(count)) => {
+                    limit = Some((parse_integer(offset)?, parse_integer(count)?));
+                }
+                _ => return Err(serialize_error("-ERR syntax error")),
+            },
+            _ => return Err(serialize_error("-ERR syntax error")),
+        }
+    }
+    if by_score && by_lex {
+        return Err(serialize_error("-ERR syntax error"));
+    }
+    if limit.is_some() && !by_score && !by_lex {
+        return Err(serialize_error(
+            "-ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+        ));
+    }
+    if with_scores && by_lex {
+        return Err(serialize_error(
+            "-ERR syntax error, WITHSCORES not supported in combination with BYLEX",
+        ));
+    }
+
+    // With REV the score and lex bounds come as max then min.
+    let (min, max) = if reverse && (by_score || by_lex) {
+        (stop, start)
+    } else {
+        (start, stop)
+    };
+    let spec = if by_score {
+        RangeSpec::Score(parse_score_range(min, max)?)
+    } else if by_lex {
+        RangeSpec::Lex(parse_lex_range(min, max)?)
+    } else {
+        RangeSpec::Rank(parse_integer(start)?, parse_integer(stop)?)
+    };
+    // A negative offset returns nothing and a negative count returns everything.
+    let (offset, limit) = match limit {
+        None => (0, None),
+        Some((offset, _)) if offset < 0 => (0, Some(0)),
+        Some((offset, count)) => (offset as usize, usize::try_from(count).ok()),
+    };
+    Ok((
+        RangeQuery {
+            spec,
+            reverse,
+            offset,
+            limit,
+        },
+        with_scores,
+    ))
+}
+
+/// ZADD key [NX|XX] [GT|LT] [CH] [INCR] score member [score member ...]
+pub fn handle_zadd(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [key, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("zadd");
+    };
+    if rest.len() < 2 {
+        return throw_err_if_num_of_args_wrong("zadd");
+    }
+    let mut options = ZAddOptions::default();
+    let mut changed = false;
+    let mut flags = 0;
+    for option in rest {
+        match option.to_uppercase().as_str() {
+            "NX" => options.nx = true,
+            "XX" => options.xx = true,
+            "GT" => options.gt = true,
+            "LT" => options.lt = true,
+            "INCR" => options.incr = true,
+            "CH" => changed = true,
+            _ => break,
+        }
+        flags += 1;
+    }
+    let pairs = &rest[flags..];
+    if pairs.is_empty() || pairs.len() % 2 != 0 {
+        return serialize_error("-ERR syntax error");
+    }
+    if options.nx && options.xx {
+        return serialize_error("-ERR XX and NX options at the same time are not compatible");
+    }
+    if (options.nx && (options.gt || options.lt)) || (options.gt && options.lt) {
+        return serialize_error(
+            "-ERR GT, LT, and/or NX options at the same time are not compatible",
+        );
+    }
+    if options.incr && pairs.len() > 2 {
+        return serialize_error("-ERR INCR option supports a single increment-element pair");
+    }
+    let mut elements = Vec::with_capacity(pairs.len() / 2);
+    for pair in pairs.chunks(2) {
+        match parse_float(&pair[0]) {
+            Ok(score) => elements.push((score, pair[1].clone())),
+            Err(err) => return err,
+        }
+    }
+
+    match cache.zadd(key, &options, &elements) {
+        Ok(outcome) if options.incr => serialize(optional_score(outcome.score, client.protocol)),
+        Ok(outcome) if changed => serialize_len(outcome.added + outcome.updated),
+        Ok(outcome) => serialize_len(outcome.added),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_zincrby(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [key, increment, member] = args else {
+        return throw_err_if_num_of_args_wrong("zincrby");
+    };
+    let increment = match parse_float(increment) {
+        Ok(increment) => increment,
+        Err(err) => return err,
+    };
+    let options = ZAddOptions {
+        incr: true,
+        ..Default::default()
+    };
+    match cache.zadd(key, &options, &[(increment, member.clone())]) {
+        Ok(outcome) => serialize(optional_score(outcome.score, client.protocol)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_zrem(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, members @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("zrem");
+    };
+    if members.is_empty() {
+        return throw_err_if_num_of_args_wrong("zrem");
+    }
+    match cache.zrem(key, members) {
+        Ok(removed) => serialize_len(removed),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_zscore(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [key, member] = args else {
+        return throw_err_if_num_of_args_wrong("zscore");
+    };
+    match cache.zmscore(key, std::slice::from_ref(member)) {
+        Ok(scores) => serialize(optional_score(scores[0], client.protocol)),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_zmscore(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let [key, members @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("zmscore");
+    };
+    if members.is_empty() {
+        return throw_err_if_num_of_args_wrong("zmscore");
+    }
+    match cache.zmscore(key, members) {
+        Ok(scores) => serialize(InputVariants::Array(
+            scores
+                .into_iter()
+                .map(|score| optional_score(score, client.protocol))
+                .collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_zcard(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key] = args else {
+        return throw_err_if_num_of_args_wrong("zcard");
+    };
+    match cache.zcard(key) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_zcount(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, min, max] = args else {
+        return throw_err_if_num_of_args_wrong("zcount");
+    };
+    let range = match parse_score_range(min, max) {
+        Ok(range) => range,
+        Err(err) => return err,
+    };
+    match cache.zcount(key, &range) {
+        Ok(count) => serialize_len(count),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZRANK and ZREVRANK key member [WITHSCORE]
+pub fn handle_zrank(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+    command: &str,
+) -> Cow<'static, str> {
+    let (key, member, with_score) = match args {
+        [key, member] => (key, member, false),
+        [key, member, option] if option.eq_ignore_ascii_case("withscore") => (key, member, true),
+        [_, _, _] => return serialize_error("-ERR syntax error"),
+        _ => return throw_err_if_num_of_args_wrong(command),
+    };
+    match cache.zrank(key, member, command == "zrevrank") {
+        Ok(Some((rank, score))) if with_score => serialize(InputVariants::Array(vec![
+            InputVariants::NumberVariant(rank as i64),
+            score_variant(score, client.protocol),
+        ])),
+        Ok(Some((rank, _))) => serialize_len(rank),
+        Ok(None) if with_score => serialize(InputVariants::NullArray),
+        Ok(None) => serialize(InputVariants::Nullish),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZRANGE key start stop [BYSCORE|BYLEX] [REV] [LIMIT offset count] [WITHSCORES], along with
+/// ZREVRANGE, ZRANGEBYSCORE, ZREVRANGEBYSCORE, ZRANGEBYLEX and ZREVRANGEBYLEX, which are ZRANGE
+/// with some of the flags implied.
+pub fn handle_zrange(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+    command: &str,
+) -> Cow<'static, str> {
+    let [key, start, stop, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let implied: &[&str] = match command {
+        "zrevrange" => &["REV"],
+        "zrangebyscore" => &["BYSCORE"],
+        "zrevrangebyscore" => &["BYSCORE", "REV"],
+        "zrangebylex" => &["BYLEX"],
+        "zrevrangebylex" => &["BYLEX", "REV"],
+        _ => &[],
+    };
+    let range: Vec<String> = [start.clone(), stop.clone()]
+        .into_iter()
+        .chain(implied.iter().map(|flag| flag.to_string()))
+        .chain(options.iter().cloned())
+        .collect();
+    let (query, with_scores) = match parse_zrange(&range, command) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.zrange(key, &query) {
+        Ok(entries) if with_scores => serialize_scored(entries, client.protocol, false),
+        Ok(entries) => serialize(InputVariants::StringVariantArr(
+            entries.into_iter().map(|(member, _)| member).collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZRANGESTORE dst src min max [BYSCORE|BYLEX] [REV] [LIMIT offset count]
+pub fn handle_zrangestore(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [destination, source, range @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("zrangestore");
+    };
+    let query = match parse_zrange(range, "zrangestore") {
+        Ok((_, true)) => return serialize_error("-ERR syntax error"),
+        Ok((query, false)) => query,
+        Err(err) => return err,
+    };
+    match cache.zrangestore(destination, source, &query) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// Inputs, weights, aggregate and WITHSCORES of ZUNION/ZINTER/ZDIFF and their STORE variants.
+struct CombineArgs<'a> {
+    keys: &'a [String],
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    with_scores: bool,
+}
+
+/// Parses `numkeys key [key ...] [WEIGHTS weight ...] [AGGREGATE SUM|MIN|MAX] [WITHSCORES]`.
+/// ZDIFF takes neither weights nor an aggregate and the STORE variants take no WITHSCORES.
+fn parse_combine<'a>(
+    args: &'a [String],
+    command: &str,
+    operation: SetOperation,
+    allow_with_scores: bool,
+) -> Result<CombineArgs<'a>, Cow<'static, str>> {
+    let [numkeys, rest @ ..] = args else {
+        return Err(throw_err_if_num_of_args_wrong(command));
+    };
+    let numkeys = match parse_integer(numkeys)? {
+        numkeys if numkeys > 0 => numkeys as usize,
+        _ => {
+            let message = concat_string!(
+                "-ERR at least 1 input key is needed for '",
+                command,
+                "' command"
+            );
+            return Err(serialize_error(message.as_str()));
+        }
+    };
+    if rest.len() < numkeys {
+        return Err(serialize_error("-ERR syntax error"));
+    }
+    let (keys, options) = rest.split_at(numkeys);
+    let mut parsed = CombineArgs {
+        keys,
+        weights: Vec::new(),
+        aggregate: Aggregate::Sum,
+        with_scores: false,
+    };
+    let takes_weights = operation != SetOperation::Diff;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.to_uppercase().as_str() {
+            "WEIGHTS" if takes_weights => {
+                for _ in 0..numkeys {
+                    let weight = options
+                        .next()
+                        .ok_or_else(|| serialize_error("-ERR syntax error"))?;
+                    let weight = weight
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|weight| !weight.is_nan())
+                        .ok_or_else(|| serialize_error("-ERR weight value is not a float"))?;
+                    parsed.weights.push(weight);
+                }
+            }
+            "AGGREGATE" if takes_weights => {
+                parsed.aggregate = match options.next().map(|value| value.to_uppercase()) {
+                    Some(value) if value == "SUM" => Aggregate::Sum,
+                    Some(value) if value == "MIN" => Aggregate::Min,
+                    Some(value) if value == "MAX" => Aggregate::Max,
+                    _ => return Err(serialize_error("-ERR syntax error")),
+                };
+            }
+            "WITHSCORES" if allow_with_scores => parsed.with_scores = true,
+            _ => return Err(serialize_error("-ERR syntax error")),
+        }
+    }
+    Ok(parsed)
+}
+
+/// ZUNION, ZINTER and ZDIFF numkeys key [key ...] [WEIGHTS ...] [AGGREGATE ...] [WITHSCORES]
+pub fn handle_zcombine(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+    operation: SetOperation,
+    command: &str,
+) -> Cow<'static, str> {
+    let parsed = match parse_combine(args, command, operation, true) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.zcombine(operation, parsed.keys, &parsed.weights, parsed.aggregate) {
+        Ok(entries) if parsed.with_scores => serialize_scored(entries, client.protocol, false),
+        Ok(entries) => serialize(InputVariants::StringVariantArr(
+            entries.into_iter().map(|(member, _)| member).collect(),
+        )),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZUNIONSTORE, ZINTERSTORE and ZDIFFSTORE destination numkeys key [key ...] [...]
+pub fn handle_zcombine_store(
+    args: &[String],
+    cache: &Cache,
+    operation: SetOperation,
+    command: &str,
+) -> Cow<'static, str> {
+    let [destination, rest @ ..] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let parsed = match parse_combine(rest, command, operation, false) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.zcombine_store(
+        operation,
+        destination,
+        parsed.keys,
+        &parsed.weights,
+        parsed.aggregate,
+    ) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZINTERCARD numkeys key [key ...] [LIMIT limit]
+pub fn handle_zintercard(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let (keys, limit) = match parse_intercard_args(args, "zintercard") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.zintercard(keys, limit) {
+        Ok(len) => serialize_len(len),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+pub fn handle_zlexcount(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, min, max] = args else {
+        return throw_err_if_num_of_args_wrong("zlexcount");
+    };
+    let range = match parse_lex_range(min, max) {
+        Ok(range) => range,
+        Err(err) => return err,
+    };
+    match cache.zlexcount(key, &range) {
+        Ok(count) => serialize_len(count),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZREMRANGEBYRANK key start stop, ZREMRANGEBYSCORE key min max and ZREMRANGEBYLEX key min max
+pub fn handle_zremrange(args: &[String], cache: &Cache, command: &str) -> Cow<'static, str> {
+    let [key, min, max] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    let spec = match command {
+        "zremrangebyscore" => parse_score_range(min, max).map(RangeSpec::Score),
+        "zremrangebylex" => parse_lex_range(min, max).map(RangeSpec::Lex),
+        _ => parse_integer(min).and_then(|start| Ok(RangeSpec::Rank(start, parse_integer(max)?))),
+    };
+    let spec = match spec {
+        Ok(spec) => spec,
+        Err(err) => return err,
+    };
+    match cache.zremrange(key, spec) {
+        Ok(removed) => serialize_len(removed),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZPOPMIN and ZPOPMAX key [count]
+pub fn handle_zpop(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+    command: &str,
+) -> Cow<'static, str> {
+    let (key, count) = match args {
+        [key] => (key, None),
+        [key, count] => match parse_integer(count) {
+            Ok(count) if count >= 0 => (key, Some(count as usize)),
+            Ok(_) => return serialize_error("-ERR value is out of range, must be positive"),
+            Err(err) => return err,
+        },
+        _ => return throw_err_if_num_of_args_wrong(command),
+    };
+    match cache.zpop(key, count.unwrap_or(1), command == "zpopmax") {
+        Ok(popped) => serialize_scored(popped, client.protocol, count.is_none()),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// ZMPOP numkeys key [key ...] MIN|MAX [COUNT count]
+pub fn handle_zmpop(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let (keys, max, count) = match parse_mpop_args(args) {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+    match cache.zpop_first(&keys, count, max) {
+        Ok(Some((key, popped))) => serialize_mpop(key, popped, client.protocol),
+        Ok(None) => serialize(InputVariants::NullArray),
+        Err(err) => serialize_error(concat_string!("-", err).as_str()),
+    }
+}
+
+/// BZPOPMIN and BZPOPMAX key [key ...] timeout
+pub fn handle_bzpop(
+    args: &[String],
+    cache: &Cache,
+    client: &Client,
+    command: &str,
+) -> Cow<'static, str> {
+    let [keys @ .., timeout] = args else {
+        return throw_err_if_num_of_args_wrong(command);
+    };
+    if keys.is_empty() {
+        return throw_err_if_num_of_args_wrong(command);
+    }
+    let timeout = match parse_timeout(timeout) {
+        Ok(timeout) => timeout,
+        Err(err) => return err,
+    };
+    let (max, protocol) = (command == "bzpopmax", client.protocol);
+    let serve: ServeFn = Box::new(move |data, key| {
+        Ok(zpop_from(data, key, 1, max)?
+            .and_then(|popped| popped.into_iter().next())
+            .map(|(member, score)| {
+                serialize(InputVariants::Array(vec![
+                    InputVariants::StringVariant(key.to_string()),
+                    InputVariants::StringVariant(member),
+                    score_variant(score, protocol),
+                ]))
+                .into_owned()
+            }))
+    });
+    let outcome = cache.block_on(
+        client.id,
+        keys.to_vec(),
+        timeout,
+        client.may_block(),
+        serve,
+        &|| client.is_connected(),
+    );
+    serialize_block_outcome(outcome, InputVariants::NullArray)
+}
+
+/// BZMPOP timeout numkeys key [key ...] MIN|MAX [COUNT count]
+pub fn handle_bzmpop(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let Some((timeout, args)) = args.split_first() else {
+        return throw_err_if_num_of_args_wrong("bzmpop");
+    };
+    let (timeout, (keys, max, count)) = match (parse_timeout(timeout), parse_mpop_args(args)) {
+        (Ok(timeout), Ok(parsed)) => (timeout, parsed),
+        (Err(err), _) | (_, Err(err)) => return err,
+    };
+    let protocol = client.protocol;
+    let serve: ServeFn = Box::new(move |data, key| {
+        Ok(zpop_from(data, key, count, max)?
+            .map(|popped| serialize_mpop(key.to_string(), popped, protocol).into_owned()))
+    });
+    let outcome = cache.block_on(client.id, keys, timeout, client.may_block(), serve, &|| {
+        client.is_connected()
+    });
+    serialize_block_outcome(outcome, InputVariants::NullArray)
+}
+
+/// ZRANDMEMBER key [count [WITHSCORES]]
+pub fn handle_zrandmember(args: &[String], cache: &Cache, client: &Client) -> Cow<'static, str> {
+    let (key, count, with_scores) = match args {
+        [key] => (key, None, false),
+        [key, count] => (key, Some(count), false),
+        [key, count, option] if option.eq_ignore_ascii_case("withscores") => {
+            (key, Some(count), true)
+        }
+        [_, _, _] => return serialize_error("-ERR syntax error"),
+        _ => return throw_err_if_num_of_args_wrong("zrandmember"),
+    };
+    let count = match count.map(|count| parse_integer(count)).transpose() {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    let picked = match cache.zrandmember(key, count.unwrap_or(1)) {
+        Ok(picked) => picked,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+
+    match (count, with_scores) {
+        (None, _) => serialize(match picked.into_iter().next() {
+            Some((member, _)) => InputVariants::StringVariant(member),
+            None => InputVariants::Nullish,
+        }),
+        (Some(_), false) => serialize(InputVariants::StringVariantArr(
+            picked.into_iter().map(|(member, _)| member).collect(),
+        )),
+        (Some(_), true) => serialize_scored(picked, client.protocol, false),
+    }
+}
+
+/// ZSCAN key cursor [MATCH pattern] [COUNT count]
+pub fn handle_zscan(args: &[String], cache: &Cache) -> Cow<'static, str> {
+    let [key, cursor, options @ ..] = args else {
+        return throw_err_if_num_of_args_wrong("zscan");
+    };
+    let (cursor, options) = match parse_scan_args(cursor, options, "zscan") {
+        Ok(parsed) => parsed,
+        Err(err) => return err,
+    };
+
+    let (cursor, entries) = match cache.zscan(key, cursor, options.count) {
+        Ok(page) => page,
+        Err(err) => return serialize_error(concat_string!("-", err).as_str()),
+    };
+    let items: Vec<String> = entries
+        .into_iter()
+        .filter(|(member, _)| {
+            options
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, member))
+        })
+        .flat_map(|(member, score)| [member, format_double(score)])
+        .collect();
+    serialize_scan_page(cursor, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::connection_manager::test_utils::run;
+
+    use super::*;
+
+    #[test]
+    fn should_add_with_flags_and_read_scores() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        assert_eq!(
+            run(&cache, client, &["ZADD", "z", "1", "a", "2.5", "b"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZADD", "z", "CH", "GT", "2", "a", "1", "b"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZADD", "z", "NX", "XX", "1", "a"]),
+            "-ERR XX and NX options at the same time are not compatible\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZADD", "z", "INCR", "1", "a", "1", "b"]),
+            "-ERR INCR option supports a single increment-element pair\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZADD", "z", "XX", "INCR", "1", "c"]),
+            "$-1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZADD", "z", "nope", "a"]),
+            "-ERR value is not a valid float\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZINCRBY", "z", "0.1", "b"]),
+            "$3\r\n2.6\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZMSCORE", "z", "a", "c"]),
+            "*2\r\n$1\r\n2\r\n$-1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["ZCARD", "z"]), ":2\r\n");
+        assert_eq!(
+            run(&cache, client, &["ZCOUNT", "z", "(2", "+inf"]),
+            ":1\r\n"
+        );
+
+        run(&cache, client, &["HELLO", "3"]);
+        assert_eq!(run(&cache, client, &["ZSCORE", "z", "a"]), ",2\r\n");
+        assert_eq!(
+            run(&cache, client, &["ZREVRANK", "z", "a", "WITHSCORE"]),
+            "*2\r\n:1\r\n,2\r\n"
+        );
+    }
+
+    #[test]
+    fn should_range_with_every_flavour() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(
+            &cache,
+            client,
+            &["ZADD", "z", "1", "a", "2", "b", "3", "c", "4", "d"],
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "z", "0", "1", "WITHSCORES"]),
+            "*4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["ZRANGE", "z", "-1", "-2"]), "*0\r\n");
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZRANGE", "z", "+inf", "(1", "BYSCORE", "REV", "LIMIT", "1", "2"]
+            ),
+            "*2\r\n$1\r\nc\r\n$1\r\nb\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "z", "[b", "(d", "BYLEX"]),
+            "*2\r\n$1\r\nb\r\n$1\r\nc\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "z", "0", "1", "LIMIT", "0", "1"]),
+            "-ERR syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "z", "x", "1", "BYSCORE"]),
+            "-ERR min or max is not a float\r\n"
+        );
+        assert_eq!(run(&cache, client, &["ZRANK", "z", "c"]), ":2\r\n");
+    }
+
+    #[test]
+    fn should_combine_and_store() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["ZADD", "eu", "1", "ann", "5", "bob"]);
+        run(&cache, client, &["ZADD", "us", "3", "bob", "4", "cid"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZUNION", "2", "eu", "us", "WEIGHTS", "2", "1", "WITHSCORES"]
+            ),
+            "*6\r\n$3\r\nann\r\n$1\r\n2\r\n$3\r\ncid\r\n$1\r\n4\r\n$3\r\nbob\r\n$2\r\n13\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZINTERSTORE", "both", "2", "eu", "us", "AGGREGATE", "MIN"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZSCORE", "both", "bob"]),
+            "$1\r\n3\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZDIFF", "2", "eu", "us"]),
+            "*1\r\n$3\r\nann\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZDIFF", "2", "eu", "us", "WEIGHTS", "1", "1"]
+            ),
+            "-ERR syntax error\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZUNIONSTORE", "x", "0", "eu"]),
+            "-ERR at least 1 input key is needed for 'zunionstore' command\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZINTERCARD", "2", "eu", "us"]),
+            ":1\r\n"
+        );
+        run(&cache, client, &["SADD", "plain", "ann", "bob"]);
+        assert_eq!(
+            run(&cache, client, &["ZINTERCARD", "2", "eu", "plain"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZINTERCARD", "2", "eu", "plain", "LIMIT", "1"]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &[
+                    "ZRANGESTORE",
+                    "top",
+                    "us",
+                    "+inf",
+                    "0",
+                    "BYSCORE",
+                    "REV",
+                    "LIMIT",
+                    "0",
+                    "1"
+                ]
+            ),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "top", "0", "-1"]),
+            "*1\r\n$3\r\ncid\r\n"
+        );
+    }
+
+    #[test]
+    fn should_drop_the_ttl_of_a_stored_destination() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["ZADD", "z", "1", "a"]);
+        for command in [
+            &["ZUNIONSTORE", "d", "1", "z"][..],
+            &["ZINTERSTORE", "d", "1", "z"],
+            &["ZDIFFSTORE", "d", "1", "z"],
+            &["ZRANGESTORE", "d", "z", "0", "-1"],
+        ] {
+            run(&cache, client, &["SET", "d", "x", "EX", "1000"]);
+            assert_eq!(run(&cache, client, command), ":1\r\n");
+            assert_eq!(run(&cache, client, &["TTL", "d"]), ":-1\r\n");
+        }
+    }
+
+    #[test]
+    fn should_count_range_and_remove_lexicographically() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(
+            &cache,
+            client,
+            &[
+                "ZADD", "z", "0", "a", "0", "b", "0", "c", "0", "d", "0", "e",
+            ],
+        );
+        assert_eq!(
+            run(&cache, client, &["ZLEXCOUNT", "z", "[b", "(e"]),
+            ":3\r\n"
+        );
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZRANGEBYLEX", "z", "-", "[c", "LIMIT", "1", "5"]
+            ),
+            "*2\r\n$1\r\nb\r\n$1\r\nc\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZREVRANGEBYLEX", "z", "+", "(c"]),
+            "*2\r\n$1\r\ne\r\n$1\r\nd\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGEBYLEX", "z", "a", "c"]),
+            "-ERR min or max not valid string range item\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZREMRANGEBYLEX", "z", "[a", "[b"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZREMRANGEBYRANK", "z", "-1", "-1"]),
+            ":1\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZREMRANGEBYSCORE", "z", "-inf", "(0"]),
+            ":0\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANGE", "z", "0", "-1"]),
+            "*2\r\n$1\r\nc\r\n$1\r\nd\r\n"
+        );
+    }
+
+    fn spawn_blocked(
+        cache: &Cache,
+        command: &'static [&'static str],
+    ) -> (u64, thread::JoinHandle<String>) {
+        let blocked = cache.clone();
+        let mut client = Client::new(None);
+        let id = client.id;
+        let handle = thread::spawn(move || run(&blocked, &mut client, command));
+        while !cache.is_client_blocked(id) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        (id, handle)
+    }
+
+    #[test]
+    fn should_pop_from_the_first_non_empty_key() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["ZADD", "b", "1", "x", "2", "y", "3", "z"]);
+        assert_eq!(
+            run(
+                &cache,
+                client,
+                &["ZMPOP", "2", "a", "b", "MAX", "COUNT", "2"]
+            ),
+            "*2\r\n$1\r\nb\r\n*2\r\n*2\r\n$1\r\nz\r\n$1\r\n3\r\n*2\r\n$1\r\ny\r\n$1\r\n2\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["BZPOPMIN", "a", "b", "0"]),
+            "*3\r\n$1\r\nb\r\n$1\r\nx\r\n$1\r\n1\r\n"
+        );
+        assert_eq!(run(&cache, client, &["ZMPOP", "1", "b", "MIN"]), "*-1\r\n");
+        assert_eq!(
+            run(&cache, client, &["ZMPOP", "1", "b", "SIDEWAYS"]),
+            "-ERR syntax error\r\n"
+        );
+    }
+
+    #[test]
+    fn should_time_out_with_millisecond_precision() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        let started = Instant::now();
+        assert_eq!(run(&cache, client, &["BZPOPMAX", "z", "0.05"]), "*-1\r\n");
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn should_wake_blocked_clients_in_order_on_zadd() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        let (_, first) = spawn_blocked(&cache, &["BZPOPMIN", "jobs", "0"]);
+        let (_, second) = spawn_blocked(&cache, &["BZMPOP", "0", "1", "jobs", "MIN"]);
+
+        assert_eq!(
+            run(&cache, client, &["ZADD", "jobs", "2", "late", "1", "early"]),
+            ":2\r\n"
+        );
+        assert_eq!(
+            first.join().unwrap(),
+            "*3\r\n$4\r\njobs\r\n$5\r\nearly\r\n$1\r\n1\r\n"
+        );
+        assert_eq!(
+            second.join().unwrap(),
+            "*2\r\n$4\r\njobs\r\n*1\r\n*2\r\n$4\r\nlate\r\n$1\r\n2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "jobs"]), ":0\r\n");
+    }
+
+    #[test]
+    fn should_pop_sample_and_scan() {
+        let cache = Cache::new();
+        let client = &mut Client::new(None);
+        run(&cache, client, &["ZADD", "z", "1", "a", "2", "b", "3", "c"]);
+        assert_eq!(
+            run(&cache, client, &["ZPOPMAX", "z"]),
+            "*2\r\n$1\r\nc\r\n$1\r\n3\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANDMEMBER", "z", "-4"])
+                .lines()
+                .next(),
+            Some("*4")
+        );
+        assert_eq!(
+            run(&cache, client, &["ZRANDMEMBER", "z", "-10000000000000"]),
+            "-ERR value is out of range\r\n"
+        );
+        assert_eq!(
+            run(&cache, client, &["ZSCAN", "z", "0", "MATCH", "a"]),
+            "*2\r\n$1\r\n0\r\n*2\r\n$1\r\na\r\n$1\r\n1\r\n"
+        );
+        run(&cache, client, &["HELLO", "3"]);
+        assert_eq!(
+            run(&cache, client, &["ZPOPMIN", "z", "5"]),
+            "*2\r\n*2\r\n$1\r\na\r\n,1\r\n*2\r\n$1\r\nb\r\n,2\r\n"
+        );
+        assert_eq!(run(&cache, client, &["EXISTS", "z"]), ":0\r\n");
+    }
+}
@@ -8,7 +8,10 @@ use std::thread;
 
 use connection_manager::client_handler::handle_stream;
 use resp::deserialize::deserialize;
-use store::db::Cache;
+use store::{
+    db::Cache,
+    eviction::{parse_memory, EvictionPolicy, MaxmemoryConfig},
+};
 
 #[macro_use(concat_string)]
 extern crate concat_string;
@@ -16,12 +19,55 @@ extern crate concat_string;
 const ADDR: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
 const PORT: u16 = 6379; //Redis PORT
 
+/// Value of a `--<name> <value>` argument, like the Redis config directive of that name.
+/// Exits with `expected` as the message when the value does not `parse`.
+fn config_arg<T>(name: &str, expected: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    let args: Vec<String> = std::env::args().collect();
+    let flag = concat_string!("--", name);
+    match args.iter().position(|arg| *arg == flag) {
+        Some(position) => match args.get(position + 1).and_then(|value| parse(value)) {
+            Some(value) => Some(value),
+            None => {
+                println!("{} expects {}", flag, expected);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    }
+}
+
+fn databases_arg() -> Option<usize> {
+    config_arg("databases", "a positive number", |count| {
+        count.parse().ok().filter(|count| *count > 0)
+    })
+}
+
+/// The memory limit and eviction policy from `--maxmemory` and `--maxmemory-policy`.
+fn maxmemory_args() -> MaxmemoryConfig {
+    let mut config = MaxmemoryConfig::default();
+    if let Some(maxmemory) = config_arg("maxmemory", "a memory size like 100mb", parse_memory) {
+        config.maxmemory = maxmemory;
+    }
+    if let Some(policy) = config_arg(
+        "maxmemory-policy",
+        "an eviction policy like allkeys-lru",
+        EvictionPolicy::parse,
+    ) {
+        config.policy = policy;
+    }
+    config
+}
+
 fn main() {
     let listener = TcpListener::bind(SocketAddrV4::new(ADDR, PORT)).unwrap_or_else(|err| {
         println!("Failed to bind to address: {}", err);
         std::process::exit(1);
     });
-    let cache = Arc::new(Cache::new());
+    let cache = Arc::new(databases_arg().map_or_else(Cache::new, Cache::with_databases));
+    if let Err(err) = cache.set_maxmemory_config(maxmemory_args()) {
+        println!("{}", err);
+        std::process::exit(1);
+    }
 
     for stream in listener.incoming() {
         match stream {
@@ -35,6 +35,56 @@ pub fn deserialize(serialized_input: &str) -> Result<RespResponse, ErrMessages>
     }
 }
 
+/// Longest bulk string a client may send, like Redis' default `proto-max-bulk-len` of 512 MB.
+pub const PROTO_MAX_BULK_LEN: i64 = 512 * 1024 * 1024;
+pub const INVALID_BULK_LENGTH_ERR: &str = "-ERR Protocol error: invalid bulk length";
+
+/// Returns the byte length of the first complete command in `input`, or `None` when more bytes
+/// are needed. Malformed headers are reported as complete so `deserialize` can reject them, but
+/// a bulk length past `PROTO_MAX_BULK_LEN` is an error, as no buffer should wait for it.
+pub fn frame_length(input: &[u8]) -> Result<Option<usize>, &'static str> {
+    fn read_line(input: &[u8], from: usize) -> Option<(&[u8], usize)> {
+        let end = input[from..].windows(2).position(|w| w == b"\r\n")? + from;
+        Some((&input[from..end], end + 2))
+    }
+    fn read_length(line: &[u8]) -> Option<i64> {
+        std::str::from_utf8(line).ok()?.parse::<i64>().ok()
+    }
+
+    let Some((header, mut cursor)) = read_line(input, 0) else {
+        return Ok(None);
+    };
+    if header.first() != Some(&b'*') {
+        return Ok(Some(cursor));
+    }
+    let count = match read_length(&header[1..]) {
+        Some(count) => count,
+        None => return Ok(Some(cursor)),
+    };
+    for _ in 0..count {
+        let Some((item_header, next)) = read_line(input, cursor) else {
+            return Ok(None);
+        };
+        cursor = next;
+        if item_header.first() != Some(&b'$') {
+            continue;
+        }
+        match read_length(&item_header[1..]) {
+            Some(len) if len > PROTO_MAX_BULK_LEN => return Err(INVALID_BULK_LENGTH_ERR),
+            Some(len) if len >= 0 => {
+                cursor = cursor
+                    .checked_add(len as usize + 2)
+                    .ok_or(INVALID_BULK_LENGTH_ERR)?;
+                if input.len() < cursor {
+                    return Ok(None);
+                }
+            }
+            _ => return Ok(Some(cursor)),
+        }
+    }
+    Ok(Some(cursor))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,6 +120,27 @@ mod tests {
         )
     }
 
+    #[test]
+    fn should_measure_complete_and_partial_frames() {
+        let frame = b"*2\r\n$4\r\nECHO\r\n$2\r\nhi\r\n*1\r\n$4\r\nPING\r\n";
+        assert_eq!(frame_length(frame), Ok(Some(22)));
+        assert_eq!(frame_length(&frame[..20]), Ok(None));
+        assert_eq!(frame_length(b"*2\r\n$4\r\nEC"), Ok(None));
+    }
+
+    #[test]
+    fn should_reject_bulk_lengths_past_the_limit() {
+        assert_eq!(
+            frame_length(b"*1\r\n$536870913\r\n"),
+            Err(INVALID_BULK_LENGTH_ERR)
+        );
+        assert_eq!(
+            frame_length(b"*1\r\n$9223372036854775807\r\n"),
+            Err(INVALID_BULK_LENGTH_ERR)
+        );
+        assert_eq!(frame_length(b"*1\r\n$536870912\r\n"), Ok(None));
+    }
+
     #[test]
     fn should_deserialize_array() {
         assert_eq!(
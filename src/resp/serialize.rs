@@ -3,13 +3,31 @@ use std::borrow::Cow;
 #[allow(dead_code)]
 #[derive(PartialEq)]
 pub enum InputVariants {
-    NumberVariant(i32),
+    NumberVariant(i64),
     StringVariant(String),
+    /// Always a bulk string, for values read from the keyspace: unlike `StringVariant`, one
+    /// starting with '+' is not mistaken for a simple string.
+    BulkString(String),
     ErrorVariant(String),
     StringVariantArr(Vec<String>),
+    Array(Vec<InputVariants>),
+    Map(Vec<(InputVariants, InputVariants)>),
+    /// RESP3 double, written the way Redis prints scores.
+    DoubleVariant(f64),
+    NullArray,
     Nullish,
 }
 
+/// Length of a bulk string on the wire. Strings carry one char per byte (see `store::bytes`),
+/// so that is the number of chars rather than the UTF-8 length.
+fn bulk_len(string: &str) -> usize {
+    if string.is_ascii() {
+        string.len()
+    } else {
+        string.chars().count()
+    }
+}
+
 pub fn serialize(input: InputVariants) -> Cow<'static, str> {
     match input {
         InputVariants::NumberVariant(number) => {
@@ -18,18 +36,14 @@ pub fn serialize(input: InputVariants) -> Cow<'static, str> {
         InputVariants::StringVariant(string) if string.starts_with('+') => {
             Cow::Owned(concat_string!(string, "\r\n"))
         }
-        InputVariants::StringVariant(string) => Cow::Owned(concat_string!(
-            "$",
-            string.len().to_string(),
-            "\r\n",
-            string,
-            "\r\n"
-        )),
+        InputVariants::StringVariant(string) | InputVariants::BulkString(string) => Cow::Owned(
+            concat_string!("$", bulk_len(&string).to_string(), "\r\n", string, "\r\n"),
+        ),
         InputVariants::ErrorVariant(string) => Cow::Owned(concat_string!(string, "\r\n")),
         InputVariants::StringVariantArr(string_arr) => {
             let serialized_items: Vec<String> = string_arr
                 .iter()
-                .map(|item| serialize(InputVariants::StringVariant(item.clone())).into_owned())
+                .map(|item| concat_string!("$", bulk_len(item).to_string(), "\r\n", item, "\r\n"))
                 .collect();
 
             Cow::Owned(concat_string!(
@@ -39,9 +53,84 @@ pub fn serialize(input: InputVariants) -> Cow<'static, str> {
                 serialized_items.concat().to_string()
             ))
         }
+        InputVariants::Array(items) => {
+            let serialized_items: Vec<String> = items
+                .into_iter()
+                .map(|item| serialize(item).into_owned())
+                .collect();
+
+            Cow::Owned(concat_string!(
+                "*",
+                serialized_items.len().to_string(),
+                "\r\n",
+                serialized_items.concat()
+            ))
+        }
+        InputVariants::Map(pairs) => {
+            let serialized_pairs: Vec<String> = pairs
+                .into_iter()
+                .map(|(key, value)| {
+                    let (key, value) = (serialize(key), serialize(value));
+                    concat_string!(key, value)
+                })
+                .collect();
+
+            Cow::Owned(concat_string!(
+                "%",
+                serialized_pairs.len().to_string(),
+                "\r\n",
+                serialized_pairs.concat()
+            ))
+        }
+        InputVariants::DoubleVariant(value) => {
+            Cow::Owned(concat_string!(",", format_double(value), "\r\n"))
+        }
+        InputVariants::NullArray => Cow::Borrowed("*-1\r\n"),
         _ => Cow::Borrowed("$-1\r\n"),
     }
 }
+/// Formats a double like Redis' `%.17g`, but with the shortest digits that round-trip: fixed
+/// notation for exponents in `-4..17`, scientific notation such as `1e+20` otherwise.
+pub fn format_double(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let scientific = format!("{:e}", value);
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("{:e} always writes an exponent");
+    let exponent: i32 = exponent.parse().expect("{:e} writes a valid exponent");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    if !(-4..17).contains(&exponent) {
+        let exponent_sign = if exponent < 0 { "-" } else { "+" };
+        return format!(
+            "{}{}e{}{:02}",
+            sign,
+            mantissa,
+            exponent_sign,
+            exponent.unsigned_abs()
+        );
+    }
+    let digits = mantissa.replace('.', "");
+    let body = if exponent < 0 {
+        concat_string!("0.", "0".repeat((-exponent - 1) as usize), digits)
+    } else {
+        let integer_len = exponent as usize + 1;
+        if digits.len() <= integer_len {
+            concat_string!(digits, "0".repeat(integer_len - digits.len()))
+        } else {
+            concat_string!(&digits[..integer_len], ".", &digits[integer_len..])
+        }
+    };
+    concat_string!(sign, body)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -70,6 +159,47 @@ mod tests {
         )
     }
 
+    #[test]
+    fn should_serialize_nested_array() {
+        assert_eq!(
+            serialize(InputVariants::Array(vec![
+                InputVariants::StringVariant("list".to_string()),
+                InputVariants::StringVariantArr(vec!["a".to_string()]),
+                InputVariants::NullArray,
+            ])),
+            "*3\r\n$4\r\nlist\r\n*1\r\n$1\r\na\r\n*-1\r\n"
+        )
+    }
+
+    #[test]
+    fn should_serialize_resp3_map() {
+        assert_eq!(
+            serialize(InputVariants::Map(vec![(
+                InputVariants::StringVariant("proto".to_string()),
+                InputVariants::NumberVariant(3)
+            )])),
+            "%1\r\n$5\r\nproto\r\n:3\r\n"
+        )
+    }
+
+    #[test]
+    fn should_format_doubles_like_redis() {
+        assert_eq!(format_double(0.0), "0");
+        assert_eq!(format_double(1.5), "1.5");
+        assert_eq!(format_double(-42.0), "-42");
+        assert_eq!(format_double(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format_double(0.0001), "0.0001");
+        assert_eq!(format_double(0.00001), "1e-05");
+        assert_eq!(format_double(1e20), "1e+20");
+        assert_eq!(format_double(12345678901234567.0), "12345678901234568");
+        assert_eq!(format_double(1e17), "1e+17");
+        assert_eq!(format_double(f64::NEG_INFINITY), "-inf");
+        assert_eq!(
+            serialize(InputVariants::DoubleVariant(2.5)),
+            ",2.5\r\n".to_string()
+        );
+    }
+
     #[test]
     fn should_serialize_simple_string() {
         assert_eq!(
@@ -78,6 +208,14 @@ mod tests {
         )
     }
 
+    #[test]
+    fn should_serialize_bulk_string_starting_with_plus() {
+        assert_eq!(
+            serialize(InputVariants::BulkString("+PONG".to_string())),
+            "$5\r\n+PONG\r\n".to_string()
+        )
+    }
+
     #[test]
     fn should_serialize_integer() {
         assert_eq!(
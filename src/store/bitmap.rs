@@ -0,0 +1,403 @@
+//! Bit operations on string values. Bit 0 is the most significant bit of the first byte, and
+//! strings are zero padded on the right as far as reads are concerned.
+
+use super::{
+    bytes::{replace_bytes, store_bytes, string_bytes},
+    db::Cache,
+    keyspace::Keyspace,
+};
+
+/// Highest bit offset a string may reach, matching a 512 MB maximum string size.
+pub const MAX_BIT_OFFSET: u64 = (512 << 20) * 8 - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitUnit {
+    Byte,
+    Bit,
+}
+
+/// A `start end [BYTE|BIT]` range where negative indexes count from the end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitRange {
+    pub start: i64,
+    pub end: Option<i64>,
+    pub unit: BitUnit,
+}
+
+impl BitRange {
+    /// Resolves to inclusive bit positions within a string of `len` bytes, or `None` when the
+    /// range is empty.
+    fn resolve(&self, len: usize) -> Option<(u64, u64)> {
+        let total = match self.unit {
+            BitUnit::Byte => len as i64,
+            BitUnit::Bit => len as i64 * 8,
+        };
+        let from_end = |index: i64| {
+            if index < 0 {
+                (total + index).max(0)
+            } else {
+                index
+            }
+        };
+        let start = from_end(self.start);
+        let end = from_end(self.end.unwrap_or(-1)).min(total - 1);
+        if total == 0 || start > end {
+            return None;
+        }
+        Some(match self.unit {
+            BitUnit::Byte => (start as u64 * 8, end as u64 * 8 + 7),
+            BitUnit::Bit => (start as u64, end as u64),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+    /// Bits set in the first key but in none of the others.
+    Diff,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// A signed (`i1` to `i64`) or unsigned (`u1` to `u63`) integer type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitfieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+impl BitfieldType {
+    fn bounds(&self) -> (i128, i128) {
+        if self.signed {
+            (-(1 << (self.bits - 1)), (1 << (self.bits - 1)) - 1)
+        } else {
+            (0, (1 << self.bits) - 1)
+        }
+    }
+
+    fn decode(&self, raw: u64) -> i64 {
+        if self.signed && self.bits < 64 && raw >> (self.bits - 1) & 1 == 1 {
+            (raw | u64::MAX << self.bits) as i64
+        } else {
+            raw as i64
+        }
+    }
+
+    /// Brings `value` within range according to `overflow`, or `None` when it fails.
+    fn fit(&self, value: i128, overflow: Overflow) -> Option<i64> {
+        let (min, max) = self.bounds();
+        if (min..=max).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Fail => None,
+            Overflow::Sat => Some(value.clamp(min, max) as i64),
+            Overflow::Wrap => {
+                let mask = (1_u128 << self.bits) - 1;
+                Some(self.decode((value as u128 & mask) as u64))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitfieldOp {
+    Get,
+    Set(i64),
+    IncrBy(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitfieldCommand {
+    pub op: BitfieldOp,
+    pub kind: BitfieldType,
+    pub offset: u64,
+    pub overflow: Overflow,
+}
+
+fn get_bit(bytes: &[u8], offset: u64) -> bool {
+    usize::try_from(offset / 8)
+        .ok()
+        .and_then(|index| bytes.get(index))
+        .is_some_and(|byte| byte >> (7 - offset % 8) & 1 == 1)
+}
+
+/// Sets a bit within `bytes`, which must already be long enough.
+fn set_bit(bytes: &mut [u8], offset: u64, value: bool) {
+    let mask = 1 << (7 - offset % 8);
+    let byte = &mut bytes[(offset / 8) as usize];
+    if value {
+        *byte |= mask;
+    } else {
+        *byte &= !mask;
+    }
+}
+
+fn read_bits(bytes: &[u8], offset: u64, bits: u32) -> u64 {
+    (0..u64::from(bits)).fold(0, |value, i| {
+        value << 1 | u64::from(get_bit(bytes, offset + i))
+    })
+}
+
+fn write_bits(bytes: &mut [u8], offset: u64, bits: u32, value: u64) {
+    for i in 0..bits {
+        set_bit(
+            bytes,
+            offset + u64::from(i),
+            value >> (bits - 1 - i) & 1 == 1,
+        );
+    }
+}
+
+/// Masks of the bits between `first` and `last` (inclusive) for every byte they span.
+fn masks(first: u64, last: u64) -> impl Iterator<Item = (usize, u8)> {
+    let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+    (first_byte..=last_byte).map(move |index| {
+        let mut mask = u8::MAX;
+        if index == first_byte {
+            mask &= u8::MAX >> (first % 8);
+        }
+        if index == last_byte {
+            mask &= u8::MAX << (7 - last % 8);
+        }
+        (index, mask)
+    })
+}
+
+/// Grows the string at `key` to hold `bits` bits, creating it when missing.
+fn grown_bytes(data: &Keyspace, key: &str, bits: u64) -> Result<Vec<u8>, &'static str> {
+    let mut bytes = string_bytes(data, key)?.unwrap_or_default();
+    let len = bits.div_ceil(8) as usize;
+    if bytes.len() < len {
+        bytes.resize(len, 0);
+    }
+    Ok(bytes)
+}
+
+impl Cache {
+    /// Returns the previous value of the bit.
+    pub fn setbit(&self, key: &str, offset: u64, value: bool) -> Result<bool, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let mut bytes = grown_bytes(&data, key, offset + 1)?;
+        let previous = get_bit(&bytes, offset);
+        set_bit(&mut bytes, offset, value);
+        store_bytes(&mut data, key, &bytes);
+        Ok(previous)
+    }
+
+    pub fn getbit(&self, key: &str, offset: u64) -> Result<bool, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(string_bytes(&data, key)?.is_some_and(|bytes| get_bit(&bytes, offset)))
+    }
+
+    pub fn bitcount(&self, key: &str, range: Option<BitRange>) -> Result<u64, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let bytes = string_bytes(&data, key)?.unwrap_or_default();
+        let range = range.unwrap_or(BitRange {
+            start: 0,
+            end: None,
+            unit: BitUnit::Byte,
+        });
+        let Some((first, last)) = range.resolve(bytes.len()) else {
+            return Ok(0);
+        };
+        Ok(masks(first, last)
+            .map(|(index, mask)| u64::from((bytes[index] & mask).count_ones()))
+            .sum())
+    }
+
+    /// Position of the first bit set to `bit` within `range`, or -1. When looking for a clear
+    /// bit without an explicit end, the zero padding past the string counts as a match.
+    pub fn bitpos(
+        &self,
+        key: &str,
+        bit: bool,
+        range: Option<BitRange>,
+    ) -> Result<i64, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let Some(bytes) = string_bytes(&data, key)? else {
+            return Ok(if bit { -1 } else { 0 });
+        };
+        let range = range.unwrap_or(BitRange {
+            start: 0,
+            end: None,
+            unit: BitUnit::Byte,
+        });
+        let Some((first, last)) = range.resolve(bytes.len()) else {
+            return Ok(-1);
+        };
+        let found = masks(first, last).find_map(|(index, mask)| {
+            let candidates = if bit { bytes[index] } else { !bytes[index] } & mask;
+            (candidates != 0).then(|| index as i64 * 8 + i64::from(candidates.leading_zeros()))
+        });
+        Ok(match found {
+            Some(position) => position,
+            None if !bit && range.end.is_none() => last as i64 + 1,
+            None => -1,
+        })
+    }
+
+    /// Stores the result at `destination`, deleting it when the result is empty, and returns
+    /// its length.
+    pub fn bitop(
+        &self,
+        operation: BitOperation,
+        destination: &str,
+        keys: &[String],
+    ) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let sources = keys
+            .iter()
+            .map(|key| Ok(string_bytes(&data, key)?.unwrap_or_default()))
+            .collect::<Result<Vec<_>, &'static str>>()?;
+        let len = sources.iter().map(Vec::len).max().unwrap_or(0);
+        let byte = |source: &Vec<u8>, index: usize| source.get(index).copied().unwrap_or(0);
+        let result: Vec<u8> = (0..len)
+            .map(|index| {
+                let others = sources[1..].iter().map(|source| byte(source, index));
+                let first = byte(&sources[0], index);
+                match operation {
+                    BitOperation::And => others.fold(first, |acc, b| acc & b),
+                    BitOperation::Or => others.fold(first, |acc, b| acc | b),
+                    BitOperation::Xor => others.fold(first, |acc, b| acc ^ b),
+                    BitOperation::Not => !first,
+                    BitOperation::Diff => first & !others.fold(0, |acc, b| acc | b),
+                }
+            })
+            .collect();
+        if result.is_empty() {
+            data.remove(destination);
+        } else {
+            replace_bytes(&mut data, destination, &result);
+        }
+        Ok(result.len())
+    }
+
+    /// Runs the commands in order, replying `None` for writes refused by `Overflow::Fail`. The
+    /// string is created and grown up front whenever any command writes.
+    pub fn bitfield(
+        &self,
+        key: &str,
+        commands: &[BitfieldCommand],
+    ) -> Result<Vec<Option<i64>>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let writes = commands
+            .iter()
+            .filter(|command| command.op != BitfieldOp::Get);
+        let end = writes
+            .map(|command| command.offset + u64::from(command.kind.bits))
+            .max();
+        let mut bytes = match end {
+            Some(end) => grown_bytes(&data, key, end)?,
+            None => string_bytes(&data, key)?.unwrap_or_default(),
+        };
+
+        let replies = commands
+            .iter()
+            .map(|command| {
+                let BitfieldCommand {
+                    op,
+                    kind,
+                    offset,
+                    overflow,
+                } = *command;
+                let current = kind.decode(read_bits(&bytes, offset, kind.bits));
+                let updated = match op {
+                    BitfieldOp::Get => return Some(current),
+                    // Unsigned fields take the value as its two's complement bit pattern.
+                    BitfieldOp::Set(value) if !kind.signed => {
+                        kind.fit(i128::from(value as u64), overflow)
+                    }
+                    BitfieldOp::Set(value) => kind.fit(i128::from(value), overflow),
+                    BitfieldOp::IncrBy(increment) => {
+                        kind.fit(i128::from(current) + i128::from(increment), overflow)
+                    }
+                }?;
+                write_bits(&mut bytes, offset, kind.bits, updated as u64);
+                Some(if let BitfieldOp::Set(_) = op {
+                    current
+                } else {
+                    updated
+                })
+            })
+            .collect();
+        if end.is_some() {
+            store_bytes(&mut data, key, &bytes);
+        }
+        Ok(replies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_wrap_saturate_or_fail_on_overflow() {
+        let i8_type = BitfieldType {
+            signed: true,
+            bits: 8,
+        };
+        assert_eq!(i8_type.fit(128, Overflow::Wrap), Some(-128));
+        assert_eq!(i8_type.fit(-129, Overflow::Wrap), Some(127));
+        assert_eq!(i8_type.fit(300, Overflow::Sat), Some(127));
+        assert_eq!(i8_type.fit(-300, Overflow::Sat), Some(-128));
+        assert_eq!(i8_type.fit(128, Overflow::Fail), None);
+
+        let u2_type = BitfieldType {
+            signed: false,
+            bits: 2,
+        };
+        assert_eq!(u2_type.fit(5, Overflow::Wrap), Some(1));
+        assert_eq!(u2_type.fit(-1, Overflow::Sat), Some(0));
+        let i64_type = BitfieldType {
+            signed: true,
+            bits: 64,
+        };
+        assert_eq!(
+            i64_type.fit(i128::from(i64::MAX) + 1, Overflow::Wrap),
+            Some(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn should_resolve_ranges_from_either_end() {
+        let range = |start, end, unit| BitRange {
+            start,
+            end: Some(end),
+            unit,
+        };
+        assert_eq!(range(0, -1, BitUnit::Byte).resolve(3), Some((0, 23)));
+        assert_eq!(range(-2, 100, BitUnit::Byte).resolve(3), Some((8, 23)));
+        assert_eq!(range(5, -3, BitUnit::Bit).resolve(3), Some((5, 21)));
+        assert_eq!(range(2, 1, BitUnit::Byte).resolve(3), None);
+        assert_eq!(range(0, -1, BitUnit::Byte).resolve(0), None);
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::{db::Cache, keyspace::Keyspace};
+
+pub type ClientId = u64;
+
+/// Tries to serve a blocked client from `key`. Returns the reply to hand over, `Ok(None)` if the
+/// key cannot serve it yet and `Err` if the key holds the wrong type.
+pub type ServeFn =
+    Box<dyn FnMut(&mut Keyspace, &str) -> Result<Option<String>, &'static str> + Send>;
+
+/// How often a blocked client wakes up to check whether its connection is still open.
+const LIVENESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, PartialEq)]
+pub enum BlockOutcome {
+    Served(String),
+    TimedOut,
+    Aborted,
+}
+
+struct Waiter {
+    keys: Vec<String>,
+    serve: ServeFn,
+}
+
+#[derive(Default)]
+struct BlockingState {
+    queues: HashMap<String, VecDeque<ClientId>>,
+    waiters: HashMap<ClientId, Waiter>,
+    outcomes: HashMap<ClientId, BlockOutcome>,
+}
+
+/// Clients blocked on keys, queued per key in the order they blocked. Writers that may unblock
+/// someone call `serve` while still holding the data write lock, so the element they pushed is
+/// handed to the longest waiting client before anyone else can see it.
+#[derive(Default)]
+pub struct Blocking {
+    state: Mutex<BlockingState>,
+    wakeup: Condvar,
+}
+
+impl fmt::Debug for Blocking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Blocking").finish_non_exhaustive()
+    }
+}
+
+impl BlockingState {
+    fn remove_waiter(&mut self, client_id: ClientId) -> bool {
+        let Some(waiter) = self.waiters.remove(&client_id) else {
+            return false;
+        };
+        for key in waiter.keys {
+            if let Some(queue) = self.queues.get_mut(&key) {
+                queue.retain(|&id| id != client_id);
+                if queue.is_empty() {
+                    self.queues.remove(&key);
+                }
+            }
+        }
+        true
+    }
+
+    fn serve_key(&mut self, data: &mut Keyspace, key: &str) -> bool {
+        let queued: Vec<ClientId> = match self.queues.get(key) {
+            Some(queue) => queue.iter().copied().collect(),
+            None => return false,
+        };
+        let mut served_any = false;
+        for client_id in queued {
+            let Some(waiter) = self.waiters.get_mut(&client_id) else {
+                continue;
+            };
+            if let Ok(Some(reply)) = (waiter.serve)(data, key) {
+                self.remove_waiter(client_id);
+                self.outcomes.insert(client_id, BlockOutcome::Served(reply));
+                served_any = true;
+            }
+        }
+        served_any
+    }
+}
+
+impl Blocking {
+    fn lock(&self) -> MutexGuard<'_, BlockingState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Serves clients blocked on `key`. Serving one client may write to other keys (BLMOVE), so
+    /// once anyone is served every blocked key is revisited until nothing changes.
+    pub fn serve(&self, data: &mut Keyspace, key: &str) {
+        let mut state = self.lock();
+        if !state.queues.contains_key(key) {
+            return;
+        }
+        let mut progress = state.serve_key(data, key);
+        let notify = progress;
+        while progress {
+            progress = false;
+            let keys: Vec<String> = state.queues.keys().cloned().collect();
+            for key in keys {
+                progress |= state.serve_key(data, &key);
+            }
+        }
+        if notify {
+            self.wakeup.notify_all();
+        }
+    }
+
+    /// Serves every blocked key, used after writes that may have touched arbitrary keys.
+    pub fn serve_all(&self, data: &mut Keyspace) {
+        let keys: Vec<String> = self.lock().queues.keys().cloned().collect();
+        for key in keys {
+            self.serve(data, &key);
+        }
+    }
+
+    /// Wakes up a blocked client as if its timeout had elapsed, or with an error when `error`
+    /// is set. Returns whether the client was blocked.
+    pub fn unblock(&self, client_id: ClientId, error: bool) -> bool {
+        let mut state = self.lock();
+        if !state.remove_waiter(client_id) {
+            return false;
+        }
+        let outcome = if error {
+            BlockOutcome::Aborted
+        } else {
+            BlockOutcome::TimedOut
+        };
+        state.outcomes.insert(client_id, outcome);
+        self.wakeup.notify_all();
+        true
+    }
+
+    #[cfg(test)]
+    pub fn is_blocked(&self, client_id: ClientId) -> bool {
+        self.lock().waiters.contains_key(&client_id)
+    }
+}
+
+impl Cache {
+    /// Runs `serve` against `keys` in order and returns the first reply. When no key can serve
+    /// it and `may_block` is set, the client is queued on every key until it is served, the
+    /// timeout (`None` blocks forever) elapses, it gets unblocked or `is_connected` fails.
+    pub fn block_on(
+        &self,
+        client_id: ClientId,
+        keys: Vec<String>,
+        timeout: Option<Duration>,
+        may_block: bool,
+        mut serve: ServeFn,
+        is_connected: &dyn Fn() -> bool,
+    ) -> Result<BlockOutcome, &'static str> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        for key in &keys {
+            if let Some(reply) = serve(&mut data, key)? {
+                self.blocking.serve_all(&mut data);
+                return Ok(BlockOutcome::Served(reply));
+            }
+        }
+        if !may_block {
+            return Ok(BlockOutcome::TimedOut);
+        }
+
+        let mut state = self.blocking.lock();
+        for key in &keys {
+            let queue = state.queues.entry(key.clone()).or_default();
+            if !queue.contains(&client_id) {
+                queue.push_back(client_id);
+            }
+        }
+        state.waiters.insert(client_id, Waiter { keys, serve });
+        drop(data);
+
+        loop {
+            if let Some(outcome) = state.outcomes.remove(&client_id) {
+                return Ok(outcome);
+            }
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) || !is_connected() {
+                state.remove_waiter(client_id);
+                return Ok(BlockOutcome::TimedOut);
+            }
+            let wait = deadline.map_or(LIVENESS_INTERVAL, |deadline| {
+                (deadline - now).min(LIVENESS_INTERVAL)
+            });
+            state = match self.blocking.wakeup.wait_timeout(state, wait) {
+                Ok((state, _)) => state,
+                Err(err) => err.into_inner().0,
+            };
+        }
+    }
+
+    /// Clients can be blocked in any database, so every one of them is searched.
+    pub fn unblock_client(&self, client_id: ClientId, error: bool) -> bool {
+        self.databases
+            .iter()
+            .any(|database| database.blocking.unblock(client_id, error))
+    }
+
+    #[cfg(test)]
+    pub fn is_client_blocked(&self, client_id: ClientId) -> bool {
+        self.databases
+            .iter()
+            .any(|database| database.blocking.is_blocked(client_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::store::{
+        db::Value,
+        list::{List, ListEnd},
+    };
+
+    fn pop_front() -> ServeFn {
+        Box::new(|data, key| match data.get_mut(key) {
+            Some(Value::List(list)) => Ok(list.pop(ListEnd::Left)),
+            _ => Ok(None),
+        })
+    }
+
+    #[test]
+    fn should_serve_blocked_clients_in_fifo_order() {
+        let cache = Cache::new();
+        let handles: Vec<_> = (1..=2)
+            .map(|client_id| {
+                let blocked = cache.clone();
+                let handle = thread::spawn(move || {
+                    blocked
+                        .block_on(
+                            client_id,
+                            vec!["queue".to_string()],
+                            None,
+                            true,
+                            pop_front(),
+                            &|| true,
+                        )
+                        .unwrap()
+                });
+                while !cache.is_client_blocked(client_id) {
+                    thread::sleep(Duration::from_millis(5));
+                }
+                handle
+            })
+            .collect();
+
+        {
+            let mut data = cache.data.write().unwrap();
+            data.insert(
+                "queue".to_string(),
+                Value::List(List::from_iter(["first".to_string(), "second".to_string()])),
+            );
+            cache.blocking.serve(&mut data, "queue");
+        }
+
+        let outcomes: Vec<BlockOutcome> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                BlockOutcome::Served("first".to_string()),
+                BlockOutcome::Served("second".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn should_time_out_and_forget_the_client() {
+        let cache = Cache::new();
+        let outcome = cache
+            .block_on(
+                7,
+                vec!["queue".to_string()],
+                Some(Duration::from_millis(50)),
+                true,
+                pop_front(),
+                &|| true,
+            )
+            .unwrap();
+        assert_eq!(outcome, BlockOutcome::TimedOut);
+        assert!(!cache.is_client_blocked(7));
+    }
+}
@@ -0,0 +1,73 @@
+//! Values are binary safe: every byte read from a client becomes the char with the same code
+//! point (ISO-8859-1), and replies are written back the same way. A `String` in the store
+//! therefore holds exactly one char per byte, whatever the client sent.
+
+use std::borrow::Cow;
+
+use super::{
+    db::{Value, WRONGTYPE_ERR},
+    keyspace::Keyspace,
+};
+
+pub fn from_bytes(bytes: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if text.is_ascii() => Cow::Borrowed(text),
+        _ => Cow::Owned(bytes.iter().map(|&byte| char::from(byte)).collect()),
+    }
+}
+
+/// Chars above U+00FF never come from a client; they are written as UTF-8 should one slip
+/// through.
+pub fn to_bytes(text: &str) -> Cow<'_, [u8]> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text.as_bytes());
+    }
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match u8::try_from(c) {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Cow::Owned(bytes)
+}
+
+/// Bytes of the string at `key`; a missing key yields `None`.
+pub(super) fn string_bytes(data: &Keyspace, key: &str) -> Result<Option<Vec<u8>>, &'static str> {
+    match data.get(key) {
+        Some(Value::String(value)) => Ok(Some(to_bytes(value).into_owned())),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// Replaces the value at `key` with a string holding `bytes`, keeping its TTL like the in-place
+/// edits of SETBIT or SETRANGE do.
+pub(super) fn store_bytes(data: &mut Keyspace, key: &str, bytes: &[u8]) {
+    let value = Value::String(from_bytes(bytes).into_owned());
+    match data.get_mut(key) {
+        Some(existing) => *existing = value,
+        None => {
+            data.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Overwrites `key` with a string holding `bytes` the way SET does, dropping any TTL.
+pub(super) fn replace_bytes(data: &mut Keyspace, key: &str, bytes: &[u8]) {
+    store_bytes(data, key, bytes);
+    data.persist(key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_arbitrary_bytes() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let text = from_bytes(&bytes);
+        assert_eq!(text.chars().count(), 256);
+        assert_eq!(to_bytes(&text).as_ref(), bytes.as_slice());
+    }
+}
@@ -0,0 +1,699 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem::size_of,
+};
+
+use super::{
+    db::{unix_time_ms, Cache, Value},
+    keyspace::Keyspace,
+    memory::{estimate, string_usage, ELEMENT_OVERHEAD},
+    stream::{stream_mut, stream_ref, Stream, StreamEntry, StreamId},
+};
+
+/// The key or the group does not exist. Every command words this differently, so the command
+/// layer spells it out with the key and group names.
+pub const NOGROUP_ERR: &str = "NOGROUP No such key or consumer group";
+pub const XGROUP_NO_KEY_ERR: &str = "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.";
+const BUSYGROUP_ERR: &str = "BUSYGROUP Consumer Group name already exists";
+
+/// An entry handed to a consumer. The fields are `None` when the entry was deleted from the
+/// stream after it was delivered.
+pub type DeliveredEntry = (StreamId, Option<Vec<(String, String)>>);
+
+/// An entry of the pending entries list: delivered to `consumer` but not acknowledged yet.
+#[derive(Debug, Clone, PartialEq)]
+struct PendingEntry {
+    consumer: String,
+    delivery_time: u64,
+    delivery_count: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Consumer {
+    seen_time: u64,
+    active_time: Option<u64>,
+    pending: BTreeSet<StreamId>,
+}
+
+/// A consumer group tracks how far it has read the stream and which delivered entries are
+/// still waiting for an XACK, both group wide (to look them up by ID) and per consumer (for
+/// history replay).
+#[derive(Debug, Clone)]
+pub struct ConsumerGroup {
+    last_delivered: StreamId,
+    entries_read: Option<u64>,
+    pending: BTreeMap<StreamId, PendingEntry>,
+    consumers: BTreeMap<String, Consumer>,
+}
+
+/// Where a group starts reading: an ID, or `$` for the last entry of the stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupStart {
+    Id(StreamId),
+    LastEntry,
+}
+
+/// `>` reads entries never delivered to the group; an ID replays the consumer's pending
+/// entries after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupRead {
+    New,
+    History(StreamId),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XClaimOptions {
+    pub idle: Option<u64>,
+    pub time: Option<u64>,
+    pub retry_count: Option<u64>,
+    pub force: bool,
+    pub just_id: bool,
+    pub last_id: Option<StreamId>,
+}
+
+/// The extended form of XPENDING.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingFilter {
+    pub min_idle: Option<u64>,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: usize,
+    pub consumer: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingSummary {
+    pub count: usize,
+    pub first: Option<StreamId>,
+    pub last: Option<StreamId>,
+    pub consumers: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingInfo {
+    pub id: StreamId,
+    pub consumer: String,
+    pub idle: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoClaimOptions {
+    pub min_idle: u64,
+    pub start: StreamId,
+    pub count: usize,
+    pub just_id: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoClaimed {
+    pub next: StreamId,
+    pub claimed: Vec<StreamEntry>,
+    pub deleted: Vec<StreamId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupInfo {
+    pub name: String,
+    pub consumers: usize,
+    pub pending: usize,
+    pub last_delivered_id: StreamId,
+    pub entries_read: Option<u64>,
+    pub lag: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumerInfo {
+    pub name: String,
+    pub pending: usize,
+    pub idle: u64,
+    pub inactive: Option<u64>,
+}
+
+impl Consumer {
+    fn new(now: u64) -> Self {
+        Self {
+            seen_time: now,
+            active_time: None,
+            pending: BTreeSet::new(),
+        }
+    }
+}
+
+impl ConsumerGroup {
+    pub(super) fn memory_usage(&self, samples: usize) -> usize {
+        let pending = self.pending.values().map(|entry| {
+            size_of::<StreamId>() + size_of::<PendingEntry>() + entry.consumer.capacity()
+        });
+        let consumers = self.consumers.iter().map(|(name, consumer)| {
+            string_usage(name)
+                + size_of::<Consumer>()
+                + consumer.pending.len() * (size_of::<StreamId>() + ELEMENT_OVERHEAD)
+        });
+        size_of::<ConsumerGroup>()
+            + estimate(pending, self.pending.len(), samples)
+            + estimate(consumers, self.consumers.len(), samples)
+    }
+
+    fn new(last_delivered: StreamId, entries_read: Option<u64>) -> Self {
+        Self {
+            last_delivered,
+            entries_read,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    /// Looks up a consumer, creating it on first use, and records that it was seen.
+    fn consumer(&mut self, name: &str, now: u64) -> &mut Consumer {
+        let consumer = self
+            .consumers
+            .entry(name.to_string())
+            .or_insert_with(|| Consumer::new(now));
+        consumer.seen_time = now;
+        consumer
+    }
+
+    /// Puts `id` in the pending list of `consumer`, taking it away from whoever had it before.
+    fn assign(&mut self, id: StreamId, consumer: &str, delivery_time: u64, delivery_count: u64) {
+        let previous = self.pending.insert(
+            id,
+            PendingEntry {
+                consumer: consumer.to_string(),
+                delivery_time,
+                delivery_count,
+            },
+        );
+        if let Some(previous) = previous.filter(|previous| previous.consumer != consumer) {
+            if let Some(owner) = self.consumers.get_mut(&previous.consumer) {
+                owner.pending.remove(&id);
+            }
+        }
+        self.consumer(consumer, delivery_time).pending.insert(id);
+    }
+
+    fn ack(&mut self, id: StreamId) -> bool {
+        let Some(entry) = self.pending.remove(&id) else {
+            return false;
+        };
+        if let Some(owner) = self.consumers.get_mut(&entry.consumer) {
+            owner.pending.remove(&id);
+        }
+        true
+    }
+
+    fn read_new(
+        &mut self,
+        stream: &Stream,
+        consumer: &str,
+        count: Option<usize>,
+        no_ack: bool,
+        now: u64,
+    ) -> Option<Vec<DeliveredEntry>> {
+        self.consumer(consumer, now);
+        let entries = stream.range(self.last_delivered.next()?, StreamId::MAX, count, false);
+        if entries.is_empty() {
+            return None;
+        }
+        for (id, _) in &entries {
+            self.last_delivered = *id;
+            self.entries_read = match self.entries_read {
+                Some(read) if !stream.has_tombstones_from(*id) => Some(read + 1),
+                _ => stream.entries_read_until(*id),
+            };
+            if !no_ack {
+                self.assign(*id, consumer, now, 1);
+            }
+        }
+        self.consumer(consumer, now).active_time = Some(now);
+        Some(
+            entries
+                .into_iter()
+                .map(|(id, fields)| (id, Some(fields)))
+                .collect(),
+        )
+    }
+
+    fn read_history(
+        &mut self,
+        stream: &Stream,
+        consumer: &str,
+        after: StreamId,
+        count: Option<usize>,
+        now: u64,
+    ) -> Vec<DeliveredEntry> {
+        let Some(start) = after.next() else {
+            return Vec::new();
+        };
+        let ids: Vec<StreamId> = self
+            .consumer(consumer, now)
+            .pending
+            .range(start..)
+            .take(count.unwrap_or(usize::MAX))
+            .copied()
+            .collect();
+        ids.into_iter()
+            .map(|id| {
+                if let Some(entry) = self.pending.get_mut(&id) {
+                    entry.delivery_time = now;
+                    entry.delivery_count += 1;
+                }
+                (id, stream.entry(id))
+            })
+            .collect()
+    }
+
+    fn info(&self, name: &str, stream: &Stream) -> GroupInfo {
+        GroupInfo {
+            name: name.to_string(),
+            consumers: self.consumers.len(),
+            pending: self.pending.len(),
+            last_delivered_id: self.last_delivered,
+            entries_read: self.entries_read,
+            lag: stream.lag(self.last_delivered, self.entries_read),
+        }
+    }
+}
+
+/// Runs `f` with the stream and one of its groups borrowed separately.
+fn with_group<T>(
+    stream: &mut Stream,
+    name: &str,
+    f: impl FnOnce(&Stream, &mut ConsumerGroup) -> T,
+) -> Result<T, &'static str> {
+    let (name, mut group) = stream.groups.remove_entry(name).ok_or(NOGROUP_ERR)?;
+    let result = f(stream, &mut group);
+    stream.groups.insert(name, group);
+    Ok(result)
+}
+
+/// Who reads in XREADGROUP and how.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupReader {
+    pub group: String,
+    pub consumer: String,
+    pub count: Option<usize>,
+    pub no_ack: bool,
+}
+
+/// XREADGROUP on a single stream, for both the first attempt and blocked clients. `Ok(None)`
+/// means nothing new was there to read; history reads always answer.
+pub fn read_group(
+    data: &mut Keyspace,
+    key: &str,
+    reader: &GroupReader,
+    read: GroupRead,
+    now: u64,
+) -> Result<Option<Vec<DeliveredEntry>>, &'static str> {
+    let stream = stream_mut(data, key)?.ok_or(NOGROUP_ERR)?;
+    let GroupReader {
+        consumer,
+        count,
+        no_ack,
+        ..
+    } = reader;
+    with_group(stream, &reader.group, |stream, group| match read {
+        GroupRead::New => group.read_new(stream, consumer, *count, *no_ack, now),
+        GroupRead::History(after) => Some(group.read_history(stream, consumer, after, *count, now)),
+    })
+}
+
+impl Cache {
+    fn write_group<T>(
+        &self,
+        key: &str,
+        group: &str,
+        f: impl FnOnce(&Stream, &mut ConsumerGroup) -> T,
+    ) -> Result<T, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let stream = stream_mut(&mut data, key)?.ok_or(NOGROUP_ERR)?;
+        with_group(stream, group, f)
+    }
+
+    /// XGROUP subcommands other than CREATE need the stream to exist.
+    fn write_existing_stream<T>(
+        &self,
+        key: &str,
+        f: impl FnOnce(&mut Stream) -> Result<T, &'static str>,
+    ) -> Result<T, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        f(stream_mut(&mut data, key)?.ok_or(XGROUP_NO_KEY_ERR)?)
+    }
+
+    pub fn xgroup_create(
+        &self,
+        key: &str,
+        group: &str,
+        start: GroupStart,
+        mkstream: bool,
+        entries_read: Option<u64>,
+    ) -> Result<(), &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        if stream_ref(&data, key)?.is_none() {
+            if !mkstream {
+                return Err(XGROUP_NO_KEY_ERR);
+            }
+            data.insert(key.to_string(), Value::Stream(Box::default()));
+        }
+        let Some(stream) = stream_mut(&mut data, key)? else {
+            unreachable!("stream was created above");
+        };
+        if stream.groups.contains_key(group) {
+            return Err(BUSYGROUP_ERR);
+        }
+        let last_delivered = match start {
+            GroupStart::Id(id) => id,
+            GroupStart::LastEntry => stream.last_id(),
+        };
+        stream.groups.insert(
+            group.to_string(),
+            ConsumerGroup::new(last_delivered, entries_read),
+        );
+        Ok(())
+    }
+
+    pub fn xgroup_setid(
+        &self,
+        key: &str,
+        group: &str,
+        start: GroupStart,
+        entries_read: Option<u64>,
+    ) -> Result<(), &'static str> {
+        self.write_existing_stream(key, |stream| {
+            let last_id = stream.last_id();
+            let group = stream.groups.get_mut(group).ok_or(NOGROUP_ERR)?;
+            group.last_delivered = match start {
+                GroupStart::Id(id) => id,
+                GroupStart::LastEntry => last_id,
+            };
+            group.entries_read = entries_read;
+            Ok(())
+        })
+    }
+
+    /// Drops a group; clients blocked reading from it are answered with an error.
+    pub fn xgroup_destroy(&self, key: &str, group: &str) -> Result<bool, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let stream = stream_mut(&mut data, key)?.ok_or(XGROUP_NO_KEY_ERR)?;
+        if stream.groups.remove(group).is_none() {
+            return Ok(false);
+        }
+        self.blocking.serve(&mut data, key);
+        Ok(true)
+    }
+
+    pub fn xgroup_createconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> Result<bool, &'static str> {
+        self.write_existing_stream(key, |stream| {
+            let group = stream.groups.get_mut(group).ok_or(NOGROUP_ERR)?;
+            if group.consumers.contains_key(consumer) {
+                return Ok(false);
+            }
+            group.consumer(consumer, unix_time_ms());
+            Ok(true)
+        })
+    }
+
+    /// Removes a consumer along with its pending entries and returns how many it had.
+    pub fn xgroup_delconsumer(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> Result<usize, &'static str> {
+        self.write_existing_stream(key, |stream| {
+            let group = stream.groups.get_mut(group).ok_or(NOGROUP_ERR)?;
+            let Some(removed) = group.consumers.remove(consumer) else {
+                return Ok(0);
+            };
+            for id in &removed.pending {
+                group.pending.remove(id);
+            }
+            Ok(removed.pending.len())
+        })
+    }
+
+    /// XREADGROUP without blocking. Every stream and group must exist before anything is read.
+    pub fn xreadgroup(
+        &self,
+        streams: &[(String, GroupRead)],
+        reader: &GroupReader,
+    ) -> Result<Vec<(String, Vec<DeliveredEntry>)>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        for (key, _) in streams {
+            let stream = stream_ref(&data, key)?.ok_or(NOGROUP_ERR)?;
+            if !stream.groups.contains_key(&reader.group) {
+                return Err(NOGROUP_ERR);
+            }
+        }
+        let now = unix_time_ms();
+        let mut found = Vec::new();
+        for (key, read) in streams {
+            if let Some(entries) = read_group(&mut data, key, reader, *read, now)? {
+                found.push((key.clone(), entries));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Whether `key` holds a stream with the given group, used to word XREADGROUP errors.
+    pub fn xgroup_exists(&self, key: &str, group: &str) -> bool {
+        self.data.read().is_ok_and(|data| {
+            matches!(stream_ref(&data, key), Ok(Some(stream)) if stream.groups.contains_key(group))
+        })
+    }
+
+    /// XACK. A missing stream or group acknowledges nothing.
+    pub fn xack(&self, key: &str, group: &str, ids: &[StreamId]) -> Result<usize, &'static str> {
+        match self.write_group(key, group, |_, group| {
+            ids.iter().filter(|id| group.ack(**id)).count()
+        }) {
+            Err(NOGROUP_ERR) => Ok(0),
+            acked => acked,
+        }
+    }
+
+    pub fn xpending_summary(&self, key: &str, group: &str) -> Result<PendingSummary, &'static str> {
+        self.write_group(key, group, |_, group| PendingSummary {
+            count: group.pending.len(),
+            first: group.pending.keys().next().copied(),
+            last: group.pending.keys().next_back().copied(),
+            consumers: group
+                .consumers
+                .iter()
+                .filter(|(_, consumer)| !consumer.pending.is_empty())
+                .map(|(name, consumer)| (name.clone(), consumer.pending.len()))
+                .collect(),
+        })
+    }
+
+    pub fn xpending(
+        &self,
+        key: &str,
+        group: &str,
+        filter: &PendingFilter,
+    ) -> Result<Vec<PendingInfo>, &'static str> {
+        let now = unix_time_ms();
+        self.write_group(key, group, |_, group| {
+            if filter.start > filter.end {
+                return Vec::new();
+            }
+            group
+                .pending
+                .range(filter.start..=filter.end)
+                .filter(|(_, entry)| {
+                    filter
+                        .consumer
+                        .as_ref()
+                        .is_none_or(|consumer| *consumer == entry.consumer)
+                })
+                .map(|(id, entry)| PendingInfo {
+                    id: *id,
+                    consumer: entry.consumer.clone(),
+                    idle: now.saturating_sub(entry.delivery_time),
+                    delivery_count: entry.delivery_count,
+                })
+                .filter(|info| filter.min_idle.is_none_or(|min_idle| info.idle >= min_idle))
+                .take(filter.count)
+                .collect()
+        })
+    }
+
+    /// XCLAIM. Pending entries idle for at least `min_idle` ms move to `consumer`; the ones
+    /// deleted from the stream meanwhile are dropped from the pending list instead.
+    pub fn xclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: &XClaimOptions,
+    ) -> Result<Vec<StreamEntry>, &'static str> {
+        let now = unix_time_ms();
+        let delivery_time = match (options.idle, options.time) {
+            (Some(idle), _) => now.saturating_sub(idle),
+            (None, Some(time)) => time,
+            (None, None) => now,
+        };
+        self.write_group(key, group, |stream, group| {
+            group.consumer(consumer, now);
+            let mut claimed = Vec::new();
+            for &id in ids {
+                let fields = stream.entry(id);
+                let (forced, delivery_count) = match group.pending.get(&id) {
+                    Some(entry) => (false, entry.delivery_count),
+                    None if options.force && fields.is_some() => (true, 0),
+                    None => continue,
+                };
+                let idle = now.saturating_sub(
+                    group
+                        .pending
+                        .get(&id)
+                        .map_or(now, |entry| entry.delivery_time),
+                );
+                if !forced && idle < min_idle {
+                    continue;
+                }
+                let Some(fields) = fields else {
+                    group.ack(id);
+                    continue;
+                };
+                let delivery_count = match options.retry_count {
+                    Some(retry_count) => retry_count,
+                    None if options.just_id => delivery_count,
+                    None => delivery_count + 1,
+                };
+                group.assign(id, consumer, delivery_time, delivery_count);
+                claimed.push((id, fields));
+            }
+            if let Some(last_id) = options.last_id {
+                group.last_delivered = group.last_delivered.max(last_id);
+            }
+            if !claimed.is_empty() {
+                group.consumer(consumer, now).active_time = Some(now);
+            }
+            claimed
+        })
+    }
+
+    /// XAUTOCLAIM. Scans the pending list from `start`, looking at no more than ten times
+    /// `count` entries, and returns where the next call should continue (`0-0` when done).
+    pub fn xautoclaim(
+        &self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        options: &AutoClaimOptions,
+    ) -> Result<AutoClaimed, &'static str> {
+        let now = unix_time_ms();
+        let AutoClaimOptions {
+            min_idle,
+            start,
+            count,
+            just_id,
+        } = *options;
+        self.write_group(key, group, |stream, group| {
+            group.consumer(consumer, now);
+            let mut attempts = count.saturating_mul(10);
+            let scanned: Vec<StreamId> = group
+                .pending
+                .range(start..)
+                .map(|(id, _)| *id)
+                .take(attempts.saturating_add(1))
+                .collect();
+            let mut next = StreamId::MIN;
+            let mut claimed = Vec::new();
+            let mut deleted = Vec::new();
+            for id in scanned {
+                if attempts == 0 || claimed.len() == count {
+                    next = id;
+                    break;
+                }
+                attempts -= 1;
+                let Some(entry) = group.pending.get(&id) else {
+                    continue;
+                };
+                if now.saturating_sub(entry.delivery_time) < min_idle {
+                    continue;
+                }
+                let delivery_count = entry.delivery_count + u64::from(!just_id);
+                let Some(fields) = stream.entry(id) else {
+                    group.ack(id);
+                    deleted.push(id);
+                    continue;
+                };
+                group.assign(id, consumer, now, delivery_count);
+                claimed.push((id, fields));
+            }
+            if !claimed.is_empty() {
+                group.consumer(consumer, now).active_time = Some(now);
+            }
+            AutoClaimed {
+                next,
+                claimed,
+                deleted,
+            }
+        })
+    }
+
+    /// XINFO GROUPS, or `None` for a missing key.
+    pub fn xinfo_groups(&self, key: &str) -> Result<Option<Vec<GroupInfo>>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(stream_ref(&data, key)?.map(|stream| {
+            stream
+                .groups
+                .iter()
+                .map(|(name, group)| group.info(name, stream))
+                .collect()
+        }))
+    }
+
+    pub fn xinfo_consumers(
+        &self,
+        key: &str,
+        group: &str,
+    ) -> Result<Vec<ConsumerInfo>, &'static str> {
+        let now = unix_time_ms();
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let stream = stream_ref(&data, key)?.ok_or(NOGROUP_ERR)?;
+        let group = stream.groups.get(group).ok_or(NOGROUP_ERR)?;
+        Ok(group
+            .consumers
+            .iter()
+            .map(|(name, consumer)| ConsumerInfo {
+                name: name.clone(),
+                pending: consumer.pending.len(),
+                idle: now.saturating_sub(consumer.seen_time),
+                inactive: consumer
+                    .active_time
+                    .map(|active_time| now.saturating_sub(active_time)),
+            })
+            .collect())
+    }
+}
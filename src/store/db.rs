@@ -1,76 +1,243 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use super::{
+    blocking::Blocking,
+    eviction::Eviction,
+    hash::{expire_hash_fields, Hash},
+    keyspace::{active_expire_cycle, Keyspace},
+    list::List,
+    set::Set,
+    stream::Stream,
+    zset::SortedSet,
+};
+
+pub const DB_INDEX_ERR: &str = "ERR DB index is out of range";
+/// Longest string Redis stores in one allocation with its object header.
+const EMBSTR_SIZE_LIMIT: usize = 44;
+
+pub const WRONGTYPE_ERR: &str = "WRONGTYPE Operation against a key holding the wrong kind of value";
+
+/// Every collection but the list is boxed, as it is larger than a string and every key would
+/// otherwise pay for the largest one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    List(List),
+    Hash(Box<Hash>),
+    Set(Box<Set>),
+    SortedSet(Box<SortedSet>),
+    Stream(Box<Stream>),
+}
+
+impl Value {
+    /// Name of the value's type as TYPE reports it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+            Value::Stream(_) => "stream",
+        }
+    }
+
+    /// Name of the value's internal representation as OBJECT ENCODING reports it, using Redis'
+    /// names for the closest equivalent.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Value::String(value) if value.parse::<i64>().is_ok_and(|n| n.to_string() == *value) => {
+                "int"
+            }
+            Value::String(value) if value.len() <= EMBSTR_SIZE_LIMIT => "embstr",
+            Value::String(_) => "raw",
+            Value::List(list) => list.encoding(),
+            Value::Hash(hash) => hash.encoding(),
+            Value::Set(set) => set.encoding(),
+            Value::SortedSet(zset) => zset.encoding(),
+            Value::Stream(_) => "stream",
+        }
+    }
+}
+
+/// How often the active expiry cycle runs, like Redis' default `hz 10`.
+const EXPIRE_CYCLE_INTERVAL: Duration = Duration::from_millis(100);
+/// Share of each interval the cycle may spend, like Redis' 25% slow cycle.
+const EXPIRE_CYCLE_BUDGET: Duration = Duration::from_millis(25);
+/// Hash fields are swept on every tenth cycle, once a second.
+const HASH_FIELD_SWEEP_EVERY: u32 = 10;
+
+/// How many logical databases a server has unless configured otherwise, like Redis.
+pub const DEFAULT_DATABASES: usize = 16;
+
+/// One logical database: its key space, the hashes that may hold fields with a TTL (so the
+/// sweeper does not have to scan every key) and the clients blocked on its keys.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Database {
+    pub(super) data: Arc<RwLock<Keyspace>>,
+    pub(super) volatile_hashes: Arc<RwLock<HashSet<String>>>,
+    pub(super) blocking: Arc<Blocking>,
+}
+
+/// Handle to one of the server's logical databases. Handles are cheap to clone, and `select`
+/// gives one for any other database of the same server.
 #[derive(Debug, Clone)]
 pub struct Cache {
-    data: Arc<RwLock<HashMap<String, String>>>,
-    expirations: Arc<RwLock<HashMap<String, Instant>>>,
+    pub(super) data: Arc<RwLock<Keyspace>>,
+    pub(super) volatile_hashes: Arc<RwLock<HashSet<String>>>,
+    pub(super) blocking: Arc<Blocking>,
+    pub(super) databases: Arc<Vec<Database>>,
+    pub(super) eviction: Arc<Eviction>,
+    pub(super) index: usize,
+}
+
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Impossibru!")
+        .as_millis() as u64
 }
 
 impl Cache {
     pub fn new() -> Self {
-        let data = Arc::new(RwLock::new(HashMap::new()));
-        let expirations = Arc::new(RwLock::new(HashMap::new()));
-        let cache = Self { data, expirations };
-
-        let data_clone = cache.data.clone();
-        let expirations_clone = cache.expirations.clone();
-        thread::spawn(move || loop {
-            let now = Instant::now();
-            let keys_to_remove: Vec<String> = {
-                let expirations = expirations_clone.read().unwrap();
-                expirations
-                    .iter()
-                    .filter_map(|(key, &time)| if time <= now { Some(key.clone()) } else { None })
-                    .collect()
-            };
-
-            if !keys_to_remove.is_empty() {
-                let mut data = data_clone.write().unwrap();
-                for key in keys_to_remove {
-                    data.remove(&key);
+        Self::with_databases(DEFAULT_DATABASES)
+    }
+
+    /// A server with `count` databases (at least one), handing out database 0.
+    pub fn with_databases(count: usize) -> Self {
+        let databases: Arc<Vec<Database>> =
+            Arc::new((0..count.max(1)).map(|_| Database::default()).collect());
+
+        let databases_clone = databases.clone();
+        thread::spawn(move || {
+            for cycle in 1_u32.. {
+                // The databases share one budget per cycle.
+                let started = Instant::now();
+                for database in databases_clone.iter() {
+                    let budget = EXPIRE_CYCLE_BUDGET.saturating_sub(started.elapsed());
+                    active_expire_cycle(&database.data, budget);
+
+                    if cycle % HASH_FIELD_SWEEP_EVERY == 0
+                        && !database.volatile_hashes.read().unwrap().is_empty()
+                    {
+                        let mut data = database.data.write().unwrap();
+                        let mut volatile_hashes = database.volatile_hashes.write().unwrap();
+                        expire_hash_fields(&mut data, &mut volatile_hashes, unix_time_ms());
+                    }
                 }
-            }
 
-            thread::sleep(Duration::from_secs(1));
+                thread::sleep(EXPIRE_CYCLE_INTERVAL);
+            }
         });
 
-        cache
+        Self::handle(databases, Arc::default(), 0)
+    }
+
+    fn handle(databases: Arc<Vec<Database>>, eviction: Arc<Eviction>, index: usize) -> Self {
+        let database = databases[index].clone();
+        Self {
+            data: database.data,
+            volatile_hashes: database.volatile_hashes,
+            blocking: database.blocking,
+            databases,
+            eviction,
+            index,
+        }
+    }
+
+    /// Handle to database `index` of the same server, if there is one.
+    pub fn select(&self, index: usize) -> Option<Cache> {
+        (index < self.databases.len())
+            .then(|| Self::handle(self.databases.clone(), self.eviction.clone(), index))
     }
 
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn database_count(&self) -> usize {
+        self.databases.len()
+    }
+
+    #[cfg(test)]
     pub fn set_with_expiration(
         &self,
         key: String,
         value: String,
         secs: Duration,
     ) -> Result<(), &'static str> {
-        if let Ok(mut data) = self.data.write() {
-            if let Ok(mut expirations) = self.expirations.write() {
-                data.insert(key.clone(), value);
-                expirations.insert(key, Instant::now() + secs);
-                Ok(())
-            } else {
-                Err("Could not acquire expirations write lock")
-            }
-        } else {
-            Err("Could not acquire data write lock")
-        }
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        data.insert(key.clone(), Value::String(value));
+        data.set_expire_at(&key, unix_time_ms() + secs.as_millis() as u64);
+        Ok(())
     }
 
+    /// Replaces the value at `key`, dropping any TTL it had.
+    #[cfg(test)]
     pub fn set(&self, key: String, value: String) -> Result<(), &'static str> {
-        if let Ok(mut data) = self.data.write() {
-            data.insert(key, value);
-            Ok(())
-        } else {
-            Err("Could not acquire data write lock")
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        data.persist(&key);
+        data.insert(key, Value::String(value));
+        Ok(())
+    }
+
+    /// Runs `write` with the key space locked for writing.
+    pub(super) fn write_keyspace<T>(
+        &self,
+        write: impl FnOnce(&mut Keyspace) -> Result<T, &'static str>,
+    ) -> Result<T, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        write(&mut data)
+    }
+
+    /// Runs `write` with this database's key space and the one of database `db`, which is `None`
+    /// when `db` is this database, along with a handle to `db`. Both are locked lower index
+    /// first, so commands crossing databases in opposite directions cannot deadlock.
+    pub(super) fn write_across<T>(
+        &self,
+        db: usize,
+        write: impl FnOnce(&mut Keyspace, Option<&mut Keyspace>, &Cache) -> Result<T, &'static str>,
+    ) -> Result<T, &'static str> {
+        fn lock(cache: &Cache) -> Result<RwLockWriteGuard<'_, Keyspace>, &'static str> {
+            cache
+                .data
+                .write()
+                .map_err(|_| "Could not acquire data write lock")
+        }
+
+        let target = self.select(db).ok_or(DB_INDEX_ERR)?;
+        if db == self.index {
+            return write(&mut *lock(self)?, None, &target);
         }
+        let (mut data, mut other) = if self.index < db {
+            let data = lock(self)?;
+            (data, lock(&target)?)
+        } else {
+            let other = lock(&target)?;
+            (lock(self)?, other)
+        };
+        write(&mut data, Some(&mut other), &target)
     }
 
     pub fn get(&self, key: &str) -> Result<Option<String>, &'static str> {
         match self.data.read() {
-            Ok(data) => Ok(data.get(key).cloned()),
+            Ok(data) => match data.get(key) {
+                Some(Value::String(value)) => Ok(Some(value.clone())),
+                Some(_) => Err(WRONGTYPE_ERR),
+                None => Ok(None),
+            },
             Err(_) => Err("Could not acquire data read lock"),
         }
     }
@@ -82,11 +249,10 @@ impl Cache {
         }
     }
 
-    pub fn del(&self, key: &str) -> Result<Option<String>, &'static str> {
-        if let Ok(mut data) = self.data.write() {
-            Ok(data.remove(key))
-        } else {
-            Err("Could not acquire data write lock")
+    pub fn del(&self, key: &str) -> Result<Option<Value>, &'static str> {
+        match self.data.write() {
+            Ok(mut data) => Ok(data.remove(key)),
+            Err(_) => Err("Could not acquire data write lock"),
         }
     }
 }
@@ -95,6 +261,11 @@ impl Cache {
 mod tests {
     use super::*;
 
+    #[test]
+    fn should_keep_values_small() {
+        assert!(std::mem::size_of::<Value>() <= 40);
+    }
+
     #[test]
     fn should_initialize_and_get_set() {
         let cache = Cache::new();
@@ -117,4 +288,20 @@ mod tests {
         thread::sleep(Duration::from_secs(4));
         assert!(cache.get("name").unwrap().is_none());
     }
+
+    #[test]
+    fn should_hide_expired_keys_before_they_are_reclaimed() {
+        let cache = Cache::new();
+        cache
+            .set_with_expiration(
+                "name".to_string(),
+                "oz".to_string(),
+                Duration::from_millis(20),
+            )
+            .unwrap();
+        thread::sleep(Duration::from_millis(30));
+        assert!(!cache.exists("name").unwrap());
+        assert!(cache.get("name").unwrap().is_none());
+        assert!(cache.del("name").unwrap().is_none());
+    }
 }
@@ -0,0 +1,391 @@
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Mutex, RwLock,
+};
+
+use super::db::Cache;
+
+pub const OOM_ERR: &str = "OOM command not allowed when used memory > 'maxmemory'.";
+
+/// Candidates the eviction pool remembers between rounds, like Redis' EVPOOL_SIZE.
+const POOL_SIZE: usize = 16;
+
+/// What to do when a write would take the server past `maxmemory`, named like Redis'
+/// `maxmemory-policy` values.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EvictionPolicy {
+    #[default]
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    AllKeysRandom,
+    VolatileLru,
+    VolatileLfu,
+    VolatileRandom,
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    pub const ALL: [EvictionPolicy; 8] = [
+        EvictionPolicy::NoEviction,
+        EvictionPolicy::AllKeysLru,
+        EvictionPolicy::AllKeysLfu,
+        EvictionPolicy::AllKeysRandom,
+        EvictionPolicy::VolatileLru,
+        EvictionPolicy::VolatileLfu,
+        EvictionPolicy::VolatileRandom,
+        EvictionPolicy::VolatileTtl,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EvictionPolicy::NoEviction => "noeviction",
+            EvictionPolicy::AllKeysLru => "allkeys-lru",
+            EvictionPolicy::AllKeysLfu => "allkeys-lfu",
+            EvictionPolicy::AllKeysRandom => "allkeys-random",
+            EvictionPolicy::VolatileLru => "volatile-lru",
+            EvictionPolicy::VolatileLfu => "volatile-lfu",
+            EvictionPolicy::VolatileRandom => "volatile-random",
+            EvictionPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        Self::ALL.into_iter().find(|policy| policy.name() == name)
+    }
+
+    /// Whether only keys with a TTL may be evicted.
+    fn volatile_only(self) -> bool {
+        matches!(
+            self,
+            EvictionPolicy::VolatileLru
+                | EvictionPolicy::VolatileLfu
+                | EvictionPolicy::VolatileRandom
+                | EvictionPolicy::VolatileTtl
+        )
+    }
+
+    /// Whether keys are picked by access frequency, which OBJECT FREQ needs.
+    pub fn is_lfu(self) -> bool {
+        matches!(
+            self,
+            EvictionPolicy::AllKeysLfu | EvictionPolicy::VolatileLfu
+        )
+    }
+
+    fn is_random(self) -> bool {
+        matches!(
+            self,
+            EvictionPolicy::AllKeysRandom | EvictionPolicy::VolatileRandom
+        )
+    }
+}
+
+/// The memory limit in bytes (zero for none), what happens when it is reached and how many keys
+/// each database contributes to the eviction pool per round.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaxmemoryConfig {
+    pub maxmemory: usize,
+    pub policy: EvictionPolicy,
+    pub samples: usize,
+}
+
+impl Default for MaxmemoryConfig {
+    fn default() -> Self {
+        Self {
+            maxmemory: 0,
+            policy: EvictionPolicy::default(),
+            samples: 5,
+        }
+    }
+}
+
+/// Parses a memory size like Redis' config files do: plain bytes or a `k`, `kb`, `m`, `mb`,
+/// `g` or `gb` suffix, where the `b` forms are powers of 1024 and the others of 1000.
+pub fn parse_memory(value: &str) -> Option<usize> {
+    let value = value.to_lowercase();
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(digits_end);
+    let multiplier: usize = match unit {
+        "" | "b" => 1,
+        "k" => 1_000,
+        "kb" => 1 << 10,
+        "m" => 1_000_000,
+        "mb" => 1 << 20,
+        "g" => 1_000_000_000,
+        "gb" => 1 << 30,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+/// A key the pool may evict. Higher scores are better victims.
+#[derive(Debug)]
+struct Candidate {
+    score: u64,
+    db: usize,
+    key: String,
+}
+
+/// Eviction state shared by every database of a server.
+///
+/// Like Redis, LRU, LFU and TTL eviction are approximate: each round samples a few keys of
+/// every database and merges them into a small pool of the best candidates seen so far, which
+/// carries over to later rounds. The victim is the best candidate that still exists.
+#[derive(Debug, Default)]
+pub struct Eviction {
+    config: RwLock<MaxmemoryConfig>,
+    pool: Mutex<Vec<Candidate>>,
+    evicted_keys: AtomicU64,
+    /// Database the random policies evict from next, so they spread over all of them.
+    next_db: AtomicUsize,
+}
+
+impl Eviction {
+    /// Puts `candidate` into `pool`, which is sorted by ascending score and keeps only the
+    /// best `POOL_SIZE` entries.
+    fn offer(pool: &mut Vec<Candidate>, candidate: Candidate) {
+        if pool
+            .iter()
+            .any(|known| known.db == candidate.db && known.key == candidate.key)
+        {
+            return;
+        }
+        if pool.len() == POOL_SIZE {
+            if pool[0].score >= candidate.score {
+                return;
+            }
+            pool.remove(0);
+        }
+        let position = pool.partition_point(|known| known.score < candidate.score);
+        pool.insert(position, candidate);
+    }
+}
+
+impl Cache {
+    pub fn maxmemory_config(&self) -> MaxmemoryConfig {
+        self.eviction
+            .config
+            .read()
+            .map(|config| *config)
+            .unwrap_or_default()
+    }
+
+    pub fn set_maxmemory_config(&self, config: MaxmemoryConfig) -> Result<(), &'static str> {
+        let mut current = self
+            .eviction
+            .config
+            .write()
+            .map_err(|_| "Could not acquire config write lock")?;
+        if current.policy != config.policy {
+            // Scores of different policies cannot be compared.
+            if let Ok(mut pool) = self.eviction.pool.lock() {
+                pool.clear();
+            }
+        }
+        *current = config;
+        Ok(())
+    }
+
+    /// Estimated bytes held by the keys of every database.
+    pub fn used_memory(&self) -> Result<usize, &'static str> {
+        self.databases.iter().try_fold(0, |total, database| {
+            let data = database
+                .data
+                .read()
+                .map_err(|_| "Could not acquire data read lock")?;
+            Ok(total + data.used_memory())
+        })
+    }
+
+    /// Keys deleted to stay under `maxmemory` since the server started.
+    pub fn evicted_keys(&self) -> u64 {
+        self.eviction.evicted_keys.load(Ordering::Relaxed)
+    }
+
+    /// Evicts keys by the configured policy until the server is back under `maxmemory`. Fails
+    /// with the OOM error when it is over the limit and the policy does not allow evicting, or
+    /// there is nothing left to evict.
+    pub fn free_memory_if_needed(&self) -> Result<(), &'static str> {
+        let config = self.maxmemory_config();
+        if config.maxmemory == 0 {
+            return Ok(());
+        }
+        let mut used = self.used_memory()?;
+        while used > config.maxmemory {
+            if config.policy == EvictionPolicy::NoEviction {
+                return Err(OOM_ERR);
+            }
+            let freed = if config.policy.is_random() {
+                self.evict_random(config.policy)?
+            } else {
+                self.evict_from_pool(config)?
+            };
+            used = used.saturating_sub(freed.ok_or(OOM_ERR)?);
+            self.eviction.evicted_keys.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Deletes `key` from database `db` and returns the bytes freed, or `None` if it is gone.
+    fn evict_key(&self, db: usize, key: &str) -> Result<Option<usize>, &'static str> {
+        let mut data = self.databases[db]
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        Ok(data.evict(key))
+    }
+
+    /// Evicts a random key, trying the databases in turn until one has a key to give.
+    fn evict_random(&self, policy: EvictionPolicy) -> Result<Option<usize>, &'static str> {
+        for _ in 0..self.databases.len() {
+            let db = self.eviction.next_db.fetch_add(1, Ordering::Relaxed) % self.databases.len();
+            let sampled = {
+                let data = self.databases[db]
+                    .data
+                    .read()
+                    .map_err(|_| "Could not acquire data read lock")?;
+                data.sample_keys(1, policy.volatile_only())
+            };
+            for key in sampled {
+                if let Some(freed) = self.evict_key(db, &key)? {
+                    return Ok(Some(freed));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Refills the pool from every database and evicts its best candidate that still exists.
+    fn evict_from_pool(&self, config: MaxmemoryConfig) -> Result<Option<usize>, &'static str> {
+        let mut pool = self
+            .eviction
+            .pool
+            .lock()
+            .map_err(|_| "Could not acquire eviction pool lock")?;
+        for (db, database) in self.databases.iter().enumerate() {
+            let data = database
+                .data
+                .read()
+                .map_err(|_| "Could not acquire data read lock")?;
+            for key in data.sample_keys(config.samples, config.policy.volatile_only()) {
+                // Expired keys are the best victims of all.
+                let score = if !data.contains_key(&key) {
+                    u64::MAX
+                } else {
+                    match config.policy {
+                        policy if policy.is_lfu() => {
+                            u8::MAX as u64 - data.frequency(&key).unwrap_or(0) as u64
+                        }
+                        EvictionPolicy::VolatileTtl => {
+                            u64::MAX - 1 - data.expire_at(&key).unwrap_or(u64::MAX - 1)
+                        }
+                        _ => data.idle_time(&key).unwrap_or(0),
+                    }
+                };
+                Eviction::offer(&mut pool, Candidate { score, db, key });
+            }
+        }
+        while let Some(candidate) = pool.pop() {
+            if let Some(freed) = self.evict_key(candidate.db, &candidate.key)? {
+                return Ok(Some(freed));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::store::db::unix_time_ms;
+
+    use super::*;
+
+    fn limit(cache: &Cache, policy: EvictionPolicy, maxmemory: usize) {
+        cache
+            .set_maxmemory_config(MaxmemoryConfig {
+                maxmemory,
+                policy,
+                samples: 5,
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn should_parse_memory_sizes_and_policies() {
+        assert_eq!(parse_memory("100"), Some(100));
+        assert_eq!(parse_memory("2kb"), Some(2048));
+        assert_eq!(parse_memory("1M"), Some(1_000_000));
+        assert_eq!(parse_memory("1gb"), Some(1 << 30));
+        assert_eq!(parse_memory("1tb"), None);
+        assert_eq!(parse_memory("mb"), None);
+        assert_eq!(
+            EvictionPolicy::parse("ALLKEYS-LRU"),
+            Some(EvictionPolicy::AllKeysLru)
+        );
+        assert_eq!(EvictionPolicy::parse("lru"), None);
+    }
+
+    #[test]
+    fn should_refuse_writes_under_noeviction() {
+        let cache = Cache::new();
+        cache.set("a".to_string(), "x".repeat(1000)).unwrap();
+        limit(&cache, EvictionPolicy::NoEviction, 100);
+        assert_eq!(cache.free_memory_if_needed(), Err(OOM_ERR));
+        limit(&cache, EvictionPolicy::VolatileLru, 100);
+        assert_eq!(cache.free_memory_if_needed(), Err(OOM_ERR));
+        assert!(cache.exists("a").unwrap());
+
+        limit(&cache, EvictionPolicy::AllKeysRandom, 100);
+        assert_eq!(cache.free_memory_if_needed(), Ok(()));
+        assert!(!cache.exists("a").unwrap());
+        assert_eq!(cache.evicted_keys(), 1);
+    }
+
+    #[test]
+    fn should_evict_cold_keys_first_under_lru() {
+        let cache = Cache::with_databases(2);
+        let other = cache.select(1).unwrap();
+        for i in 0..50 {
+            cache.set(format!("cold:{}", i), "v".repeat(100)).unwrap();
+        }
+        thread::sleep(Duration::from_millis(50));
+        for i in 0..50 {
+            other.set(format!("hot:{}", i), "v".repeat(100)).unwrap();
+        }
+        let used = cache.used_memory().unwrap();
+        limit(&cache, EvictionPolicy::AllKeysLru, used / 2);
+        cache.free_memory_if_needed().unwrap();
+
+        assert!(cache.used_memory().unwrap() <= used / 2);
+        let cold_left = cache.dbsize().unwrap();
+        let hot_left = other.dbsize().unwrap();
+        assert!(hot_left > cold_left, "{} hot, {} cold", hot_left, cold_left);
+        assert_eq!(cache.evicted_keys() as usize, 100 - cold_left - hot_left);
+    }
+
+    #[test]
+    fn should_evict_only_volatile_keys_soonest_first_under_volatile_ttl() {
+        let cache = Cache::new();
+        let now = unix_time_ms() as i64;
+        for i in 0..20 {
+            cache
+                .set(format!("persistent:{}", i), "v".repeat(100))
+                .unwrap();
+            let key = format!("volatile:{}", i);
+            cache.set(key.clone(), "v".repeat(100)).unwrap();
+            cache.expire(&key, now + 100_000 + i * 1000, &[]).unwrap();
+        }
+        let used = cache.used_memory().unwrap();
+        limit(&cache, EvictionPolicy::VolatileTtl, used - 1);
+        cache.free_memory_if_needed().unwrap();
+        assert_eq!(cache.dbsize().unwrap(), 39);
+        assert!((0..20).all(|i| cache.exists(&format!("persistent:{}", i)).unwrap()));
+        assert!(cache.exists("volatile:19").unwrap());
+    }
+}
@@ -0,0 +1,85 @@
+use super::{db::Cache, hash::ExpireCondition};
+
+/// Replies of TTL, PTTL, EXPIRETIME and PEXPIRETIME for keys without a deadline to report.
+pub const KEY_MISSING: i64 = -2;
+pub const KEY_WITHOUT_TTL: i64 = -1;
+
+impl Cache {
+    /// Sets the expiry of `key` to `at` Unix milliseconds when every condition allows it. Times
+    /// in the past delete the key. Returns whether the TTL was changed.
+    pub fn expire(
+        &self,
+        key: &str,
+        at: i64,
+        conditions: &[ExpireCondition],
+    ) -> Result<bool, &'static str> {
+        self.write_keyspace(|data| {
+            if !data.contains_key(key) {
+                return Ok(false);
+            }
+            let current = data.expire_at(key);
+            let at = at.max(0) as u64;
+            if !conditions
+                .iter()
+                .all(|condition| condition.allows(current, at))
+            {
+                return Ok(false);
+            }
+            data.set_expire_at(key, at);
+            Ok(true)
+        })
+    }
+
+    /// Absolute expiry of `key` in Unix milliseconds, or KEY_MISSING / KEY_WITHOUT_TTL.
+    pub fn expire_time(&self, key: &str) -> Result<i64, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(match data.expire_at(key) {
+            Some(at) => at as i64,
+            None if data.contains_key(key) => KEY_WITHOUT_TTL,
+            None => KEY_MISSING,
+        })
+    }
+
+    /// Removes the TTL of `key`, returning whether it had one.
+    pub fn persist(&self, key: &str) -> Result<bool, &'static str> {
+        self.write_keyspace(|data| Ok(data.persist(key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::unix_time_ms;
+
+    use super::*;
+
+    #[test]
+    fn should_apply_conditions_like_redis() {
+        let cache = Cache::new();
+        let later = unix_time_ms() as i64 + 100_000;
+        cache.set("k".to_string(), "v".to_string()).unwrap();
+        assert!(!cache.expire("k", later, &[ExpireCondition::Xx]).unwrap());
+        assert!(!cache.expire("k", later, &[ExpireCondition::Gt]).unwrap());
+        assert!(cache.expire("k", later, &[ExpireCondition::Lt]).unwrap());
+        assert!(!cache
+            .expire("k", later + 1, &[ExpireCondition::Nx])
+            .unwrap());
+        assert!(!cache
+            .expire("k", later - 1, &[ExpireCondition::Gt])
+            .unwrap());
+        assert!(cache
+            .expire("k", later + 1, &[ExpireCondition::Xx, ExpireCondition::Gt])
+            .unwrap());
+        assert_eq!(cache.expire_time("k").unwrap(), later + 1);
+
+        assert!(cache.persist("k").unwrap());
+        assert!(!cache.persist("k").unwrap());
+        assert_eq!(cache.expire_time("k").unwrap(), KEY_WITHOUT_TTL);
+
+        assert!(cache.expire("k", -5, &[]).unwrap());
+        assert_eq!(cache.expire_time("k").unwrap(), KEY_MISSING);
+        assert!(!cache.expire("k", later, &[]).unwrap());
+    }
+}
@@ -0,0 +1,377 @@
+//! Geospatial indexes are sorted sets scored with 52 bit geohashes, exactly like Redis: the
+//! latitude and longitude are each quantized to 26 bits and interleaved, latitude in the even
+//! bits, so members close on the map tend to sit next to each other in score order.
+
+use std::f64::consts::PI;
+
+use super::{
+    db::Cache,
+    skiplist::ScoreRange,
+    zset::{store_zset, zset_ref, RangeQuery, RangeSpec, SortedSet},
+};
+
+pub const GEO_LONG_MIN: f64 = -180.0;
+pub const GEO_LONG_MAX: f64 = 180.0;
+/// Latitudes are limited to what the EPSG:900913 web mercator projection covers.
+pub const GEO_LAT_MIN: f64 = -85.051_128_78;
+pub const GEO_LAT_MAX: f64 = 85.051_128_78;
+const GEO_STEP_MAX: u32 = 26;
+const EARTH_RADIUS_IN_METERS: f64 = 6_372_797.560_856;
+const MERCATOR_MAX: f64 = 20_037_726.37;
+const GEOHASH_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+/// Searches never scan more cells than this, coarsening the grid when needed.
+const MAX_SEARCH_CELLS: i64 = 16;
+
+pub const MEMBER_NOT_FOUND_ERR: &str = "ERR could not decode requested zset member";
+
+pub fn valid_coordinates(longitude: f64, latitude: f64) -> bool {
+    (GEO_LONG_MIN..=GEO_LONG_MAX).contains(&longitude)
+        && (GEO_LAT_MIN..=GEO_LAT_MAX).contains(&latitude)
+}
+
+/// Spreads the low 32 bits of `x` over the even bits of the result.
+fn spread(x: u32) -> u64 {
+    let mut x = u64::from(x);
+    x = (x | x << 16) & 0x0000_FFFF_0000_FFFF;
+    x = (x | x << 8) & 0x00FF_00FF_00FF_00FF;
+    x = (x | x << 4) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | x << 2) & 0x3333_3333_3333_3333;
+    (x | x << 1) & 0x5555_5555_5555_5555
+}
+
+/// Gathers the even bits of `x`, undoing `spread`.
+fn squash(x: u64) -> u32 {
+    let mut x = x & 0x5555_5555_5555_5555;
+    x = (x | x >> 1) & 0x3333_3333_3333_3333;
+    x = (x | x >> 2) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | x >> 4) & 0x00FF_00FF_00FF_00FF;
+    x = (x | x >> 8) & 0x0000_FFFF_0000_FFFF;
+    ((x | x >> 16) & 0x0000_0000_FFFF_FFFF) as u32
+}
+
+/// Cell index of `value` within `min..max` split into `2^step` cells.
+fn cell(value: f64, min: f64, max: f64, step: u32) -> f64 {
+    (value - min) / (max - min) * (1_u64 << step) as f64
+}
+
+fn encode(longitude: f64, latitude: f64, lat_range: (f64, f64), step: u32) -> u64 {
+    let cells = (1_u64 << step) - 1;
+    let lat = (cell(latitude, lat_range.0, lat_range.1, step) as u64).min(cells);
+    let long = (cell(longitude, GEO_LONG_MIN, GEO_LONG_MAX, step) as u64).min(cells);
+    spread(lat as u32) | spread(long as u32) << 1
+}
+
+/// The geohash score of a position.
+pub fn geohash_score(longitude: f64, latitude: f64) -> f64 {
+    encode(
+        longitude,
+        latitude,
+        (GEO_LAT_MIN, GEO_LAT_MAX),
+        GEO_STEP_MAX,
+    ) as f64
+}
+
+/// Decodes a score to the centre of its cell as `(longitude, latitude)`.
+pub fn decode_score(score: f64) -> (f64, f64) {
+    let bits = score as u64;
+    let cells = (1_u64 << GEO_STEP_MAX) as f64;
+    let centre = |index: u32, min: f64, max: f64| {
+        let low = min + f64::from(index) / cells * (max - min);
+        let high = min + (f64::from(index) + 1.0) / cells * (max - min);
+        ((low + high) / 2.0).clamp(min, max)
+    };
+    (
+        centre(squash(bits >> 1), GEO_LONG_MIN, GEO_LONG_MAX),
+        centre(squash(bits), GEO_LAT_MIN, GEO_LAT_MAX),
+    )
+}
+
+/// The standard 11 character base32 geohash of a score, computed over the usual -90..90
+/// latitude range so it can be used with other geohash tools.
+pub fn geohash_string(score: f64) -> String {
+    let (longitude, latitude) = decode_score(score);
+    let bits = encode(longitude, latitude, (-90.0, 90.0), GEO_STEP_MAX);
+    (0..11)
+        .map(|i| {
+            // 52 bits fill ten characters and a bit; the last one is always '0'.
+            let index = if i == 10 {
+                0
+            } else {
+                (bits >> (52 - (i + 1) * 5)) & 0x1f
+            };
+            char::from(GEOHASH_ALPHABET[index as usize])
+        })
+        .collect()
+}
+
+fn to_radians(degrees: f64) -> f64 {
+    degrees * PI / 180.0
+}
+
+fn to_degrees(radians: f64) -> f64 {
+    radians * 180.0 / PI
+}
+
+fn latitude_distance(lat1: f64, lat2: f64) -> f64 {
+    EARTH_RADIUS_IN_METERS * (to_radians(lat2) - to_radians(lat1)).abs()
+}
+
+/// Haversine distance in meters.
+pub fn distance(long1: f64, lat1: f64, long2: f64, lat2: f64) -> f64 {
+    let v = ((to_radians(long2) - to_radians(long1)) / 2.0).sin();
+    if v == 0.0 {
+        return latitude_distance(lat1, lat2);
+    }
+    let u = ((to_radians(lat2) - to_radians(lat1)) / 2.0).sin();
+    let a = u * u + to_radians(lat1).cos() * to_radians(lat2).cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeoCenter {
+    Member(String),
+    Coordinates(f64, f64),
+}
+
+/// Search area with sizes in meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoShape {
+    Radius(f64),
+    Box { width: f64, height: f64 },
+}
+
+impl GeoShape {
+    /// Distance from `(long, lat)` to the point when it lies within the shape centred there.
+    fn distance_within(&self, long: f64, lat: f64, point: (f64, f64)) -> Option<f64> {
+        let (x, y) = point;
+        match *self {
+            GeoShape::Radius(radius) => Some(distance(long, lat, x, y)).filter(|&d| d <= radius),
+            GeoShape::Box { width, height } => {
+                if latitude_distance(y, lat) > height / 2.0 || distance(x, y, long, y) > width / 2.0
+                {
+                    return None;
+                }
+                Some(distance(long, lat, x, y))
+            }
+        }
+    }
+
+    /// Half extents of the shape in meters, north-south and east-west.
+    fn half_extents(&self) -> (f64, f64) {
+        match *self {
+            GeoShape::Radius(radius) => (radius, radius),
+            GeoShape::Box { width, height } => (height / 2.0, width / 2.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GeoOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoSearch {
+    pub center: GeoCenter,
+    pub shape: GeoShape,
+    pub order: Option<GeoOrder>,
+    pub count: Option<usize>,
+    /// Stop as soon as `count` matches are found instead of looking for the closest ones.
+    pub any: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoMatch {
+    pub member: String,
+    /// Distance from the centre in meters.
+    pub distance: f64,
+    pub score: f64,
+    pub longitude: f64,
+    pub latitude: f64,
+}
+
+/// Geohash precision for a search of the given radius: cells about as big as the area, a bit
+/// bigger towards the poles where they shrink east-west.
+fn estimate_step(radius: f64, latitude: f64) -> u32 {
+    if radius == 0.0 {
+        return GEO_STEP_MAX;
+    }
+    let mut range = radius;
+    let mut step: i32 = 1;
+    while range < MERCATOR_MAX {
+        range *= 2.0;
+        step += 1;
+    }
+    step -= 2;
+    if latitude.abs() > 66.0 {
+        step -= 1;
+        if latitude.abs() > 80.0 {
+            step -= 1;
+        }
+    }
+    step.clamp(1, GEO_STEP_MAX as i32) as u32
+}
+
+/// Score ranges of the geohash cells covering the search area.
+fn covering_ranges(long: f64, lat: f64, shape: &GeoShape) -> Vec<ScoreRange> {
+    let (north_south, east_west) = shape.half_extents();
+    let lat_delta = to_degrees(north_south / EARTH_RADIUS_IN_METERS);
+    let widest = (lat.abs() + lat_delta).min(90.0);
+    let long_delta = if widest >= 90.0 {
+        180.0
+    } else {
+        to_degrees(east_west / EARTH_RADIUS_IN_METERS / to_radians(widest).cos()).min(180.0)
+    };
+    let radius = north_south.hypot(east_west);
+
+    let mut step = estimate_step(radius, lat);
+    loop {
+        let cells = 1_i64 << step;
+        let index = |value: f64, min: f64, max: f64| cell(value, min, max, step).floor() as i64;
+        let lat_cells = index(lat - lat_delta, GEO_LAT_MIN, GEO_LAT_MAX).max(0)
+            ..=index(lat + lat_delta, GEO_LAT_MIN, GEO_LAT_MAX).min(cells - 1);
+        let mut long_cells = index(long - long_delta, GEO_LONG_MIN, GEO_LONG_MAX)
+            ..=index(long + long_delta, GEO_LONG_MIN, GEO_LONG_MAX);
+        if long_cells.end() - long_cells.start() + 1 >= cells {
+            long_cells = 0..=cells - 1;
+        }
+        let count =
+            (lat_cells.end() - lat_cells.start() + 1) * (long_cells.end() - long_cells.start() + 1);
+        if count > MAX_SEARCH_CELLS && step > 1 {
+            step -= 1;
+            continue;
+        }
+
+        let shift = 2 * (GEO_STEP_MAX - step);
+        let mut ranges = Vec::new();
+        for lat_cell in lat_cells {
+            for long_cell in long_cells.clone() {
+                // Cells past the antimeridian wrap around to the other side.
+                let long_cell = long_cell.rem_euclid(cells);
+                let bits = spread(lat_cell as u32) | spread(long_cell as u32) << 1;
+                ranges.push(ScoreRange {
+                    min: (bits << shift) as f64,
+                    max: ((bits + 1) << shift) as f64,
+                    min_exclusive: false,
+                    max_exclusive: true,
+                });
+            }
+        }
+        return ranges;
+    }
+}
+
+fn search_zset(zset: &SortedSet, search: &GeoSearch) -> Result<Vec<GeoMatch>, &'static str> {
+    let (long, lat) = match &search.center {
+        GeoCenter::Coordinates(long, lat) => (*long, *lat),
+        GeoCenter::Member(member) => decode_score(zset.score(member).ok_or(MEMBER_NOT_FOUND_ERR)?),
+    };
+    let limit = search.count.filter(|_| search.any);
+    let mut matches = Vec::new();
+    'cells: for range in covering_ranges(long, lat, &search.shape) {
+        let query = RangeQuery {
+            spec: RangeSpec::Score(range),
+            reverse: false,
+            offset: 0,
+            limit: None,
+        };
+        for (member, score) in zset.range(&query) {
+            let (longitude, latitude) = decode_score(score);
+            let Some(distance) = search
+                .shape
+                .distance_within(long, lat, (longitude, latitude))
+            else {
+                continue;
+            };
+            matches.push(GeoMatch {
+                member,
+                distance,
+                score,
+                longitude,
+                latitude,
+            });
+            if limit.is_some_and(|limit| matches.len() >= limit) {
+                break 'cells;
+            }
+        }
+    }
+
+    // COUNT without ANY asks for the closest members.
+    let order = search
+        .order
+        .or(search.count.filter(|_| !search.any).map(|_| GeoOrder::Asc));
+    match order {
+        Some(GeoOrder::Asc) => matches.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+        Some(GeoOrder::Desc) => matches.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+        None => {}
+    }
+    if let Some(count) = search.count {
+        matches.truncate(count);
+    }
+    Ok(matches)
+}
+
+impl Cache {
+    pub fn geosearch(&self, key: &str, search: &GeoSearch) -> Result<Vec<GeoMatch>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        match zset_ref(&data, key)? {
+            Some(zset) => search_zset(zset, search),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Stores the matches at `destination` scored by geohash, or by their distance converted
+    /// with `distance_unit` meters per unit when given. Returns how many were stored.
+    pub fn geosearchstore(
+        &self,
+        destination: &str,
+        key: &str,
+        search: &GeoSearch,
+        distance_unit: Option<f64>,
+    ) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let matches = match zset_ref(&data, key)? {
+            Some(zset) => search_zset(zset, search)?,
+            None => Vec::new(),
+        };
+        let mut result = SortedSet::default();
+        for found in matches {
+            let score = distance_unit.map_or(found.score, |unit| found.distance / unit);
+            result.insert(&found.member, score);
+        }
+        let len = store_zset(&mut data, destination, result);
+        self.blocking.serve(&mut data, destination);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_encode_and_decode_like_redis() {
+        let score = geohash_score(13.361389, 38.115556);
+        assert_eq!(score, 3_479_099_956_230_698.0);
+        let (longitude, latitude) = decode_score(score);
+        assert!((longitude - 13.361_389_338_970_184).abs() < 1e-12);
+        assert!((latitude - 38.115_556_395_496_3).abs() < 1e-12);
+        assert_eq!(geohash_string(score), "sqc8b49rny0");
+    }
+
+    #[test]
+    fn should_cover_the_antimeridian() {
+        let ranges = covering_ranges(179.99, 0.0, &GeoShape::Radius(5000.0));
+        let east = geohash_score(-179.99, 0.0);
+        assert!(ranges
+            .iter()
+            .any(|range| range.above_min(east) && range.below_max(east)));
+    }
+}
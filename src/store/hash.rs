@@ -0,0 +1,698 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem::size_of,
+};
+
+use super::{
+    db::{unix_time_ms, Cache, Value, WRONGTYPE_ERR},
+    keyspace::Keyspace,
+    listpack::Listpack,
+    memory::{estimate, string_usage, ELEMENT_OVERHEAD},
+    random::{check_sample_count, sample_distinct, sample_with_repetition},
+    scan::{scan_page, ScanPage},
+    string::parse_strict_integer,
+};
+
+pub const HASH_NOT_INTEGER_ERR: &str = "ERR hash value is not an integer";
+pub const HASH_NOT_FLOAT_ERR: &str = "ERR hash value is not a float";
+pub const OVERFLOW_ERR: &str = "ERR increment or decrement would overflow";
+pub const NAN_OR_INFINITY_ERR: &str = "ERR increment would produce NaN or Infinity";
+
+/// Per field results of HEXPIRE, HTTL and HPERSIST, encoded the way Redis replies.
+pub const FIELD_MISSING: i64 = -2;
+pub const FIELD_WITHOUT_TTL: i64 = -1;
+pub const CONDITION_NOT_MET: i64 = 0;
+pub const TTL_UPDATED: i64 = 1;
+pub const FIELD_DELETED: i64 = 2;
+
+/// Hashes stay in a listpack up to this many fields...
+pub const HASH_MAX_LISTPACK_ENTRIES: usize = 128;
+/// ...as long as no field or value is longer than this many bytes.
+pub const HASH_MAX_LISTPACK_VALUE: usize = 64;
+
+/// Field values plus the absolute expiry (Unix milliseconds) of the fields that have one.
+/// Expired fields are invisible to readers and purged by writers and the background sweeper.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Hash {
+    fields: Fields,
+    expirations: HashMap<String, u64>,
+}
+
+/// Small hashes keep each field next to its value in a listpack. Too many fields, or a field or
+/// value that is too long, moves them to a hash table for good.
+#[derive(Debug, Clone)]
+enum Fields {
+    Listpack(Listpack),
+    HashTable(HashMap<String, String>),
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Fields::Listpack(Listpack::default())
+    }
+}
+
+impl PartialEq for Fields {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(field, value)| other.get(field) == Some(value))
+    }
+}
+
+/// Whether a listpack of `len` pairs could take one more pair of `field` and `value`.
+fn fits_listpack(len: usize, field: &str, value: &str) -> bool {
+    len < HASH_MAX_LISTPACK_ENTRIES
+        && field.len() <= HASH_MAX_LISTPACK_VALUE
+        && value.len() <= HASH_MAX_LISTPACK_VALUE
+}
+
+impl Fields {
+    fn len(&self) -> usize {
+        match self {
+            Fields::Listpack(pack) => pack.len() / 2,
+            Fields::HashTable(fields) => fields.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index of the pair holding `field` in a listpack.
+    fn position(pack: &Listpack, field: &str) -> Option<usize> {
+        pack.pairs().position(|(known, _)| known == field)
+    }
+
+    fn get(&self, field: &str) -> Option<&str> {
+        match self {
+            Fields::Listpack(pack) => pack
+                .pairs()
+                .find(|&(known, _)| known == field)
+                .map(|(_, value)| value),
+            Fields::HashTable(fields) => fields.get(field).map(String::as_str),
+        }
+    }
+
+    fn contains_key(&self, field: &str) -> bool {
+        self.get(field).is_some()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        let (pairs, fields) = match self {
+            Fields::Listpack(pack) => (Some(pack.pairs()), None),
+            Fields::HashTable(fields) => (None, Some(fields.iter())),
+        };
+        pairs.into_iter().flatten().chain(
+            fields
+                .into_iter()
+                .flatten()
+                .map(|(field, value)| (field.as_str(), value.as_str())),
+        )
+    }
+
+    fn convert_to_hash_table(&mut self) {
+        if let Fields::Listpack(pack) = self {
+            let fields = pack
+                .pairs()
+                .map(|(field, value)| (field.to_string(), value.to_string()))
+                .collect();
+            *self = Fields::HashTable(fields);
+        }
+    }
+
+    /// Sets `field` to `value` and returns whether the field is new.
+    fn insert(&mut self, field: String, value: String) -> bool {
+        if let Fields::Listpack(pack) = self {
+            match Self::position(pack, &field) {
+                Some(index) if value.len() <= HASH_MAX_LISTPACK_VALUE => {
+                    pack.replace(2 * index + 1, &value);
+                    return false;
+                }
+                None if fits_listpack(pack.len() / 2, &field, &value) => {
+                    pack.push_back(&field);
+                    pack.push_back(&value);
+                    return true;
+                }
+                _ => self.convert_to_hash_table(),
+            }
+        }
+        match self {
+            Fields::HashTable(fields) => fields.insert(field, value).is_none(),
+            Fields::Listpack(_) => unreachable!("converted above"),
+        }
+    }
+
+    fn remove(&mut self, field: &str) -> bool {
+        match self {
+            Fields::Listpack(pack) => match Self::position(pack, field) {
+                Some(index) => pack.remove_range(2 * index, 2).len() == 2,
+                None => false,
+            },
+            Fields::HashTable(fields) => fields.remove(field).is_some(),
+        }
+    }
+
+    fn memory_usage(&self, samples: usize) -> usize {
+        match self {
+            Fields::Listpack(pack) => pack.allocated(),
+            Fields::HashTable(fields) => estimate(
+                fields.iter().map(|(field, value)| {
+                    string_usage(field) + string_usage(value) + ELEMENT_OVERHEAD
+                }),
+                fields.len(),
+                samples,
+            ),
+        }
+    }
+}
+
+/// NX/XX/GT/LT flags of the HEXPIRE family.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExpireCondition {
+    Always,
+    Nx,
+    Xx,
+    Gt,
+    Lt,
+}
+
+impl ExpireCondition {
+    /// Whether a new expiry `at` may replace `current`; no TTL counts as an infinite one.
+    pub(super) fn allows(self, current: Option<u64>, at: u64) -> bool {
+        match self {
+            ExpireCondition::Always => true,
+            ExpireCondition::Nx => current.is_none(),
+            ExpireCondition::Xx => current.is_some(),
+            ExpireCondition::Gt => current.is_some_and(|current| at > current),
+            ExpireCondition::Lt => current.is_none_or(|current| at < current),
+        }
+    }
+}
+
+impl Hash {
+    fn is_live(&self, field: &str, now: u64) -> bool {
+        self.fields.contains_key(field) && self.expirations.get(field).is_none_or(|&at| at > now)
+    }
+
+    fn get(&self, field: &str, now: u64) -> Option<&str> {
+        self.fields.get(field).filter(|_| self.is_live(field, now))
+    }
+
+    fn iter(&self, now: u64) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .filter(move |(field, _)| self.is_live(field, now))
+    }
+
+    fn len(&self, now: u64) -> usize {
+        let expired = self.expirations.values().filter(|&&at| at <= now).count();
+        self.fields.len() - expired
+    }
+
+    /// Setting a value drops any TTL the field had, like HSET does in Redis.
+    fn insert(&mut self, field: String, value: String) -> bool {
+        self.expirations.remove(&field);
+        self.fields.insert(field, value)
+    }
+
+    fn remove(&mut self, field: &str) -> bool {
+        self.expirations.remove(field);
+        self.fields.remove(field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub(super) fn memory_usage(&self, samples: usize) -> usize {
+        let expirations = self
+            .expirations
+            .keys()
+            .map(|field| string_usage(field) + size_of::<u64>() + ELEMENT_OVERHEAD);
+        self.fields.memory_usage(samples) + estimate(expirations, self.expirations.len(), samples)
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self.fields {
+            Fields::Listpack(_) => "listpack",
+            Fields::HashTable(_) => "hashtable",
+        }
+    }
+
+    pub fn has_volatile_fields(&self) -> bool {
+        !self.expirations.is_empty()
+    }
+
+    /// Drops every field whose TTL has passed and returns how many were dropped.
+    pub fn purge_expired(&mut self, now: u64) -> usize {
+        let expired: Vec<String> = self
+            .expirations
+            .iter()
+            .filter(|(_, &at)| at <= now)
+            .map(|(field, _)| field.clone())
+            .collect();
+        for field in &expired {
+            self.remove(field);
+        }
+        expired.len()
+    }
+
+    /// One HSCAN page of live fields. Like Redis, a listpack hash comes back whole with a zero
+    /// cursor.
+    fn page(&self, cursor: u64, count: usize, now: u64) -> ScanPage<(String, String)> {
+        let owned = |(field, value): (&str, &str)| (field.to_string(), value.to_string());
+        if let Fields::Listpack(_) = self.fields {
+            return (0, self.iter(now).map(owned).collect());
+        }
+        let (cursor, page) = scan_page(
+            self.iter(now).map(|pair| (pair.0, pair)),
+            self.len(now),
+            cursor,
+            count,
+        );
+        (cursor, page.into_iter().map(owned).collect())
+    }
+
+    fn expire_field(&mut self, field: &str, at: u64, condition: ExpireCondition, now: u64) -> i64 {
+        if !self.is_live(field, now) {
+            return FIELD_MISSING;
+        }
+        if !condition.allows(self.expirations.get(field).copied(), at) {
+            return CONDITION_NOT_MET;
+        }
+        if at <= now {
+            self.remove(field);
+            return FIELD_DELETED;
+        }
+        self.expirations.insert(field.to_string(), at);
+        TTL_UPDATED
+    }
+}
+
+impl Cache {
+    fn read_hash<T>(
+        &self,
+        key: &str,
+        read: impl FnOnce(Option<&Hash>, u64) -> T,
+    ) -> Result<T, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let now = unix_time_ms();
+        match data.get(key) {
+            Some(Value::Hash(hash)) if hash.len(now) > 0 => Ok(read(Some(hash), now)),
+            Some(Value::Hash(_)) | None => Ok(read(None, now)),
+            Some(_) => Err(WRONGTYPE_ERR),
+        }
+    }
+
+    /// Runs `write` against the hash at `key`, creating an empty one first and dropping the key
+    /// once the hash ends up empty. Expired fields are purged before `write` sees the hash.
+    fn write_hash<T>(
+        &self,
+        key: &str,
+        write: impl FnOnce(&mut Hash, u64) -> Result<T, &'static str>,
+    ) -> Result<T, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let now = unix_time_ms();
+        let hash = match data.get_or_insert_with(key.to_string(), || Value::Hash(Box::default())) {
+            Value::Hash(hash) => hash,
+            _ => return Err(WRONGTYPE_ERR),
+        };
+        hash.purge_expired(now);
+        let result = write(hash, now);
+        let (empty, volatile) = (hash.is_empty(), hash.has_volatile_fields());
+        if empty {
+            data.remove(key);
+        } else if volatile {
+            if let Ok(mut volatile_hashes) = self.volatile_hashes.write() {
+                volatile_hashes.insert(key.to_string());
+            }
+        }
+        result
+    }
+
+    /// Sets every field/value pair and returns how many fields were new.
+    pub fn hset(&self, key: &str, pairs: &[(String, String)]) -> Result<usize, &'static str> {
+        self.write_hash(key, |hash, _| {
+            Ok(pairs
+                .iter()
+                .filter(|(field, value)| hash.insert(field.clone(), value.clone()))
+                .count())
+        })
+    }
+
+    pub fn hsetnx(&self, key: &str, field: &str, value: &str) -> Result<bool, &'static str> {
+        self.write_hash(key, |hash, now| {
+            if hash.is_live(field, now) {
+                return Ok(false);
+            }
+            hash.insert(field.to_string(), value.to_string());
+            Ok(true)
+        })
+    }
+
+    pub fn hget(&self, key: &str, field: &str) -> Result<Option<String>, &'static str> {
+        self.read_hash(key, |hash, now| {
+            hash.and_then(|hash| hash.get(field, now).map(str::to_string))
+        })
+    }
+
+    pub fn hmget(&self, key: &str, fields: &[String]) -> Result<Vec<Option<String>>, &'static str> {
+        self.read_hash(key, |hash, now| {
+            fields
+                .iter()
+                .map(|field| hash.and_then(|hash| hash.get(field, now).map(str::to_string)))
+                .collect()
+        })
+    }
+
+    pub fn hdel(&self, key: &str, fields: &[String]) -> Result<usize, &'static str> {
+        self.write_hash(key, |hash, _| {
+            Ok(fields.iter().filter(|field| hash.remove(field)).count())
+        })
+    }
+
+    pub fn hexists(&self, key: &str, field: &str) -> Result<bool, &'static str> {
+        self.read_hash(key, |hash, now| {
+            hash.is_some_and(|hash| hash.is_live(field, now))
+        })
+    }
+
+    pub fn hlen(&self, key: &str) -> Result<usize, &'static str> {
+        self.read_hash(key, |hash, now| hash.map_or(0, |hash| hash.len(now)))
+    }
+
+    pub fn hstrlen(&self, key: &str, field: &str) -> Result<usize, &'static str> {
+        self.read_hash(key, |hash, now| {
+            hash.and_then(|hash| hash.get(field, now))
+                .map_or(0, |value| value.chars().count())
+        })
+    }
+
+    pub fn hgetall(&self, key: &str) -> Result<Vec<(String, String)>, &'static str> {
+        self.read_hash(key, |hash, now| {
+            hash.map(|hash| {
+                hash.iter(now)
+                    .map(|(field, value)| (field.to_string(), value.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default()
+        })
+    }
+
+    /// One HSCAN page of live fields and their values.
+    pub fn hscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        count: usize,
+    ) -> Result<ScanPage<(String, String)>, &'static str> {
+        self.read_hash(key, |hash, now| {
+            hash.map(|hash| hash.page(cursor, count, now))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Increments keep the field's TTL, only HSET-style overwrites drop it.
+    pub fn hincrby(&self, key: &str, field: &str, delta: i64) -> Result<i64, &'static str> {
+        self.write_hash(key, |hash, now| {
+            let current = match hash.get(field, now) {
+                Some(value) => parse_strict_integer(value).ok_or(HASH_NOT_INTEGER_ERR)?,
+                None => 0,
+            };
+            let updated = current.checked_add(delta).ok_or(OVERFLOW_ERR)?;
+            hash.fields.insert(field.to_string(), updated.to_string());
+            Ok(updated)
+        })
+    }
+
+    pub fn hincrbyfloat(&self, key: &str, field: &str, delta: f64) -> Result<f64, &'static str> {
+        self.write_hash(key, |hash, now| {
+            let current = match hash.get(field, now) {
+                Some(value) => value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or(HASH_NOT_FLOAT_ERR)?,
+                None => 0.0,
+            };
+            let updated = current + delta;
+            if !updated.is_finite() {
+                return Err(NAN_OR_INFINITY_ERR);
+            }
+            hash.fields.insert(field.to_string(), format_float(updated));
+            Ok(updated)
+        })
+    }
+
+    /// HRANDFIELD semantics: a positive count returns distinct fields, a negative one allows
+    /// the same field to be returned several times.
+    pub fn hrandfield(&self, key: &str, count: i64) -> Result<Vec<(String, String)>, &'static str> {
+        check_sample_count(count)?;
+        self.read_hash(key, |hash, now| {
+            let Some(hash) = hash else {
+                return Vec::new();
+            };
+            let pairs: Vec<(&str, &str)> = hash.iter(now).collect();
+            let picked = if count >= 0 {
+                sample_distinct(&pairs, count as usize)
+            } else {
+                sample_with_repetition(&pairs, count.unsigned_abs() as usize)
+            };
+            picked
+                .into_iter()
+                .map(|(field, value)| (field.to_string(), value.to_string()))
+                .collect()
+        })
+    }
+
+    /// Sets the absolute expiry (Unix milliseconds) of each field, returning a status per field.
+    pub fn hexpire(
+        &self,
+        key: &str,
+        fields: &[String],
+        at: u64,
+        condition: ExpireCondition,
+    ) -> Result<Vec<i64>, &'static str> {
+        self.write_hash(key, |hash, now| {
+            Ok(fields
+                .iter()
+                .map(|field| hash.expire_field(field, at, condition, now))
+                .collect())
+        })
+    }
+
+    /// Absolute expiry of each field in Unix milliseconds, or -1/-2 for no TTL/no field.
+    pub fn hexpiretime(&self, key: &str, fields: &[String]) -> Result<Vec<i64>, &'static str> {
+        self.read_hash(key, |hash, now| {
+            fields
+                .iter()
+                .map(|field| match hash {
+                    Some(hash) if hash.is_live(field, now) => hash
+                        .expirations
+                        .get(field)
+                        .map_or(FIELD_WITHOUT_TTL, |&at| at as i64),
+                    _ => FIELD_MISSING,
+                })
+                .collect()
+        })
+    }
+
+    pub fn hpersist(&self, key: &str, fields: &[String]) -> Result<Vec<i64>, &'static str> {
+        self.write_hash(key, |hash, now| {
+            Ok(fields
+                .iter()
+                .map(|field| {
+                    if !hash.is_live(field, now) {
+                        FIELD_MISSING
+                    } else if hash.expirations.remove(field.as_str()).is_some() {
+                        TTL_UPDATED
+                    } else {
+                        FIELD_WITHOUT_TTL
+                    }
+                })
+                .collect())
+        })
+    }
+}
+
+/// Background half of field expiry: purges expired fields of every hash known to have field
+/// TTLs and deletes the key once its last field is gone.
+pub fn expire_hash_fields(data: &mut Keyspace, volatile_hashes: &mut HashSet<String>, now: u64) {
+    volatile_hashes.retain(|key| match data.peek_mut(key) {
+        Some(Value::Hash(hash)) => {
+            hash.purge_expired(now);
+            if hash.is_empty() {
+                data.remove(key);
+                return false;
+            }
+            hash.has_volatile_fields()
+        }
+        _ => false,
+    });
+}
+
+/// Formats an INCRBYFLOAT result the way Redis prints its long doubles: fixed notation, at most
+/// 17 decimals and no trailing zeros. Redis adds with extra precision, so the last-ulp error of
+/// the f64 addition is dropped by preferring 15 or 16 significant digits when they are that close,
+/// which turns `0.1 + 0.2` into `0.3` just like Redis.
+pub fn format_float(value: f64) -> String {
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    let ulp = f64::from_bits(value.abs().to_bits() + 1) - value.abs();
+    let value = (15..17)
+        .filter_map(|digits| format!("{:.*e}", digits - 1, value).parse::<f64>().ok())
+        .find(|rounded| (rounded - value).abs() <= ulp)
+        .unwrap_or(value);
+    let text = value.to_string();
+    match text.split_once('.') {
+        Some((_, decimals)) if decimals.len() > 17 => {
+            let text = format!("{:.17}", value);
+            let text = text.trim_end_matches('0').trim_end_matches('.');
+            if text == "-0" { "0" } else { text }.to_string()
+        }
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_drop_key_once_last_field_is_deleted() {
+        let cache = Cache::new();
+        cache
+            .hset("user", &[("name".to_string(), "oz".to_string())])
+            .unwrap();
+        assert_eq!(cache.hdel("user", &["name".to_string()]).unwrap(), 1);
+        assert!(!cache.exists("user").unwrap());
+    }
+
+    #[test]
+    fn should_reject_overflowing_and_non_numeric_increments() {
+        let cache = Cache::new();
+        cache
+            .hset("h", &[("n".to_string(), i64::MAX.to_string())])
+            .unwrap();
+        assert_eq!(cache.hincrby("h", "n", 1), Err(OVERFLOW_ERR));
+        cache
+            .hset("h", &[("s".to_string(), "abc".to_string())])
+            .unwrap();
+        assert_eq!(cache.hincrby("h", "s", 1), Err(HASH_NOT_INTEGER_ERR));
+        assert_eq!(cache.hincrbyfloat("h", "s", 1.0), Err(HASH_NOT_FLOAT_ERR));
+        assert_eq!(cache.hincrbyfloat("h", "f", 10.5).unwrap(), 10.5);
+        assert_eq!(cache.hget("h", "f").unwrap().unwrap(), "10.5");
+    }
+
+    #[test]
+    fn should_convert_listpack_to_hash_table_past_thresholds() {
+        let mut hash = Hash::default();
+        for index in 0..HASH_MAX_LISTPACK_ENTRIES {
+            hash.insert(format!("f{}", index), index.to_string());
+        }
+        assert!(!hash.insert("f0".to_string(), "updated".to_string()));
+        assert!(hash.remove("f1"));
+        assert_eq!(hash.get("f0", 0), Some("updated"));
+        assert_eq!(hash.len(0), HASH_MAX_LISTPACK_ENTRIES - 1);
+        assert_eq!(hash.encoding(), "listpack");
+        hash.insert("f1".to_string(), "1".to_string());
+        assert_eq!(hash.encoding(), "listpack");
+        hash.insert("one-more".to_string(), "x".to_string());
+        assert_eq!(hash.encoding(), "hashtable");
+
+        let mut hash = Hash::default();
+        hash.insert("f".to_string(), "short".to_string());
+        hash.insert("f".to_string(), "v".repeat(HASH_MAX_LISTPACK_VALUE + 1));
+        assert_eq!(hash.encoding(), "hashtable");
+        assert_eq!(hash.len(0), 1);
+    }
+
+    #[test]
+    fn should_format_floats_like_long_doubles() {
+        assert_eq!(format_float(0.1 + 0.2), "0.3");
+        assert_eq!(format_float(10.6 - 5.0), "5.6");
+        assert_eq!(format_float(5.0e3 + 2.0e2), "5200");
+        assert_eq!(format_float(1e20), "100000000000000000000");
+        assert_eq!(format_float(1e-20), "0");
+        assert_eq!(format_float(-1.5), "-1.5");
+    }
+
+    #[test]
+    fn should_not_create_a_key_when_writing_to_wrong_type() {
+        let cache = Cache::new();
+        cache.set("name".to_string(), "oz".to_string()).unwrap();
+        assert_eq!(
+            cache.hset("name", &[("a".to_string(), "b".to_string())]),
+            Err(WRONGTYPE_ERR)
+        );
+    }
+
+    #[test]
+    fn should_apply_expire_conditions() {
+        let mut hash = Hash::default();
+        hash.insert("f".to_string(), "v".to_string());
+        let now = 1_000;
+        assert_eq!(
+            hash.expire_field("f", 5_000, ExpireCondition::Xx, now),
+            CONDITION_NOT_MET
+        );
+        assert_eq!(
+            hash.expire_field("f", 5_000, ExpireCondition::Gt, now),
+            CONDITION_NOT_MET
+        );
+        assert_eq!(
+            hash.expire_field("f", 5_000, ExpireCondition::Nx, now),
+            TTL_UPDATED
+        );
+        assert_eq!(
+            hash.expire_field("f", 9_000, ExpireCondition::Lt, now),
+            CONDITION_NOT_MET
+        );
+        assert_eq!(
+            hash.expire_field("f", 9_000, ExpireCondition::Gt, now),
+            TTL_UPDATED
+        );
+        assert_eq!(
+            hash.expire_field("g", 9_000, ExpireCondition::Always, now),
+            FIELD_MISSING
+        );
+        assert_eq!(
+            hash.expire_field("f", 500, ExpireCondition::Always, now),
+            FIELD_DELETED
+        );
+        assert!(hash.is_empty());
+    }
+
+    #[test]
+    fn should_sweep_expired_fields_and_empty_hashes() {
+        let mut hash = Hash::default();
+        hash.insert("token".to_string(), "a".to_string());
+        hash.insert("name".to_string(), "b".to_string());
+        hash.expirations.insert("token".to_string(), 10);
+        let mut only_volatile = Hash::default();
+        only_volatile.insert("token".to_string(), "a".to_string());
+        only_volatile.expirations.insert("token".to_string(), 10);
+
+        let mut data = Keyspace::default();
+        data.insert("user:1".to_string(), Value::Hash(Box::new(hash)));
+        data.insert("user:2".to_string(), Value::Hash(Box::new(only_volatile)));
+        let mut volatile = HashSet::from(["user:1".to_string(), "user:2".to_string()]);
+        expire_hash_fields(&mut data, &mut volatile, 20);
+
+        assert!(volatile.is_empty());
+        assert!(!data.contains_key("user:2"));
+        match data.get("user:1") {
+            Some(Value::Hash(hash)) => assert_eq!(hash.len(20), 1),
+            other => panic!("unexpected value {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! HyperLogLog counters stored as plain strings using the same byte layout as Redis, so a value
+//! read with GET on one server can be restored with SET on the other.
+//!
+//! The 16 byte header is the "HYLL" magic, an encoding byte, three unused bytes and the cached
+//! cardinality as a little-endian u64 whose top bit marks the cache as stale. The dense encoding
+//! packs 16384 six bit registers into 12288 bytes. The sparse encoding run-length encodes the
+//! registers with three opcodes:
+//!
+//! * `ZERO`  `00xxxxxx`: 1 to 64 zero registers.
+//! * `XZERO` `01xxxxxx yyyyyyyy`: 1 to 16384 zero registers.
+//! * `VAL`   `1vvvvvxx`: 1 to 4 registers holding the value 1 to 32.
+
+use super::{
+    bytes::{store_bytes, string_bytes, to_bytes},
+    db::Cache,
+    keyspace::Keyspace,
+};
+
+pub const INVALID_HLL_ERR: &str = "WRONGTYPE Key is not a valid HyperLogLog string value.";
+pub const CORRUPTED_HLL_ERR: &str = "INVALIDOBJ Corrupted HLL object detected";
+
+const HLL_P: u32 = 14;
+const HLL_Q: u32 = 64 - HLL_P;
+const HLL_REGISTERS: usize = 1 << HLL_P;
+const HLL_BITS: usize = 6;
+const HLL_REGISTER_MAX: u8 = (1 << HLL_BITS) - 1;
+const HLL_HDR_SIZE: usize = 16;
+const HLL_DENSE_SIZE: usize = HLL_HDR_SIZE + (HLL_REGISTERS * HLL_BITS).div_ceil(8);
+const HLL_MAGIC: &[u8] = b"HYLL";
+const HLL_DENSE: u8 = 0;
+const HLL_SPARSE: u8 = 1;
+/// Sparse counters are promoted to dense once they grow past this many bytes, header included.
+const HLL_SPARSE_MAX_BYTES: usize = 3000;
+const HLL_SPARSE_VAL_MAX_VALUE: u8 = 32;
+const HLL_SPARSE_VAL_MAX_LEN: usize = 4;
+const HLL_SPARSE_ZERO_MAX_LEN: usize = 64;
+const HLL_SPARSE_XZERO_MAX_LEN: usize = 16384;
+const HLL_ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+const HLL_STALE_CACHE: u8 = 1 << 7;
+const HLL_HASH_SEED: u64 = 0xadc8_3b19;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+    dense: bool,
+    /// The cached cardinality bytes exactly as found in the header.
+    card: [u8; 8],
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS],
+            dense: false,
+            card: [0; 8],
+        }
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, &'static str> {
+        check_header(bytes)?;
+        let payload = &bytes[HLL_HDR_SIZE..];
+        let registers = if bytes[4] == HLL_DENSE {
+            (0..HLL_REGISTERS)
+                .map(|index| dense_register(payload, index))
+                .collect()
+        } else {
+            sparse_registers(payload)?
+        };
+        Ok(Self {
+            registers,
+            dense: bytes[4] == HLL_DENSE,
+            card: bytes[8..HLL_HDR_SIZE]
+                .try_into()
+                .expect("header is 16 bytes"),
+        })
+    }
+
+    /// Adds an element, returning whether any register changed.
+    fn add(&mut self, element: &[u8]) -> bool {
+        let (index, count) = pattern_len(element);
+        if self.registers[index] < count {
+            self.registers[index] = count;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (register, &value) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(value);
+        }
+        self.dense |= other.dense;
+    }
+
+    fn invalidate_cache(&mut self) {
+        self.card[7] |= HLL_STALE_CACHE;
+    }
+
+    /// Estimates the cardinality with the improved estimator from Otmar Ertl's "New cardinality
+    /// estimation algorithms for HyperLogLog sketches".
+    fn count(&self) -> u64 {
+        let mut histogram = [0_u32; 64];
+        for &register in &self.registers {
+            histogram[usize::from(register)] += 1;
+        }
+        let m = HLL_REGISTERS as f64;
+        let mut z = m * tau((m - f64::from(histogram[HLL_Q as usize + 1])) / m);
+        for &registers in histogram[1..=HLL_Q as usize].iter().rev() {
+            z += f64::from(registers);
+            z *= 0.5;
+        }
+        z += m * sigma(f64::from(histogram[0]) / m);
+        (HLL_ALPHA_INF * m * m / z).round() as u64
+    }
+
+    /// Encodes as sparse while the counter fits, promoting it to dense for good otherwise.
+    fn encode(&mut self) -> Vec<u8> {
+        let sparse = if self.dense {
+            None
+        } else {
+            encode_sparse(&self.registers)
+                .filter(|payload| HLL_HDR_SIZE + payload.len() <= HLL_SPARSE_MAX_BYTES)
+        };
+        self.dense = sparse.is_none();
+        let payload = sparse.unwrap_or_else(|| encode_dense(&self.registers));
+
+        let mut bytes = Vec::with_capacity(HLL_HDR_SIZE + payload.len());
+        bytes.extend_from_slice(HLL_MAGIC);
+        bytes.push(if self.dense { HLL_DENSE } else { HLL_SPARSE });
+        bytes.extend_from_slice(&[0; 3]);
+        bytes.extend_from_slice(&self.card);
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+}
+
+fn check_header(bytes: &[u8]) -> Result<(), &'static str> {
+    let valid = bytes.len() >= HLL_HDR_SIZE
+        && bytes.starts_with(HLL_MAGIC)
+        && match bytes[4] {
+            HLL_DENSE => bytes.len() == HLL_DENSE_SIZE,
+            HLL_SPARSE => true,
+            _ => false,
+        };
+    if valid {
+        Ok(())
+    } else {
+        Err(INVALID_HLL_ERR)
+    }
+}
+
+fn cached_count(bytes: &[u8]) -> Option<u64> {
+    let card: [u8; 8] = bytes[8..HLL_HDR_SIZE].try_into().ok()?;
+    (card[7] & HLL_STALE_CACHE == 0).then(|| u64::from_le_bytes(card))
+}
+
+fn dense_register(payload: &[u8], index: usize) -> u8 {
+    let byte = index * HLL_BITS / 8;
+    let shift = (index * HLL_BITS) & 7;
+    // The last register never reaches into the byte after the payload.
+    let low = u16::from(payload[byte]);
+    let high = u16::from(payload.get(byte + 1).copied().unwrap_or(0));
+    (((low | high << 8) >> shift) as u8) & HLL_REGISTER_MAX
+}
+
+fn encode_dense(registers: &[u8]) -> Vec<u8> {
+    let mut payload = vec![0_u8; HLL_DENSE_SIZE - HLL_HDR_SIZE];
+    for (index, &value) in registers.iter().enumerate() {
+        let byte = index * HLL_BITS / 8;
+        let bits = u16::from(value) << ((index * HLL_BITS) & 7);
+        payload[byte] |= bits as u8;
+        if let Some(next) = payload.get_mut(byte + 1) {
+            *next |= (bits >> 8) as u8;
+        }
+    }
+    payload
+}
+
+fn sparse_registers(payload: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut registers = Vec::with_capacity(HLL_REGISTERS);
+    let mut bytes = payload.iter();
+    while let Some(&opcode) = bytes.next() {
+        let (value, len) = match opcode >> 6 {
+            0 => (0, usize::from(opcode & 0x3f) + 1),
+            1 => {
+                let &low = bytes.next().ok_or(CORRUPTED_HLL_ERR)?;
+                (0, (usize::from(opcode & 0x3f) << 8 | usize::from(low)) + 1)
+            }
+            _ => ((opcode >> 2 & 0x1f) + 1, usize::from(opcode & 0x3) + 1),
+        };
+        if registers.len() + len > HLL_REGISTERS {
+            return Err(CORRUPTED_HLL_ERR);
+        }
+        registers.resize(registers.len() + len, value);
+    }
+    if registers.len() == HLL_REGISTERS {
+        Ok(registers)
+    } else {
+        Err(CORRUPTED_HLL_ERR)
+    }
+}
+
+/// Returns `None` when a register is too large for the `VAL` opcode.
+fn encode_sparse(registers: &[u8]) -> Option<Vec<u8>> {
+    let mut payload = Vec::new();
+    let mut index = 0;
+    while index < registers.len() {
+        let value = registers[index];
+        let run = registers[index..]
+            .iter()
+            .take_while(|&&register| register == value)
+            .count();
+        index += run;
+        let mut left = run;
+        while left > 0 {
+            if value > HLL_SPARSE_VAL_MAX_VALUE {
+                return None;
+            } else if value > 0 {
+                let len = left.min(HLL_SPARSE_VAL_MAX_LEN);
+                payload.push(0x80 | (value - 1) << 2 | (len - 1) as u8);
+                left -= len;
+            } else if left > HLL_SPARSE_ZERO_MAX_LEN {
+                let len = left.min(HLL_SPARSE_XZERO_MAX_LEN) - 1;
+                payload.extend_from_slice(&[0x40 | (len >> 8) as u8, len as u8]);
+                left -= len + 1;
+            } else {
+                payload.push((left - 1) as u8);
+                left = 0;
+            }
+        }
+    }
+    Some(payload)
+}
+
+/// Splits the hash of `element` into the register it lands in and the length of the run of
+/// zero bits that follows, plus one.
+fn pattern_len(element: &[u8]) -> (usize, u8) {
+    let hash = murmur_hash64a(element, HLL_HASH_SEED);
+    let index = (hash & (HLL_REGISTERS as u64 - 1)) as usize;
+    let pattern = hash >> HLL_P | 1 << HLL_Q;
+    (index, pattern.trailing_zeros() as u8 + 1)
+}
+
+fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().expect("chunks are 8 bytes"));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate() {
+            h ^= u64::from(byte) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// Reads the counter at `key`; a missing key yields `None`.
+fn hll_bytes(data: &Keyspace, key: &str) -> Result<Option<Vec<u8>>, &'static str> {
+    let bytes = string_bytes(data, key)?;
+    if let Some(bytes) = &bytes {
+        check_header(bytes)?;
+    }
+    Ok(bytes)
+}
+
+impl Cache {
+    /// Returns whether the counter was created or any of its registers changed.
+    pub fn pfadd(&self, key: &str, elements: &[String]) -> Result<bool, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let existing = hll_bytes(&data, key)?;
+        let mut updated = existing.is_none();
+        let mut hll = match existing {
+            Some(bytes) => HyperLogLog::parse(&bytes)?,
+            None => HyperLogLog::new(),
+        };
+        for element in elements {
+            updated |= hll.add(&to_bytes(element));
+        }
+        if updated {
+            hll.invalidate_cache();
+            store_bytes(&mut data, key, &hll.encode());
+        }
+        Ok(updated)
+    }
+
+    /// Counts a single key through its cached cardinality, refreshing the cache when stale.
+    /// Several keys are counted as the union of their counters, leaving them untouched.
+    pub fn pfcount(&self, keys: &[String]) -> Result<u64, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        if let [key] = keys {
+            let Some(mut bytes) = hll_bytes(&data, key)? else {
+                return Ok(0);
+            };
+            if let Some(count) = cached_count(&bytes) {
+                return Ok(count);
+            }
+            let count = HyperLogLog::parse(&bytes)?.count();
+            bytes[8..HLL_HDR_SIZE].copy_from_slice(&count.to_le_bytes());
+            store_bytes(&mut data, key, &bytes);
+            return Ok(count);
+        }
+
+        let mut union = HyperLogLog::new();
+        for key in keys {
+            if let Some(bytes) = hll_bytes(&data, key)? {
+                union.merge(&HyperLogLog::parse(&bytes)?);
+            }
+        }
+        Ok(union.count())
+    }
+
+    /// Merges the sources into `destination`, counting its own registers when it exists. The
+    /// result is dense if any of the inputs was.
+    pub fn pfmerge(&self, destination: &str, sources: &[String]) -> Result<(), &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let mut merged = match hll_bytes(&data, destination)? {
+            Some(bytes) => HyperLogLog::parse(&bytes)?,
+            None => HyperLogLog::new(),
+        };
+        for source in sources {
+            if let Some(bytes) = hll_bytes(&data, source)? {
+                merged.merge(&HyperLogLog::parse(&bytes)?);
+            }
+        }
+        merged.invalidate_cache();
+        store_bytes(&mut data, destination, &merged.encode());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_encode_an_empty_counter_like_redis() {
+        let mut expected = b"HYLL\x01\0\0\0\0\0\0\0\0\0\0\0".to_vec();
+        expected.extend_from_slice(&[0x7f, 0xff]);
+        assert_eq!(HyperLogLog::new().encode(), expected);
+        assert_eq!(HyperLogLog::parse(&expected).unwrap(), HyperLogLog::new());
+    }
+
+    #[test]
+    fn should_promote_to_dense_and_round_trip() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..20_000 {
+            hll.add(i.to_string().as_bytes());
+        }
+        let bytes = hll.encode();
+        assert_eq!(bytes.len(), HLL_DENSE_SIZE);
+        assert_eq!(bytes[4], HLL_DENSE);
+        let parsed = HyperLogLog::parse(&bytes).unwrap();
+        assert_eq!(parsed.registers, hll.registers);
+        let count = parsed.count() as f64;
+        assert!((count - 20_000.0).abs() / 20_000.0 < 0.03, "{count}");
+    }
+
+    #[test]
+    fn should_reject_corrupted_sparse_data() {
+        let mut bytes = HyperLogLog::new().encode();
+        bytes.push(0x00);
+        assert_eq!(HyperLogLog::parse(&bytes), Err(CORRUPTED_HLL_ERR));
+        assert_eq!(HyperLogLog::parse(b"HYLL"), Err(INVALID_HLL_ERR));
+    }
+}
@@ -0,0 +1,324 @@
+use std::thread;
+
+use super::{
+    db::{Cache, Value, DB_INDEX_ERR},
+    keyspace::{Keyspace, KeyspaceStats},
+};
+
+pub const NO_SUCH_KEY_ERR: &str = "ERR no such key";
+pub const SAME_OBJECT_ERR: &str = "ERR source and destination objects are the same";
+
+/// Whether FLUSHDB and FLUSHALL free the old keys on the caller's thread or in the background.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FlushMode {
+    #[default]
+    Sync,
+    Async,
+}
+
+/// What OBJECT reports about a key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectInfo {
+    pub encoding: &'static str,
+    /// Milliseconds since the last access.
+    pub idle_time: u64,
+    /// Logarithmic access frequency, see `Keyspace::frequency`.
+    pub frequency: u8,
+}
+
+/// Drops `values` on a background thread, so freeing a large collection does not hold up the
+/// client that deleted it.
+fn free_lazily<T: Send + 'static>(values: T) {
+    thread::spawn(move || drop(values));
+}
+
+impl Cache {
+    /// Stores `value` under `destination` with the expiry `at`, replacing whatever was there, and
+    /// keeps the hash field sweeper and blocked clients in step with the new key.
+    fn place(&self, data: &mut Keyspace, destination: &str, value: Value, at: Option<u64>) {
+        let volatile_hash = matches!(&value, Value::Hash(hash) if hash.has_volatile_fields());
+        data.remove(destination);
+        data.insert(destination.to_string(), value);
+        if let Some(at) = at {
+            data.set_expire_at(destination, at);
+        }
+        if volatile_hash {
+            if let Ok(mut volatile_hashes) = self.volatile_hashes.write() {
+                volatile_hashes.insert(destination.to_string());
+            }
+        }
+        self.blocking.serve(data, destination);
+    }
+
+    /// Moves `source` to `destination` together with its TTL. With `only_if_missing` nothing
+    /// happens when the destination exists. Returns whether the key was renamed.
+    pub fn rename(
+        &self,
+        source: &str,
+        destination: &str,
+        only_if_missing: bool,
+    ) -> Result<bool, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        if !data.contains_key(source) {
+            return Err(NO_SUCH_KEY_ERR);
+        }
+        if source == destination {
+            return Ok(!only_if_missing);
+        }
+        if only_if_missing && data.contains_key(destination) {
+            return Ok(false);
+        }
+        let at = data.expire_at(source);
+        let Some(value) = data.remove(source) else {
+            return Err(NO_SUCH_KEY_ERR);
+        };
+        self.place(&mut data, destination, value, at);
+        Ok(true)
+    }
+
+    /// Copies `source` and its TTL to `destination` in database `db`, overwriting it only with
+    /// `replace`. Returns whether the copy was made.
+    pub fn copy(
+        &self,
+        source: &str,
+        destination: &str,
+        db: usize,
+        replace: bool,
+    ) -> Result<bool, &'static str> {
+        self.write_across(db, |data, other, target| {
+            if other.is_none() && source == destination {
+                return Err(SAME_OBJECT_ERR);
+            }
+            let Some(value) = data.get(source).cloned() else {
+                return Ok(false);
+            };
+            let at = data.expire_at(source);
+            let target_data = other.unwrap_or(data);
+            if !replace && target_data.contains_key(destination) {
+                return Ok(false);
+            }
+            target.place(target_data, destination, value, at);
+            Ok(true)
+        })
+    }
+
+    /// Moves `key` and its TTL to database `db`, unless it already has such a key. Returns
+    /// whether the key was moved.
+    pub fn move_key(&self, key: &str, db: usize) -> Result<bool, &'static str> {
+        self.write_across(db, |data, other, target| {
+            let Some(target_data) = other else {
+                return Err(SAME_OBJECT_ERR);
+            };
+            if !data.contains_key(key) || target_data.contains_key(key) {
+                return Ok(false);
+            }
+            let at = data.expire_at(key);
+            let Some(value) = data.remove(key) else {
+                return Ok(false);
+            };
+            target.place(target_data, key, value, at);
+            Ok(true)
+        })
+    }
+
+    /// Swaps the contents of databases `first` and `second` in one step. Connections keep their
+    /// database number and see the other contents right away, and clients blocked on either
+    /// database are served from what it holds now.
+    pub fn swapdb(&self, first: usize, second: usize) -> Result<(), &'static str> {
+        let first = self.select(first).ok_or(DB_INDEX_ERR)?;
+        first.write_across(second, |data, other, second| {
+            let Some(other) = other else {
+                return Ok(());
+            };
+            std::mem::swap(data, other);
+            if let (Ok(mut ours), Ok(mut theirs)) = (
+                first.volatile_hashes.write(),
+                second.volatile_hashes.write(),
+            ) {
+                std::mem::swap(&mut *ours, &mut *theirs);
+            }
+            first.blocking.serve_all(data);
+            second.blocking.serve_all(other);
+            Ok(())
+        })
+    }
+
+    /// Type name of the value at `key`, or "none" when there is no such key.
+    pub fn key_type(&self, key: &str) -> Result<&'static str, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(data.peek(key).map_or("none", Value::type_name))
+    }
+
+    /// Encoding and access data of `key`. Looking does not count as an access.
+    pub fn object(&self, key: &str) -> Result<Option<ObjectInfo>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(data.peek(key).map(|value| ObjectInfo {
+            encoding: value.encoding(),
+            idle_time: data.idle_time(key).unwrap_or(0),
+            frequency: data.frequency(key).unwrap_or(0),
+        }))
+    }
+
+    /// Records an access to each of `keys` and returns how many exist.
+    pub fn touch(&self, keys: &[String]) -> Result<usize, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(keys.iter().filter(|key| data.get(key).is_some()).count())
+    }
+
+    /// Deletes `keys` and returns how many existed. The values are freed in the background.
+    pub fn unlink(&self, keys: &[String]) -> Result<usize, &'static str> {
+        let removed: Vec<Value> = self
+            .write_keyspace(|data| Ok(keys.iter().filter_map(|key| data.remove(key)).collect()))?;
+        let count = removed.len();
+        free_lazily(removed);
+        Ok(count)
+    }
+
+    pub fn random_key(&self) -> Result<Option<String>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(data.random_key().cloned())
+    }
+
+    /// Number of keys, including expired ones that have not been reclaimed yet, like Redis.
+    pub fn dbsize(&self) -> Result<usize, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(data.len())
+    }
+
+    /// Statistics of every database that holds keys, by database index.
+    pub fn keyspace_stats(&self) -> Result<Vec<(usize, KeyspaceStats)>, &'static str> {
+        let mut stats = Vec::new();
+        for (index, database) in self.databases.iter().enumerate() {
+            let data = database
+                .data
+                .read()
+                .map_err(|_| "Could not acquire data read lock")?;
+            if data.len() > 0 {
+                stats.push((index, data.stats()));
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Deletes every key of this database. In async mode the old key space is dropped on
+    /// another thread.
+    pub fn flushdb(&self, mode: FlushMode) -> Result<(), &'static str> {
+        let flushed = self.write_keyspace(|data| Ok(std::mem::take(data)))?;
+        if let Ok(mut volatile_hashes) = self.volatile_hashes.write() {
+            volatile_hashes.clear();
+        }
+        match mode {
+            FlushMode::Sync => drop(flushed),
+            FlushMode::Async => free_lazily(flushed),
+        }
+        Ok(())
+    }
+
+    /// Deletes every key of every database.
+    pub fn flushall(&self, mode: FlushMode) -> Result<(), &'static str> {
+        (0..self.database_count())
+            .filter_map(|index| self.select(index))
+            .try_for_each(|database| database.flushdb(mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::db::unix_time_ms;
+
+    use super::*;
+
+    #[test]
+    fn should_carry_ttls_over_on_rename_and_copy() {
+        let cache = Cache::new();
+        let later = unix_time_ms() as i64 + 100_000;
+        cache.set("a".to_string(), "1".to_string()).unwrap();
+        cache.set("b".to_string(), "2".to_string()).unwrap();
+        cache.expire("a", later, &[]).unwrap();
+        cache.expire("b", later + 1, &[]).unwrap();
+
+        assert!(cache.copy("a", "c", 0, false).unwrap());
+        assert_eq!(cache.expire_time("c").unwrap(), later);
+        assert!(!cache.copy("a", "b", 0, false).unwrap());
+        assert_eq!(cache.copy("a", "a", 0, true), Err(SAME_OBJECT_ERR));
+
+        // RENAME replaces the destination and its TTL with the source's.
+        cache.persist("a").unwrap();
+        assert!(cache.rename("a", "b", false).unwrap());
+        assert_eq!(cache.expire_time("b").unwrap(), -1);
+        assert_eq!(cache.get("b").unwrap().as_deref(), Some("1"));
+        assert_eq!(cache.rename("a", "b", false), Err(NO_SUCH_KEY_ERR));
+        assert!(!cache.rename("b", "c", true).unwrap());
+        assert!(cache.rename("c", "d", true).unwrap());
+        assert_eq!(cache.expire_time("d").unwrap(), later);
+        assert_eq!(cache.expire_time("c").unwrap(), -2);
+    }
+
+    #[test]
+    fn should_flush_and_count_keys() {
+        let cache = Cache::new();
+        for key in ["a", "b", "c"] {
+            cache.set(key.to_string(), key.to_string()).unwrap();
+        }
+        assert_eq!(cache.dbsize().unwrap(), 3);
+        assert!(cache.random_key().unwrap().is_some());
+        assert_eq!(cache.unlink(&["a".to_string(), "z".to_string()]), Ok(1));
+        cache.flushdb(FlushMode::Async).unwrap();
+        assert_eq!(cache.dbsize().unwrap(), 0);
+        assert_eq!(cache.random_key().unwrap(), None);
+        assert_eq!(cache.key_type("b").unwrap(), "none");
+    }
+
+    #[test]
+    fn should_move_copy_and_swap_across_databases() {
+        let cache = Cache::with_databases(3);
+        let other = cache.select(1).unwrap();
+        let later = unix_time_ms() as i64 + 100_000;
+        cache.set("k".to_string(), "zero".to_string()).unwrap();
+        cache.expire("k", later, &[]).unwrap();
+
+        assert!(cache.copy("k", "k", 1, false).unwrap());
+        assert_eq!(other.expire_time("k").unwrap(), later);
+        assert!(!cache.move_key("k", 1).unwrap());
+        assert_eq!(cache.move_key("k", 0), Err(SAME_OBJECT_ERR));
+        assert_eq!(cache.move_key("k", 3), Err(DB_INDEX_ERR));
+        other.del("k").unwrap();
+        assert!(cache.move_key("k", 1).unwrap());
+        assert!(!cache.exists("k").unwrap());
+        assert_eq!(other.expire_time("k").unwrap(), later);
+
+        cache.set("only".to_string(), "zero".to_string()).unwrap();
+        cache.swapdb(0, 1).unwrap();
+        assert_eq!(cache.get("k").unwrap().as_deref(), Some("zero"));
+        assert_eq!(cache.expire_time("k").unwrap(), later);
+        assert!(!cache.exists("only").unwrap());
+        assert!(other.exists("only").unwrap());
+        assert_eq!(cache.swapdb(0, 5), Err(DB_INDEX_ERR));
+
+        cache
+            .select(2)
+            .unwrap()
+            .set("x".to_string(), "y".to_string())
+            .unwrap();
+        cache.flushall(FlushMode::Sync).unwrap();
+        assert!((0..3).all(|db| cache.select(db).unwrap().dbsize().unwrap() == 0));
+    }
+}
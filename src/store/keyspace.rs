@@ -0,0 +1,648 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use super::db::{unix_time_ms, Value};
+use super::memory::{key_usage, DEFAULT_SAMPLES, EXPIRY_OVERHEAD, KEY_OVERHEAD};
+use super::random;
+use super::scan::{scan_position, scan_sorted, scan_start, ScanPage};
+#[cfg(feature = "timing-wheel")]
+use super::timing_wheel::TimingWheel;
+
+/// Volatile keys checked per round of the active expiry cycle, like Redis' default effort.
+#[cfg(not(feature = "timing-wheel"))]
+const ACTIVE_EXPIRE_SAMPLE: usize = 20;
+/// Another round runs while more than this share of a sample turned out to be expired.
+#[cfg(not(feature = "timing-wheel"))]
+const ACCEPTABLE_STALE_PERCENT: usize = 10;
+
+/// Expiry times of the keys that have one, stored so a random key can be picked in O(1).
+/// The `timing-wheel` build also indexes them by deadline, so the active cycle can go straight
+/// to the keys that are due instead of sampling.
+#[derive(Debug, Default)]
+struct VolatileKeys {
+    /// The name is shared by the slot, the index and the wheel rather than copied into each.
+    slots: Vec<(Arc<str>, u64)>,
+    index: HashMap<Arc<str>, usize>,
+    #[cfg(feature = "timing-wheel")]
+    wheel: TimingWheel,
+}
+
+impl VolatileKeys {
+    fn get(&self, key: &str) -> Option<u64> {
+        self.index.get(key).map(|&slot| self.slots[slot].1)
+    }
+
+    fn insert(&mut self, key: &str, at: u64) {
+        match self.index.get(key) {
+            Some(&slot) => self.slots[slot].1 = at,
+            None => {
+                let key: Arc<str> = Arc::from(key);
+                self.index.insert(key.clone(), self.slots.len());
+                self.slots.push((key, at));
+            }
+        }
+        #[cfg(feature = "timing-wheel")]
+        self.wheel.insert(self.slots[self.index[key]].0.clone(), at);
+    }
+
+    fn remove(&mut self, key: &str) -> Option<u64> {
+        #[cfg(feature = "timing-wheel")]
+        self.wheel.remove(key);
+        let slot = self.index.remove(key)?;
+        let (_, at) = self.slots.swap_remove(slot);
+        if let Some((moved, _)) = self.slots.get(slot) {
+            if let Some(index) = self.index.get_mut(moved) {
+                *index = slot;
+            }
+        }
+        Some(at)
+    }
+}
+
+/// Access frequency new keys start with, so they are not evicted before they had a chance to be
+/// used again, like Redis' LFU_INIT_VAL.
+const LFU_INIT_VAL: u8 = 5;
+/// The higher the factor, the more accesses it takes to raise a frequency, like Redis'
+/// `lfu-log-factor`.
+const LFU_LOG_FACTOR: u32 = 10;
+/// Frequencies drop by one for every this many milliseconds a key is not accessed, like Redis'
+/// `lfu-decay-time` of one minute.
+const LFU_DECAY_MS: u64 = 60_000;
+
+/// A value along with what eviction needs to know about it. The access data sits in atomics so
+/// reads can update it under the shared lock.
+#[derive(Debug)]
+struct Slot {
+    value: Value,
+    /// Estimated bytes of the key and value, see `memory::key_usage`.
+    size: usize,
+    /// Where the key sits in `Keyspace::order`.
+    position: usize,
+    /// Unix milliseconds of the last access.
+    last_access: AtomicU64,
+    /// Logarithmic access counter, see `Keyspace::touch`.
+    frequency: AtomicU8,
+    /// Handed out mutably since it was last measured.
+    unmeasured: bool,
+}
+
+impl Slot {
+    /// Access frequency after the decay for the time since the last access.
+    fn decayed_frequency(&self, now: u64) -> u8 {
+        let idle = now.saturating_sub(self.last_access.load(Ordering::Relaxed));
+        let periods = (idle / LFU_DECAY_MS).min(u8::MAX as u64) as u8;
+        self.frequency
+            .load(Ordering::Relaxed)
+            .saturating_sub(periods)
+    }
+}
+
+/// What INFO keyspace reports about a database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyspaceStats {
+    pub keys: usize,
+    pub expires: usize,
+    /// Mean remaining TTL of the volatile keys in milliseconds.
+    pub avg_ttl: u64,
+}
+
+/// Every key with its value and, for volatile keys, its absolute expiry in Unix milliseconds.
+/// Lookups treat expired keys as missing, and lookups that can write delete them on the spot,
+/// so an expired key is never observable even before the active expiry cycle reclaims it.
+///
+/// The key space also keeps the estimated memory of every key for `maxmemory`. Values handed
+/// out mutably are measured again on the next write, so a command's growth is accounted for
+/// before the following one checks the limit.
+#[derive(Debug, Default)]
+pub struct Keyspace {
+    values: HashMap<String, Slot>,
+    volatile: VolatileKeys,
+    /// Every key, so eviction can sample them in O(1).
+    order: Vec<String>,
+    /// Every key by scan position, so a SCAN page only reads the keys it returns.
+    scan_order: BTreeSet<(u64, String)>,
+    /// Sum of the sizes of all slots.
+    used_memory: usize,
+    /// Keys whose value may have changed size since it was measured.
+    unmeasured: Vec<String>,
+}
+
+impl Keyspace {
+    fn is_expired(&self, key: &str) -> bool {
+        self.volatile
+            .get(key)
+            .is_some_and(|at| at <= unix_time_ms())
+    }
+
+    /// Deletes `key` if its TTL has passed and returns whether it did.
+    fn purge_if_expired(&mut self, key: &str) -> bool {
+        if !self.is_expired(key) {
+            return false;
+        }
+        self.remove_slot(key);
+        true
+    }
+
+    fn insert_slot(&mut self, key: String, value: Value) -> Option<Value> {
+        let size = key_usage(&key, &value, DEFAULT_SAMPLES);
+        self.used_memory += size;
+        if let Some(slot) = self.values.get_mut(&key) {
+            self.used_memory -= slot.size;
+            slot.size = size;
+            return Some(std::mem::replace(&mut slot.value, value));
+        }
+        let slot = Slot {
+            value,
+            size,
+            position: self.order.len(),
+            last_access: AtomicU64::new(unix_time_ms()),
+            frequency: AtomicU8::new(LFU_INIT_VAL),
+            unmeasured: false,
+        };
+        self.order.push(key.clone());
+        self.scan_order.insert((scan_position(&key), key.clone()));
+        self.values.insert(key, slot);
+        None
+    }
+
+    /// Deletes `key` and its TTL whether or not it has expired.
+    fn remove_slot(&mut self, key: &str) -> Option<Value> {
+        self.volatile.remove(key);
+        let slot = self.values.remove(key)?;
+        self.used_memory -= slot.size;
+        self.scan_order
+            .remove(&(scan_position(key), key.to_string()));
+        self.order.swap_remove(slot.position);
+        if let Some(moved) = self.order.get(slot.position) {
+            if let Some(moved) = self.values.get_mut(moved) {
+                moved.position = slot.position;
+            }
+        }
+        Some(slot.value)
+    }
+
+    /// Measures the values handed out mutably since the last call again.
+    fn settle(&mut self) {
+        for key in std::mem::take(&mut self.unmeasured) {
+            if let Some(slot) = self.values.get_mut(&key) {
+                let size = key_usage(&key, &slot.value, DEFAULT_SAMPLES);
+                self.used_memory = self.used_memory - slot.size + size;
+                slot.size = size;
+                slot.unmeasured = false;
+            }
+        }
+    }
+
+    /// Records an access for LRU and LFU. Like Redis, the frequency is a logarithmic counter:
+    /// the higher it is, the less likely an access raises it, so 255 stands for about a
+    /// million accesses. It first decays by the idle time.
+    fn touch(slot: &Slot) {
+        let now = unix_time_ms();
+        let mut frequency = slot.decayed_frequency(now);
+        if frequency < u8::MAX {
+            let base = frequency.saturating_sub(LFU_INIT_VAL) as usize;
+            let odds = base * LFU_LOG_FACTOR as usize + 1;
+            if random::index_below(odds) == 0 {
+                frequency += 1;
+            }
+        }
+        slot.frequency.store(frequency, Ordering::Relaxed);
+        slot.last_access.store(now, Ordering::Relaxed);
+    }
+
+    fn live_slot(&self, key: &str) -> Option<&Slot> {
+        self.values.get(key).filter(|_| !self.is_expired(key))
+    }
+
+    /// Value of a live key, recording the access.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        let slot = self.live_slot(key)?;
+        Self::touch(slot);
+        Some(&slot.value)
+    }
+
+    /// Value of a live key without recording an access, for introspection.
+    pub fn peek(&self, key: &str) -> Option<&Value> {
+        self.live_slot(key).map(|slot| &slot.value)
+    }
+
+    /// Slot of a live key handed out mutably, which gets it measured again later.
+    fn live_slot_mut(&mut self, key: &str) -> Option<&mut Slot> {
+        self.settle();
+        self.purge_if_expired(key);
+        let slot = self.values.get_mut(key)?;
+        if !slot.unmeasured {
+            slot.unmeasured = true;
+            self.unmeasured.push(key.to_string());
+        }
+        Some(slot)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        let slot = self.live_slot_mut(key)?;
+        Self::touch(slot);
+        Some(&mut slot.value)
+    }
+
+    /// Value of a live key to change without recording an access, for internal maintenance
+    /// such as the field expiry sweep.
+    pub fn peek_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.live_slot_mut(key).map(|slot| &mut slot.value)
+    }
+
+    /// Value of `key`, inserting the one `default` returns if the key is missing or expired.
+    pub fn get_or_insert_with(
+        &mut self,
+        key: String,
+        default: impl FnOnce() -> Value,
+    ) -> &mut Value {
+        self.purge_if_expired(&key);
+        if !self.values.contains_key(&key) {
+            self.insert_slot(key.clone(), default());
+        }
+        self.get_mut(&key).expect("the key was just inserted")
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.live_slot(key).is_some()
+    }
+
+    /// Number of keys, counting expired ones the active cycle has not reclaimed yet.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn stats(&self) -> KeyspaceStats {
+        let now = unix_time_ms();
+        let (live, total_ttl) = self
+            .volatile
+            .slots
+            .iter()
+            .filter(|(_, at)| *at > now)
+            .fold((0, 0), |(live, total), (_, at)| {
+                (live + 1, total + (at - now))
+            });
+        KeyspaceStats {
+            keys: self.values.len(),
+            expires: self.volatile.slots.len(),
+            avg_ttl: total_ttl.checked_div(live).unwrap_or(0),
+        }
+    }
+
+    /// Estimated bytes held by the keys, their values and their expiry times. Values changed
+    /// since they were last measured are measured on the spot.
+    pub fn used_memory(&self) -> usize {
+        let unsettled: isize = self
+            .unmeasured
+            .iter()
+            .filter_map(|key| self.values.get_key_value(key.as_str()))
+            .map(|(key, slot)| {
+                key_usage(key, &slot.value, DEFAULT_SAMPLES) as isize - slot.size as isize
+            })
+            .sum();
+        (self.used_memory as isize + unsettled) as usize
+            + self.volatile.slots.len() * EXPIRY_OVERHEAD
+    }
+
+    /// A live key picked uniformly at random. Gives up after a few expired picks, which only
+    /// happens when most keys are waiting to be reclaimed.
+    pub fn random_key(&self) -> Option<&String> {
+        const ATTEMPTS: usize = 100;
+        if self.order.is_empty() {
+            return None;
+        }
+        (0..ATTEMPTS)
+            .map(|_| &self.order[random::index_below(self.order.len())])
+            .find(|key| !self.is_expired(key))
+    }
+
+    /// Up to `count` random keys, expired ones included, drawn from the keys with a TTL only
+    /// when `volatile_only` is set. The same key can come up more than once.
+    pub fn sample_keys(&self, count: usize, volatile_only: bool) -> Vec<String> {
+        if volatile_only {
+            let slots = &self.volatile.slots;
+            (0..count.min(slots.len()))
+                .map(|_| slots[random::index_below(slots.len())].0.to_string())
+                .collect()
+        } else {
+            (0..count.min(self.order.len()))
+                .map(|_| self.order[random::index_below(self.order.len())].clone())
+                .collect()
+        }
+    }
+
+    /// Milliseconds since a live key was last accessed.
+    pub fn idle_time(&self, key: &str) -> Option<u64> {
+        self.live_slot(key)
+            .map(|slot| unix_time_ms().saturating_sub(slot.last_access.load(Ordering::Relaxed)))
+    }
+
+    /// The logarithmic access frequency of a live key, with the decay applied.
+    pub fn frequency(&self, key: &str) -> Option<u8> {
+        self.live_slot(key)
+            .map(|slot| slot.decayed_frequency(unix_time_ms()))
+    }
+
+    /// Estimated bytes of a live key, its value and its expiry, measuring `samples` elements of
+    /// collections (all of them when zero).
+    pub fn key_memory(&self, key: &str, samples: usize) -> Option<usize> {
+        let slot = self.live_slot(key)?;
+        let expiry = self.volatile.get(key).map_or(0, |_| EXPIRY_OVERHEAD);
+        Some(key_usage(key, &slot.value, samples) + expiry)
+    }
+
+    /// Bytes spent on bookkeeping rather than data: the per key overhead and the expiry index.
+    pub fn overhead(&self) -> (usize, usize) {
+        (
+            self.values.len() * KEY_OVERHEAD,
+            self.volatile.slots.len() * EXPIRY_OVERHEAD,
+        )
+    }
+
+    /// The `count` keys holding the most memory with their values and estimated sizes, largest
+    /// first.
+    pub fn largest_keys(&self, count: usize) -> Vec<(&String, &Value, usize)> {
+        let mut largest: Vec<(&String, &Value, usize)> = Vec::with_capacity(count + 1);
+        for (key, slot) in &self.values {
+            let size = if slot.unmeasured {
+                key_usage(key, &slot.value, DEFAULT_SAMPLES)
+            } else {
+                slot.size
+            };
+            if largest.len() == count && largest.last().is_none_or(|(_, _, last)| *last >= size) {
+                continue;
+            }
+            let position = largest.partition_point(|(_, _, known)| *known >= size);
+            largest.insert(position, (key, &slot.value, size));
+            largest.truncate(count);
+        }
+        largest
+    }
+
+    /// Deletes `key` to free memory, expired or not, and returns the bytes it held.
+    pub fn evict(&mut self, key: &str) -> Option<usize> {
+        self.settle();
+        let before = self.used_memory();
+        self.remove_slot(key)?;
+        Some(before - self.used_memory())
+    }
+
+    /// Every live key with its value, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        let now = unix_time_ms();
+        self.values
+            .iter()
+            .filter(move |(key, _)| self.volatile.get(key).is_none_or(|at| at > now))
+            .map(|(key, slot)| (key, &slot.value))
+    }
+
+    /// One SCAN page of the live keys with their values, see `scan::scan_page`.
+    pub fn scan(&self, cursor: u64, count: usize) -> ScanPage<(&String, &Value)> {
+        let now = unix_time_ms();
+        let start = (scan_start(self.values.len(), cursor), String::new());
+        let entries = self
+            .scan_order
+            .range(start..)
+            .filter(|(_, key)| self.volatile.get(key).is_none_or(|at| at > now))
+            .filter_map(|(position, key)| {
+                let (key, slot) = self.values.get_key_value(key.as_str())?;
+                Some((*position, (key, &slot.value)))
+            });
+        scan_sorted(entries, self.values.len(), count)
+    }
+
+    /// Replaces the value at `key`, keeping its TTL. Commands that overwrite a key the way SET
+    /// does call `persist` as well.
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        self.settle();
+        self.purge_if_expired(&key);
+        self.insert_slot(key, value)
+    }
+
+    /// Deletes `key` together with its TTL.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.settle();
+        if self.purge_if_expired(key) {
+            return None;
+        }
+        self.remove_slot(key)
+    }
+
+    /// Absolute expiry of a live key in Unix milliseconds.
+    pub fn expire_at(&self, key: &str) -> Option<u64> {
+        self.volatile.get(key).filter(|_| self.contains_key(key))
+    }
+
+    /// Gives an existing key a TTL. Times in the past delete it right away.
+    pub fn set_expire_at(&mut self, key: &str, at: u64) {
+        if !self.contains_key(key) {
+            return;
+        }
+        if at <= unix_time_ms() {
+            self.remove(key);
+        } else {
+            self.volatile.insert(key, at);
+        }
+    }
+
+    /// Drops the TTL of a live key and returns whether it had one.
+    pub fn persist(&mut self, key: &str) -> bool {
+        !self.purge_if_expired(key) && self.volatile.remove(key).is_some()
+    }
+
+    #[cfg(not(feature = "timing-wheel"))]
+    /// One round of active expiry: checks a random sample of volatile keys and deletes the
+    /// expired ones. Returns how many keys were sampled and how many of them were deleted.
+    fn expire_sample(&mut self) -> (usize, usize) {
+        let now = unix_time_ms();
+        let sampled = ACTIVE_EXPIRE_SAMPLE.min(self.volatile.slots.len());
+        let mut expired = 0;
+        for _ in 0..sampled {
+            if self.volatile.slots.is_empty() {
+                break;
+            }
+            let (key, at) = &self.volatile.slots[random::index_below(self.volatile.slots.len())];
+            if *at <= now {
+                let key = key.clone();
+                self.remove_slot(&key);
+                expired += 1;
+            }
+        }
+        (sampled, expired)
+    }
+}
+
+/// With the timing wheel the cycle only touches keys that are due, so it needs no sampling and
+/// no budget beyond the work of deleting them.
+#[cfg(feature = "timing-wheel")]
+pub fn active_expire_cycle(data: &RwLock<Keyspace>, _budget: Duration) -> usize {
+    let Ok(mut data) = data.write() else {
+        return 0;
+    };
+    let due = data.volatile.wheel.advance(unix_time_ms());
+    for key in &due {
+        data.remove_slot(key);
+    }
+    due.len()
+}
+
+/// Redis' adaptive active expiry: keeps sampling volatile keys while a large share of them
+/// turns out to be expired, giving up once `budget` is spent. The lock is taken per round so
+/// clients get in between, and the work done follows the number of expired keys rather than
+/// the size of the key space. Returns how many keys were deleted.
+#[cfg(not(feature = "timing-wheel"))]
+pub fn active_expire_cycle(data: &RwLock<Keyspace>, budget: Duration) -> usize {
+    let started = std::time::Instant::now();
+    let mut expired_total = 0;
+    loop {
+        let Ok(mut data) = data.write() else {
+            return expired_total;
+        };
+        let (sampled, expired) = data.expire_sample();
+        expired_total += expired;
+        if expired * 100 <= sampled * ACCEPTABLE_STALE_PERCENT || started.elapsed() >= budget {
+            return expired_total;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn should_hide_and_purge_expired_keys() {
+        let mut keyspace = Keyspace::default();
+        keyspace.insert("k".to_string(), string("v"));
+        keyspace.volatile.insert("k", unix_time_ms() - 1);
+        assert!(keyspace.get("k").is_none());
+        assert!(!keyspace.contains_key("k"));
+        assert_eq!(keyspace.expire_at("k"), None);
+        assert!(keyspace.remove("k").is_none());
+        assert!(keyspace.values.is_empty());
+        assert!(keyspace.volatile.slots.is_empty());
+
+        // A new value under an expired key does not inherit the old TTL.
+        keyspace.insert("k".to_string(), string("v"));
+        keyspace.volatile.insert("k", unix_time_ms() - 1);
+        assert_eq!(keyspace.insert("k".to_string(), string("w")), None);
+        assert_eq!(keyspace.get("k"), Some(&string("w")));
+        assert_eq!(keyspace.expire_at("k"), None);
+    }
+
+    #[test]
+    fn should_keep_the_volatile_index_consistent() {
+        let mut keyspace = Keyspace::default();
+        let later = unix_time_ms() + 100_000;
+        for key in ["a", "b", "c"] {
+            keyspace.insert(key.to_string(), string(key));
+            keyspace.set_expire_at(key, later);
+        }
+        assert!(keyspace.persist("a"));
+        assert!(!keyspace.persist("a"));
+        assert_eq!(keyspace.expire_at("b"), Some(later));
+        assert_eq!(keyspace.expire_at("c"), Some(later));
+        keyspace.remove("c");
+        assert_eq!(keyspace.volatile.slots, vec![(Arc::from("b"), later)]);
+        assert_eq!(keyspace.volatile.index.get("b"), Some(&0));
+    }
+
+    #[test]
+    fn should_scan_in_place_like_a_sorted_page() {
+        let mut keyspace = Keyspace::default();
+        for i in 0..500 {
+            keyspace.insert(format!("key:{}", i), string("v"));
+        }
+        for i in (0..500).step_by(3) {
+            keyspace.remove(&format!("key:{}", i));
+        }
+        assert_eq!(keyspace.scan_order.len(), keyspace.values.len());
+        let mut cursor = 0;
+        loop {
+            let (next, page) = keyspace.scan(cursor, 10);
+            let expected = super::super::scan::scan_page(
+                keyspace.values.keys().map(|key| (key.as_str(), key)),
+                keyspace.len(),
+                cursor,
+                10,
+            );
+            let keys: Vec<&String> = page.into_iter().map(|(key, _)| key).collect();
+            assert_eq!((next, keys), expected);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+    }
+
+    #[test]
+    fn should_leave_access_stats_alone_when_peeking_mutably() {
+        let mut keyspace = Keyspace::default();
+        keyspace.insert("k".to_string(), string("a"));
+        keyspace.values["k"].last_access.store(0, Ordering::Relaxed);
+        if let Some(Value::String(value)) = keyspace.peek_mut("k") {
+            value.push('b');
+        }
+        assert!(keyspace.idle_time("k").unwrap() > 1_000_000);
+        assert_eq!(keyspace.peek("k"), Some(&string("ab")));
+        keyspace.get_mut("k");
+        assert!(keyspace.idle_time("k").unwrap() < 1_000);
+    }
+
+    #[test]
+    fn should_account_for_memory_as_values_change() {
+        let mut keyspace = Keyspace::default();
+        assert_eq!(keyspace.used_memory(), 0);
+        keyspace.insert("a".to_string(), string("v"));
+        keyspace.insert("b".to_string(), string("v"));
+        let small = keyspace.used_memory();
+
+        if let Some(Value::String(value)) = keyspace.get_mut("a") {
+            value.push_str(&"x".repeat(1000));
+        }
+        assert!(keyspace.used_memory() >= small + 1000);
+        keyspace.set_expire_at("a", unix_time_ms() + 100_000);
+        assert_eq!(
+            keyspace.remove("a"),
+            Some(string(&format!("v{}", "x".repeat(1000))))
+        );
+        assert!(keyspace.used_memory() < small);
+        assert_eq!(keyspace.order, vec!["b".to_string()]);
+        assert_eq!(keyspace.values["b"].position, 0);
+
+        assert_eq!(keyspace.random_key(), Some(&"b".to_string()));
+        assert_eq!(keyspace.frequency("b"), Some(LFU_INIT_VAL));
+        assert_eq!(keyspace.evict("b"), Some(small / 2));
+        assert_eq!(keyspace.used_memory(), 0);
+    }
+
+    #[test]
+    fn should_reclaim_expired_keys_actively() {
+        let keyspace = RwLock::new(Keyspace::default());
+        {
+            let mut keyspace = keyspace.write().unwrap();
+            let past = unix_time_ms() - 1;
+            for i in 0..1000 {
+                let key = i.to_string();
+                keyspace.insert(key.clone(), string("v"));
+                keyspace.volatile.insert(&key, past);
+            }
+            keyspace.insert("persistent".to_string(), string("v"));
+        }
+        let expired = active_expire_cycle(&keyspace, Duration::from_secs(10));
+        assert_eq!(expired, 1000);
+        let keyspace = keyspace.read().unwrap();
+        assert_eq!(keyspace.values.len(), 1);
+        assert!(keyspace.volatile.slots.is_empty());
+    }
+}
@@ -0,0 +1,317 @@
+use std::{collections::VecDeque, mem::size_of};
+
+use super::{
+    db::{Cache, Value, WRONGTYPE_ERR},
+    keyspace::Keyspace,
+    listpack::Listpack,
+    memory::estimate,
+};
+
+/// Lists stay in a single listpack while it takes at most this many bytes, like Redis'
+/// `list-max-listpack-size -2`.
+pub const LIST_MAX_LISTPACK_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+/// A list is a listpack while it is small and a deque of strings once it outgrows
+/// `LIST_MAX_LISTPACK_SIZE`, for good.
+#[derive(Debug, Clone)]
+pub enum List {
+    Listpack(Listpack),
+    Deque(VecDeque<String>),
+}
+
+impl Default for List {
+    fn default() -> Self {
+        List::Listpack(Listpack::default())
+    }
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl FromIterator<String> for List {
+    fn from_iter<I: IntoIterator<Item = String>>(values: I) -> Self {
+        let mut list = List::default();
+        for value in values {
+            list.push(ListEnd::Right, value);
+        }
+        list
+    }
+}
+
+impl List {
+    pub fn len(&self) -> usize {
+        match self {
+            List::Listpack(pack) => pack.len(),
+            List::Deque(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        let (pack, values) = match self {
+            List::Listpack(pack) => (Some(pack.iter()), None),
+            List::Deque(values) => (None, Some(values.iter().map(String::as_str))),
+        };
+        pack.into_iter()
+            .flatten()
+            .chain(values.into_iter().flatten())
+    }
+
+    pub fn push(&mut self, end: ListEnd, value: String) {
+        if let List::Listpack(pack) = self {
+            if pack.bytes() + value.len() < LIST_MAX_LISTPACK_SIZE {
+                match end {
+                    ListEnd::Left => pack.push_front(&value),
+                    ListEnd::Right => pack.push_back(&value),
+                }
+                return;
+            }
+            *self = List::Deque(pack.iter().map(str::to_string).collect());
+        }
+        if let List::Deque(values) = self {
+            match end {
+                ListEnd::Left => values.push_front(value),
+                ListEnd::Right => values.push_back(value),
+            }
+        }
+    }
+
+    pub fn pop(&mut self, end: ListEnd) -> Option<String> {
+        match (self, end) {
+            (List::Listpack(pack), ListEnd::Left) => pack.pop_front(),
+            (List::Listpack(pack), ListEnd::Right) => pack.pop_back(),
+            (List::Deque(values), ListEnd::Left) => values.pop_front(),
+            (List::Deque(values), ListEnd::Right) => values.pop_back(),
+        }
+    }
+
+    /// Elements `start` to `stop`, both included and within bounds.
+    pub fn range(&self, start: usize, stop: usize) -> Vec<String> {
+        match self {
+            List::Listpack(pack) => pack
+                .iter()
+                .skip(start)
+                .take(stop + 1 - start)
+                .map(str::to_string)
+                .collect(),
+            List::Deque(values) => values.range(start..=stop).cloned().collect(),
+        }
+    }
+
+    pub(super) fn memory_usage(&self, samples: usize) -> usize {
+        match self {
+            List::Listpack(pack) => pack.allocated(),
+            List::Deque(values) => {
+                values.capacity() * size_of::<String>()
+                    + estimate(values.iter().map(String::capacity), values.len(), samples)
+            }
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            List::Listpack(_) => "listpack",
+            List::Deque(_) => "quicklist",
+        }
+    }
+}
+
+fn list_mut<'a>(data: &'a mut Keyspace, key: &str) -> Result<Option<&'a mut List>, &'static str> {
+    match data.get_mut(key) {
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// Pushes `values` one by one onto `end` of the list at `key`, creating it unless
+/// `only_if_exists` is set. Returns the new length.
+pub fn push_to(
+    data: &mut Keyspace,
+    key: &str,
+    values: &[String],
+    end: ListEnd,
+    only_if_exists: bool,
+) -> Result<usize, &'static str> {
+    let list = match list_mut(data, key)? {
+        Some(list) => list,
+        None if only_if_exists => return Ok(0),
+        None => match data.get_or_insert_with(key.to_string(), || Value::List(List::default())) {
+            Value::List(list) => list,
+            _ => unreachable!("entry was just created as a list"),
+        },
+    };
+    for value in values {
+        list.push(end, value.clone());
+    }
+    Ok(list.len())
+}
+
+/// Pops up to `count` elements from `end` of the list at `key`, removing the key once the list
+/// is empty. Returns `None` when the key does not exist.
+pub fn pop_from(
+    data: &mut Keyspace,
+    key: &str,
+    end: ListEnd,
+    count: usize,
+) -> Result<Option<Vec<String>>, &'static str> {
+    let Some(list) = list_mut(data, key)? else {
+        return Ok(None);
+    };
+    let popped: Vec<String> = (0..count).map_while(|_| list.pop(end)).collect();
+    if list.is_empty() {
+        data.remove(key);
+    }
+    Ok(Some(popped))
+}
+
+/// Atomically pops from `from` of `source` and pushes onto `to` of `destination`.
+pub fn move_between(
+    data: &mut Keyspace,
+    source: &str,
+    destination: &str,
+    from: ListEnd,
+    to: ListEnd,
+) -> Result<Option<String>, &'static str> {
+    if list_mut(data, source)?.is_none() {
+        return Ok(None);
+    }
+    list_mut(data, destination)?;
+    let value = match pop_from(data, source, from, 1)? {
+        Some(mut popped) => popped.remove(0),
+        None => return Ok(None),
+    };
+    push_to(data, destination, std::slice::from_ref(&value), to, false)?;
+    Ok(Some(value))
+}
+
+/// Redis style inclusive range where negative indexes count from the tail.
+pub fn range_of(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        None
+    } else {
+        Some((start as usize, stop as usize))
+    }
+}
+
+impl Cache {
+    pub fn push(
+        &self,
+        key: &str,
+        values: &[String],
+        end: ListEnd,
+        only_if_exists: bool,
+    ) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let len = push_to(&mut data, key, values, end, only_if_exists)?;
+        self.blocking.serve(&mut data, key);
+        Ok(len)
+    }
+
+    pub fn pop(
+        &self,
+        key: &str,
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<Vec<String>>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        pop_from(&mut data, key, end, count)
+    }
+
+    /// Pops from the first non-empty list among `keys`, as LMPOP does.
+    pub fn pop_first(
+        &self,
+        keys: &[String],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<(String, Vec<String>)>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        for key in keys {
+            if let Some(popped) = pop_from(&mut data, key, end, count)? {
+                return Ok(Some((key.clone(), popped)));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn list_move(
+        &self,
+        source: &str,
+        destination: &str,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<String>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let moved = move_between(&mut data, source, destination, from, to)?;
+        if moved.is_some() {
+            self.blocking.serve(&mut data, destination);
+        }
+        Ok(moved)
+    }
+
+    pub fn list_range(
+        &self,
+        key: &str,
+        start: i64,
+        stop: i64,
+    ) -> Result<Vec<String>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        match data.get(key) {
+            Some(Value::List(list)) => Ok(range_of(list.len(), start, stop)
+                .map(|(start, stop)| list.range(start, stop))
+                .unwrap_or_default()),
+            Some(_) => Err(WRONGTYPE_ERR),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn list_len(&self, key: &str) -> Result<usize, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        match data.get(key) {
+            Some(Value::List(list)) => Ok(list.len()),
+            Some(_) => Err(WRONGTYPE_ERR),
+            None => Ok(0),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+/// Strings packed back to back in a single buffer, each behind its length as a LEB128 varint,
+/// like Redis' listpack. Small collections keep their elements here instead of paying for one
+/// allocation per element and the slots of a hash table. Finding an element is a linear scan,
+/// which at these sizes is about as fast as hashing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listpack {
+    buffer: Vec<u8>,
+    len: usize,
+}
+
+fn encoded_len(bytes: usize) -> usize {
+    let mut header = 1;
+    let mut rest = bytes >> 7;
+    while rest > 0 {
+        header += 1;
+        rest >>= 7;
+    }
+    header + bytes
+}
+
+fn encode(value: &str) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(encoded_len(value.len()));
+    let mut rest = value.len();
+    loop {
+        let byte = (rest & 0x7f) as u8;
+        rest >>= 7;
+        if rest == 0 {
+            encoded.push(byte);
+            break;
+        }
+        encoded.push(byte | 0x80);
+    }
+    encoded.extend_from_slice(value.as_bytes());
+    encoded
+}
+
+/// Iterator over the strings of a listpack, front to back.
+pub struct Iter<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.offset >= self.buffer.len() {
+            return None;
+        }
+        let (start, end) = Listpack::span(self.buffer, self.offset);
+        self.offset = end;
+        Some(std::str::from_utf8(&self.buffer[start..end]).expect("listpacks hold whole strings"))
+    }
+}
+
+impl Listpack {
+    /// Where the string of the entry at `offset` starts and ends.
+    fn span(buffer: &[u8], offset: usize) -> (usize, usize) {
+        let mut len = 0;
+        let mut shift = 0;
+        let mut position = offset;
+        loop {
+            let byte = buffer[position];
+            position += 1;
+            len |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return (position, position + len);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Byte offset of the entry at `index`, or the end of the buffer for `len`.
+    fn offset(&self, index: usize) -> usize {
+        let mut offset = 0;
+        for _ in 0..index {
+            offset = Self::span(&self.buffer, offset).1;
+        }
+        offset
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Bytes the entries take up.
+    pub fn bytes(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Bytes the buffer holds on the heap.
+    pub fn allocated(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            buffer: &self.buffer,
+            offset: 0,
+        }
+    }
+
+    /// The entries two by two, for collections that store a field next to its value.
+    pub fn pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        let mut entries = self.iter();
+        std::iter::from_fn(move || Some((entries.next()?, entries.next()?)))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.iter().nth(index)
+    }
+
+    /// Inserts `value` before the entry at `index`, or at the end when `index` is `len`. The
+    /// buffer grows by exactly the new entry, so it never holds spare room.
+    pub fn insert(&mut self, index: usize, value: &str) {
+        let offset = self.offset(index.min(self.len));
+        let encoded = encode(value);
+        self.buffer.reserve_exact(encoded.len());
+        self.buffer.splice(offset..offset, encoded);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, value: &str) {
+        self.insert(self.len, value);
+    }
+
+    pub fn push_front(&mut self, value: &str) {
+        self.insert(0, value);
+    }
+
+    /// Removes `count` entries starting at `index` and returns them.
+    pub fn remove_range(&mut self, index: usize, count: usize) -> Vec<String> {
+        let count = count.min(self.len.saturating_sub(index));
+        let start = self.offset(index);
+        let mut end = start;
+        let mut removed = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (value_start, value_end) = Self::span(&self.buffer, end);
+            removed.push(
+                String::from_utf8(self.buffer[value_start..value_end].to_vec())
+                    .expect("listpacks hold whole strings"),
+            );
+            end = value_end;
+        }
+        self.buffer.drain(start..end);
+        self.len -= count;
+        if self.buffer.capacity() > 2 * self.buffer.len() {
+            self.buffer.shrink_to_fit();
+        }
+        removed
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<String> {
+        self.remove_range(index, 1).pop()
+    }
+
+    pub fn pop_front(&mut self) -> Option<String> {
+        self.remove(0)
+    }
+
+    pub fn pop_back(&mut self) -> Option<String> {
+        self.len.checked_sub(1).and_then(|last| self.remove(last))
+    }
+
+    /// Replaces the entry at `index` with `value`.
+    pub fn replace(&mut self, index: usize, value: &str) {
+        let start = self.offset(index);
+        let end = Self::span(&self.buffer, start).1;
+        let encoded = encode(value);
+        self.buffer
+            .reserve_exact(encoded.len().saturating_sub(end - start));
+        self.buffer.splice(start..end, encoded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_pack_insert_and_remove_strings() {
+        let mut pack = Listpack::default();
+        let long = "x".repeat(300);
+        pack.push_back("b");
+        pack.push_front("a");
+        pack.push_back(&long);
+        pack.insert(2, "");
+        assert_eq!(
+            pack.iter().collect::<Vec<_>>(),
+            ["a", "b", "", long.as_str()]
+        );
+        // One byte of length for short strings and two for the long one.
+        assert_eq!(pack.allocated(), 2 + 2 + 1 + 302);
+
+        pack.replace(1, "éé");
+        assert_eq!(pack.get(1), Some("éé"));
+        assert_eq!(
+            pack.pairs().collect::<Vec<_>>(),
+            [("a", "éé"), ("", long.as_str())]
+        );
+        assert_eq!(pack.pop_back(), Some(long));
+        assert_eq!(pack.remove_range(0, 5), ["a", "éé", ""]);
+        assert!(pack.is_empty());
+        assert_eq!(pack.pop_front(), None);
+        assert_eq!(pack.pop_back(), None);
+    }
+}
@@ -0,0 +1,177 @@
+use std::mem::size_of;
+
+use super::{
+    db::{Cache, Value},
+    hash::Hash,
+    set::Set,
+    stream::Stream,
+    zset::SortedSet,
+};
+
+/// Bookkeeping around every key: its hash table entry, the slot holding the value's size and
+/// access data, and the copies of the name kept for random sampling and in scan order.
+pub const KEY_OVERHEAD: usize = 136;
+/// What an expiry adds to a key: its entries in the volatile index, which share one copy of the
+/// name.
+pub const EXPIRY_OVERHEAD: usize = 64;
+/// Hash table or tree entry overhead per element of a collection, on top of the element.
+pub const ELEMENT_OVERHEAD: usize = 16;
+/// Elements looked at to estimate a collection, like the default of MEMORY USAGE.
+pub const DEFAULT_SAMPLES: usize = 5;
+
+/// Heap and inline size of a string.
+pub fn string_usage(string: &String) -> usize {
+    size_of::<String>() + string.capacity()
+}
+
+/// Estimates the total size of `len` elements from the sizes `elements` yields: all of them
+/// when `samples` is zero, otherwise the first `samples`, like Redis' MEMORY USAGE.
+pub fn estimate(elements: impl Iterator<Item = usize>, len: usize, samples: usize) -> usize {
+    let wanted = if samples == 0 { len } else { samples.min(len) };
+    let (counted, total) = elements
+        .take(wanted)
+        .fold((0_usize, 0_usize), |(counted, total), size| {
+            (counted + 1, total + size)
+        });
+    if counted == 0 {
+        return 0;
+    }
+    (total as u128 * len as u128 / counted as u128) as usize
+}
+
+/// Estimated memory held by `value`, sampling `samples` elements of collections (all of them
+/// when zero). The estimates follow the Rust structures on a 64 bit build, so they track growth
+/// closely without walking large collections.
+pub fn value_usage(value: &Value, samples: usize) -> usize {
+    size_of::<Value>()
+        + match value {
+            Value::String(string) => string.capacity(),
+            Value::List(list) => list.memory_usage(samples),
+            Value::Hash(hash) => size_of::<Hash>() + hash.memory_usage(samples),
+            Value::Set(set) => size_of::<Set>() + set.memory_usage(samples),
+            Value::SortedSet(zset) => size_of::<SortedSet>() + zset.memory_usage(samples),
+            Value::Stream(stream) => size_of::<Stream>() + stream.memory_usage(samples),
+        }
+}
+
+/// Estimated memory of `key` holding `value`, including the key space's own bookkeeping.
+pub fn key_usage(key: &str, value: &Value, samples: usize) -> usize {
+    KEY_OVERHEAD + 3 * key.len() + value_usage(value, samples)
+}
+
+/// Byte counts the way INFO prints them, e.g. `1.50M`.
+pub fn human_bytes(bytes: usize) -> String {
+    const UNITS: [(f64, &str); 4] = [
+        (1024.0 * 1024.0 * 1024.0 * 1024.0, "T"),
+        (1024.0 * 1024.0 * 1024.0, "G"),
+        (1024.0 * 1024.0, "M"),
+        (1024.0, "K"),
+    ];
+    let bytes_f = bytes as f64;
+    UNITS.iter().find(|(unit, _)| bytes_f >= *unit).map_or_else(
+        || concat_string!(bytes.to_string(), "B"),
+        |(unit, suffix)| format!("{:.2}{}", bytes_f / unit, suffix),
+    )
+}
+
+/// Where the memory of one database goes, as MEMORY STATS reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseMemory {
+    pub index: usize,
+    pub keys: usize,
+    pub used: usize,
+    /// Per key bookkeeping of the key space.
+    pub overhead_main: usize,
+    /// The expiry index.
+    pub overhead_expires: usize,
+}
+
+/// A key among the largest of the server, for MEMORY DOCTOR.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LargeKey {
+    pub db: usize,
+    pub key: String,
+    pub type_name: &'static str,
+    pub size: usize,
+}
+
+impl Cache {
+    /// Estimated bytes of `key`, sampling `samples` elements of collections (all when zero).
+    pub fn memory_usage(&self, key: &str, samples: usize) -> Result<Option<usize>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(data.key_memory(key, samples))
+    }
+
+    /// Memory breakdown of every database that holds keys.
+    pub fn memory_stats(&self) -> Result<Vec<DatabaseMemory>, &'static str> {
+        let mut stats = Vec::new();
+        for (index, database) in self.databases.iter().enumerate() {
+            let data = database
+                .data
+                .read()
+                .map_err(|_| "Could not acquire data read lock")?;
+            if data.len() == 0 {
+                continue;
+            }
+            let (overhead_main, overhead_expires) = data.overhead();
+            stats.push(DatabaseMemory {
+                index,
+                keys: data.len(),
+                used: data.used_memory(),
+                overhead_main,
+                overhead_expires,
+            });
+        }
+        Ok(stats)
+    }
+
+    /// The `count` largest keys across all databases, largest first.
+    pub fn largest_keys(&self, count: usize) -> Result<Vec<LargeKey>, &'static str> {
+        let mut largest = Vec::new();
+        for (db, database) in self.databases.iter().enumerate() {
+            let data = database
+                .data
+                .read()
+                .map_err(|_| "Could not acquire data read lock")?;
+            largest.extend(
+                data.largest_keys(count)
+                    .into_iter()
+                    .map(|(key, value, size)| LargeKey {
+                        db,
+                        key: key.clone(),
+                        type_name: value.type_name(),
+                        size,
+                    }),
+            );
+        }
+        largest.sort_by_key(|large| std::cmp::Reverse(large.size));
+        largest.truncate(count);
+        Ok(largest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::store::list::List;
+
+    use super::*;
+
+    #[test]
+    fn should_extrapolate_from_samples() {
+        assert_eq!(estimate([10, 20].into_iter(), 100, 2), 1500);
+        assert_eq!(estimate([10, 20, 60].into_iter(), 3, 0), 90);
+        assert_eq!(estimate(std::iter::empty(), 0, 5), 0);
+
+        let short = Value::List(List::Deque(VecDeque::from(vec!["a".to_string(); 10])));
+        let long = Value::List(List::Deque(VecDeque::from(vec!["a".to_string(); 1000])));
+        assert!(value_usage(&long, 5) > 50 * value_usage(&short, 5));
+        assert_eq!(human_bytes(512), "512B");
+        assert_eq!(human_bytes(1536), "1.50K");
+        assert_eq!(human_bytes(3 * 1024 * 1024), "3.00M");
+    }
+}
@@ -1 +1,26 @@
+pub mod bitmap;
+pub mod blocking;
+pub mod bytes;
+pub mod consumer_group;
 pub mod db;
+pub mod eviction;
+pub mod expire;
+pub mod geo;
+pub mod hash;
+pub mod hyperloglog;
+pub mod keys;
+pub mod keyspace;
+pub mod list;
+pub mod listpack;
+pub mod memory;
+pub mod pattern;
+pub mod radix;
+pub mod random;
+pub mod scan;
+pub mod set;
+pub mod skiplist;
+pub mod stream;
+pub mod string;
+#[cfg(feature = "timing-wheel")]
+pub mod timing_wheel;
+pub mod zset;
@@ -0,0 +1,110 @@
+/// Redis glob-style matching: `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and `\` escapes.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&pattern, &text)
+}
+
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            '*' => {
+                while pattern.get(p + 1) == Some(&'*') {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                return (t..=text.len()).any(|start| matches(&pattern[p + 1..], &text[start..]));
+            }
+            '?' => {
+                if t == text.len() {
+                    return false;
+                }
+                t += 1;
+            }
+            '[' => {
+                let Some(&ch) = text.get(t) else {
+                    return false;
+                };
+                let (matched, end) = match_class(pattern, p + 1, ch);
+                if !matched {
+                    return false;
+                }
+                p = end;
+                t += 1;
+            }
+            '\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if text.get(t) != Some(&pattern[p]) {
+                    return false;
+                }
+                t += 1;
+            }
+            literal => {
+                if text.get(t) != Some(&literal) {
+                    return false;
+                }
+                t += 1;
+            }
+        }
+        p += 1;
+    }
+    t == text.len()
+}
+
+/// Matches `ch` against the class starting right after `[`. Returns whether it matched and the
+/// index of the closing `]` (or the last pattern index for an unterminated class).
+fn match_class(pattern: &[char], mut p: usize, ch: char) -> (bool, usize) {
+    let negate = pattern.get(p) == Some(&'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != ']' {
+        if pattern[p] == '\\' && p + 1 < pattern.len() {
+            p += 1;
+            matched |= pattern[p] == ch;
+        } else if pattern.get(p + 1) == Some(&'-') && p + 2 < pattern.len() && pattern[p + 2] != ']'
+        {
+            let (mut low, mut high) = (pattern[p], pattern[p + 2]);
+            if low > high {
+                std::mem::swap(&mut low, &mut high);
+            }
+            matched |= (low..=high).contains(&ch);
+            p += 2;
+        } else {
+            matched |= pattern[p] == ch;
+        }
+        p += 1;
+    }
+    let end = p.min(pattern.len() - 1);
+    (matched != negate, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_match_wildcards() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("user:*:name", "user:42:name"));
+        assert!(glob_match("h?llo", "hello"));
+        assert!(!glob_match("h?llo", "hllo"));
+        assert!(!glob_match("user:*", "session:1"));
+    }
+
+    #[test]
+    fn should_match_classes_and_escapes() {
+        assert!(glob_match("h[ae]llo", "hallo"));
+        assert!(!glob_match("h[ae]llo", "hillo"));
+        assert!(glob_match("h[^e]llo", "hallo"));
+        assert!(!glob_match("h[^e]llo", "hello"));
+        assert!(glob_match("h[a-c]llo", "hbllo"));
+        assert!(glob_match("what\\?", "what?"));
+        assert!(!glob_match("what\\?", "whats"));
+        assert!(glob_match("\\*[\\]]", "*]"));
+    }
+}
@@ -0,0 +1,388 @@
+use std::cmp::Ordering;
+
+/// A node owns the bytes of `prefix`; each child is reached through one more byte.
+#[derive(Debug, Clone)]
+struct Node<V> {
+    prefix: Vec<u8>,
+    value: Option<V>,
+    children: Vec<(u8, Node<V>)>,
+}
+
+/// Compressed radix tree in the spirit of Redis' rax: chains of single-child nodes are
+/// collapsed into one prefix, children stay sorted by byte, and besides exact lookups it can
+/// seek to the nearest key on either side, which is what stream range scans need.
+#[derive(Debug, Clone)]
+pub struct RadixTree<V> {
+    root: Node<V>,
+    len: usize,
+}
+
+impl<V> Default for RadixTree<V> {
+    fn default() -> Self {
+        Self {
+            root: Node::leaf(Vec::new(), None),
+            len: 0,
+        }
+    }
+}
+
+/// Compares the prefix of a node against the same number of leading bytes of `key`. A key
+/// that runs out first sorts before every key below the node.
+fn compare_prefix(prefix: &[u8], key: &[u8]) -> Ordering {
+    let shared = prefix.len().min(key.len());
+    prefix[..shared]
+        .cmp(&key[..shared])
+        .then(if key.len() < prefix.len() {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        })
+}
+
+impl<V> Node<V> {
+    fn leaf(prefix: Vec<u8>, value: Option<V>) -> Self {
+        Self {
+            prefix,
+            value,
+            children: Vec::new(),
+        }
+    }
+
+    fn child_position(&self, byte: u8) -> Result<usize, usize> {
+        self.children
+            .binary_search_by_key(&byte, |(child_byte, _)| *child_byte)
+    }
+
+    fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let common = self
+            .prefix
+            .iter()
+            .zip(key)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if common < self.prefix.len() {
+            let byte = self.prefix[common];
+            let split = Node {
+                prefix: self.prefix[common + 1..].to_vec(),
+                value: self.value.take(),
+                children: std::mem::take(&mut self.children),
+            };
+            self.prefix.truncate(common);
+            self.children.push((byte, split));
+        }
+        let Some((&byte, rest)) = key[common..].split_first() else {
+            return self.value.replace(value);
+        };
+        match self.child_position(byte) {
+            Ok(position) => self.children[position].1.insert(rest, value),
+            Err(position) => {
+                let leaf = Node::leaf(rest.to_vec(), Some(value));
+                self.children.insert(position, (byte, leaf));
+                None
+            }
+        }
+    }
+
+    #[cfg(test)]
+    fn find(&self, key: &[u8]) -> Option<&Node<V>> {
+        let rest = key.strip_prefix(self.prefix.as_slice())?;
+        match rest.split_first() {
+            None => Some(self),
+            Some((&byte, rest)) => {
+                let position = self.child_position(byte).ok()?;
+                self.children[position].1.find(rest)
+            }
+        }
+    }
+
+    fn find_mut(&mut self, key: &[u8]) -> Option<&mut Node<V>> {
+        let rest = key.strip_prefix(self.prefix.as_slice())?;
+        match rest.split_first() {
+            None => Some(self),
+            Some((&byte, rest)) => {
+                let position = self.child_position(byte).ok()?;
+                self.children[position].1.find_mut(rest)
+            }
+        }
+    }
+
+    /// Removes `key` and re-compresses the path: empty children are dropped and a child left
+    /// without a value and with a single child of its own absorbs it.
+    fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let rest = key.strip_prefix(self.prefix.as_slice())?;
+        let Some((&byte, rest)) = rest.split_first() else {
+            return self.value.take();
+        };
+        let position = self.child_position(byte).ok()?;
+        let removed = self.children[position].1.remove(rest)?;
+        let child = &mut self.children[position].1;
+        if child.value.is_none() {
+            match child.children.len() {
+                0 => {
+                    self.children.remove(position);
+                }
+                1 => {
+                    let (grandchild_byte, grandchild) = child.children.remove(0);
+                    child.prefix.push(grandchild_byte);
+                    child.prefix.extend(grandchild.prefix);
+                    child.value = grandchild.value;
+                    child.children = grandchild.children;
+                }
+                _ => {}
+            }
+        }
+        Some(removed)
+    }
+
+    fn first(&self, path: &mut Vec<u8>) -> Option<&V> {
+        path.extend_from_slice(&self.prefix);
+        if let Some(value) = &self.value {
+            return Some(value);
+        }
+        let (byte, child) = self.children.first()?;
+        path.push(*byte);
+        child.first(path)
+    }
+
+    fn last(&self, path: &mut Vec<u8>) -> Option<&V> {
+        path.extend_from_slice(&self.prefix);
+        match self.children.last() {
+            Some((byte, child)) => {
+                path.push(*byte);
+                child.last(path)
+            }
+            None => self.value.as_ref(),
+        }
+    }
+
+    /// Smallest key that is greater than or equal to `key`.
+    fn ceiling(&self, key: &[u8], path: &mut Vec<u8>) -> Option<&V> {
+        let start = path.len();
+        match compare_prefix(&self.prefix, key) {
+            Ordering::Greater => return self.first(path),
+            Ordering::Less => return None,
+            Ordering::Equal => {}
+        }
+        path.extend_from_slice(&self.prefix);
+        let Some((&byte, rest)) = key[self.prefix.len()..].split_first() else {
+            path.truncate(start);
+            return self.first(path);
+        };
+        let position = match self.child_position(byte) {
+            Ok(position) => {
+                let (child_byte, child) = &self.children[position];
+                path.push(*child_byte);
+                if let Some(value) = child.ceiling(rest, path) {
+                    return Some(value);
+                }
+                path.pop();
+                position + 1
+            }
+            Err(position) => position,
+        };
+        match self.children.get(position) {
+            Some((child_byte, child)) => {
+                path.push(*child_byte);
+                child.first(path)
+            }
+            None => {
+                path.truncate(start);
+                None
+            }
+        }
+    }
+
+    /// Greatest key that is less than or equal to `key`.
+    fn floor(&self, key: &[u8], path: &mut Vec<u8>) -> Option<&V> {
+        let start = path.len();
+        match compare_prefix(&self.prefix, key) {
+            Ordering::Less => return self.last(path),
+            Ordering::Greater => return None,
+            Ordering::Equal => {}
+        }
+        path.extend_from_slice(&self.prefix);
+        let Some((&byte, rest)) = key[self.prefix.len()..].split_first() else {
+            if self.value.is_none() {
+                path.truncate(start);
+            }
+            return self.value.as_ref();
+        };
+        let position = match self.child_position(byte) {
+            Ok(position) => {
+                let (child_byte, child) = &self.children[position];
+                path.push(*child_byte);
+                if let Some(value) = child.floor(rest, path) {
+                    return Some(value);
+                }
+                path.pop();
+                position
+            }
+            Err(position) => position,
+        };
+        match position
+            .checked_sub(1)
+            .map(|position| &self.children[position])
+        {
+            Some((child_byte, child)) => {
+                path.push(*child_byte);
+                child.last(path)
+            }
+            None if self.value.is_some() => self.value.as_ref(),
+            None => {
+                path.truncate(start);
+                None
+            }
+        }
+    }
+
+    fn count_nodes(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .map(|(_, child)| child.count_nodes())
+            .sum::<usize>()
+    }
+}
+
+impl<V> RadixTree<V> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of nodes, as reported by XINFO STREAM.
+    pub fn node_count(&self) -> usize {
+        self.root.count_nodes()
+    }
+
+    pub fn insert(&mut self, key: &[u8], value: V) -> Option<V> {
+        let replaced = self.root.insert(key, value);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    #[cfg(test)]
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        self.root.find(key)?.value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        self.root.find_mut(key)?.value.as_mut()
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        let removed = self.root.remove(key);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn first(&self) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.first(&mut path).map(|value| (path, value))
+    }
+
+    pub fn last(&self) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.last(&mut path).map(|value| (path, value))
+    }
+
+    /// Smallest entry whose key is `key` or comes after it.
+    pub fn ceiling(&self, key: &[u8]) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.ceiling(key, &mut path).map(|value| (path, value))
+    }
+
+    /// Greatest entry whose key is `key` or comes before it.
+    pub fn floor(&self, key: &[u8]) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        self.root.floor(key, &mut path).map(|value| (path, value))
+    }
+
+    /// Entry right after `key`, which does not have to be in the tree.
+    pub fn successor(&self, key: &[u8]) -> Option<(Vec<u8>, &V)> {
+        let mut next = key.to_vec();
+        next.push(0);
+        self.ceiling(&next)
+    }
+
+    /// Entry right before `key`, which does not have to be in the tree.
+    pub fn predecessor(&self, key: &[u8]) -> Option<(Vec<u8>, &V)> {
+        let mut path = Vec::new();
+        let value = self.root.floor(key, &mut path)?;
+        if path != key {
+            return Some((path, value));
+        }
+        // Fixed length keys: step back by decrementing the key with borrow.
+        let mut previous = key.to_vec();
+        for byte in previous.iter_mut().rev() {
+            if *byte > 0 {
+                *byte -= 1;
+                return self.floor(&previous);
+            }
+            *byte = u8::MAX;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(n: u64) -> [u8; 8] {
+        n.to_be_bytes()
+    }
+
+    #[test]
+    fn should_insert_find_and_remove() {
+        let mut tree = RadixTree::default();
+        for n in [5_u64, 1, 300, 70_000, 256, 2] {
+            assert_eq!(tree.insert(&key(n), n), None);
+        }
+        assert_eq!(tree.insert(&key(5), 50), Some(5));
+        assert_eq!(tree.len(), 6);
+        assert_eq!(tree.get(&key(256)), Some(&256));
+        assert_eq!(tree.get(&key(257)), None);
+        *tree.get_mut(&key(2)).unwrap() = 20;
+
+        assert_eq!(tree.remove(&key(256)), Some(256));
+        assert_eq!(tree.remove(&key(256)), None);
+        assert_eq!(tree.get(&key(300)), Some(&300));
+        assert_eq!(tree.first().map(|(_, v)| *v), Some(1));
+        assert_eq!(
+            tree.last().map(|(k, v)| (k, *v)),
+            Some((key(70_000).to_vec(), 70_000))
+        );
+        for n in [1_u64, 2, 5, 300, 70_000] {
+            tree.remove(&key(n));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.node_count(), 1);
+    }
+
+    #[test]
+    fn should_seek_to_neighbours() {
+        let mut tree = RadixTree::default();
+        for n in [10_u64, 20, 300, 5_000] {
+            tree.insert(&key(n), n);
+        }
+        let value = |entry: Option<(Vec<u8>, &u64)>| entry.map(|(_, v)| *v);
+        assert_eq!(value(tree.ceiling(&key(11))), Some(20));
+        assert_eq!(value(tree.ceiling(&key(20))), Some(20));
+        assert_eq!(value(tree.ceiling(&key(5_001))), None);
+        assert_eq!(value(tree.floor(&key(299))), Some(20));
+        assert_eq!(value(tree.floor(&key(9))), None);
+        assert_eq!(value(tree.floor(&key(u64::MAX))), Some(5_000));
+        assert_eq!(value(tree.successor(&key(20))), Some(300));
+        assert_eq!(value(tree.predecessor(&key(300))), Some(20));
+        assert_eq!(value(tree.predecessor(&key(10))), None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// xorshift64* seeded per thread. Good enough for sampling, not for anything secret.
+pub fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+/// Uniform index in `0..len`. `len` must not be zero.
+pub fn index_below(len: usize) -> usize {
+    (next_u64() % len as u64) as usize
+}
+
+/// Picks `count` distinct items, or every item in random order if there are fewer.
+pub fn sample_distinct<T: Clone>(items: &[T], count: usize) -> Vec<T> {
+    let mut indexes: Vec<usize> = (0..items.len()).collect();
+    let count = count.min(items.len());
+    for i in 0..count {
+        let j = i + index_below(items.len() - i);
+        indexes.swap(i, j);
+    }
+    indexes[..count].iter().map(|&i| items[i].clone()).collect()
+}
+
+pub const COUNT_OUT_OF_RANGE_ERR: &str = "ERR value is out of range";
+
+/// Most items a sample with repetition may hold. Redis streams such replies and lets the client
+/// output buffer limit cut off huge ones, while replies here are built in memory first.
+pub const MAX_REPEATED_SAMPLES: u64 = 1 << 20;
+
+/// Checks the count of HRANDFIELD, SRANDMEMBER and ZRANDMEMBER. Like Redis, negative counts
+/// below `-i64::MAX / 2` are out of range, and so are ones asking for more repeated samples
+/// than a reply can hold.
+pub fn check_sample_count(count: i64) -> Result<(), &'static str> {
+    if count < -(i64::MAX / 2) || (count < 0 && count.unsigned_abs() > MAX_REPEATED_SAMPLES) {
+        return Err(COUNT_OUT_OF_RANGE_ERR);
+    }
+    Ok(())
+}
+
+/// Picks `count` items where the same item may show up more than once.
+pub fn sample_with_repetition<T: Clone>(items: &[T], count: usize) -> Vec<T> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    (0..count)
+        .map(|_| items[index_below(items.len())].clone())
+        .collect()
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use super::{db::Cache, pattern::glob_match};
+
+/// Smallest table a scan pretends to walk, like Redis' initial dict size of four buckets.
+const MIN_TABLE_BITS: u32 = 2;
+
+/// The cursor to continue from, zero once the iteration is over, and the elements of a page.
+pub type ScanPage<T> = (u64, Vec<T>);
+
+/// Stable bucket hash of a key, member or field. The std hasher keys are fixed, so a cursor
+/// handed out by one call still points at the same place in the next one.
+fn bucket_hash(name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Where `name` comes in a scan: its bucket hash with the bits reversed. Whatever the table
+/// size, the elements of a bucket are next to each other in this order and the buckets follow
+/// each other in cursor order, so a collection kept sorted by it can be scanned in place.
+pub fn scan_position(name: &str) -> u64 {
+    bucket_hash(name).reverse_bits()
+}
+
+/// How far to shift a scan position to get the bucket of a table holding `len` elements.
+fn table_shift(len: usize) -> u32 {
+    let bits = len.next_power_of_two().trailing_zeros().max(MIN_TABLE_BITS);
+    u64::BITS - bits
+}
+
+/// The smallest scan position a page of a `len` element collection starts from at `cursor`.
+pub fn scan_start(len: usize, cursor: u64) -> u64 {
+    let shift = table_shift(len);
+    // Cursors from a larger table lose their extra bits, which revisits the merged bucket.
+    (cursor.reverse_bits() >> shift) << shift
+}
+
+/// One page of Redis' reverse binary cursor iteration over `entries`, a collection of `len`
+/// elements named by their first item.
+///
+/// The elements sit in a virtual table of `len` rounded up to a power of two buckets, each in
+/// the bucket given by the low bits of its hash. The cursor is a bucket index with its bits
+/// reversed and incremented from the top, so buckets are visited in ascending order of their
+/// reversed hash bits. Growing the table splits a bucket into ones that come right after it in
+/// that order, and shrinking merges it with them, so every element present for the whole
+/// iteration is returned at least once however the collection is resized between calls.
+///
+/// Whole buckets are returned until at least `count` elements have been collected. The next
+/// cursor is zero once the last bucket has been visited.
+pub fn scan_page<'a, T>(
+    entries: impl Iterator<Item = (&'a str, T)>,
+    len: usize,
+    cursor: u64,
+    count: usize,
+) -> ScanPage<T> {
+    let start = scan_start(len, cursor);
+    let mut pending: Vec<(u64, T)> = entries
+        .map(|(name, entry)| (scan_position(name), entry))
+        .filter(|(position, _)| *position >= start)
+        .collect();
+    pending.sort_unstable_by_key(|(position, _)| *position);
+    scan_sorted(pending.into_iter(), len, count)
+}
+
+/// The page `scan_page` returns, taken from `entries` that are already sorted by scan position
+/// and start at the one `scan_start` gives for the cursor. Only the elements of the page and the
+/// first one after it are read, so a collection kept in that order pays for the page alone.
+pub fn scan_sorted<T>(
+    entries: impl Iterator<Item = (u64, T)>,
+    len: usize,
+    count: usize,
+) -> ScanPage<T> {
+    let shift = table_shift(len);
+    let mut page = Vec::new();
+    let mut entries = entries.peekable();
+    while let Some((position, entry)) = entries.next() {
+        page.push(entry);
+        match entries.peek() {
+            Some((next, _)) if next >> shift != position >> shift && page.len() >= count => {
+                return (((next >> shift) << shift).reverse_bits(), page);
+            }
+            _ => {}
+        }
+    }
+    (0, page)
+}
+
+impl Cache {
+    /// Every live key matching `pattern`.
+    pub fn keys(&self, pattern: &str) -> Result<Vec<String>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(data
+            .iter()
+            .filter(|(key, _)| glob_match(pattern, key))
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
+
+    /// One SCAN page: the next cursor and the keys of the visited buckets that match `pattern`
+    /// and hold a value of `type_name`. Filtering happens after the buckets are picked, so a
+    /// page can come back short or even empty before the iteration is over.
+    pub fn scan(
+        &self,
+        cursor: u64,
+        count: usize,
+        pattern: Option<&str>,
+        type_name: Option<&str>,
+    ) -> Result<ScanPage<String>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let (cursor, page) = data.scan(cursor, count);
+        let keys = page
+            .into_iter()
+            .filter(|(key, value)| {
+                pattern.is_none_or(|pattern| glob_match(pattern, key))
+                    && type_name.is_none_or(|type_name| value.type_name() == type_name)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        Ok((cursor, keys))
+    }
+}
+
+/// The type names TYPE reports and SCAN's TYPE option accepts.
+pub fn is_type_name(name: &str) -> bool {
+    ["string", "list", "set", "zset", "hash", "stream"].contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn scan_all(names: &[String], count: usize) -> Vec<String> {
+        let mut cursor = 0;
+        let mut seen = Vec::new();
+        loop {
+            let (next, page) = scan_page(
+                names.iter().map(|name| (name.as_str(), name.clone())),
+                names.len(),
+                cursor,
+                count,
+            );
+            seen.extend(page);
+            if next == 0 {
+                return seen;
+            }
+            cursor = next;
+        }
+    }
+
+    #[test]
+    fn should_visit_every_element_exactly_once_without_resizes() {
+        let names: Vec<String> = (0..1000).map(|i| format!("key:{}", i)).collect();
+        for count in [1, 10, 100, 5000] {
+            let mut seen = scan_all(&names, count);
+            seen.sort();
+            let mut expected = names.clone();
+            expected.sort();
+            assert_eq!(seen, expected);
+        }
+    }
+
+    #[test]
+    fn should_return_stable_elements_while_the_table_resizes() {
+        let stable: Vec<String> = (0..300).map(|i| format!("stable:{}", i)).collect();
+        // Grow to thousands of elements, then shrink below the stable set, between pages.
+        for phases in [[0, 50, 4000, 20, 0, 9000], [9000, 0, 4000, 10, 600, 0]] {
+            let mut seen = HashSet::new();
+            let mut cursor = 0;
+            for extra in phases.iter().cycle() {
+                let mut names = stable.clone();
+                names.extend((0..*extra).map(|i| format!("extra:{}", i)));
+                let (next, page) = scan_page(
+                    names.iter().map(|name| (name.as_str(), name.clone())),
+                    names.len(),
+                    cursor,
+                    25,
+                );
+                seen.extend(page);
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+            assert!(stable.iter().all(|name| seen.contains(name)));
+        }
+    }
+}
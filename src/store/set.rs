@@ -0,0 +1,549 @@
+use std::{borrow::Cow, collections::HashSet, mem::size_of};
+
+use super::{
+    db::{Cache, Value, WRONGTYPE_ERR},
+    keyspace::Keyspace,
+    listpack::Listpack,
+    memory::{estimate, string_usage, ELEMENT_OVERHEAD},
+    random::{check_sample_count, index_below, sample_distinct, sample_with_repetition},
+    scan::{scan_page, ScanPage},
+};
+
+/// Sets made only of integers stay in a sorted vector until they grow past this many members.
+pub const SET_MAX_INTSET_ENTRIES: usize = 512;
+/// Other small sets stay in a listpack up to this many members...
+pub const SET_MAX_LISTPACK_ENTRIES: usize = 128;
+/// ...as long as none of them is longer than this many bytes.
+pub const SET_MAX_LISTPACK_VALUE: usize = 64;
+
+/// A set is a sorted vector of integers (Redis' intset), a listpack of members or a plain hash
+/// set. Sets start as intsets and move to a bigger representation for good as soon as a member
+/// does not fit the current one: a non-integer member turns an intset into a listpack, and too
+/// many or too long members turn either into a hash set.
+#[derive(Debug, Clone)]
+pub enum Set {
+    Intset(Vec<i64>),
+    Listpack(Listpack),
+    HashTable(HashSet<String>),
+}
+
+impl PartialEq for Set {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.members().iter().all(|member| other.contains(member))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOperation {
+    Inter,
+    Union,
+    Diff,
+}
+
+/// Parses `member` as an integer only if it prints back identically, so "01" or "+1" stay
+/// strings and members round-trip byte for byte.
+fn as_integer(member: &str) -> Option<i64> {
+    member
+        .parse::<i64>()
+        .ok()
+        .filter(|value| value.to_string() == member)
+}
+
+/// Whether a set of `len` members could take `member` and remain a listpack.
+fn fits_listpack(len: usize, member: &str) -> bool {
+    len < SET_MAX_LISTPACK_ENTRIES && member.len() <= SET_MAX_LISTPACK_VALUE
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Set::Intset(Vec::new())
+    }
+}
+
+impl Set {
+    pub fn from_members<I: IntoIterator<Item = String>>(members: I) -> Self {
+        let mut set = Set::default();
+        for member in members {
+            set.insert(member);
+        }
+        set
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Set::Intset(values) => values.len(),
+            Set::Listpack(pack) => pack.len(),
+            Set::HashTable(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        match self {
+            Set::Intset(values) => {
+                as_integer(member).is_some_and(|value| values.binary_search(&value).is_ok())
+            }
+            Set::Listpack(pack) => pack.iter().any(|known| known == member),
+            Set::HashTable(members) => members.contains(member),
+        }
+    }
+
+    fn convert_to_hash_set(&mut self) {
+        if !matches!(self, Set::HashTable(_)) {
+            *self = Set::HashTable(self.members().into_iter().collect());
+        }
+    }
+
+    pub fn insert(&mut self, member: String) -> bool {
+        if let Set::Intset(values) = self {
+            match as_integer(&member) {
+                Some(value) => match values.binary_search(&value) {
+                    Ok(_) => return false,
+                    Err(position) if values.len() < SET_MAX_INTSET_ENTRIES => {
+                        values.insert(position, value);
+                        return true;
+                    }
+                    Err(_) => self.convert_to_hash_set(),
+                },
+                // Integers are always short enough for a listpack.
+                None if fits_listpack(values.len(), &member) => {
+                    let mut pack = Listpack::default();
+                    for value in values.iter() {
+                        pack.push_back(&value.to_string());
+                    }
+                    *self = Set::Listpack(pack);
+                }
+                None => self.convert_to_hash_set(),
+            }
+        }
+        if let Set::Listpack(pack) = self {
+            if pack.iter().any(|known| known == member) {
+                return false;
+            }
+            if fits_listpack(pack.len(), &member) {
+                pack.push_back(&member);
+                return true;
+            }
+            self.convert_to_hash_set();
+        }
+        match self {
+            Set::HashTable(members) => members.insert(member),
+            Set::Intset(_) | Set::Listpack(_) => unreachable!("converted above"),
+        }
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self {
+            Set::Intset(values) => match as_integer(member).map(|v| values.binary_search(&v)) {
+                Some(Ok(position)) => {
+                    values.remove(position);
+                    true
+                }
+                _ => false,
+            },
+            Set::Listpack(pack) => match pack.iter().position(|known| known == member) {
+                Some(position) => pack.remove(position).is_some(),
+                None => false,
+            },
+            Set::HashTable(members) => members.remove(member),
+        }
+    }
+
+    pub(super) fn memory_usage(&self, samples: usize) -> usize {
+        match self {
+            Set::Intset(values) => values.capacity() * size_of::<i64>(),
+            Set::Listpack(pack) => pack.allocated(),
+            Set::HashTable(members) => estimate(
+                members
+                    .iter()
+                    .map(|member| string_usage(member) + ELEMENT_OVERHEAD),
+                members.len(),
+                samples,
+            ),
+        }
+    }
+
+    pub fn members(&self) -> Vec<String> {
+        self.iter().map(Cow::into_owned).collect()
+    }
+
+    /// Members one at a time, so a caller that stops early does not copy the whole set.
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, str>> {
+        let (values, pack, members) = match self {
+            Set::Intset(values) => (Some(values), None, None),
+            Set::Listpack(pack) => (None, Some(pack.iter()), None),
+            Set::HashTable(members) => (None, None, Some(members)),
+        };
+        values
+            .into_iter()
+            .flatten()
+            .map(|value| Cow::Owned(value.to_string()))
+            .chain(pack.into_iter().flatten().map(Cow::Borrowed))
+            .chain(
+                members
+                    .into_iter()
+                    .flatten()
+                    .map(|member| Cow::Borrowed(member.as_str())),
+            )
+    }
+
+    /// One SSCAN page. Like Redis with its intsets and listpacks, a compact set comes back whole
+    /// with a zero cursor.
+    pub fn page(&self, cursor: u64, count: usize) -> ScanPage<String> {
+        match self {
+            Set::Intset(_) | Set::Listpack(_) => (0, self.members()),
+            Set::HashTable(members) => {
+                let (cursor, page) = scan_page(
+                    members.iter().map(|member| (member.as_str(), member)),
+                    members.len(),
+                    cursor,
+                    count,
+                );
+                (cursor, page.into_iter().cloned().collect())
+            }
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Set::Intset(_) => "intset",
+            Set::Listpack(_) => "listpack",
+            Set::HashTable(_) => "hashtable",
+        }
+    }
+
+    fn random_member(&self) -> Option<String> {
+        match self {
+            Set::Intset(values) if !values.is_empty() => {
+                Some(values[index_below(values.len())].to_string())
+            }
+            Set::Listpack(pack) if !pack.is_empty() => {
+                pack.get(index_below(pack.len())).map(str::to_string)
+            }
+            Set::HashTable(members) if !members.is_empty() => {
+                members.iter().nth(index_below(members.len())).cloned()
+            }
+            _ => None,
+        }
+    }
+}
+
+fn set_ref<'a>(data: &'a Keyspace, key: &str) -> Result<Option<&'a Set>, &'static str> {
+    match data.get(key) {
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// Computes SINTER/SUNION/SDIFF over `keys`, treating missing keys as empty sets.
+fn combine(data: &Keyspace, operation: SetOperation, keys: &[String]) -> Result<Set, &'static str> {
+    let sets = keys
+        .iter()
+        .map(|key| set_ref(data, key))
+        .collect::<Result<Vec<Option<&Set>>, &'static str>>()?;
+    let Some((first, others)) = sets.split_first() else {
+        return Ok(Set::default());
+    };
+    let result = match operation {
+        SetOperation::Union => {
+            Set::from_members(sets.iter().flatten().flat_map(|set| set.members()))
+        }
+        SetOperation::Diff => Set::from_members(
+            first
+                .map(Set::members)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|member| others.iter().flatten().all(|other| !other.contains(member))),
+        ),
+        SetOperation::Inter if sets.iter().any(Option::is_none) => Set::default(),
+        SetOperation::Inter => {
+            // Probe the smallest set against the others.
+            let mut sets: Vec<&Set> = sets.iter().flatten().copied().collect();
+            sets.sort_by_key(|set| set.len());
+            Set::from_members(
+                sets[0]
+                    .members()
+                    .into_iter()
+                    .filter(|member| sets[1..].iter().all(|other| other.contains(member))),
+            )
+        }
+    };
+    Ok(result)
+}
+
+impl Cache {
+    fn read_set<T>(
+        &self,
+        key: &str,
+        read: impl FnOnce(Option<&Set>) -> T,
+    ) -> Result<T, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(read(set_ref(&data, key)?))
+    }
+
+    /// Runs `write` against the set at `key`, creating it first and dropping the key once the
+    /// set ends up empty.
+    fn write_set<T>(
+        &self,
+        key: &str,
+        write: impl FnOnce(&mut Set) -> T,
+    ) -> Result<T, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let set = match data.get_or_insert_with(key.to_string(), || Value::Set(Box::default())) {
+            Value::Set(set) => set,
+            _ => return Err(WRONGTYPE_ERR),
+        };
+        let result = write(set);
+        if set.is_empty() {
+            data.remove(key);
+        }
+        Ok(result)
+    }
+
+    pub fn sadd(&self, key: &str, members: &[String]) -> Result<usize, &'static str> {
+        self.write_set(key, |set| {
+            members
+                .iter()
+                .filter(|member| set.insert(member.to_string()))
+                .count()
+        })
+    }
+
+    pub fn srem(&self, key: &str, members: &[String]) -> Result<usize, &'static str> {
+        self.write_set(key, |set| {
+            members.iter().filter(|member| set.remove(member)).count()
+        })
+    }
+
+    pub fn smismember(&self, key: &str, members: &[String]) -> Result<Vec<bool>, &'static str> {
+        self.read_set(key, |set| {
+            members
+                .iter()
+                .map(|member| set.is_some_and(|set| set.contains(member)))
+                .collect()
+        })
+    }
+
+    pub fn sscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        count: usize,
+    ) -> Result<ScanPage<String>, &'static str> {
+        self.read_set(key, |set| {
+            set.map(|set| set.page(cursor, count)).unwrap_or_default()
+        })
+    }
+
+    pub fn smembers(&self, key: &str) -> Result<Vec<String>, &'static str> {
+        self.read_set(key, |set| set.map(Set::members).unwrap_or_default())
+    }
+
+    pub fn scard(&self, key: &str) -> Result<usize, &'static str> {
+        self.read_set(key, |set| set.map_or(0, Set::len))
+    }
+
+    pub fn spop(&self, key: &str, count: usize) -> Result<Vec<String>, &'static str> {
+        self.write_set(key, |set| {
+            let popped = match count {
+                1 => set.random_member().into_iter().collect(),
+                _ => sample_distinct(&set.members(), count),
+            };
+            for member in &popped {
+                set.remove(member);
+            }
+            popped
+        })
+    }
+
+    /// SRANDMEMBER semantics: a positive count returns distinct members, a negative one allows
+    /// the same member to be returned several times.
+    pub fn srandmember(&self, key: &str, count: i64) -> Result<Vec<String>, &'static str> {
+        check_sample_count(count)?;
+        self.read_set(key, |set| {
+            let members = set.map(Set::members).unwrap_or_default();
+            if count >= 0 {
+                sample_distinct(&members, count as usize)
+            } else {
+                sample_with_repetition(&members, count.unsigned_abs() as usize)
+            }
+        })
+    }
+
+    pub fn smove(
+        &self,
+        source: &str,
+        destination: &str,
+        member: &str,
+    ) -> Result<bool, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        set_ref(&data, destination)?;
+        let removed = match data.get_mut(source) {
+            Some(Value::Set(set)) => {
+                let removed = set.remove(member);
+                if set.is_empty() {
+                    data.remove(source);
+                }
+                removed
+            }
+            Some(_) => return Err(WRONGTYPE_ERR),
+            None => false,
+        };
+        if removed {
+            match data.get_or_insert_with(destination.to_string(), || Value::Set(Box::default())) {
+                Value::Set(set) => {
+                    set.insert(member.to_string());
+                }
+                _ => unreachable!("destination type was checked above"),
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn set_combine(
+        &self,
+        operation: SetOperation,
+        keys: &[String],
+    ) -> Result<Vec<String>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(combine(&data, operation, keys)?.members())
+    }
+
+    /// SINTERCARD: size of the intersection, capped at `limit` unless it is 0.
+    pub fn sintercard(&self, keys: &[String], limit: usize) -> Result<usize, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let sets = keys
+            .iter()
+            .map(|key| set_ref(&data, key))
+            .collect::<Result<Vec<Option<&Set>>, &'static str>>()?;
+        // A missing key empties the intersection.
+        let Some(mut sets) = sets.into_iter().collect::<Option<Vec<&Set>>>() else {
+            return Ok(0);
+        };
+        sets.sort_by_key(|set| set.len());
+        let Some((smallest, others)) = sets.split_first() else {
+            return Ok(0);
+        };
+        let common = smallest
+            .iter()
+            .filter(|member| others.iter().all(|other| other.contains(member)));
+        Ok(if limit == 0 {
+            common.count()
+        } else {
+            common.take(limit).count()
+        })
+    }
+
+    /// SINTERSTORE/SUNIONSTORE/SDIFFSTORE: replaces `destination` with the result, deleting it
+    /// when the result is empty. Returns the size of the result.
+    pub fn set_combine_store(
+        &self,
+        operation: SetOperation,
+        destination: &str,
+        keys: &[String],
+    ) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let result = combine(&data, operation, keys)?;
+        let len = result.len();
+        // The destination is overwritten like SET does, TTL included.
+        data.remove(destination);
+        if !result.is_empty() {
+            data.insert(destination.to_string(), Value::Set(Box::new(result)));
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_keep_small_integer_sets_compact() {
+        let mut set = Set::default();
+        assert!(set.insert("3".to_string()));
+        assert!(set.insert("-1".to_string()));
+        assert!(!set.insert("3".to_string()));
+        assert_eq!(set, Set::Intset(vec![-1, 3]));
+        assert!(set.contains("3"));
+        assert!(!set.contains("03"));
+
+        set.insert("03".to_string());
+        assert_eq!(set.encoding(), "listpack");
+        assert!(set.contains("3") && set.contains("03") && set.contains("-1"));
+        assert!(!set.insert("03".to_string()));
+        assert!(set.remove("3"));
+        assert_eq!(set.members(), ["-1", "03"]);
+
+        set.insert("x".repeat(SET_MAX_LISTPACK_VALUE + 1));
+        assert_eq!(set.encoding(), "hashtable");
+        assert!(set.contains("03") && set.contains("-1") && set.len() == 3);
+    }
+
+    #[test]
+    fn should_convert_once_listpack_grows_too_big() {
+        let mut set = Set::from_members((0..SET_MAX_LISTPACK_ENTRIES).map(|n| format!("m{}", n)));
+        assert_eq!(set.encoding(), "listpack");
+        set.insert("one more".to_string());
+        assert_eq!(set.encoding(), "hashtable");
+        assert_eq!(set.len(), SET_MAX_LISTPACK_ENTRIES + 1);
+        assert!(set.contains("m0"));
+    }
+
+    #[test]
+    fn should_convert_once_intset_grows_too_big() {
+        let set = Set::from_members((0..=SET_MAX_INTSET_ENTRIES).map(|n| n.to_string()));
+        assert_eq!(set.encoding(), "hashtable");
+        assert_eq!(set.len(), SET_MAX_INTSET_ENTRIES + 1);
+    }
+
+    #[test]
+    fn should_combine_sets() {
+        let cache = Cache::new();
+        let to_vec = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        cache.sadd("a", &to_vec(&["1", "2", "x"])).unwrap();
+        cache.sadd("b", &to_vec(&["2", "x", "y"])).unwrap();
+
+        let mut inter = cache
+            .set_combine(SetOperation::Inter, &to_vec(&["a", "b"]))
+            .unwrap();
+        inter.sort();
+        assert_eq!(inter, to_vec(&["2", "x"]));
+        let diff = cache
+            .set_combine(SetOperation::Diff, &to_vec(&["a", "b"]))
+            .unwrap();
+        assert_eq!(diff, to_vec(&["1"]));
+        assert_eq!(
+            cache
+                .set_combine_store(SetOperation::Union, "c", &to_vec(&["a", "b"]))
+                .unwrap(),
+            4
+        );
+        assert!(cache
+            .set_combine(SetOperation::Inter, &to_vec(&["a", "missing"]))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,487 @@
+use std::{cmp::Ordering, mem::size_of};
+
+use super::random::next_u64;
+
+const MAX_LEVEL: usize = 32;
+const HEADER: usize = 0;
+
+/// Score interval used by ZCOUNT, ZRANGE BYSCORE and friends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreRange {
+    pub min: f64,
+    pub max: f64,
+    pub min_exclusive: bool,
+    pub max_exclusive: bool,
+}
+
+impl ScoreRange {
+    pub fn above_min(&self, score: f64) -> bool {
+        if self.min_exclusive {
+            score > self.min
+        } else {
+            score >= self.min
+        }
+    }
+
+    pub fn below_max(&self, score: f64) -> bool {
+        if self.max_exclusive {
+            score < self.max
+        } else {
+            score <= self.max
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min > self.max || (self.min == self.max && (self.min_exclusive || self.max_exclusive))
+    }
+}
+
+/// One end of a ZRANGE BYLEX interval: `-`, `+`, `[member` or `(member`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexBound {
+    NegativeInfinity,
+    PositiveInfinity,
+    Inclusive(String),
+    Exclusive(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexRange {
+    pub min: LexBound,
+    pub max: LexBound,
+}
+
+impl LexRange {
+    pub fn above_min(&self, member: &str) -> bool {
+        match &self.min {
+            LexBound::NegativeInfinity => true,
+            LexBound::PositiveInfinity => false,
+            LexBound::Inclusive(min) => member >= min.as_str(),
+            LexBound::Exclusive(min) => member > min.as_str(),
+        }
+    }
+
+    pub fn below_max(&self, member: &str) -> bool {
+        match &self.max {
+            LexBound::NegativeInfinity => false,
+            LexBound::PositiveInfinity => true,
+            LexBound::Inclusive(max) => member <= max.as_str(),
+            LexBound::Exclusive(max) => member < max.as_str(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (&self.min, &self.max) {
+            (LexBound::PositiveInfinity, _) | (_, LexBound::NegativeInfinity) => true,
+            (LexBound::NegativeInfinity, _) | (_, LexBound::PositiveInfinity) => false,
+            (LexBound::Inclusive(min), LexBound::Inclusive(max)) => min > max,
+            (
+                LexBound::Inclusive(min) | LexBound::Exclusive(min),
+                LexBound::Inclusive(max) | LexBound::Exclusive(max),
+            ) => min >= max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    forward: Option<usize>,
+    /// Number of level 0 links crossed when following `forward`, which is what makes rank
+    /// lookups logarithmic.
+    span: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    member: String,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+/// Redis' zskiplist: members ordered by (score, member) with spans on every link. Nodes live in
+/// an arena and point at each other by index; freed slots are reused by later inserts.
+#[derive(Debug, Clone)]
+pub struct SkipList {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    tail: Option<usize>,
+    level: usize,
+    len: usize,
+}
+
+fn compare(score: f64, member: &str, other_score: f64, other_member: &str) -> Ordering {
+    score
+        .partial_cmp(&other_score)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| member.cmp(other_member))
+}
+
+/// Levels follow a geometric distribution with p = 1/4, like Redis.
+fn random_level() -> usize {
+    let mut level = 1;
+    while level < MAX_LEVEL && next_u64().is_multiple_of(4) {
+        level += 1;
+    }
+    level
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        let header = Node {
+            member: String::new(),
+            score: 0.0,
+            backward: None,
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0,
+                };
+                MAX_LEVEL
+            ],
+        };
+        Self {
+            nodes: vec![header],
+            free: Vec::new(),
+            tail: None,
+            level: 1,
+            len: 0,
+        }
+    }
+}
+
+impl SkipList {
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Node arena size, counting the 4/3 levels a node has on average but not the members,
+    /// which the sorted set accounts for.
+    pub fn memory_usage(&self) -> usize {
+        self.nodes.capacity() * (size_of::<Node>() + size_of::<Level>() * 4 / 3)
+            + self.free.capacity() * size_of::<usize>()
+    }
+
+    pub fn member(&self, node: usize) -> &str {
+        &self.nodes[node].member
+    }
+
+    pub fn score(&self, node: usize) -> f64 {
+        self.nodes[node].score
+    }
+
+    pub fn first(&self) -> Option<usize> {
+        self.nodes[HEADER].levels[0].forward
+    }
+
+    pub fn last(&self) -> Option<usize> {
+        self.tail
+    }
+
+    pub fn next(&self, node: usize) -> Option<usize> {
+        self.nodes[node].levels[0].forward
+    }
+
+    pub fn prev(&self, node: usize) -> Option<usize> {
+        self.nodes[node].backward
+    }
+
+    fn forward(&self, node: usize, level: usize) -> Option<usize> {
+        self.nodes[node].levels[level].forward
+    }
+
+    fn allocate(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Inserts a member that must not already be in the list.
+    pub fn insert(&mut self, score: f64, member: String) {
+        let mut update = [HEADER; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.forward(x, i) {
+                let node = &self.nodes[next];
+                if compare(node.score, &node.member, score, &member) != Ordering::Less {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEADER;
+                self.nodes[HEADER].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let new = self.allocate(Node {
+            member,
+            score,
+            backward: (update[0] != HEADER).then_some(update[0]),
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0,
+                };
+                level
+            ],
+        });
+        for i in 0..level {
+            let previous = self.nodes[update[i]].levels[i];
+            self.nodes[new].levels[i] = Level {
+                forward: previous.forward,
+                span: previous.span - (rank[0] - rank[i]),
+            };
+            self.nodes[update[i]].levels[i] = Level {
+                forward: Some(new),
+                span: rank[0] - rank[i] + 1,
+            };
+        }
+        for (i, &node) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[node].levels[i].span += 1;
+        }
+
+        match self.next(new) {
+            Some(next) => self.nodes[next].backward = Some(new),
+            None => self.tail = Some(new),
+        }
+        self.len += 1;
+    }
+
+    /// Removes the node holding exactly `score` and `member`, returning whether it existed.
+    pub fn remove(&mut self, score: f64, member: &str) -> bool {
+        let mut update = [HEADER; MAX_LEVEL];
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                let node = &self.nodes[next];
+                if compare(node.score, &node.member, score, member) != Ordering::Less {
+                    break;
+                }
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let Some(target) = self.next(x) else {
+            return false;
+        };
+        if self.nodes[target].score != score || self.nodes[target].member != member {
+            return false;
+        }
+
+        for (i, &node) in update.iter().enumerate().take(self.level) {
+            if self.forward(node, i) == Some(target) {
+                let removed = self.nodes[target].levels[i];
+                let previous = &mut self.nodes[node].levels[i];
+                previous.span += removed.span;
+                previous.span -= 1;
+                previous.forward = removed.forward;
+            } else {
+                self.nodes[node].levels[i].span -= 1;
+            }
+        }
+        let backward = self.nodes[target].backward;
+        match self.next(target) {
+            Some(next) => self.nodes[next].backward = backward,
+            None => self.tail = backward,
+        }
+        while self.level > 1 && self.forward(HEADER, self.level - 1).is_none() {
+            self.level -= 1;
+        }
+
+        self.nodes[target].member = String::new();
+        self.nodes[target].levels = Vec::new();
+        self.free.push(target);
+        self.len -= 1;
+        true
+    }
+
+    /// 0-based rank of `member`, which must be stored with `score`.
+    pub fn rank(&self, score: f64, member: &str) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                let node = &self.nodes[next];
+                if compare(node.score, &node.member, score, member) == Ordering::Greater {
+                    break;
+                }
+                rank += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if x != HEADER && self.nodes[x].member == member {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    /// Node at the 0-based `rank`.
+    pub fn by_rank(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if traversed + self.nodes[x].levels[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    pub fn first_in_score_range(&self, range: &ScoreRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if range.above_min(self.nodes[next].score) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.next(x)
+            .filter(|&node| range.below_max(self.nodes[node].score))
+    }
+
+    pub fn last_in_score_range(&self, range: &ScoreRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !range.below_max(self.nodes[next].score) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        Some(x).filter(|&node| node != HEADER && range.above_min(self.nodes[node].score))
+    }
+
+    pub fn first_in_lex_range(&self, range: &LexRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if range.above_min(&self.nodes[next].member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.next(x)
+            .filter(|&node| range.below_max(&self.nodes[node].member))
+    }
+
+    pub fn last_in_lex_range(&self, range: &LexRange) -> Option<usize> {
+        if range.is_empty() {
+            return None;
+        }
+        let mut x = HEADER;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.forward(x, i) {
+                if !range.below_max(&self.nodes[next].member) {
+                    break;
+                }
+                x = next;
+            }
+        }
+        Some(x).filter(|&node| node != HEADER && range.above_min(&self.nodes[node].member))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(list: &SkipList) -> Vec<String> {
+        let mut members = Vec::new();
+        let mut node = list.first();
+        while let Some(current) = node {
+            members.push(list.member(current).to_string());
+            node = list.next(current);
+        }
+        members
+    }
+
+    #[test]
+    fn should_keep_members_ordered_and_ranked() {
+        let mut list = SkipList::default();
+        for n in (0..200).rev() {
+            list.insert((n / 2) as f64, format!("m{:03}", n));
+        }
+        assert_eq!(list.len(), 200);
+        let expected: Vec<String> = (0..200).map(|n| format!("m{:03}", n)).collect();
+        assert_eq!(members(&list), expected);
+        for n in 0..200 {
+            assert_eq!(list.rank((n / 2) as f64, &format!("m{:03}", n)), Some(n));
+            assert_eq!(list.member(list.by_rank(n).unwrap()), format!("m{:03}", n));
+        }
+
+        for n in (0..200).step_by(2) {
+            assert!(list.remove((n / 2) as f64, &format!("m{:03}", n)));
+        }
+        assert!(!list.remove(0.0, "m000"));
+        assert_eq!(list.len(), 100);
+        assert_eq!(list.rank(99.0, "m199"), Some(99));
+        assert_eq!(list.member(list.last().unwrap()), "m199");
+        assert_eq!(list.prev(list.first().unwrap()), None);
+    }
+
+    #[test]
+    fn should_find_score_and_lex_ranges() {
+        let mut list = SkipList::default();
+        for (score, member) in [(1.0, "a"), (2.0, "b"), (2.0, "c"), (3.0, "d")] {
+            list.insert(score, member.to_string());
+        }
+        let range = ScoreRange {
+            min: 2.0,
+            max: 3.0,
+            min_exclusive: false,
+            max_exclusive: true,
+        };
+        assert_eq!(list.member(list.first_in_score_range(&range).unwrap()), "b");
+        assert_eq!(list.member(list.last_in_score_range(&range).unwrap()), "c");
+        let empty = ScoreRange { min: 3.5, ..range };
+        assert_eq!(list.first_in_score_range(&empty), None);
+
+        let lex = LexRange {
+            min: LexBound::Exclusive("a".to_string()),
+            max: LexBound::PositiveInfinity,
+        };
+        assert_eq!(list.member(list.first_in_lex_range(&lex).unwrap()), "b");
+        assert_eq!(list.member(list.last_in_lex_range(&lex).unwrap()), "d");
+    }
+}
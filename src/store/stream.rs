@@ -0,0 +1,845 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem::size_of;
+
+use super::{
+    consumer_group::ConsumerGroup,
+    db::{unix_time_ms, Cache, Value, WRONGTYPE_ERR},
+    keyspace::Keyspace,
+    memory::{estimate, string_usage},
+    radix::RadixTree,
+};
+
+/// Size of a radix tree node holding part of a block's key.
+const RADIX_NODE_OVERHEAD: usize = 64;
+/// A block is closed for appends once it holds this many entries (stream-node-max-entries)...
+const STREAM_NODE_MAX_ENTRIES: usize = 100;
+/// ...or this many bytes (stream-node-max-bytes).
+const STREAM_NODE_MAX_BYTES: usize = 4096;
+/// What `~` trimming may remove in one go unless LIMIT says otherwise.
+pub const STREAM_DEFAULT_TRIM_LIMIT: usize = 100 * STREAM_NODE_MAX_ENTRIES;
+
+const FLAG_DELETED: u8 = 1;
+const FLAG_SAME_FIELDS: u8 = 2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+/// An entry ID along with its field/value pairs.
+pub type StreamEntry = (StreamId, Vec<(String, String)>);
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    /// Parses `ms-seq`, or a bare `ms` whose sequence then defaults to `missing_seq`.
+    pub fn parse(arg: &str, missing_seq: u64) -> Option<StreamId> {
+        let (ms, seq) = match arg.split_once('-') {
+            Some((ms, seq)) => (ms, seq.parse().ok()?),
+            None => (arg, missing_seq),
+        };
+        Some(StreamId {
+            ms: ms.parse().ok()?,
+            seq,
+        })
+    }
+
+    /// The ID right after this one, used for exclusive range starts.
+    pub fn next(self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId { seq, ..self }),
+            None => Some(StreamId {
+                ms: self.ms.checked_add(1)?,
+                seq: 0,
+            }),
+        }
+    }
+
+    /// The ID right before this one, used for exclusive range ends.
+    pub fn prev(self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId { seq, ..self }),
+            None => Some(StreamId {
+                ms: self.ms.checked_sub(1)?,
+                seq: u64::MAX,
+            }),
+        }
+    }
+
+    /// Big endian bytes, so the radix tree orders keys the way IDs compare.
+    fn to_key(self) -> [u8; 16] {
+        let mut key = [0; 16];
+        key[..8].copy_from_slice(&self.ms.to_be_bytes());
+        key[8..].copy_from_slice(&self.seq.to_be_bytes());
+        key
+    }
+
+    fn from_key(key: &[u8]) -> StreamId {
+        let mut ms = [0; 8];
+        let mut seq = [0; 8];
+        ms.copy_from_slice(&key[..8]);
+        seq.copy_from_slice(&key[8..16]);
+        StreamId {
+            ms: u64::from_be_bytes(ms),
+            seq: u64::from_be_bytes(seq),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// How XADD picks the ID of a new entry: `*`, `ms-*` or a full ID.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdSpec {
+    Auto,
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimStrategy {
+    MaxLen(usize),
+    MinId(StreamId),
+}
+
+/// MAXLEN/MINID trimming. With `approximate` (`~`) only whole blocks are dropped, which is
+/// much cheaper and may leave a few more entries than asked for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrimOptions {
+    pub strategy: TrimStrategy,
+    pub approximate: bool,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct XAddOptions {
+    pub no_mkstream: bool,
+    pub id: IdSpec,
+    pub trim: Option<TrimOptions>,
+}
+
+/// What XINFO STREAM reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    pub length: usize,
+    pub radix_tree_keys: usize,
+    pub radix_tree_nodes: usize,
+    pub last_generated_id: StreamId,
+    pub max_deleted_entry_id: StreamId,
+    pub entries_added: u64,
+    pub groups: usize,
+    pub first_entry: Option<StreamEntry>,
+    pub last_entry: Option<StreamEntry>,
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*position];
+        *position += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    write_varint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+fn read_string(bytes: &[u8], position: &mut usize) -> String {
+    let len = read_varint(bytes, position) as usize;
+    let value = String::from_utf8_lossy(&bytes[*position..*position + len]).into_owned();
+    *position += len;
+    value
+}
+
+/// One decoded entry of a block, with the offset of its flags byte so it can be deleted in
+/// place.
+struct Decoded {
+    id: StreamId,
+    offset: usize,
+    deleted: bool,
+    fields: Vec<(String, String)>,
+}
+
+/// A run of consecutive entries packed into one byte buffer, the counterpart of a listpack in
+/// a Redis stream. IDs are stored relative to the block's first ID (its key in the radix
+/// tree) and entries that repeat the field names of the first one only store their values.
+/// Deleting an entry just flags it; the block goes away once nothing in it is live.
+#[derive(Debug, Clone)]
+struct Block {
+    master_fields: Vec<String>,
+    bytes: Vec<u8>,
+    entries: usize,
+    live: usize,
+    last: StreamId,
+}
+
+impl Block {
+    fn new(id: StreamId, fields: &[(String, String)]) -> Self {
+        let mut block = Block {
+            master_fields: fields.iter().map(|(field, _)| field.clone()).collect(),
+            bytes: Vec::new(),
+            entries: 0,
+            live: 0,
+            last: id,
+        };
+        block.append(id, id, fields);
+        block
+    }
+
+    fn is_full(&self) -> bool {
+        self.entries >= STREAM_NODE_MAX_ENTRIES || self.bytes.len() >= STREAM_NODE_MAX_BYTES
+    }
+
+    fn append(&mut self, master: StreamId, id: StreamId, fields: &[(String, String)]) {
+        let same_fields = fields.len() == self.master_fields.len()
+            && fields
+                .iter()
+                .zip(&self.master_fields)
+                .all(|((field, _), master)| field == master);
+        self.bytes
+            .push(if same_fields { FLAG_SAME_FIELDS } else { 0 });
+        write_varint(&mut self.bytes, id.ms - master.ms);
+        write_varint(&mut self.bytes, id.seq);
+        if !same_fields {
+            write_varint(&mut self.bytes, fields.len() as u64);
+            for (field, _) in fields {
+                write_string(&mut self.bytes, field);
+            }
+        }
+        for (_, value) in fields {
+            write_string(&mut self.bytes, value);
+        }
+        self.entries += 1;
+        self.live += 1;
+        self.last = id;
+    }
+
+    fn decode(&self, master: StreamId) -> Vec<Decoded> {
+        let mut decoded = Vec::with_capacity(self.entries);
+        let mut position = 0;
+        while position < self.bytes.len() {
+            let offset = position;
+            let flags = self.bytes[position];
+            position += 1;
+            let id = StreamId {
+                ms: master.ms + read_varint(&self.bytes, &mut position),
+                seq: read_varint(&self.bytes, &mut position),
+            };
+            let names = if flags & FLAG_SAME_FIELDS != 0 {
+                self.master_fields.clone()
+            } else {
+                let count = read_varint(&self.bytes, &mut position);
+                (0..count)
+                    .map(|_| read_string(&self.bytes, &mut position))
+                    .collect()
+            };
+            let fields = names
+                .into_iter()
+                .map(|name| (name, read_string(&self.bytes, &mut position)))
+                .collect();
+            decoded.push(Decoded {
+                id,
+                offset,
+                deleted: flags & FLAG_DELETED != 0,
+                fields,
+            });
+        }
+        decoded
+    }
+
+    fn memory_usage(&self) -> usize {
+        size_of::<Block>()
+            + self.bytes.capacity()
+            + self.master_fields.iter().map(string_usage).sum::<usize>()
+    }
+
+    fn delete_at(&mut self, offset: usize) {
+        self.bytes[offset] |= FLAG_DELETED;
+        self.live -= 1;
+    }
+}
+
+/// An append-only log of entries kept in a radix tree of blocks keyed by their first ID.
+#[derive(Debug, Clone, Default)]
+pub struct Stream {
+    blocks: RadixTree<Block>,
+    length: usize,
+    last_id: StreamId,
+    max_deleted_id: StreamId,
+    entries_added: u64,
+    pub(super) groups: BTreeMap<String, ConsumerGroup>,
+}
+
+impl PartialEq for Stream {
+    fn eq(&self, other: &Self) -> bool {
+        self.last_id == other.last_id
+            && self.range(StreamId::MIN, StreamId::MAX, None, false)
+                == other.range(StreamId::MIN, StreamId::MAX, None, false)
+    }
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub(super) fn memory_usage(&self, samples: usize) -> usize {
+        let blocks =
+            std::iter::successors(self.blocks.first(), |(key, _)| self.blocks.successor(key))
+                .map(|(_, block)| block.memory_usage());
+        estimate(blocks, self.blocks.len(), samples)
+            + self.blocks.node_count() * RADIX_NODE_OVERHEAD
+            + self
+                .groups
+                .iter()
+                .map(|(name, group)| string_usage(name) + group.memory_usage(samples))
+                .sum::<usize>()
+    }
+
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// Picks the ID of the next entry, which must be greater than every ID handed out so far.
+    fn next_id(&self, spec: IdSpec, now_ms: u64) -> Result<StreamId, &'static str> {
+        let last = self.last_id;
+        let id = match spec {
+            IdSpec::Explicit(id) => id,
+            IdSpec::AutoSeq(ms) if ms == last.ms => match last.seq.checked_add(1) {
+                Some(seq) => StreamId { ms, seq },
+                None => return Err("ERR The ID specified in XADD is equal or smaller than the target stream top item"),
+            },
+            IdSpec::AutoSeq(ms) => StreamId { ms, seq: 0 },
+            IdSpec::Auto if now_ms > last.ms => StreamId { ms: now_ms, seq: 0 },
+            IdSpec::Auto => last.next().ok_or(
+                "ERR The stream has exhausted the last possible ID, unable to add more items",
+            )?,
+        };
+        if id == StreamId::MIN {
+            return Err("ERR The ID specified in XADD must be greater than 0-0");
+        }
+        if id <= last {
+            return Err(
+                "ERR The ID specified in XADD is equal or smaller than the target stream top item",
+            );
+        }
+        Ok(id)
+    }
+
+    fn append(&mut self, id: StreamId, fields: &[(String, String)]) {
+        let open_block = self
+            .blocks
+            .last()
+            .filter(|(_, block)| !block.is_full())
+            .map(|(key, _)| key);
+        match open_block {
+            Some(key) => {
+                let master = StreamId::from_key(&key);
+                if let Some(block) = self.blocks.get_mut(&key) {
+                    block.append(master, id, fields);
+                }
+            }
+            None => {
+                self.blocks.insert(&id.to_key(), Block::new(id, fields));
+            }
+        }
+        self.length += 1;
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    /// Entries with IDs in `start..=end`, newest first when `reverse` is set.
+    pub fn range(
+        &self,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> Vec<StreamEntry> {
+        let mut entries = Vec::new();
+        if start > end || count == Some(0) {
+            return entries;
+        }
+        let full = |entries: &Vec<StreamEntry>| count.is_some_and(|count| entries.len() >= count);
+        if reverse {
+            let mut cursor = self.blocks.floor(&end.to_key());
+            while let Some((key, block)) = cursor {
+                if block.last < start {
+                    break;
+                }
+                for entry in block.decode(StreamId::from_key(&key)).into_iter().rev() {
+                    if entry.deleted || entry.id > end {
+                        continue;
+                    }
+                    if entry.id < start || full(&entries) {
+                        return entries;
+                    }
+                    entries.push((entry.id, entry.fields));
+                }
+                cursor = self.blocks.predecessor(&key);
+            }
+        } else {
+            let mut cursor = self
+                .blocks
+                .floor(&start.to_key())
+                .or_else(|| self.blocks.first());
+            while let Some((key, block)) = cursor {
+                if StreamId::from_key(&key) > end {
+                    break;
+                }
+                for entry in block.decode(StreamId::from_key(&key)) {
+                    if entry.deleted || entry.id < start {
+                        continue;
+                    }
+                    if entry.id > end || full(&entries) {
+                        return entries;
+                    }
+                    entries.push((entry.id, entry.fields));
+                }
+                cursor = self.blocks.successor(&key);
+            }
+        }
+        entries
+    }
+
+    fn delete(&mut self, id: StreamId) -> bool {
+        let Some((key, block)) = self.blocks.floor(&id.to_key()) else {
+            return false;
+        };
+        let Some(offset) = block
+            .decode(StreamId::from_key(&key))
+            .into_iter()
+            .find(|entry| entry.id == id && !entry.deleted)
+            .map(|entry| entry.offset)
+        else {
+            return false;
+        };
+        if let Some(block) = self.blocks.get_mut(&key) {
+            block.delete_at(offset);
+            if block.live == 0 {
+                self.blocks.remove(&key);
+            }
+        }
+        self.length -= 1;
+        self.max_deleted_id = self.max_deleted_id.max(id);
+        true
+    }
+
+    /// Drops the oldest entries according to `options` and returns how many went away.
+    pub fn trim(&mut self, options: &TrimOptions) -> usize {
+        let mut deleted = 0;
+        while let Some((key, block)) = self.blocks.first() {
+            let whole_block = match options.strategy {
+                TrimStrategy::MaxLen(max_len) => self.length - block.live >= max_len,
+                TrimStrategy::MinId(min_id) => block.last < min_id,
+            };
+            if whole_block {
+                if options
+                    .limit
+                    .is_some_and(|limit| deleted + block.live > limit)
+                {
+                    break;
+                }
+                let live = block.live;
+                self.blocks.remove(&key);
+                self.length -= live;
+                deleted += live;
+                continue;
+            }
+            if options.approximate {
+                break;
+            }
+
+            let decoded = block.decode(StreamId::from_key(&key));
+            let Some(block) = self.blocks.get_mut(&key) else {
+                break;
+            };
+            for entry in decoded.into_iter().filter(|entry| !entry.deleted) {
+                let trim = match options.strategy {
+                    TrimStrategy::MaxLen(max_len) => self.length > max_len,
+                    TrimStrategy::MinId(min_id) => entry.id < min_id,
+                };
+                if !trim {
+                    break;
+                }
+                block.delete_at(entry.offset);
+                self.length -= 1;
+                deleted += 1;
+            }
+            if block.live == 0 {
+                self.blocks.remove(&key);
+            }
+            break;
+        }
+        deleted
+    }
+
+    /// The fields of entry `id`, if it is still in the stream.
+    pub(super) fn entry(&self, id: StreamId) -> Option<Vec<(String, String)>> {
+        self.range(id, id, Some(1), false)
+            .pop()
+            .map(|(_, fields)| fields)
+    }
+
+    /// Whether an entry at or after `start` was deleted, in which case counting entries from
+    /// `entries_added` no longer works.
+    pub(super) fn has_tombstones_from(&self, start: StreamId) -> bool {
+        self.length > 0 && self.max_deleted_id != StreamId::MIN && start <= self.max_deleted_id
+    }
+
+    /// How many entries were ever added up to and including `id`, when that can be told
+    /// without walking the stream.
+    pub(super) fn entries_read_until(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        if self.length == 0 && id <= self.max_deleted_id {
+            return Some(self.entries_added);
+        }
+        if id == self.last_id {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+        let first = self
+            .range(StreamId::MIN, StreamId::MAX, Some(1), false)
+            .pop()
+            .map_or(StreamId::MIN, |(id, _)| id);
+        if self.max_deleted_id == StreamId::MIN || self.max_deleted_id < first {
+            let before_first = self.entries_added - self.length as u64;
+            if id < first {
+                return Some(before_first);
+            }
+            if id == first {
+                return Some(before_first + 1);
+            }
+        }
+        None
+    }
+
+    /// Entries a group has not read yet, or `None` when that cannot be worked out.
+    pub(super) fn lag(&self, last_delivered: StreamId, entries_read: Option<u64>) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        let read = match entries_read {
+            Some(read) if !self.has_tombstones_from(last_delivered) => Some(read),
+            _ => self.entries_read_until(last_delivered),
+        };
+        read.map(|read| self.entries_added.saturating_sub(read))
+    }
+
+    pub fn info(&self) -> StreamInfo {
+        StreamInfo {
+            length: self.length,
+            radix_tree_keys: self.blocks.len(),
+            radix_tree_nodes: self.blocks.node_count(),
+            last_generated_id: self.last_id,
+            max_deleted_entry_id: self.max_deleted_id,
+            entries_added: self.entries_added,
+            groups: self.groups.len(),
+            first_entry: self
+                .range(StreamId::MIN, StreamId::MAX, Some(1), false)
+                .pop(),
+            last_entry: self
+                .range(StreamId::MIN, StreamId::MAX, Some(1), true)
+                .pop(),
+        }
+    }
+}
+
+pub(super) fn stream_ref<'a>(
+    data: &'a Keyspace,
+    key: &str,
+) -> Result<Option<&'a Stream>, &'static str> {
+    match data.get(key) {
+        Some(Value::Stream(stream)) => Ok(Some(stream)),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+pub(super) fn stream_mut<'a>(
+    data: &'a mut Keyspace,
+    key: &str,
+) -> Result<Option<&'a mut Stream>, &'static str> {
+    match data.get_mut(key) {
+        Some(Value::Stream(stream)) => Ok(Some(stream)),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// Up to `count` entries of the stream at `key` that come after `after`, or `None` when there
+/// are none yet. This is what XREAD, blocking or not, hands out.
+pub fn read_after(
+    data: &Keyspace,
+    key: &str,
+    after: StreamId,
+    count: Option<usize>,
+) -> Result<Option<Vec<StreamEntry>>, &'static str> {
+    let Some(start) = after.next() else {
+        return Ok(None);
+    };
+    let entries = stream_ref(data, key)?
+        .map(|stream| stream.range(start, StreamId::MAX, count, false))
+        .unwrap_or_default();
+    Ok((!entries.is_empty()).then_some(entries))
+}
+
+impl Cache {
+    fn read_stream<T>(
+        &self,
+        key: &str,
+        read: impl FnOnce(Option<&Stream>) -> T,
+    ) -> Result<T, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(read(stream_ref(&data, key)?))
+    }
+
+    fn write_stream<T>(
+        &self,
+        key: &str,
+        write: impl FnOnce(Option<&mut Stream>) -> T,
+    ) -> Result<T, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        Ok(write(stream_mut(&mut data, key)?))
+    }
+
+    /// XADD. Returns the ID of the new entry, or `None` when NOMKSTREAM found no stream.
+    pub fn xadd(
+        &self,
+        key: &str,
+        options: &XAddOptions,
+        fields: &[(String, String)],
+    ) -> Result<Option<StreamId>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let id = match stream_ref(&data, key)? {
+            Some(stream) => stream.next_id(options.id, unix_time_ms())?,
+            None if options.no_mkstream => return Ok(None),
+            None => Stream::default().next_id(options.id, unix_time_ms())?,
+        };
+        let Value::Stream(stream) =
+            data.get_or_insert_with(key.to_string(), || Value::Stream(Box::default()))
+        else {
+            unreachable!("type was checked above");
+        };
+        stream.append(id, fields);
+        if let Some(trim) = &options.trim {
+            stream.trim(trim);
+        }
+        self.blocking.serve(&mut data, key);
+        Ok(Some(id))
+    }
+
+    pub fn xlen(&self, key: &str) -> Result<usize, &'static str> {
+        self.read_stream(key, |stream| stream.map_or(0, Stream::len))
+    }
+
+    pub fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+        reverse: bool,
+    ) -> Result<Vec<StreamEntry>, &'static str> {
+        self.read_stream(key, |stream| {
+            stream
+                .map(|stream| stream.range(start, end, count, reverse))
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn xdel(&self, key: &str, ids: &[StreamId]) -> Result<usize, &'static str> {
+        self.write_stream(key, |stream| {
+            stream.map_or(0, |stream| {
+                ids.iter().filter(|id| stream.delete(**id)).count()
+            })
+        })
+    }
+
+    pub fn xtrim(&self, key: &str, options: &TrimOptions) -> Result<usize, &'static str> {
+        self.write_stream(key, |stream| {
+            stream.map_or(0, |stream| stream.trim(options))
+        })
+    }
+
+    pub fn xinfo_stream(&self, key: &str) -> Result<Option<StreamInfo>, &'static str> {
+        self.read_stream(key, |stream| stream.map(Stream::info))
+    }
+
+    /// Last generated ID of each stream, what `$` stands for in XREAD. Missing keys count as
+    /// empty streams.
+    pub fn xlast_ids(&self, keys: &[String]) -> Result<Vec<StreamId>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        keys.iter()
+            .map(|key| Ok(stream_ref(&data, key)?.map_or(StreamId::MIN, Stream::last_id)))
+            .collect()
+    }
+
+    /// XREAD without blocking: entries after the given ID of every stream that has some.
+    pub fn xread(
+        &self,
+        streams: &[(String, StreamId)],
+        count: Option<usize>,
+    ) -> Result<Vec<(String, Vec<StreamEntry>)>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let mut found = Vec::new();
+        for (key, after) in streams {
+            if let Some(entries) = read_after(&data, key, *after, count)? {
+                found.push((key.clone(), entries));
+            }
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(field, value)| (field.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    fn filled(count: u64) -> Stream {
+        let mut stream = Stream::default();
+        for n in 1..=count {
+            let pairs = if n % 3 == 0 {
+                fields(&[("other", "x")])
+            } else {
+                fields(&[("n", &n.to_string())])
+            };
+            stream.append(id(n, 0), &pairs);
+        }
+        stream
+    }
+
+    #[test]
+    fn should_generate_increasing_ids() {
+        let mut stream = Stream::default();
+        assert_eq!(stream.next_id(IdSpec::Auto, 1_000), Ok(id(1_000, 0)));
+        stream.append(id(1_000, 0), &fields(&[("a", "1")]));
+        assert_eq!(stream.next_id(IdSpec::Auto, 999), Ok(id(1_000, 1)));
+        assert_eq!(stream.next_id(IdSpec::AutoSeq(1_000), 0), Ok(id(1_000, 1)));
+        assert_eq!(stream.next_id(IdSpec::AutoSeq(2_000), 0), Ok(id(2_000, 0)));
+        assert!(stream.next_id(IdSpec::Explicit(id(1_000, 0)), 0).is_err());
+        assert_eq!(
+            Stream::default().next_id(IdSpec::Explicit(StreamId::MIN), 0),
+            Err("ERR The ID specified in XADD must be greater than 0-0")
+        );
+        assert_eq!(StreamId::parse("5", u64::MAX), Some(id(5, u64::MAX)));
+        assert_eq!(StreamId::parse("5-x", 0), None);
+    }
+
+    #[test]
+    fn should_split_into_blocks_and_range_both_ways() {
+        let stream = filled(250);
+        assert_eq!(stream.len(), 250);
+        assert_eq!(stream.blocks.len(), 3);
+
+        let forward = stream.range(id(99, 0), id(102, 0), None, false);
+        assert_eq!(
+            forward.iter().map(|(id, _)| id.ms).collect::<Vec<_>>(),
+            [99, 100, 101, 102]
+        );
+        assert_eq!(forward[0].1, fields(&[("other", "x")]));
+        assert_eq!(forward[1].1, fields(&[("n", "100")]));
+
+        let backward = stream.range(id(0, 0), id(201, 5), Some(3), true);
+        assert_eq!(
+            backward.iter().map(|(id, _)| id.ms).collect::<Vec<_>>(),
+            [201, 200, 199]
+        );
+        assert!(stream
+            .range(id(300, 0), StreamId::MAX, None, false)
+            .is_empty());
+    }
+
+    #[test]
+    fn should_delete_and_trim() {
+        let mut stream = filled(250);
+        assert!(stream.delete(id(5, 0)));
+        assert!(!stream.delete(id(5, 0)));
+        assert_eq!(stream.max_deleted_id, id(5, 0));
+
+        let approximate = TrimOptions {
+            strategy: TrimStrategy::MaxLen(120),
+            approximate: true,
+            limit: None,
+        };
+        assert_eq!(stream.trim(&approximate), 99);
+        assert_eq!(stream.len(), 150);
+
+        let exact = TrimOptions {
+            approximate: false,
+            ..approximate
+        };
+        assert_eq!(stream.trim(&exact), 30);
+        assert_eq!(
+            stream.range(StreamId::MIN, StreamId::MAX, Some(1), false)[0].0,
+            id(131, 0)
+        );
+
+        let min_id = TrimOptions {
+            strategy: TrimStrategy::MinId(id(240, 0)),
+            approximate: false,
+            limit: None,
+        };
+        assert_eq!(stream.trim(&min_id), 109);
+        assert_eq!(stream.len(), 11);
+        assert_eq!(
+            stream.info().first_entry.map(|(id, _)| id),
+            Some(id(240, 0))
+        );
+    }
+}
@@ -0,0 +1,465 @@
+use super::{
+    bytes::{from_bytes, store_bytes, string_bytes, to_bytes},
+    db::{Cache, Value, WRONGTYPE_ERR},
+    hash::{format_float, NAN_OR_INFINITY_ERR, OVERFLOW_ERR},
+    keyspace::Keyspace,
+};
+
+/// Largest string a command may build, matching Redis' default proto-max-bulk-len.
+pub const MAX_STRING_LEN: usize = 512 << 20;
+pub const STRING_TOO_LONG_ERR: &str =
+    "ERR string exceeds maximum allowed size (proto-max-bulk-len)";
+pub const NOT_INTEGER_ERR: &str = "ERR value is not an integer or out of range";
+pub const NOT_FLOAT_ERR: &str = "ERR value is not a valid float";
+const LCS_WRONGTYPE_ERR: &str = "ERR The specified keys must contain string values";
+const LCS_TOO_LONG_ERR: &str =
+    "ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len";
+
+/// What a command does to the TTL of the key it writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TtlUpdate {
+    Keep,
+    Persist,
+    /// Expire at this Unix time in milliseconds; times in the past delete the key right away.
+    At(u64),
+}
+
+/// Whether a write may go ahead depending on what the key currently holds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SetCondition {
+    #[default]
+    Always,
+    /// Only when the key does not exist.
+    Nx,
+    /// Only when the key exists.
+    Xx,
+    /// Only when the key holds exactly this string.
+    IfEq(String),
+}
+
+impl SetCondition {
+    fn allows(&self, current: Option<&Value>) -> Result<bool, &'static str> {
+        Ok(match (self, current) {
+            (SetCondition::Always, _) => true,
+            (SetCondition::Nx, current) => current.is_none(),
+            (SetCondition::Xx, current) => current.is_some(),
+            (SetCondition::IfEq(expected), Some(Value::String(value))) => value == expected,
+            (SetCondition::IfEq(_), Some(_)) => return Err(WRONGTYPE_ERR),
+            (SetCondition::IfEq(_), None) => false,
+        })
+    }
+}
+
+/// What SET found at the key and whether it wrote the new value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetOutcome {
+    pub written: bool,
+    /// Only read when asked for, since SET without GET overwrites any type.
+    pub previous: Option<String>,
+}
+
+/// Applies `update` to `key`, returning false when it deleted the key.
+pub(super) fn apply_ttl(data: &mut Keyspace, key: &str, update: TtlUpdate) -> bool {
+    match update {
+        TtlUpdate::Keep => {}
+        TtlUpdate::Persist => {
+            data.persist(key);
+        }
+        TtlUpdate::At(at) => data.set_expire_at(key, at),
+    }
+    data.contains_key(key)
+}
+
+/// Parses an integer the way Redis' `string2ll` does: no sign but `-`, no whitespace and no
+/// leading zeros, so "+1", " 1", "01" and "-0" are all rejected.
+pub fn parse_strict_integer(text: &str) -> Option<i64> {
+    if text == "0" {
+        return Some(0);
+    }
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    if !digits.starts_with(|c: char| matches!(c, '1'..='9'))
+        || !digits.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn string_value(data: &Keyspace, key: &str) -> Result<Option<String>, &'static str> {
+    match data.get(key) {
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// A run of characters common to both strings, as inclusive ranges into each.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LcsMatch {
+    pub first: (usize, usize),
+    pub second: (usize, usize),
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lcs {
+    pub text: String,
+    /// Matching runs from the end of the strings backwards, the order Redis reports them in.
+    pub matches: Vec<LcsMatch>,
+}
+
+/// Longest common subsequence by dynamic programming over every pair of prefixes.
+pub fn longest_common_subsequence(a: &[u8], b: &[u8]) -> Lcs {
+    let width = b.len() + 1;
+    let mut table = vec![0_u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+
+    let mut text = Vec::with_capacity(table[a.len() * width + b.len()] as usize);
+    let mut matches = Vec::new();
+    let mut current: Option<LcsMatch> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            text.push(a[i - 1]);
+            current = Some(match current {
+                // Walking backwards, a match right before the current run extends it.
+                Some(run) if run.first.0 == i && run.second.0 == j => LcsMatch {
+                    first: (i - 1, run.first.1),
+                    second: (j - 1, run.second.1),
+                    len: run.len + 1,
+                },
+                _ => LcsMatch {
+                    first: (i - 1, i - 1),
+                    second: (j - 1, j - 1),
+                    len: 1,
+                },
+            });
+            i -= 1;
+            j -= 1;
+        } else {
+            if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            matches.extend(current.take());
+        }
+    }
+    matches.extend(current);
+    text.reverse();
+    Lcs {
+        text: from_bytes(&text).into_owned(),
+        matches,
+    }
+}
+
+impl Cache {
+    /// Adds `delta` to the integer stored at `key`, starting from 0 and keeping the TTL.
+    pub fn incr_by(&self, key: &str, delta: i64) -> Result<i64, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let current = match string_value(&data, key)? {
+            Some(value) => parse_strict_integer(&value).ok_or(NOT_INTEGER_ERR)?,
+            None => 0,
+        };
+        let updated = current.checked_add(delta).ok_or(OVERFLOW_ERR)?;
+        store_bytes(&mut data, key, updated.to_string().as_bytes());
+        Ok(updated)
+    }
+
+    /// Adds `delta` to the float stored at `key` and returns the new value as stored.
+    pub fn incr_by_float(&self, key: &str, delta: f64) -> Result<String, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let current = match string_value(&data, key)? {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or(NOT_FLOAT_ERR)?,
+            None => 0.0,
+        };
+        let updated = current + delta;
+        if !updated.is_finite() {
+            return Err(NAN_OR_INFINITY_ERR);
+        }
+        let updated = format_float(updated);
+        store_bytes(&mut data, key, updated.as_bytes());
+        Ok(updated)
+    }
+
+    /// Returns the length of the string after appending.
+    pub fn append(&self, key: &str, suffix: &str) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let mut bytes = string_bytes(&data, key)?.unwrap_or_default();
+        let suffix = to_bytes(suffix);
+        if bytes.len() + suffix.len() > MAX_STRING_LEN {
+            return Err(STRING_TOO_LONG_ERR);
+        }
+        bytes.extend_from_slice(&suffix);
+        store_bytes(&mut data, key, &bytes);
+        Ok(bytes.len())
+    }
+
+    pub fn strlen(&self, key: &str) -> Result<usize, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(string_bytes(&data, key)?.map_or(0, |bytes| bytes.len()))
+    }
+
+    /// The bytes between `start` and `end` inclusive, negative offsets counting from the end.
+    pub fn getrange(&self, key: &str, start: i64, end: i64) -> Result<String, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let bytes = string_bytes(&data, key)?.unwrap_or_default();
+        let len = bytes.len() as i64;
+        if start < 0 && end < 0 && start > end {
+            return Ok(String::new());
+        }
+        let start = if start < 0 { len + start } else { start }.max(0);
+        let end = if end < 0 { len + end } else { end }.max(0).min(len - 1);
+        if len == 0 || start > end {
+            return Ok(String::new());
+        }
+        Ok(from_bytes(&bytes[start as usize..=end as usize]).into_owned())
+    }
+
+    /// Overwrites part of the string starting at `offset`, zero padding it when it is shorter.
+    /// Returns the resulting length.
+    pub fn setrange(&self, key: &str, offset: usize, value: &str) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let existing = string_bytes(&data, key)?;
+        let value = to_bytes(value);
+        if value.is_empty() {
+            return Ok(existing.map_or(0, |bytes| bytes.len()));
+        }
+        if offset + value.len() > MAX_STRING_LEN {
+            return Err(STRING_TOO_LONG_ERR);
+        }
+        let mut bytes = existing.unwrap_or_default();
+        if bytes.len() < offset + value.len() {
+            bytes.resize(offset + value.len(), 0);
+        }
+        bytes[offset..offset + value.len()].copy_from_slice(&value);
+        store_bytes(&mut data, key, &bytes);
+        Ok(bytes.len())
+    }
+
+    pub fn getdel(&self, key: &str) -> Result<Option<String>, &'static str> {
+        self.write_keyspace(|data| {
+            let value = string_value(data, key)?;
+            if value.is_some() {
+                data.remove(key);
+            }
+            Ok(value)
+        })
+    }
+
+    pub fn getex(&self, key: &str, update: TtlUpdate) -> Result<Option<String>, &'static str> {
+        self.write_keyspace(|data| {
+            let value = string_value(data, key)?;
+            if value.is_some() {
+                apply_ttl(data, key, update);
+            }
+            Ok(value)
+        })
+    }
+
+    /// Sets a new value, dropping the TTL, and returns the previous one.
+    pub fn getset(&self, key: &str, value: String) -> Result<Option<String>, &'static str> {
+        self.write_keyspace(|data| {
+            let previous = string_value(data, key)?;
+            data.persist(key);
+            data.insert(key.to_string(), Value::String(value));
+            Ok(previous)
+        })
+    }
+
+    /// SET with all its options. Reading the previous value fails with WRONGTYPE before anything
+    /// is written, and a TTL in the past stores the value only to delete it right away.
+    pub fn set_with(
+        &self,
+        key: &str,
+        value: String,
+        condition: &SetCondition,
+        ttl: TtlUpdate,
+        get: bool,
+    ) -> Result<SetOutcome, &'static str> {
+        self.write_keyspace(|data| {
+            let previous = if get { string_value(data, key)? } else { None };
+            let written = condition.allows(data.get(key))?;
+            if written {
+                data.insert(key.to_string(), Value::String(value));
+                apply_ttl(data, key, ttl);
+            }
+            Ok(SetOutcome { written, previous })
+        })
+    }
+
+    /// Values of the string keys, `None` for missing keys and other types.
+    pub fn mget(&self, keys: &[String]) -> Result<Vec<Option<String>>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(keys
+            .iter()
+            .map(|key| match data.get(key) {
+                Some(Value::String(value)) => Some(value.clone()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Sets every pair, or none of them when `condition` fails for any key. Returns whether
+    /// they were set.
+    pub fn mset(
+        &self,
+        pairs: &[(String, String)],
+        condition: &SetCondition,
+        ttl: TtlUpdate,
+    ) -> Result<bool, &'static str> {
+        self.write_keyspace(|data| {
+            for (key, _) in pairs {
+                if !condition.allows(data.get(key))? {
+                    return Ok(false);
+                }
+            }
+            for (key, value) in pairs {
+                data.insert(key.clone(), Value::String(value.clone()));
+                apply_ttl(data, key, ttl);
+            }
+            Ok(true)
+        })
+    }
+
+    pub fn lcs(&self, first: &str, second: &str) -> Result<Lcs, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let bytes = |key| {
+            string_bytes(&data, key)
+                .map(Option::unwrap_or_default)
+                .map_err(|_| LCS_WRONGTYPE_ERR)
+        };
+        let (a, b) = (bytes(first)?, bytes(second)?);
+        let cells = (a.len() + 1).checked_mul(b.len() + 1);
+        if cells.is_none_or(|cells| cells > MAX_STRING_LEN / std::mem::size_of::<u32>()) {
+            return Err(LCS_TOO_LONG_ERR);
+        }
+        Ok(longest_common_subsequence(&a, &b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn should_find_matching_runs_like_redis() {
+        let lcs = longest_common_subsequence(b"ohmytext", b"mynewtext");
+        assert_eq!(lcs.text, "mytext");
+        assert_eq!(
+            lcs.matches,
+            vec![
+                LcsMatch {
+                    first: (4, 7),
+                    second: (5, 8),
+                    len: 4
+                },
+                LcsMatch {
+                    first: (2, 3),
+                    second: (0, 1),
+                    len: 2
+                },
+            ]
+        );
+        assert_eq!(longest_common_subsequence(b"", b"abc").text, "");
+    }
+
+    #[test]
+    fn should_parse_integers_strictly() {
+        assert_eq!(parse_strict_integer("0"), Some(0));
+        assert_eq!(parse_strict_integer("-42"), Some(-42));
+        assert_eq!(parse_strict_integer("9223372036854775807"), Some(i64::MAX));
+        for text in [
+            "",
+            "+1",
+            " 1",
+            "1 ",
+            "01",
+            "-0",
+            "-",
+            "1.0",
+            "9223372036854775808",
+        ] {
+            assert_eq!(parse_strict_integer(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn should_drop_the_ttl_when_a_value_is_replaced() {
+        let cache = Cache::new();
+        let has_ttl = |key: &str| cache.data.read().unwrap().expire_at(key).is_some();
+        for replace in [
+            |cache: &Cache| cache.set("k".to_string(), "new".to_string()),
+            |cache: &Cache| cache.getset("k", "new".to_string()).map(|_| ()),
+            |cache: &Cache| cache.getex("k", TtlUpdate::Persist).map(|_| ()),
+            |cache: &Cache| {
+                let condition = SetCondition::Xx;
+                cache
+                    .set_with("k", "new".to_string(), &condition, TtlUpdate::Persist, true)
+                    .map(|_| ())
+            },
+        ] {
+            cache
+                .set_with_expiration("k".to_string(), "v".to_string(), Duration::from_secs(60))
+                .unwrap();
+            replace(&cache).unwrap();
+            assert!(!has_ttl("k"));
+        }
+
+        cache
+            .set_with_expiration("k".to_string(), "1".to_string(), Duration::from_secs(60))
+            .unwrap();
+        cache.append("k", "0").unwrap();
+        assert_eq!(cache.incr_by("k", 1).unwrap(), 11);
+        cache
+            .set_with(
+                "k",
+                "v".to_string(),
+                &SetCondition::Always,
+                TtlUpdate::Keep,
+                false,
+            )
+            .unwrap();
+        assert!(has_ttl("k"));
+        cache.del("k").unwrap();
+        assert!(!has_ttl("k"));
+    }
+}
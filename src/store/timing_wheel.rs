@@ -0,0 +1,265 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::db::unix_time_ms;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+/// Eleven levels of 64 slots cover every 64 bit millisecond deadline.
+const LEVELS: usize = 11;
+
+/// Where a key sits: its deadline plus the level, slot and position inside the slot.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    at: u64,
+    level: u8,
+    slot: u8,
+    position: u32,
+}
+
+#[derive(Debug)]
+struct Level {
+    slots: Vec<Vec<Arc<str>>>,
+    /// Bit `n` is set when slot `n` holds keys.
+    occupied: u64,
+}
+
+/// Hierarchical timing wheel of key deadlines in Unix milliseconds, the structure the Linux and
+/// Kafka timers use. Level `n` has 64 slots of 64^n ms each, and a key lives on the lowest level
+/// where its deadline and the wheel's clock differ only in that level's six bits. Insert,
+/// delete and update are O(1); advancing the clock jumps straight to the next occupied slot via
+/// per-level bitmaps and moves each key down at most once per level. Every key costs one
+/// location entry, and its name is shared with the caller rather than copied.
+#[derive(Debug)]
+pub struct TimingWheel {
+    levels: Vec<Level>,
+    locations: HashMap<Arc<str>, Location>,
+    /// Everything due at or before this time has been handed out by `advance`.
+    elapsed: u64,
+}
+
+impl Default for TimingWheel {
+    /// A wheel whose clock starts at the current Unix time.
+    fn default() -> Self {
+        TimingWheel::new(unix_time_ms())
+    }
+}
+
+fn slot_span(level: usize) -> u128 {
+    1 << (SLOT_BITS as usize * level)
+}
+
+impl TimingWheel {
+    pub fn new(now: u64) -> Self {
+        TimingWheel {
+            levels: (0..LEVELS)
+                .map(|_| Level {
+                    slots: vec![Vec::new(); SLOTS],
+                    occupied: 0,
+                })
+                .collect(),
+            locations: HashMap::new(),
+            elapsed: now,
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    #[cfg(test)]
+    fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    #[cfg(test)]
+    fn deadline(&self, key: &str) -> Option<u64> {
+        self.locations.get(key).map(|location| location.at)
+    }
+
+    /// Adds `key` or moves it to a new deadline. Deadlines already passed go to the current slot
+    /// and come out of the next `advance`.
+    pub fn insert(&mut self, key: Arc<str>, at: u64) {
+        self.remove(&key);
+        self.place(key, at);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<u64> {
+        let location = self.locations.remove(key)?;
+        let level = &mut self.levels[location.level as usize];
+        let slot = &mut level.slots[location.slot as usize];
+        slot.swap_remove(location.position as usize);
+        match slot.get(location.position as usize) {
+            Some(moved) => {
+                if let Some(moved) = self.locations.get_mut(moved) {
+                    moved.position = location.position;
+                }
+            }
+            None if slot.is_empty() => level.occupied &= !(1 << location.slot),
+            None => {}
+        }
+        Some(location.at)
+    }
+
+    fn place(&mut self, key: Arc<str>, at: u64) {
+        let at_or_now = at.max(self.elapsed);
+        // The highest bit where deadline and clock differ picks the level.
+        let significant = 63 - ((at_or_now ^ self.elapsed) | (SLOTS as u64 - 1)).leading_zeros();
+        let level = (significant / SLOT_BITS) as usize;
+        let slot = ((at_or_now >> (SLOT_BITS as usize * level)) & (SLOTS as u64 - 1)) as usize;
+        let entries = &mut self.levels[level].slots[slot];
+        self.locations.insert(
+            key.clone(),
+            Location {
+                at,
+                level: level as u8,
+                slot: slot as u8,
+                position: entries.len() as u32,
+            },
+        );
+        entries.push(key);
+        self.levels[level].occupied |= 1 << slot;
+    }
+
+    /// The earliest occupied slot as its level, slot and start time. Lower levels always hold
+    /// earlier deadlines than higher ones.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        self.levels.iter().enumerate().find_map(|(level, wheel)| {
+            if wheel.occupied == 0 {
+                return None;
+            }
+            let span = slot_span(level);
+            let current = ((self.elapsed as u128 / span) % SLOTS as u128) as u32;
+            // Slots behind the clock cannot be occupied, so the next one is at or after it.
+            let slot = current + (wheel.occupied >> current).trailing_zeros();
+            let level_start = self.elapsed as u128 / (span * SLOTS as u128) * span * SLOTS as u128;
+            let start = (level_start + slot as u128 * span).min(u64::MAX as u128) as u64;
+            Some((level, slot as usize, start))
+        })
+    }
+
+    /// Deadline of the key that expires next.
+    #[cfg(test)]
+    fn next_deadline(&self) -> Option<u64> {
+        let (level, slot, _) = self.next_slot()?;
+        self.levels[level].slots[slot]
+            .iter()
+            .filter_map(|key| self.deadline(key))
+            .min()
+    }
+
+    /// Moves the clock to `now` and removes every key due by then, returning them. The clock
+    /// never goes backwards, so a wall clock that jumps back just delays expiry.
+    pub fn advance(&mut self, now: u64) -> Vec<Arc<str>> {
+        let mut due = Vec::new();
+        while let Some((level, slot, start)) = self.next_slot() {
+            if start > now {
+                break;
+            }
+            self.elapsed = self.elapsed.max(start);
+            let keys = std::mem::take(&mut self.levels[level].slots[slot]);
+            self.levels[level].occupied &= !(1 << slot);
+            for key in keys {
+                let Some(location) = self.locations.remove(&key) else {
+                    continue;
+                };
+                if location.at <= now {
+                    due.push(key);
+                } else {
+                    // Cascade to a lower level, now that the clock is inside this slot.
+                    self.place(key, location.at);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The keys `advance` hands out, sorted.
+    fn advance(wheel: &mut TimingWheel, now: u64) -> Vec<String> {
+        let mut due: Vec<String> = wheel
+            .advance(now)
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        due.sort();
+        due
+    }
+
+    #[test]
+    fn should_hand_out_keys_once_their_deadline_passes() {
+        let start = 1_700_000_000_000;
+        let mut wheel = TimingWheel::new(start);
+        for (key, delay) in [
+            ("a", 5),
+            ("b", 70),
+            ("c", 5_000),
+            ("d", 86_400_000),
+            ("e", 70),
+        ] {
+            wheel.insert(key.into(), start + delay);
+        }
+        assert_eq!(wheel.next_deadline(), Some(start + 5));
+        assert!(advance(&mut wheel, start + 4).is_empty());
+        assert_eq!(advance(&mut wheel, start + 5), vec!["a"]);
+        assert_eq!(wheel.next_deadline(), Some(start + 70));
+        assert_eq!(advance(&mut wheel, start + 4_999), vec!["b", "e"]);
+        assert_eq!(advance(&mut wheel, start + 86_399_999), vec!["c"]);
+        assert_eq!(wheel.len(), 1);
+        assert_eq!(advance(&mut wheel, u64::MAX), vec!["d"]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn should_update_and_remove_in_place() {
+        let start = 1_000;
+        let mut wheel = TimingWheel::new(start);
+        for key in ["a", "b", "c"] {
+            wheel.insert(key.into(), start + 100);
+        }
+        wheel.insert("a".into(), start + 10_000);
+        assert_eq!(wheel.remove("b"), Some(start + 100));
+        assert_eq!(wheel.remove("b"), None);
+        assert_eq!(wheel.deadline("a"), Some(start + 10_000));
+        assert_eq!(advance(&mut wheel, start + 100), vec!["c"]);
+        assert_eq!(wheel.next_deadline(), Some(start + 10_000));
+
+        // Deadlines already behind the clock come out right away.
+        wheel.insert("late".into(), start - 50);
+        assert_eq!(advance(&mut wheel, start + 101), vec!["late"]);
+        assert_eq!(advance(&mut wheel, start + 10_000), vec!["a"]);
+    }
+
+    #[test]
+    fn should_match_a_sorted_reference() {
+        let start = 5_000_000;
+        let mut wheel = TimingWheel::new(start);
+        let mut reference: Vec<(u64, String)> = (0..2_000_u64)
+            .map(|i| (start + (i * 7_919) % 200_000, i.to_string()))
+            .collect();
+        for (at, key) in &reference {
+            wheel.insert(key.as_str().into(), *at);
+        }
+        reference.sort();
+        let mut now = start;
+        let mut expired = 0;
+        while expired < reference.len() {
+            now += 997;
+            let due = advance(&mut wheel, now);
+            let mut expected: Vec<String> = reference[expired..]
+                .iter()
+                .take_while(|(at, _)| *at <= now)
+                .map(|(_, key)| key.clone())
+                .collect();
+            expected.sort();
+            expired += expected.len();
+            assert_eq!(due, expected);
+        }
+        assert!(wheel.is_empty());
+    }
+}
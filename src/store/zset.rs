@@ -0,0 +1,1282 @@
+use std::{borrow::Cow, collections::HashMap, mem::size_of};
+
+use super::{
+    db::{Cache, Value, WRONGTYPE_ERR},
+    keyspace::Keyspace,
+    listpack::Listpack,
+    memory::{estimate, string_usage, ELEMENT_OVERHEAD},
+    random::{check_sample_count, sample_distinct, sample_with_repetition},
+    scan::{scan_page, ScanPage},
+    set::{Set, SetOperation},
+    skiplist::{LexRange, ScoreRange, SkipList},
+};
+
+/// Members along with their scores, in the order a command returns them.
+pub type ScoredMembers = Vec<(String, f64)>;
+
+const NAN_SCORE_ERR: &str = "ERR resulting score is not a number (NaN)";
+
+/// Sorted sets stay in a listpack up to this many members...
+pub const ZSET_MAX_LISTPACK_ENTRIES: usize = 128;
+/// ...as long as no member is longer than this many bytes.
+pub const ZSET_MAX_LISTPACK_VALUE: usize = 64;
+
+/// A small sorted set is a listpack of member/score pairs kept in (score, member) order, where
+/// ranks and ranges are a linear walk. Too many or too long members move it for good to a
+/// [`SkipListSet`].
+#[derive(Debug, Clone)]
+pub enum SortedSet {
+    Listpack(Listpack),
+    SkipList(SkipListSet),
+}
+
+impl Default for SortedSet {
+    fn default() -> Self {
+        SortedSet::Listpack(Listpack::default())
+    }
+}
+
+impl PartialEq for SortedSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .entries()
+                .iter()
+                .all(|(member, score)| other.score(member) == Some(*score))
+    }
+}
+
+/// The full representation keeps every member twice: in a dict for O(1) score lookups and in a
+/// skiplist ordered by (score, member) for rank and range queries.
+#[derive(Debug, Clone, Default)]
+pub struct SkipListSet {
+    scores: HashMap<String, f64>,
+    list: SkipList,
+}
+
+/// Whether a listpack of `len` members could take `member` and remain a listpack.
+fn fits_listpack(len: usize, member: &str) -> bool {
+    len < ZSET_MAX_LISTPACK_ENTRIES && member.len() <= ZSET_MAX_LISTPACK_VALUE
+}
+
+/// Members and scores of a listpack sorted set, lowest score first. Scores are stored as their
+/// shortest round-tripping text.
+fn packed_entries(pack: &Listpack) -> impl Iterator<Item = (&str, f64)> {
+    pack.pairs().map(|(member, score)| {
+        let score = score.parse().expect("listpacks hold valid scores");
+        (member, score)
+    })
+}
+
+/// Resolves ZRANGE start and stop indexes, negative ones counting from the end, into an
+/// inclusive range of ranks, or `None` when it is empty.
+fn clamp_ranks(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+    (start <= stop).then_some((start as usize, stop as usize))
+}
+
+/// ZADD flags. NX/XX decide whether members may be added or updated, GT/LT only let updates
+/// move the score in one direction and INCR adds to the current score instead of replacing it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZAddOptions {
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+    pub incr: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddStatus {
+    Added,
+    Updated,
+    Unchanged,
+    Skipped,
+}
+
+/// What ZADD did: how many members were added and updated, and the final score of the last
+/// element, which is what ZADD INCR answers with (`None` when the update was skipped).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZAddOutcome {
+    pub added: usize,
+    pub updated: usize,
+    pub score: Option<f64>,
+}
+
+/// How ZUNION and ZINTER merge the scores of a member found in several inputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Aggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(self, current: f64, score: f64) -> f64 {
+        let merged = match self {
+            Aggregate::Sum => current + score,
+            Aggregate::Min => current.min(score),
+            Aggregate::Max => current.max(score),
+        };
+        // inf + -inf has no meaningful value, Redis settles on zero.
+        if merged.is_nan() {
+            0.0
+        } else {
+            merged
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeSpec {
+    /// Start and stop indexes, negative ones counting from the end.
+    Rank(i64, i64),
+    Score(ScoreRange),
+    Lex(LexRange),
+}
+
+/// A ZRANGE request. For REV score and lex ranges the bounds are still given as min and max.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeQuery {
+    pub spec: RangeSpec,
+    pub reverse: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl SkipListSet {
+    fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    fn insert(&mut self, member: &str, score: f64) -> bool {
+        match self.scores.get_mut(member) {
+            Some(current) if *current == score => false,
+            Some(current) => {
+                self.list.remove(*current, member);
+                self.list.insert(score, member.to_string());
+                *current = score;
+                false
+            }
+            None => {
+                self.scores.insert(member.to_string(), score);
+                self.list.insert(score, member.to_string());
+                true
+            }
+        }
+    }
+
+    fn remove(&mut self, member: &str) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.list.remove(score, member),
+            None => false,
+        }
+    }
+
+    fn rank(&self, member: &str) -> Option<usize> {
+        self.list.rank(self.score(member)?, member)
+    }
+
+    fn entry(&self, node: usize) -> (String, f64) {
+        (self.list.member(node).to_string(), self.list.score(node))
+    }
+
+    /// Walks from `start` towards higher (or, with `reverse`, lower) scores, skipping `offset`
+    /// members and stopping after `limit` members or at the first one outside the range.
+    fn walk(
+        &self,
+        start: Option<usize>,
+        reverse: bool,
+        offset: usize,
+        limit: Option<usize>,
+        in_range: impl Fn(usize) -> bool,
+    ) -> Vec<(String, f64)> {
+        let step = |node| {
+            if reverse {
+                self.list.prev(node)
+            } else {
+                self.list.next(node)
+            }
+        };
+        let mut node = start;
+        for _ in 0..offset {
+            node = node.filter(|&node| in_range(node)).and_then(step);
+        }
+        let mut entries = Vec::new();
+        while let Some(current) = node.filter(|&node| in_range(node)) {
+            if limit.is_some_and(|limit| entries.len() >= limit) {
+                break;
+            }
+            entries.push(self.entry(current));
+            node = step(current);
+        }
+        entries
+    }
+
+    fn range(&self, query: &RangeQuery) -> Vec<(String, f64)> {
+        match &query.spec {
+            RangeSpec::Rank(start, stop) => {
+                let Some((start, stop)) = clamp_ranks(*start, *stop, self.scores.len()) else {
+                    return Vec::new();
+                };
+                let first = if query.reverse {
+                    self.scores.len() - 1 - start
+                } else {
+                    start
+                };
+                let count = stop - start + 1;
+                self.walk(
+                    self.list.by_rank(first),
+                    query.reverse,
+                    0,
+                    Some(count),
+                    |_| true,
+                )
+            }
+            RangeSpec::Score(range) => {
+                let start = if query.reverse {
+                    self.list.last_in_score_range(range)
+                } else {
+                    self.list.first_in_score_range(range)
+                };
+                self.walk(start, query.reverse, query.offset, query.limit, |node| {
+                    let score = self.list.score(node);
+                    if query.reverse {
+                        range.above_min(score)
+                    } else {
+                        range.below_max(score)
+                    }
+                })
+            }
+            RangeSpec::Lex(range) => {
+                let start = if query.reverse {
+                    self.list.last_in_lex_range(range)
+                } else {
+                    self.list.first_in_lex_range(range)
+                };
+                self.walk(start, query.reverse, query.offset, query.limit, |node| {
+                    let member = self.list.member(node);
+                    if query.reverse {
+                        range.above_min(member)
+                    } else {
+                        range.below_max(member)
+                    }
+                })
+            }
+        }
+    }
+
+    /// Number of members with a score inside `range`, computed from the ranks of both ends.
+    fn count_in_score_range(&self, range: &ScoreRange) -> usize {
+        let first = self.list.first_in_score_range(range);
+        let last = self.list.last_in_score_range(range);
+        match (first, last) {
+            (Some(first), Some(last)) => {
+                let rank = |node| self.rank(self.list.member(node)).unwrap_or_default();
+                rank(last) + 1 - rank(first)
+            }
+            _ => 0,
+        }
+    }
+
+    fn count_in_lex_range(&self, range: &LexRange) -> usize {
+        let first = self.list.first_in_lex_range(range);
+        let last = self.list.last_in_lex_range(range);
+        match (first, last) {
+            (Some(first), Some(last)) => {
+                let rank = |node| self.rank(self.list.member(node)).unwrap_or_default();
+                rank(last) + 1 - rank(first)
+            }
+            _ => 0,
+        }
+    }
+
+    fn pop(&mut self, count: usize, max: bool) -> Vec<(String, f64)> {
+        let mut popped = Vec::new();
+        while popped.len() < count {
+            let node = if max {
+                self.list.last()
+            } else {
+                self.list.first()
+            };
+            let Some(node) = node else {
+                break;
+            };
+            let (member, score) = self.entry(node);
+            self.remove(&member);
+            popped.push((member, score));
+        }
+        popped
+    }
+
+    /// One ZSCAN page of members and scores, walked in hash order rather than score order.
+    fn page(&self, cursor: u64, count: usize) -> ScanPage<(String, f64)> {
+        let (cursor, page) = scan_page(
+            self.scores
+                .iter()
+                .map(|(member, score)| (member.as_str(), (member, score))),
+            self.scores.len(),
+            cursor,
+            count,
+        );
+        let page = page
+            .into_iter()
+            .map(|(member, score)| (member.clone(), *score))
+            .collect();
+        (cursor, page)
+    }
+
+    /// Every member is stored twice, as a dict key and in its skiplist node.
+    fn memory_usage(&self, samples: usize) -> usize {
+        let members = self.scores.keys().map(|member| {
+            string_usage(member) + member.capacity() + size_of::<f64>() + ELEMENT_OVERHEAD
+        });
+        estimate(members, self.scores.len(), samples) + self.list.memory_usage()
+    }
+
+    fn entries(&self) -> Vec<(String, f64)> {
+        self.walk(self.list.first(), false, 0, None, |_| true)
+    }
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        match self {
+            SortedSet::Listpack(pack) => pack.len() / 2,
+            SortedSet::SkipList(set) => set.scores.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        match self {
+            SortedSet::Listpack(pack) => packed_entries(pack)
+                .find(|&(known, _)| known == member)
+                .map(|(_, score)| score),
+            SortedSet::SkipList(set) => set.score(member),
+        }
+    }
+
+    /// Members one at a time in no particular order, without copying them.
+    pub fn members(&self) -> impl Iterator<Item = &str> {
+        let (pack, set) = match self {
+            SortedSet::Listpack(pack) => (Some(packed_entries(pack)), None),
+            SortedSet::SkipList(set) => (None, Some(set.scores.keys())),
+        };
+        pack.into_iter()
+            .flatten()
+            .map(|(member, _)| member)
+            .chain(set.into_iter().flatten().map(String::as_str))
+    }
+
+    fn convert_to_skip_list(&mut self) {
+        if let SortedSet::Listpack(pack) = self {
+            let mut set = SkipListSet::default();
+            for (member, score) in packed_entries(pack) {
+                set.insert(member, score);
+            }
+            *self = SortedSet::SkipList(set);
+        }
+    }
+
+    /// Sets the score of `member`, adding it when missing. Returns whether it was added.
+    pub fn insert(&mut self, member: &str, score: f64) -> bool {
+        if let SortedSet::Listpack(pack) = self {
+            let existing = packed_entries(pack)
+                .enumerate()
+                .find(|&(_, (known, _))| known == member)
+                .map(|(index, (_, current))| (index, current));
+            if existing.is_some_and(|(_, current)| current == score) {
+                return false;
+            }
+            if let Some((index, _)) = existing {
+                pack.remove_range(2 * index, 2);
+            }
+            // A member that is already in the listpack fits it by definition.
+            if existing.is_some() || fits_listpack(pack.len() / 2, member) {
+                let position = packed_entries(pack)
+                    .position(|(known, known_score)| {
+                        known_score > score || (known_score == score && known > member)
+                    })
+                    .unwrap_or(pack.len() / 2);
+                pack.insert(2 * position, member);
+                pack.insert(2 * position + 1, &score.to_string());
+                return existing.is_none();
+            }
+            self.convert_to_skip_list();
+        }
+        match self {
+            SortedSet::SkipList(set) => set.insert(member, score),
+            SortedSet::Listpack(_) => unreachable!("converted above"),
+        }
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self {
+            SortedSet::Listpack(pack) => {
+                let position = packed_entries(pack).position(|(known, _)| known == member);
+                match position {
+                    Some(index) => pack.remove_range(2 * index, 2).len() == 2,
+                    None => false,
+                }
+            }
+            SortedSet::SkipList(set) => set.remove(member),
+        }
+    }
+
+    /// Applies one ZADD element and returns what happened along with the resulting score.
+    pub fn add(
+        &mut self,
+        member: &str,
+        score: f64,
+        options: &ZAddOptions,
+    ) -> Result<(AddStatus, Option<f64>), &'static str> {
+        let Some(current) = self.score(member) else {
+            if options.xx {
+                return Ok((AddStatus::Skipped, None));
+            }
+            self.insert(member, score);
+            return Ok((AddStatus::Added, Some(score)));
+        };
+        if options.nx {
+            return Ok((AddStatus::Skipped, None));
+        }
+        let score = if options.incr { current + score } else { score };
+        if score.is_nan() {
+            return Err(NAN_SCORE_ERR);
+        }
+        if (options.gt && score <= current) || (options.lt && score >= current) {
+            return Ok((AddStatus::Skipped, None));
+        }
+        if score == current {
+            return Ok((AddStatus::Unchanged, Some(score)));
+        }
+        self.insert(member, score);
+        Ok((AddStatus::Updated, Some(score)))
+    }
+
+    /// 0-based rank of `member`, counted from the highest score when `reverse` is set.
+    pub fn rank(&self, member: &str, reverse: bool) -> Option<usize> {
+        let rank = match self {
+            SortedSet::Listpack(pack) => {
+                packed_entries(pack).position(|(known, _)| known == member)
+            }
+            SortedSet::SkipList(set) => set.rank(member),
+        }?;
+        Some(if reverse { self.len() - 1 - rank } else { rank })
+    }
+
+    pub fn range(&self, query: &RangeQuery) -> Vec<(String, f64)> {
+        let pack = match self {
+            SortedSet::Listpack(pack) => pack,
+            SortedSet::SkipList(set) => return set.range(query),
+        };
+        let mut entries: Vec<(&str, f64)> = packed_entries(pack).collect();
+        if query.reverse {
+            entries.reverse();
+        }
+        let (skip, take) = match &query.spec {
+            RangeSpec::Rank(start, stop) => match clamp_ranks(*start, *stop, entries.len()) {
+                Some((start, stop)) => (start, Some(stop - start + 1)),
+                None => return Vec::new(),
+            },
+            RangeSpec::Score(_) | RangeSpec::Lex(_) => (query.offset, query.limit),
+        };
+        entries
+            .into_iter()
+            .filter(|&(member, score)| match &query.spec {
+                RangeSpec::Rank(..) => true,
+                RangeSpec::Score(range) => range.above_min(score) && range.below_max(score),
+                RangeSpec::Lex(range) => range.above_min(member) && range.below_max(member),
+            })
+            .skip(skip)
+            .take(take.unwrap_or(usize::MAX))
+            .map(|(member, score)| (member.to_string(), score))
+            .collect()
+    }
+
+    /// Number of members with a score inside `range`.
+    pub fn count_in_score_range(&self, range: &ScoreRange) -> usize {
+        match self {
+            SortedSet::Listpack(pack) => packed_entries(pack)
+                .filter(|&(_, score)| range.above_min(score) && range.below_max(score))
+                .count(),
+            SortedSet::SkipList(set) => set.count_in_score_range(range),
+        }
+    }
+
+    /// Number of members between the bounds of `range`, which only makes sense when every
+    /// member has the same score.
+    pub fn count_in_lex_range(&self, range: &LexRange) -> usize {
+        match self {
+            SortedSet::Listpack(pack) => packed_entries(pack)
+                .filter(|&(member, _)| range.above_min(member) && range.below_max(member))
+                .count(),
+            SortedSet::SkipList(set) => set.count_in_lex_range(range),
+        }
+    }
+
+    /// Removes and returns up to `count` members from the low (or, with `max`, high) end.
+    pub fn pop(&mut self, count: usize, max: bool) -> Vec<(String, f64)> {
+        let pack = match self {
+            SortedSet::Listpack(pack) => pack,
+            SortedSet::SkipList(set) => return set.pop(count, max),
+        };
+        let count = count.min(pack.len() / 2);
+        let start = if max { pack.len() / 2 - count } else { 0 };
+        let mut popped: Vec<(String, f64)> = pack
+            .remove_range(2 * start, 2 * count)
+            .chunks_exact(2)
+            .map(|pair| {
+                let score = pair[1].parse().expect("listpacks hold valid scores");
+                (pair[0].clone(), score)
+            })
+            .collect();
+        if max {
+            popped.reverse();
+        }
+        popped
+    }
+
+    /// One ZSCAN page. Like Redis, a listpack sorted set comes back whole with a zero cursor.
+    pub fn page(&self, cursor: u64, count: usize) -> ScanPage<(String, f64)> {
+        match self {
+            SortedSet::Listpack(_) => (0, self.entries()),
+            SortedSet::SkipList(set) => set.page(cursor, count),
+        }
+    }
+
+    pub(super) fn memory_usage(&self, samples: usize) -> usize {
+        match self {
+            SortedSet::Listpack(pack) => pack.allocated(),
+            SortedSet::SkipList(set) => set.memory_usage(samples),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<(String, f64)> {
+        match self {
+            SortedSet::Listpack(pack) => packed_entries(pack)
+                .map(|(member, score)| (member.to_string(), score))
+                .collect(),
+            SortedSet::SkipList(set) => set.entries(),
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            SortedSet::Listpack(_) => "listpack",
+            SortedSet::SkipList(_) => "skiplist",
+        }
+    }
+}
+
+pub(super) fn zset_ref<'a>(
+    data: &'a Keyspace,
+    key: &str,
+) -> Result<Option<&'a SortedSet>, &'static str> {
+    match data.get(key) {
+        Some(Value::SortedSet(zset)) => Ok(Some(zset)),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// Scores of the sorted set at `key`. Plain sets are accepted too, with every member scored 1,
+/// which is what lets ZUNIONSTORE mix both types.
+fn weighted_scores(
+    data: &Keyspace,
+    key: &str,
+    weight: f64,
+) -> Result<Option<HashMap<String, f64>>, &'static str> {
+    let weigh = |score: f64| {
+        let weighted = score * weight;
+        if weighted.is_nan() {
+            0.0
+        } else {
+            weighted
+        }
+    };
+    match data.get(key) {
+        Some(Value::SortedSet(zset)) => Ok(Some(
+            zset.entries()
+                .into_iter()
+                .map(|(member, score)| (member, weigh(score)))
+                .collect(),
+        )),
+        Some(Value::Set(set)) => Ok(Some(
+            set.members()
+                .into_iter()
+                .map(|member| (member, weigh(1.0)))
+                .collect(),
+        )),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// A ZINTERCARD input. Plain sets take part as they do in ZINTER.
+#[derive(Clone, Copy)]
+enum CardInput<'a> {
+    Sorted(&'a SortedSet),
+    Plain(&'a Set),
+}
+
+impl<'a> CardInput<'a> {
+    fn len(self) -> usize {
+        match self {
+            CardInput::Sorted(zset) => zset.len(),
+            CardInput::Plain(set) => set.len(),
+        }
+    }
+
+    fn contains(self, member: &str) -> bool {
+        match self {
+            CardInput::Sorted(zset) => zset.score(member).is_some(),
+            CardInput::Plain(set) => set.contains(member),
+        }
+    }
+
+    fn members(self) -> impl Iterator<Item = Cow<'a, str>> {
+        let (zset, set) = match self {
+            CardInput::Sorted(zset) => (Some(zset), None),
+            CardInput::Plain(set) => (None, Some(set)),
+        };
+        zset.into_iter()
+            .flat_map(SortedSet::members)
+            .map(Cow::Borrowed)
+            .chain(set.into_iter().flat_map(Set::iter))
+    }
+}
+
+fn card_input<'a>(data: &'a Keyspace, key: &str) -> Result<Option<CardInput<'a>>, &'static str> {
+    match data.get(key) {
+        Some(Value::SortedSet(zset)) => Ok(Some(CardInput::Sorted(zset))),
+        Some(Value::Set(set)) => Ok(Some(CardInput::Plain(set))),
+        Some(_) => Err(WRONGTYPE_ERR),
+        None => Ok(None),
+    }
+}
+
+/// Computes ZUNION/ZINTER/ZDIFF over `keys`. `weights` is either empty or has one entry per
+/// key; ZDIFF ignores both weights and the aggregate and keeps the scores of the first key.
+fn combine(
+    data: &Keyspace,
+    operation: SetOperation,
+    keys: &[String],
+    weights: &[f64],
+    aggregate: Aggregate,
+) -> Result<SortedSet, &'static str> {
+    let inputs = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let weight = match operation {
+                SetOperation::Diff => 1.0,
+                _ => weights.get(i).copied().unwrap_or(1.0),
+            };
+            weighted_scores(data, key, weight)
+        })
+        .collect::<Result<Vec<_>, &'static str>>()?;
+    let Some((first, others)) = inputs.split_first() else {
+        return Ok(SortedSet::default());
+    };
+
+    let mut merged: HashMap<String, f64> = HashMap::new();
+    match operation {
+        SetOperation::Union => {
+            for scores in inputs.iter().flatten() {
+                for (member, score) in scores {
+                    merged
+                        .entry(member.clone())
+                        .and_modify(|current| *current = aggregate.apply(*current, *score))
+                        .or_insert(*score);
+                }
+            }
+        }
+        SetOperation::Inter => {
+            if let (Some(first), true) = (first, others.iter().all(Option::is_some)) {
+                for (member, score) in first {
+                    let others: Option<Vec<f64>> = others
+                        .iter()
+                        .flatten()
+                        .map(|scores| scores.get(member).copied())
+                        .collect();
+                    if let Some(others) = others {
+                        let score = others
+                            .into_iter()
+                            .fold(*score, |merged, score| aggregate.apply(merged, score));
+                        merged.insert(member.clone(), score);
+                    }
+                }
+            }
+        }
+        SetOperation::Diff => {
+            for (member, score) in first.iter().flatten() {
+                if others
+                    .iter()
+                    .flatten()
+                    .all(|scores| !scores.contains_key(member))
+                {
+                    merged.insert(member.clone(), *score);
+                }
+            }
+        }
+    }
+
+    let mut result = SortedSet::default();
+    for (member, score) in merged {
+        result.insert(&member, score);
+    }
+    Ok(result)
+}
+
+/// Replaces `destination` with `zset`, or deletes it when `zset` is empty. Like SET, the
+/// destination does not keep the TTL it had.
+pub(super) fn store_zset(data: &mut Keyspace, destination: &str, zset: SortedSet) -> usize {
+    let len = zset.len();
+    data.remove(destination);
+    if !zset.is_empty() {
+        data.insert(destination.to_string(), Value::SortedSet(Box::new(zset)));
+    }
+    len
+}
+
+/// Runs `write` against the sorted set at `key`, creating it first and dropping the key once
+/// the set ends up empty.
+fn write_zset_in<T>(
+    data: &mut Keyspace,
+    key: &str,
+    write: impl FnOnce(&mut SortedSet) -> Result<T, &'static str>,
+) -> Result<T, &'static str> {
+    let zset = match data.get_or_insert_with(key.to_string(), || Value::SortedSet(Box::default())) {
+        Value::SortedSet(zset) => zset,
+        _ => return Err(WRONGTYPE_ERR),
+    };
+    let result = write(zset);
+    if zset.is_empty() {
+        data.remove(key);
+    }
+    result
+}
+
+/// Pops up to `count` members from the low (or, with `max`, high) end of the sorted set at
+/// `key`, removing the key once it is empty. Returns `None` when the key does not exist.
+pub fn zpop_from(
+    data: &mut Keyspace,
+    key: &str,
+    count: usize,
+    max: bool,
+) -> Result<Option<ScoredMembers>, &'static str> {
+    match data.get(key) {
+        Some(Value::SortedSet(_)) => {}
+        Some(_) => return Err(WRONGTYPE_ERR),
+        None => return Ok(None),
+    }
+    write_zset_in(data, key, |zset| Ok(Some(zset.pop(count, max))))
+}
+
+impl Cache {
+    fn read_zset<T>(
+        &self,
+        key: &str,
+        read: impl FnOnce(Option<&SortedSet>) -> T,
+    ) -> Result<T, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(read(zset_ref(&data, key)?))
+    }
+
+    fn write_zset<T>(
+        &self,
+        key: &str,
+        write: impl FnOnce(&mut SortedSet) -> Result<T, &'static str>,
+    ) -> Result<T, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        write_zset_in(&mut data, key, write)
+    }
+
+    pub fn zadd(
+        &self,
+        key: &str,
+        options: &ZAddOptions,
+        elements: &[(f64, String)],
+    ) -> Result<ZAddOutcome, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let outcome = write_zset_in(&mut data, key, |zset| {
+            let mut outcome = ZAddOutcome::default();
+            for (score, member) in elements {
+                let (status, score) = zset.add(member, *score, options)?;
+                match status {
+                    AddStatus::Added => outcome.added += 1,
+                    AddStatus::Updated => outcome.updated += 1,
+                    AddStatus::Unchanged | AddStatus::Skipped => {}
+                }
+                outcome.score = score;
+            }
+            Ok(outcome)
+        })?;
+        self.blocking.serve(&mut data, key);
+        Ok(outcome)
+    }
+
+    pub fn zrem(&self, key: &str, members: &[String]) -> Result<usize, &'static str> {
+        self.write_zset(key, |zset| {
+            Ok(members.iter().filter(|member| zset.remove(member)).count())
+        })
+    }
+
+    pub fn zmscore(&self, key: &str, members: &[String]) -> Result<Vec<Option<f64>>, &'static str> {
+        self.read_zset(key, |zset| {
+            members
+                .iter()
+                .map(|member| zset.and_then(|zset| zset.score(member)))
+                .collect()
+        })
+    }
+
+    pub fn zscan(
+        &self,
+        key: &str,
+        cursor: u64,
+        count: usize,
+    ) -> Result<ScanPage<(String, f64)>, &'static str> {
+        self.read_zset(key, |zset| {
+            zset.map(|zset| zset.page(cursor, count))
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn zcard(&self, key: &str) -> Result<usize, &'static str> {
+        self.read_zset(key, |zset| zset.map_or(0, SortedSet::len))
+    }
+
+    pub fn zcount(&self, key: &str, range: &ScoreRange) -> Result<usize, &'static str> {
+        self.read_zset(key, |zset| {
+            zset.map_or(0, |zset| zset.count_in_score_range(range))
+        })
+    }
+
+    pub fn zrank(
+        &self,
+        key: &str,
+        member: &str,
+        reverse: bool,
+    ) -> Result<Option<(usize, f64)>, &'static str> {
+        self.read_zset(key, |zset| {
+            let zset = zset?;
+            Some((zset.rank(member, reverse)?, zset.score(member)?))
+        })
+    }
+
+    pub fn zrange(
+        &self,
+        key: &str,
+        query: &RangeQuery,
+    ) -> Result<Vec<(String, f64)>, &'static str> {
+        self.read_zset(key, |zset| {
+            zset.map(|zset| zset.range(query)).unwrap_or_default()
+        })
+    }
+
+    pub fn zpop(
+        &self,
+        key: &str,
+        count: usize,
+        max: bool,
+    ) -> Result<Vec<(String, f64)>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        Ok(zpop_from(&mut data, key, count, max)?.unwrap_or_default())
+    }
+
+    /// Pops from the first non-empty sorted set among `keys`, as ZMPOP does.
+    pub fn zpop_first(
+        &self,
+        keys: &[String],
+        count: usize,
+        max: bool,
+    ) -> Result<Option<(String, ScoredMembers)>, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        for key in keys {
+            if let Some(popped) = zpop_from(&mut data, key, count, max)? {
+                return Ok(Some((key.clone(), popped)));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn zcombine(
+        &self,
+        operation: SetOperation,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> Result<Vec<(String, f64)>, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        Ok(combine(&data, operation, keys, weights, aggregate)?.entries())
+    }
+
+    /// ZUNIONSTORE/ZINTERSTORE/ZDIFFSTORE. Returns the size of the stored result.
+    pub fn zcombine_store(
+        &self,
+        operation: SetOperation,
+        destination: &str,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let result = combine(&data, operation, keys, weights, aggregate)?;
+        let len = store_zset(&mut data, destination, result);
+        self.blocking.serve(&mut data, destination);
+        Ok(len)
+    }
+
+    /// ZINTERCARD: size of the intersection, capped at `limit` unless it is 0.
+    pub fn zintercard(&self, keys: &[String], limit: usize) -> Result<usize, &'static str> {
+        let data = self
+            .data
+            .read()
+            .map_err(|_| "Could not acquire data read lock")?;
+        let inputs = keys
+            .iter()
+            .map(|key| card_input(&data, key))
+            .collect::<Result<Vec<Option<CardInput>>, &'static str>>()?;
+        // A missing key empties the intersection.
+        let Some(mut inputs) = inputs.into_iter().collect::<Option<Vec<CardInput>>>() else {
+            return Ok(0);
+        };
+        inputs.sort_by_key(|input| input.len());
+        let Some((smallest, others)) = inputs.split_first() else {
+            return Ok(0);
+        };
+        let common = smallest
+            .members()
+            .filter(|member| others.iter().all(|other| other.contains(member)));
+        Ok(if limit == 0 {
+            common.count()
+        } else {
+            common.take(limit).count()
+        })
+    }
+
+    /// ZRANGESTORE: stores the members `query` selects from `source` into `destination`.
+    pub fn zrangestore(
+        &self,
+        destination: &str,
+        source: &str,
+        query: &RangeQuery,
+    ) -> Result<usize, &'static str> {
+        let mut data = self
+            .data
+            .write()
+            .map_err(|_| "Could not acquire data write lock")?;
+        let mut result = SortedSet::default();
+        if let Some(zset) = zset_ref(&data, source)? {
+            for (member, score) in zset.range(query) {
+                result.insert(&member, score);
+            }
+        }
+        let len = store_zset(&mut data, destination, result);
+        self.blocking.serve(&mut data, destination);
+        Ok(len)
+    }
+
+    pub fn zlexcount(&self, key: &str, range: &LexRange) -> Result<usize, &'static str> {
+        self.read_zset(key, |zset| {
+            zset.map_or(0, |zset| zset.count_in_lex_range(range))
+        })
+    }
+
+    /// ZREMRANGEBYRANK/ZREMRANGEBYSCORE/ZREMRANGEBYLEX. Returns how many members were removed.
+    pub fn zremrange(&self, key: &str, spec: RangeSpec) -> Result<usize, &'static str> {
+        let query = RangeQuery {
+            spec,
+            reverse: false,
+            offset: 0,
+            limit: None,
+        };
+        self.write_zset(key, |zset| {
+            let doomed = zset.range(&query);
+            for (member, _) in &doomed {
+                zset.remove(member);
+            }
+            Ok(doomed.len())
+        })
+    }
+
+    /// ZRANDMEMBER semantics: a positive count returns distinct members, a negative one allows
+    /// the same member to be returned several times.
+    pub fn zrandmember(&self, key: &str, count: i64) -> Result<Vec<(String, f64)>, &'static str> {
+        check_sample_count(count)?;
+        self.read_zset(key, |zset| {
+            let entries = zset.map(SortedSet::entries).unwrap_or_default();
+            if count >= 0 {
+                sample_distinct(&entries, count as usize)
+            } else {
+                sample_with_repetition(&entries, count.unsigned_abs() as usize)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::skiplist::LexBound;
+    use super::*;
+
+    fn sorted_set(entries: &[(f64, &str)]) -> SortedSet {
+        let mut zset = SortedSet::default();
+        for (score, member) in entries {
+            zset.insert(member, *score);
+        }
+        zset
+    }
+
+    fn members(entries: Vec<(String, f64)>) -> Vec<String> {
+        entries.into_iter().map(|(member, _)| member).collect()
+    }
+
+    #[test]
+    fn should_apply_zadd_conditions() {
+        let mut zset = sorted_set(&[(1.0, "a")]);
+        let gt = ZAddOptions {
+            gt: true,
+            ..Default::default()
+        };
+        assert_eq!(zset.add("a", 0.5, &gt), Ok((AddStatus::Skipped, None)));
+        assert_eq!(zset.add("a", 2.0, &gt), Ok((AddStatus::Updated, Some(2.0))));
+        let incr = ZAddOptions {
+            incr: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            zset.add("a", 3.0, &incr),
+            Ok((AddStatus::Updated, Some(5.0)))
+        );
+        zset.insert("inf", f64::INFINITY);
+        assert_eq!(
+            zset.add("inf", f64::NEG_INFINITY, &incr),
+            Err(NAN_SCORE_ERR)
+        );
+        let xx = ZAddOptions {
+            xx: true,
+            ..Default::default()
+        };
+        assert_eq!(zset.add("b", 1.0, &xx), Ok((AddStatus::Skipped, None)));
+        assert_eq!(zset.rank("a", false), Some(0));
+        assert_eq!(zset.rank("a", true), Some(1));
+    }
+
+    #[test]
+    fn should_range_by_rank_score_and_lex() {
+        let zset = sorted_set(&[(1.0, "a"), (2.0, "b"), (2.0, "c"), (3.0, "d"), (4.0, "e")]);
+        let query = |spec, reverse, offset, limit| RangeQuery {
+            spec,
+            reverse,
+            offset,
+            limit,
+        };
+        assert_eq!(
+            members(zset.range(&query(RangeSpec::Rank(1, -2), false, 0, None))),
+            ["b", "c", "d"]
+        );
+        assert_eq!(
+            members(zset.range(&query(RangeSpec::Rank(0, 1), true, 0, None))),
+            ["e", "d"]
+        );
+        let scores = ScoreRange {
+            min: 2.0,
+            max: 4.0,
+            min_exclusive: false,
+            max_exclusive: true,
+        };
+        assert_eq!(
+            members(zset.range(&query(RangeSpec::Score(scores), false, 1, Some(5)))),
+            ["c", "d"]
+        );
+        assert_eq!(
+            members(zset.range(&query(RangeSpec::Score(scores), true, 0, Some(2)))),
+            ["d", "c"]
+        );
+        assert_eq!(zset.count_in_score_range(&scores), 3);
+
+        let lex = sorted_set(&[(0.0, "a"), (0.0, "b"), (0.0, "c"), (0.0, "d")]);
+        let range = LexRange {
+            min: LexBound::Inclusive("b".to_string()),
+            max: LexBound::Exclusive("d".to_string()),
+        };
+        assert_eq!(
+            members(lex.range(&query(RangeSpec::Lex(range.clone()), false, 0, None))),
+            ["b", "c"]
+        );
+        assert_eq!(
+            members(lex.range(&query(RangeSpec::Lex(range), true, 0, None))),
+            ["c", "b"]
+        );
+    }
+
+    #[test]
+    fn should_answer_alike_in_both_encodings() {
+        let entries = [
+            (2.0, "b"),
+            (1.0, "z"),
+            (2.0, "a"),
+            (f64::INFINITY, "i"),
+            (-0.5, "n"),
+        ];
+        let packed = sorted_set(&entries);
+        let mut full = packed.clone();
+        full.convert_to_skip_list();
+        assert_eq!(packed.encoding(), "listpack");
+        assert_eq!(full.encoding(), "skiplist");
+        assert_eq!(packed, full);
+        assert_eq!(members(packed.entries()), ["n", "z", "a", "b", "i"]);
+
+        let scores = ScoreRange {
+            min: 1.0,
+            max: f64::INFINITY,
+            min_exclusive: true,
+            max_exclusive: false,
+        };
+        for query in [
+            RangeQuery {
+                spec: RangeSpec::Rank(-4, 2),
+                reverse: true,
+                offset: 0,
+                limit: None,
+            },
+            RangeQuery {
+                spec: RangeSpec::Score(scores),
+                reverse: false,
+                offset: 1,
+                limit: Some(1),
+            },
+        ] {
+            assert_eq!(packed.range(&query), full.range(&query));
+        }
+        assert_eq!(packed.count_in_score_range(&scores), 3);
+        assert_eq!(full.count_in_score_range(&scores), 3);
+        assert_eq!(packed.rank("b", true), full.rank("b", true));
+
+        let (mut packed, mut full) = (packed, full);
+        assert_eq!(packed.pop(2, true), full.pop(2, true));
+        assert_eq!(packed.pop(1, false), full.pop(1, false));
+        assert_eq!(members(packed.entries()), ["z", "a"]);
+    }
+
+    #[test]
+    fn should_convert_once_a_member_is_too_long_or_too_many() {
+        let mut zset = SortedSet::default();
+        for index in 0..ZSET_MAX_LISTPACK_ENTRIES {
+            zset.insert(&index.to_string(), -(index as f64));
+        }
+        assert!(!zset.insert("0", 0.5));
+        assert_eq!(zset.rank("0", true), Some(0));
+        assert_eq!(zset.encoding(), "listpack");
+        assert!(zset.insert("one more", 0.0));
+        assert_eq!(zset.encoding(), "skiplist");
+        assert_eq!(zset.len(), ZSET_MAX_LISTPACK_ENTRIES + 1);
+
+        let mut zset = sorted_set(&[(1.0, "a")]);
+        zset.insert(&"m".repeat(ZSET_MAX_LISTPACK_VALUE + 1), 0.0);
+        assert_eq!(zset.encoding(), "skiplist");
+        assert_eq!(zset.score("a"), Some(1.0));
+    }
+
+    #[test]
+    fn should_combine_with_weights_and_aggregates() {
+        let cache = Cache::new();
+        let to_vec = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let add = |key: &str, elements: &[(f64, &str)]| {
+            let elements: Vec<(f64, String)> = elements
+                .iter()
+                .map(|(score, member)| (*score, member.to_string()))
+                .collect();
+            cache.zadd(key, &ZAddOptions::default(), &elements).unwrap();
+        };
+        add("a", &[(1.0, "x"), (2.0, "y")]);
+        add("b", &[(10.0, "y"), (20.0, "z")]);
+        cache.sadd("s", &to_vec(&["y"])).unwrap();
+
+        assert_eq!(
+            cache
+                .zcombine(
+                    SetOperation::Union,
+                    &to_vec(&["a", "b"]),
+                    &[2.0, 1.0],
+                    Aggregate::Sum
+                )
+                .unwrap(),
+            vec![
+                ("x".to_string(), 2.0),
+                ("y".to_string(), 14.0),
+                ("z".to_string(), 20.0)
+            ]
+        );
+        assert_eq!(
+            cache
+                .zcombine(
+                    SetOperation::Inter,
+                    &to_vec(&["a", "b", "s"]),
+                    &[],
+                    Aggregate::Max
+                )
+                .unwrap(),
+            vec![("y".to_string(), 10.0)]
+        );
+        assert_eq!(
+            cache
+                .zcombine_store(
+                    SetOperation::Diff,
+                    "d",
+                    &to_vec(&["a", "b"]),
+                    &[],
+                    Aggregate::Sum
+                )
+                .unwrap(),
+            1
+        );
+        assert_eq!(cache.zremrange("b", RangeSpec::Rank(0, 0)).unwrap(), 1);
+        assert_eq!(cache.zcard("b").unwrap(), 1);
+    }
+
+    #[test]
+    fn should_pop_from_both_ends() {
+        let cache = Cache::new();
+        let elements: Vec<(f64, String)> = [(1.0, "a"), (2.0, "b"), (3.0, "c")]
+            .iter()
+            .map(|(score, member)| (*score, member.to_string()))
+            .collect();
+        let outcome = cache.zadd("z", &ZAddOptions::default(), &elements).unwrap();
+        assert_eq!(outcome.added, 3);
+        assert_eq!(members(cache.zpop("z", 1, true).unwrap()), ["c"]);
+        assert_eq!(members(cache.zpop("z", 5, false).unwrap()), ["a", "b"]);
+        assert!(!cache.exists("z").unwrap());
+    }
+}